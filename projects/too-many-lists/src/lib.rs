@@ -11,3 +11,9 @@ pub mod silly2;
 pub mod sixth;
 /// Rc to share ownerships
 pub mod third;
+/// Arc to share ownerships across threads
+pub mod third_arc;
+/// Persistent double-ended queue made of two third::Lists
+pub mod third_deque;
+/// Persistent random-access list: a third::List of complete binary trees
+pub mod third_random_access;
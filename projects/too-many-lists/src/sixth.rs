@@ -97,7 +97,7 @@ impl<T> LinkedList<T> {
                 if let Some(new_back) = self.back {
                     (*new_back.as_ptr()).back = None;
                 } else {
-                    self.back = None;
+                    self.front = None;
                 }
 
                 self.len -= 1;
@@ -151,6 +151,328 @@ impl<T> LinkedList<T> {
     pub fn clear(&mut self) {
         while self.pop_front().is_some() {}
     }
+
+    /// Moves every element of `other` to the back of this list, leaving `other` empty.
+    /// O(1): the nodes are relinked, not copied.
+    pub fn append(&mut self, other: &mut Self) {
+        match self.back {
+            None => std::mem::swap(self, other),
+            Some(back) => {
+                if let Some(other_front) = other.front.take() {
+                    unsafe {
+                        (*back.as_ptr()).back = Some(other_front);
+                        (*other_front.as_ptr()).front = Some(back);
+                    }
+                    self.back = other.back.take();
+                    self.len += std::mem::replace(&mut other.len, 0);
+                }
+            }
+        }
+    }
+
+    /// Splits the list in two at the given index, returning everything from `at` onwards.
+    /// O(min(at, len - at)). Panics if `at > len`.
+    pub fn split_off(&mut self, at: usize) -> Self {
+        assert!(at <= self.len, "Cannot split off at a nonexistent index");
+
+        if at == 0 {
+            return std::mem::take(self);
+        }
+
+        // split after the element before `at`
+        let mut cursor = CursorMut {
+            cur: self.node_at(at - 1),
+            index: Some(at - 1),
+            list: self,
+        };
+        cursor.split_after()
+    }
+
+    /// O(min(index, len - index))
+    pub fn get(&self, index: usize) -> Option<&T> {
+        unsafe { self.node_at(index).map(|node| &(*node.as_ptr()).elem) }
+    }
+
+    /// O(min(index, len - index))
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        unsafe { self.node_at(index).map(|node| &mut (*node.as_ptr()).elem) }
+    }
+
+    /// Inserts an element at the given index, shifting everything after it back.
+    /// O(min(index, len - index)). Panics if `index > len`.
+    pub fn insert(&mut self, index: usize, elem: T) {
+        assert!(
+            index <= self.len,
+            "Cannot insert at an index outside of the list bounds"
+        );
+
+        if index == self.len {
+            self.push_back(elem);
+        } else {
+            let mut cursor = CursorMut {
+                cur: self.node_at(index),
+                index: Some(index),
+                list: self,
+            };
+            cursor.insert_before(elem);
+        }
+    }
+
+    /// Removes and returns the element at the given index. O(min(index, len - index)).
+    /// Panics if `index >= len`.
+    pub fn remove(&mut self, index: usize) -> T {
+        assert!(
+            index < self.len,
+            "Cannot remove at an index outside of the list bounds"
+        );
+
+        unsafe { self.unlink(self.node_at(index).unwrap()) }
+    }
+
+    /// O(len)
+    pub fn contains(&self, x: &T) -> bool
+    where
+        T: PartialEq,
+    {
+        self.iter().any(|elem| elem == x)
+    }
+
+    /// Swaps the elements at the two indices. O(min(a, len - a) + min(b, len - b)).
+    /// Panics if either index is out of bounds.
+    pub fn swap(&mut self, a: usize, b: usize) {
+        let node_a = self.node_at(a).expect("swap index out of bounds");
+        let node_b = self.node_at(b).expect("swap index out of bounds");
+
+        // the values move rather than the nodes; `ptr::swap` allows a and b to be the same
+        unsafe {
+            std::ptr::swap(
+                std::ptr::addr_of_mut!((*node_a.as_ptr()).elem),
+                std::ptr::addr_of_mut!((*node_b.as_ptr()).elem),
+            );
+        }
+    }
+
+    /// Find the node at an index by walking from whichever end is nearer
+    fn node_at(&self, index: usize) -> Link<T> {
+        if index >= self.len {
+            return None;
+        }
+
+        unsafe {
+            if index < self.len / 2 {
+                let mut node = self.front;
+                for _ in 0..index {
+                    node = (*node?.as_ptr()).back;
+                }
+                node
+            } else {
+                let mut node = self.back;
+                for _ in index + 1..self.len {
+                    node = (*node?.as_ptr()).front;
+                }
+                node
+            }
+        }
+    }
+
+    /// Unlink a node of this list, fixing up its neighbours or the list ends, and free it
+    unsafe fn unlink(&mut self, node: NonNull<Node<T>>) -> T {
+        let boxed_node = Box::from_raw(node.as_ptr());
+
+        if let Some(prev) = boxed_node.front {
+            (*prev.as_ptr()).back = boxed_node.back;
+        } else {
+            self.front = boxed_node.back;
+        }
+        if let Some(next) = boxed_node.back {
+            (*next.as_ptr()).front = boxed_node.front;
+        } else {
+            self.back = boxed_node.front;
+        }
+
+        self.len -= 1;
+        boxed_node.elem
+    }
+}
+
+impl<T> LinkedList<T> {
+    pub fn sort(&mut self)
+    where
+        T: Ord,
+    {
+        self.sort_by(|a, b| a.cmp(b));
+    }
+
+    pub fn sort_by_key<K: Ord, F: FnMut(&T) -> K>(&mut self, mut f: F) {
+        self.sort_by(|a, b| f(a).cmp(&f(b)));
+    }
+
+    /// Stable bottom-up merge sort. Nodes are relinked rather than moved, so no extra space is
+    /// needed. If `compare` panics the list is left in its original order.
+    pub fn sort_by<F: FnMut(&T, &T) -> Ordering>(&mut self, mut compare: F) {
+        // Only the `back` links are rewritten while merging. The `front` links still describe
+        // the original order, which the guard restores from if `compare` panics:
+        //
+        //     before:  A <-> B <-> C <-> D
+        //     merging: A -> C -> B -> D      (back links)
+        //              A <- B <- C <- D      (front links, untouched)
+        //
+        struct Restore<T> {
+            back: Link<T>,
+        }
+
+        impl<T> Drop for Restore<T> {
+            fn drop(&mut self) {
+                unsafe {
+                    let mut next = None;
+                    let mut node = self.back;
+                    while let Some(cur) = node {
+                        (*cur.as_ptr()).back = next;
+                        next = Some(cur);
+                        node = (*cur.as_ptr()).front;
+                    }
+                }
+            }
+        }
+
+        if self.len < 2 {
+            return;
+        }
+
+        let restore = Restore { back: self.back };
+        let mut head = self.front;
+        let mut run = 1;
+
+        unsafe {
+            loop {
+                let mut p = head;
+                let mut tail: Link<T> = None;
+                head = None;
+                let mut merges = 0;
+
+                while let Some(p_start) = p {
+                    merges += 1;
+
+                    // the run starting at q follows the run of up to `run` nodes starting at p
+                    let mut q = Some(p_start);
+                    let mut p_len = 0;
+                    while let Some(node) = q.filter(|_| p_len < run) {
+                        p_len += 1;
+                        q = (*node.as_ptr()).back;
+                    }
+                    let mut q_len = run;
+
+                    while p_len > 0 || (q_len > 0 && q.is_some()) {
+                        // take from p on ties to keep the sort stable
+                        let take_p = match (p, q) {
+                            _ if p_len == 0 => false,
+                            (_, None) => true,
+                            _ if q_len == 0 => true,
+                            (Some(a), Some(b)) => {
+                                compare(&(*a.as_ptr()).elem, &(*b.as_ptr()).elem)
+                                    != Ordering::Greater
+                            }
+                            (None, Some(_)) => unreachable!(),
+                        };
+
+                        let node = if take_p {
+                            let node = p.unwrap();
+                            p = (*node.as_ptr()).back;
+                            p_len -= 1;
+                            node
+                        } else {
+                            let node = q.unwrap();
+                            q = (*node.as_ptr()).back;
+                            q_len -= 1;
+                            node
+                        };
+
+                        match tail {
+                            Some(tail) => (*tail.as_ptr()).back = Some(node),
+                            None => head = Some(node),
+                        }
+                        tail = Some(node);
+                    }
+
+                    p = q;
+                }
+
+                (*tail.unwrap().as_ptr()).back = None;
+                if merges <= 1 {
+                    break;
+                }
+                run *= 2;
+            }
+
+            // sorted without panicking, so rebuild the front links in the new order
+            std::mem::forget(restore);
+
+            let mut prev = None;
+            let mut node = head;
+            while let Some(cur) = node {
+                (*cur.as_ptr()).front = prev;
+                prev = Some(cur);
+                node = (*cur.as_ptr()).back;
+            }
+            self.front = head;
+            self.back = prev;
+        }
+    }
+
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        self.retain_mut(|elem| f(elem));
+    }
+
+    pub fn retain_mut<F: FnMut(&mut T) -> bool>(&mut self, mut f: F) {
+        let mut cursor = self.cursor_front_mut();
+        while let Some(elem) = cursor.current() {
+            if f(elem) {
+                cursor.move_next();
+            } else {
+                cursor.remove_current();
+            }
+        }
+    }
+
+    pub fn dedup(&mut self)
+    where
+        T: PartialEq,
+    {
+        self.dedup_by(|a, b| a == b);
+    }
+
+    pub fn dedup_by_key<K: PartialEq, F: FnMut(&mut T) -> K>(&mut self, mut key: F) {
+        self.dedup_by(|a, b| key(a) == key(b));
+    }
+
+    /// Remove consecutive elements for which `same_bucket(elem, kept)` is true, where `kept` is
+    /// the element before it that was not removed
+    pub fn dedup_by<F: FnMut(&mut T, &mut T) -> bool>(&mut self, mut same_bucket: F) {
+        let mut kept = self.front;
+        unsafe {
+            while let Some(cur) = kept {
+                match (*cur.as_ptr()).back {
+                    Some(next)
+                        if same_bucket(&mut (*next.as_ptr()).elem, &mut (*cur.as_ptr()).elem) =>
+                    {
+                        self.unlink(next);
+                    }
+                    next => kept = next,
+                }
+            }
+        }
+    }
+
+    /// Remove and yield the elements for which `filter` is true, in order. Elements that were
+    /// not reached are kept if the iterator is dropped early.
+    pub fn extract_if<F: FnMut(&mut T) -> bool>(&mut self, filter: F) -> ExtractIf<'_, T, F> {
+        ExtractIf {
+            next: self.front,
+            remaining: self.len,
+            list: self,
+            filter,
+        }
+    }
 }
 
 impl<T> Drop for LinkedList<T> {
@@ -259,6 +581,34 @@ impl<'a, T> ExactSizeIterator for IterMut<'a, T> {
     }
 }
 
+pub struct ExtractIf<'a, T, F> {
+    list: &'a mut LinkedList<T>,
+    next: Link<T>,
+    remaining: usize,
+    filter: F,
+}
+
+impl<'a, T, F: FnMut(&mut T) -> bool> Iterator for ExtractIf<'a, T, F> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.next {
+            unsafe {
+                self.next = (*node.as_ptr()).back;
+                self.remaining -= 1;
+                if (self.filter)(&mut (*node.as_ptr()).elem) {
+                    return Some(self.list.unlink(node));
+                }
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
+
 pub struct IntoIter<T> {
     list: LinkedList<T>,
 }
@@ -388,6 +738,15 @@ unsafe impl<'a, T: Sync> Sync for Iter<'a, T> {}
 unsafe impl<'a, T: Send> Send for IterMut<'a, T> {}
 unsafe impl<'a, T: Sync> Sync for IterMut<'a, T> {}
 
+unsafe impl<'a, T: Sync> Send for Cursor<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for Cursor<'a, T> {}
+
+pub struct Cursor<'a, T> {
+    cur: Link<T>,
+    list: &'a LinkedList<T>,
+    index: Option<usize>,
+}
+
 pub struct CursorMut<'a, T> {
     cur: Link<T>,
     list: &'a mut LinkedList<T>,
@@ -395,13 +754,137 @@ pub struct CursorMut<'a, T> {
 }
 
 impl<T> LinkedList<T> {
-    pub fn cursor_mut(&mut self) -> CursorMut<T> {
+    pub fn cursor(&self) -> Cursor<'_, T> {
+        Cursor {
+            list: self,
+            cur: None,
+            index: None,
+        }
+    }
+
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, T> {
         CursorMut {
             list: self,
             cur: None,
             index: None,
         }
     }
+
+    /// A cursor on the front element, or on the ghost if the list is empty
+    pub fn cursor_front(&self) -> Cursor<'_, T> {
+        Cursor {
+            cur: self.front,
+            index: self.front.map(|_| 0),
+            list: self,
+        }
+    }
+
+    /// A cursor on the back element, or on the ghost if the list is empty
+    pub fn cursor_back(&self) -> Cursor<'_, T> {
+        Cursor {
+            cur: self.back,
+            index: self.back.map(|_| self.len - 1),
+            list: self,
+        }
+    }
+
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            cur: self.front,
+            index: self.front.map(|_| 0),
+            list: self,
+        }
+    }
+
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            cur: self.back,
+            index: self.back.map(|_| self.len - 1),
+            list: self,
+        }
+    }
+}
+
+impl<'a, T> Cursor<'a, T> {
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+
+    pub fn move_next(&mut self) {
+        if let Some(cur) = self.cur {
+            unsafe {
+                self.cur = (*cur.as_ptr()).back;
+                if self.cur.is_some() {
+                    *self.index.as_mut().unwrap() += 1;
+                } else {
+                    self.index = None;
+                }
+            }
+        } else if !self.list.is_empty() {
+            self.cur = self.list.front;
+            self.index = Some(0);
+        } else {
+            // pointing to ghost
+        }
+    }
+
+    pub fn move_prev(&mut self) {
+        if let Some(cur) = self.cur {
+            unsafe {
+                self.cur = (*cur.as_ptr()).front;
+                if self.cur.is_some() {
+                    *self.index.as_mut().unwrap() -= 1;
+                } else {
+                    self.index = None;
+                }
+            }
+        } else if !self.list.is_empty() {
+            self.cur = self.list.back;
+            self.index = Some(self.list.len - 1);
+        } else {
+            // pointing to ghost
+        }
+    }
+
+    // The references live as long as the list, not the cursor, since nothing can change the
+    // list while it is shared
+    pub fn current(&self) -> Option<&'a T> {
+        unsafe { self.cur.map(|node| &(*node.as_ptr()).elem) }
+    }
+
+    pub fn peek_next(&self) -> Option<&'a T> {
+        unsafe {
+            let next = if let Some(cur) = self.cur {
+                (*cur.as_ptr()).back
+            } else {
+                // ghost case
+                self.list.front
+            };
+            next.map(|node| &(*node.as_ptr()).elem)
+        }
+    }
+
+    pub fn peek_prev(&self) -> Option<&'a T> {
+        unsafe {
+            let prev = if let Some(cur) = self.cur {
+                (*cur.as_ptr()).front
+            } else {
+                // ghost case
+                self.list.back
+            };
+            prev.map(|node| &(*node.as_ptr()).elem)
+        }
+    }
+}
+
+impl<'a, T> Clone for Cursor<'a, T> {
+    fn clone(&self) -> Self {
+        Cursor {
+            cur: self.cur,
+            list: self.list,
+            index: self.index,
+        }
+    }
 }
 
 impl<'a, T> CursorMut<'a, T> {
@@ -409,6 +892,15 @@ impl<'a, T> CursorMut<'a, T> {
         self.index
     }
 
+    /// A read-only cursor at the same position, borrowing this one
+    pub fn as_cursor(&self) -> Cursor<'_, T> {
+        Cursor {
+            cur: self.cur,
+            list: self.list,
+            index: self.index,
+        }
+    }
+
     pub fn move_next(&mut self) {
         if let Some(cur) = self.cur {
             unsafe {
@@ -473,6 +965,54 @@ impl<'a, T> CursorMut<'a, T> {
         }
     }
 
+    /// Insert before the current element, or at the back if on the ghost
+    pub fn insert_before(&mut self, elem: T) {
+        let mut input = LinkedList::new();
+        input.push_back(elem);
+        self.splice_before(input);
+    }
+
+    /// Insert after the current element, or at the front if on the ghost
+    pub fn insert_after(&mut self, elem: T) {
+        let mut input = LinkedList::new();
+        input.push_back(elem);
+        self.splice_after(input);
+    }
+
+    /// Remove the current element and move to the next one, or to the ghost if it was the back.
+    /// Returns None and does nothing on the ghost.
+    pub fn remove_current(&mut self) -> Option<T> {
+        // We have this:
+        //
+        //     list.front -> A <-> B <-> C <- list.back
+        //                         ^
+        //                        cur
+        //
+        // And we want to produce this, keeping the same index:
+        //
+        //     list.front -> A <-> C <- list.back
+        //                         ^
+        //                        cur
+        //
+        let cur = self.cur?;
+        unsafe {
+            self.cur = (*cur.as_ptr()).back;
+            if self.cur.is_none() {
+                self.index = None;
+            }
+            Some(self.list.unlink(cur))
+        }
+    }
+
+    /// Replace the current element, returning the old one. On the ghost nothing is replaced and
+    /// the new element is handed back as the error.
+    pub fn replace_current(&mut self, elem: T) -> Result<T, T> {
+        match self.current() {
+            Some(current) => Ok(std::mem::replace(current, elem)),
+            None => Err(elem),
+        }
+    }
+
     pub fn split_before(&mut self) -> LinkedList<T> {
         // We have this:
         //
@@ -502,7 +1042,8 @@ impl<'a, T> CursorMut<'a, T> {
                 let new_idx = Some(0);
 
                 let output_len = old_idx;
-                let output_front = self.list.front;
+                // (empty if cur is the front, not a list that still points at cur)
+                let output_front = prev.and(self.list.front);
                 let output_back = prev;
 
                 if let Some(prev) = prev {
@@ -525,7 +1066,7 @@ impl<'a, T> CursorMut<'a, T> {
         } else {
             // We're at the ghost, just replace our list with an empty one.
             // No other state needs to be changed.
-            std::mem::replace(self.list, LinkedList::new())
+            std::mem::take(self.list)
         }
     }
 
@@ -562,8 +1103,9 @@ impl<'a, T> CursorMut<'a, T> {
 
                 // What the output will become
                 let output_len = old_len - new_len;
+                // (empty if cur is the back, not a list that still points at cur)
                 let output_front = next;
-                let output_back = self.list.back;
+                let output_back = next.and(self.list.back);
 
                 // Break the links between cur and next
                 if let Some(next) = next {
@@ -587,7 +1129,7 @@ impl<'a, T> CursorMut<'a, T> {
         } else {
             // We're at the ghost, just replace our list with an empty one.
             // No other state needs to be changed.
-            std::mem::replace(self.list, LinkedList::new())
+            std::mem::take(self.list)
         }
     }
 
@@ -725,6 +1267,9 @@ fn assert_properties() {
     is_send::<IterMut<i32>>();
     is_sync::<IterMut<i32>>();
 
+    is_send::<Cursor<i32>>();
+    is_sync::<Cursor<i32>>();
+
     fn linked_list_covariant<'a, T>(x: LinkedList<&'static T>) -> LinkedList<&'a T> {
         x
     }
@@ -1088,7 +1633,6 @@ mod test {
             &[10, 7, 1, 8, 2, 3, 4, 5, 6, 9]
         );
 
-        /* remove_current not impl'd
         let mut cursor = m.cursor_mut();
         cursor.move_next();
         cursor.move_prev();
@@ -1103,8 +1647,10 @@ mod test {
         cursor.move_next();
         assert_eq!(cursor.remove_current(), Some(10));
         check_links(&m);
-        assert_eq!(m.iter().cloned().collect::<Vec<_>>(), &[1, 8, 2, 3, 4, 5, 6]);
-        */
+        assert_eq!(
+            m.iter().cloned().collect::<Vec<_>>(),
+            &[1, 8, 2, 3, 4, 5, 6]
+        );
 
         let mut m: LinkedList<u32> = LinkedList::new();
         m.extend([1, 8, 2, 3, 4, 5, 6]);
@@ -1149,11 +1695,496 @@ mod test {
         )
     }
 
+    #[test]
+    fn test_cursor_read_only() {
+        let m: LinkedList<i32> = list_from(&[1, 2, 3, 4, 5, 6]);
+
+        let mut cursor = m.cursor_front();
+        assert_eq!(cursor.current(), Some(&1));
+        assert_eq!(cursor.peek_next(), Some(&2));
+        assert_eq!(cursor.peek_prev(), None);
+        assert_eq!(cursor.index(), Some(0));
+        cursor.move_prev();
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.peek_next(), Some(&1));
+        assert_eq!(cursor.peek_prev(), Some(&6));
+        assert_eq!(cursor.index(), None);
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&2));
+        assert_eq!(cursor.peek_next(), Some(&3));
+        assert_eq!(cursor.peek_prev(), Some(&1));
+        assert_eq!(cursor.index(), Some(1));
+
+        let mut cursor = m.cursor_back();
+        assert_eq!(cursor.current(), Some(&6));
+        assert_eq!(cursor.peek_next(), None);
+        assert_eq!(cursor.peek_prev(), Some(&5));
+        assert_eq!(cursor.index(), Some(5));
+        cursor.move_next();
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.peek_next(), Some(&1));
+        assert_eq!(cursor.peek_prev(), Some(&6));
+        assert_eq!(cursor.index(), None);
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(cursor.current(), Some(&5));
+        assert_eq!(cursor.peek_next(), Some(&6));
+        assert_eq!(cursor.peek_prev(), Some(&4));
+        assert_eq!(cursor.index(), Some(4));
+
+        // references outlive the cursor, and several cursors can share the list
+        let copy = cursor.clone();
+        let current = {
+            let mut cursor = cursor;
+            let current = cursor.current();
+            cursor.move_next();
+            current
+        };
+        assert_eq!(current, Some(&5));
+        assert_eq!(copy.current(), Some(&5));
+        assert_eq!(m.cursor().current(), None);
+
+        let empty: LinkedList<i32> = LinkedList::new();
+        let mut cursor = empty.cursor_front();
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.index(), None);
+        cursor.move_next();
+        assert_eq!(cursor.current(), None);
+        assert_eq!(empty.cursor_back().peek_prev(), None);
+    }
+
+    #[test]
+    fn test_cursor_mut_insert_remove() {
+        let mut m: LinkedList<u32> = LinkedList::new();
+        m.extend([1, 2, 3, 4, 5, 6]);
+
+        let mut cursor = m.cursor_front_mut();
+        cursor.insert_before(7);
+        cursor.insert_after(8);
+        assert_eq!(cursor.index(), Some(1));
+        assert_eq!(cursor.as_cursor().current(), Some(&1));
+        check_links(&m);
+        assert_eq!(
+            m.iter().cloned().collect::<Vec<_>>(),
+            &[7, 1, 8, 2, 3, 4, 5, 6]
+        );
+
+        let mut cursor = m.cursor_front_mut();
+        cursor.move_prev();
+        cursor.insert_before(9);
+        cursor.insert_after(10);
+        assert_eq!(cursor.index(), None);
+        check_links(&m);
+        assert_eq!(
+            m.iter().cloned().collect::<Vec<_>>(),
+            &[10, 7, 1, 8, 2, 3, 4, 5, 6, 9]
+        );
+
+        let mut cursor = m.cursor_front_mut();
+        cursor.move_prev();
+        assert_eq!(cursor.remove_current(), None);
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.remove_current(), Some(7));
+        assert_eq!(cursor.current(), Some(&mut 1));
+        assert_eq!(cursor.index(), Some(1));
+        cursor.move_prev();
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(cursor.remove_current(), Some(9));
+        assert_eq!(cursor.index(), None);
+        cursor.move_next();
+        assert_eq!(cursor.remove_current(), Some(10));
+        check_links(&m);
+        assert_eq!(
+            m.iter().cloned().collect::<Vec<_>>(),
+            &[1, 8, 2, 3, 4, 5, 6]
+        );
+
+        let mut cursor = m.cursor_back_mut();
+        assert_eq!(cursor.replace_current(60), Ok(6));
+        cursor.move_next();
+        assert_eq!(cursor.replace_current(0), Err(0));
+        assert_eq!(m.back(), Some(&60));
+        assert_eq!(m.len(), 7);
+    }
+
+    #[test]
+    fn test_cursor_mut_edges() {
+        // inserting into an empty list from the ghost
+        let mut m: LinkedList<i32> = LinkedList::new();
+        let mut cursor = m.cursor_mut();
+        cursor.insert_after(2);
+        cursor.insert_before(3);
+        cursor.insert_after(1);
+        assert_eq!(cursor.current(), None);
+        check_links(&m);
+        assert_eq!(m.iter().cloned().collect::<Vec<_>>(), &[1, 2, 3]);
+
+        // removing everything from the front leaves a usable empty list
+        let mut cursor = m.cursor_front_mut();
+        assert_eq!(cursor.remove_current(), Some(1));
+        assert_eq!(cursor.remove_current(), Some(2));
+        assert_eq!(cursor.remove_current(), Some(3));
+        assert_eq!(cursor.remove_current(), None);
+        assert_eq!(cursor.index(), None);
+        assert!(m.is_empty());
+        assert_eq!(m.front(), None);
+        assert_eq!(m.back(), None);
+        m.push_back(4);
+        m.push_front(5);
+        check_links(&m);
+        assert_eq!(m.iter().cloned().collect::<Vec<_>>(), &[5, 4]);
+
+        // removing from the back
+        let mut cursor = m.cursor_back_mut();
+        assert_eq!(cursor.remove_current(), Some(4));
+        assert_eq!(cursor.current(), None);
+        cursor.move_prev();
+        assert_eq!(cursor.remove_current(), Some(5));
+        assert!(m.is_empty());
+        m.push_front(6);
+        check_links(&m);
+        assert_eq!(m.iter().cloned().collect::<Vec<_>>(), &[6]);
+
+        // popping the last element from the back clears the front too
+        assert_eq!(m.pop_back(), Some(6));
+        assert_eq!(m.front(), None);
+        m.push_back(7);
+        assert_eq!(m.iter().cloned().collect::<Vec<_>>(), &[7]);
+    }
+
+    /// A xorshift generator, so that the property tests cover the same cases on every run
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % n
+        }
+
+        /// Lists of up to 40 small values, so that there are plenty of equal elements
+        fn vecs(&mut self, count: usize) -> Vec<Vec<u8>> {
+            (0..count)
+                .map(|_| {
+                    let len = self.below(41);
+                    (0..len).map(|_| self.below(8) as u8).collect()
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_sort_matches_vec() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+
+        for v in rng.vecs(500) {
+            // pair each value with its position to check the sort is stable
+            let mut expected: Vec<(u8, usize)> = v.iter().cloned().zip(0..).collect();
+            let mut m: LinkedList<(u8, usize)> = expected.iter().cloned().collect();
+            expected.sort_by_key(|&(value, _)| value);
+            m.sort_by_key(|&(value, _)| value);
+            check_links(&m);
+            assert_eq!(m.iter().cloned().collect::<Vec<_>>(), expected);
+
+            expected.sort_by(|a, b| a.0.cmp(&b.0).reverse());
+            m.sort_by(|a, b| a.0.cmp(&b.0).reverse());
+            check_links(&m);
+            assert_eq!(m.iter().cloned().collect::<Vec<_>>(), expected);
+
+            expected.sort();
+            m.sort();
+            check_links(&m);
+            assert_eq!(m.into_iter().collect::<Vec<_>>(), expected);
+        }
+    }
+
+    #[test]
+    fn test_sort_panic_keeps_order() {
+        let v: Vec<i32> = (0..100).map(|n| (n * 37) % 101).collect();
+        let mut m: LinkedList<i32> = v.iter().cloned().collect();
+
+        for limit in [0, 1, 50, 300] {
+            let mut comparisons = 0;
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                m.sort_by(|a, b| {
+                    comparisons += 1;
+                    if comparisons > limit {
+                        panic!("comparison limit");
+                    }
+                    a.cmp(b)
+                });
+            }));
+
+            assert!(result.is_err());
+            check_links(&m);
+            assert_eq!(m.iter().cloned().collect::<Vec<_>>(), v);
+        }
+
+        m.sort();
+        assert!(m.iter().zip(m.iter().skip(1)).all(|(a, b)| a <= b));
+    }
+
+    #[test]
+    fn test_retain_matches_vec() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+
+        for v in rng.vecs(500) {
+            let mut expected = v.clone();
+            let mut m: LinkedList<u8> = v.iter().cloned().collect();
+            expected.retain(|x| x % 3 != 0);
+            m.retain(|x| x % 3 != 0);
+            check_links(&m);
+            assert_eq!(m.iter().cloned().collect::<Vec<_>>(), expected);
+
+            expected.retain_mut(|x| {
+                *x += 1;
+                *x % 2 == 0
+            });
+            m.retain_mut(|x| {
+                *x += 1;
+                *x % 2 == 0
+            });
+            check_links(&m);
+            assert_eq!(m.iter().cloned().collect::<Vec<_>>(), expected);
+        }
+    }
+
+    #[test]
+    fn test_dedup_matches_vec() {
+        let mut rng = Rng(0xd1b5_4a32_d192_ed03);
+
+        for v in rng.vecs(500) {
+            let mut expected = v.clone();
+            let mut m: LinkedList<u8> = v.iter().cloned().collect();
+            expected.dedup();
+            m.dedup();
+            check_links(&m);
+            assert_eq!(m.iter().cloned().collect::<Vec<_>>(), expected);
+
+            // the element is compared with the last one kept, not the one just removed
+            let mut expected = v.clone();
+            let mut m: LinkedList<u8> = v.iter().cloned().collect();
+            expected.dedup_by(|a, b| a.abs_diff(*b) <= 2);
+            m.dedup_by(|a, b| a.abs_diff(*b) <= 2);
+            check_links(&m);
+            assert_eq!(m.iter().cloned().collect::<Vec<_>>(), expected);
+
+            let mut expected = v.clone();
+            let mut m: LinkedList<u8> = v.iter().cloned().collect();
+            expected.dedup_by_key(|x| *x / 3);
+            m.dedup_by_key(|x| *x / 3);
+            check_links(&m);
+            assert_eq!(m.into_iter().collect::<Vec<_>>(), expected);
+        }
+    }
+
+    #[test]
+    fn test_extract_if_matches_vec() {
+        let mut rng = Rng(0x94d0_49bb_1331_11eb);
+
+        for v in rng.vecs(500) {
+            let mut expected = v.clone();
+            let mut m: LinkedList<u8> = v.iter().cloned().collect();
+            let expected_removed: Vec<u8> = expected.extract_if(.., |x| *x >= 4).collect();
+            let removed: Vec<u8> = m.extract_if(|x| *x >= 4).collect();
+            check_links(&m);
+            assert_eq!(removed, expected_removed);
+            assert_eq!(m.iter().cloned().collect::<Vec<_>>(), expected);
+        }
+
+        // stopping early keeps the elements that were not reached
+        let mut m: LinkedList<i32> = (0..10).collect();
+        {
+            let mut evens = m.extract_if(|x| *x % 2 == 0);
+            assert_eq!(evens.size_hint(), (0, Some(10)));
+            assert_eq!(evens.next(), Some(0));
+            assert_eq!(evens.next(), Some(2));
+            assert_eq!(evens.size_hint(), (0, Some(7)));
+        }
+        check_links(&m);
+        assert_eq!(
+            m.iter().cloned().collect::<Vec<_>>(),
+            &[1, 3, 4, 5, 6, 7, 8, 9]
+        );
+
+        // removing everything
+        let removed: Vec<i32> = m.extract_if(|_| true).collect();
+        assert_eq!(removed, &[1, 3, 4, 5, 6, 7, 8, 9]);
+        check_links(&m);
+        assert!(m.is_empty());
+    }
+
+    #[test]
+    fn test_append_split_off() {
+        let mut rng = Rng(0xbf58_476d_1ce4_e5b9);
+
+        for (a, b) in rng.vecs(200).into_iter().zip(rng.vecs(200)) {
+            let mut m = list_from(&a);
+            let mut n = list_from(&b);
+            m.append(&mut n);
+            check_links(&m);
+            check_links(&n);
+            assert!(n.is_empty());
+            let joined: Vec<u8> = a.iter().chain(&b).cloned().collect();
+            assert_eq!(m.iter().cloned().collect::<Vec<_>>(), joined);
+
+            for at in [0, a.len(), joined.len() / 2, joined.len()] {
+                let mut m = list_from(&joined);
+                let tail = m.split_off(at);
+                check_links(&m);
+                check_links(&tail);
+                assert_eq!(m.iter().cloned().collect::<Vec<_>>(), &joined[..at]);
+                assert_eq!(tail.iter().cloned().collect::<Vec<_>>(), &joined[at..]);
+            }
+        }
+
+        // empty and single-element lists
+        let mut m: LinkedList<i32> = LinkedList::new();
+        let mut n = list_from(&[1]);
+        m.append(&mut LinkedList::new());
+        check_links(&m);
+        assert!(m.is_empty());
+        m.append(&mut n);
+        check_links(&m);
+        assert_eq!(m.iter().cloned().collect::<Vec<_>>(), &[1]);
+        m.append(&mut n);
+        check_links(&m);
+        assert_eq!(m.len(), 1);
+
+        let tail = m.split_off(1);
+        assert!(tail.is_empty());
+        let tail = m.split_off(0);
+        check_links(&m);
+        check_links(&tail);
+        assert!(m.is_empty());
+        assert_eq!(tail.iter().cloned().collect::<Vec<_>>(), &[1]);
+        assert!(m.split_off(0).is_empty());
+
+        // splitting a cursor off the end it is at leaves nothing behind in the output
+        let mut m = list_from(&[1, 2]);
+        let head = m.cursor_front_mut().split_before();
+        check_links(&head);
+        assert!(head.is_empty());
+        let tail = m.cursor_back_mut().split_after();
+        check_links(&tail);
+        assert!(tail.is_empty());
+        check_links(&m);
+        assert_eq!(m.len(), 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_split_off_out_of_bounds() {
+        list_from(&[1, 2]).split_off(3);
+    }
+
+    #[test]
+    fn test_positional_matches_vec() {
+        let mut rng = Rng(0x6a09_e667_f3bc_c908);
+
+        for v in rng.vecs(300) {
+            let mut expected = v.clone();
+            let mut m = list_from(&v);
+
+            for i in 0..=v.len() {
+                assert_eq!(m.get(i), expected.get(i));
+                assert_eq!(m.get_mut(i), expected.get_mut(i));
+            }
+            for x in 0..9 {
+                assert_eq!(m.contains(&x), expected.contains(&x));
+            }
+
+            for _ in 0..10 {
+                match rng.below(3) {
+                    0 => {
+                        let i = rng.below(expected.len() as u64 + 1) as usize;
+                        let x = rng.below(8) as u8;
+                        expected.insert(i, x);
+                        m.insert(i, x);
+                    }
+                    1 if !expected.is_empty() => {
+                        let i = rng.below(expected.len() as u64) as usize;
+                        assert_eq!(m.remove(i), expected.remove(i));
+                    }
+                    _ if !expected.is_empty() => {
+                        let i = rng.below(expected.len() as u64) as usize;
+                        let j = rng.below(expected.len() as u64) as usize;
+                        expected.swap(i, j);
+                        m.swap(i, j);
+                    }
+                    _ => {}
+                }
+                check_links(&m);
+                assert_eq!(m.iter().cloned().collect::<Vec<_>>(), expected);
+            }
+
+            if let Some(last) = m.get_mut(expected.len().wrapping_sub(1)) {
+                *last = 100;
+                *expected.last_mut().unwrap() = 100;
+            }
+            assert_eq!(m.into_iter().collect::<Vec<_>>(), expected);
+        }
+    }
+
+    #[test]
+    fn test_positional_edges() {
+        let mut m: LinkedList<i32> = LinkedList::new();
+        assert_eq!(m.get(0), None);
+        assert_eq!(m.get_mut(0), None);
+        assert!(!m.contains(&1));
+
+        m.insert(0, 1);
+        check_links(&m);
+        assert_eq!(m.get(0), Some(&1));
+        assert_eq!(m.get(1), None);
+        assert!(m.contains(&1));
+        m.swap(0, 0);
+        assert_eq!(m.get(0), Some(&1));
+
+        *m.get_mut(0).unwrap() = 2;
+        assert_eq!(m.remove(0), 2);
+        check_links(&m);
+        assert!(m.is_empty());
+
+        m.insert(0, 3);
+        m.insert(0, 1);
+        m.insert(1, 2);
+        check_links(&m);
+        assert_eq!(m.iter().cloned().collect::<Vec<_>>(), &[1, 2, 3]);
+        m.swap(0, 2);
+        assert_eq!(m.iter().cloned().collect::<Vec<_>>(), &[3, 2, 1]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_insert_out_of_bounds() {
+        LinkedList::new().insert(1, 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_remove_out_of_bounds() {
+        list_from(&[1]).remove(1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_swap_out_of_bounds() {
+        list_from(&[1]).swap(0, 1);
+    }
+
     fn check_links<T: Eq + std::fmt::Debug>(list: &LinkedList<T>) {
         let from_front: Vec<_> = list.iter().collect();
         let from_back: Vec<_> = list.iter().rev().collect();
         let re_reved: Vec<_> = from_back.into_iter().rev().collect();
 
         assert_eq!(from_front, re_reved);
+        assert_eq!(from_front.len(), list.len());
+        assert_eq!(list.front(), from_front.first().copied());
+        assert_eq!(list.back(), from_front.last().copied());
     }
 }
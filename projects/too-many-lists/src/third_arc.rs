@@ -0,0 +1,272 @@
+use std::fmt;
+use std::sync::Arc;
+
+pub struct List<T> {
+    head: Link<T>,
+}
+
+type Link<T> = Option<Arc<Node<T>>>;
+
+struct Node<T> {
+    elem: T,
+    next: Link<T>,
+}
+
+impl<T> List<T> {
+    pub fn new() -> Self {
+        List { head: None }
+    }
+
+    pub fn prepend(&self, elem: T) -> List<T> {
+        List {
+            head: Some(Arc::new(Node {
+                elem,
+                next: self.head.clone(),
+            })),
+        }
+    }
+
+    pub fn tail(&self) -> List<T> {
+        List {
+            head: self.head.as_ref().and_then(|node| node.next.clone()),
+        }
+    }
+
+    pub fn head(&self) -> Option<&T> {
+        self.head.as_ref().map(|node| &node.elem)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    /// Builds a list out of elements given back to front, prepending each onto `list`
+    fn prepend_all<I: IntoIterator<Item = T>>(mut list: List<T>, rev_elems: I) -> List<T> {
+        for elem in rev_elems {
+            list = list.prepend(elem);
+        }
+        list
+    }
+
+    /// A new list with the elements in the opposite order. Nothing can be shared with `self`.
+    pub fn reverse(&self) -> List<T>
+    where
+        T: Clone,
+    {
+        List::prepend_all(List::new(), self.iter().cloned())
+    }
+
+    /// A new list of `self` followed by `other`. Only the elements of `self` are copied,
+    /// the new list shares all of `other`'s nodes.
+    pub fn append(&self, other: &List<T>) -> List<T>
+    where
+        T: Clone,
+    {
+        let prefix: Vec<&T> = self.iter().collect();
+        List::prepend_all(other.clone(), prefix.into_iter().rev().cloned())
+    }
+
+    pub fn map<U, F: FnMut(&T) -> U>(&self, f: F) -> List<U> {
+        let mapped: Vec<U> = self.iter().map(f).collect();
+        List::prepend_all(List::new(), mapped.into_iter().rev())
+    }
+}
+
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for List<T> {
+    fn clone(&self) -> Self {
+        List {
+            head: self.head.clone(),
+        }
+    }
+}
+
+impl<T: PartialEq> PartialEq for List<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other)
+    }
+}
+
+impl<T: Eq> Eq for List<T> {}
+
+impl<T: fmt::Debug> fmt::Debug for List<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self).finish()
+    }
+}
+
+pub struct Iter<'a, T> {
+    next: Option<&'a Node<T>>,
+}
+
+impl<T> List<T> {
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            next: self.head.as_deref(),
+        }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next.map(|node| {
+            self.next = node.next.as_deref();
+            &node.elem
+        })
+    }
+}
+
+impl<'a, T> IntoIterator for &'a List<T> {
+    type IntoIter = Iter<'a, T>;
+    type Item = &'a T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T> Drop for List<T> {
+    fn drop(&mut self) {
+        // `Arc::into_inner` rather than `Arc::try_unwrap`: when two threads drop the last two
+        // handles on a node at once, both `try_unwrap`s can fail and leave the recursive drop
+        // to free the rest of the list. `into_inner` guarantees one of them gets the node.
+        let mut cur_link = self.head.take();
+        while let Some(node) = cur_link {
+            match Arc::into_inner(node) {
+                Some(mut node) => cur_link = node.next.take(),
+                None => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::List;
+    use std::sync::Barrier;
+    use std::thread;
+
+    fn list_from(v: &[i32]) -> List<i32> {
+        v.iter()
+            .rev()
+            .fold(List::new(), |list, &elem| list.prepend(elem))
+    }
+
+    #[test]
+    fn basics() {
+        let list = List::new();
+        assert_eq!(list.head(), None);
+
+        let list = list.prepend(1).prepend(2).prepend(3);
+        assert_eq!(list.head(), Some(&3));
+
+        let list = list.tail();
+        assert_eq!(list.head(), Some(&2));
+
+        let list = list.tail();
+        assert_eq!(list.head(), Some(&1));
+
+        let list = list.tail();
+        assert_eq!(list.head(), None);
+        assert!(list.is_empty());
+
+        let list = list.tail();
+        assert_eq!(list.head(), None);
+    }
+
+    #[test]
+    fn iter() {
+        let list = List::new().prepend(1).prepend(2).prepend(3);
+
+        let mut iter = list.iter();
+        assert_eq!(iter.next(), Some(&3));
+        assert_eq!(iter.next(), Some(&2));
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn reverse_append_map() {
+        let a = list_from(&[1, 2, 3]);
+        let b = list_from(&[4, 5]);
+
+        assert_eq!(a.reverse(), list_from(&[3, 2, 1]));
+        assert_eq!(List::<i32>::new().reverse(), List::new());
+
+        let ab = a.append(&b);
+        assert_eq!(ab, list_from(&[1, 2, 3, 4, 5]));
+        assert_eq!(a.append(&List::new()), a);
+        assert_eq!(List::new().append(&b), b);
+
+        // the suffix is shared rather than copied
+        let shared = ab.tail().tail().tail();
+        assert!(std::ptr::eq(shared.head().unwrap(), b.head().unwrap()));
+
+        // the originals are unchanged
+        assert_eq!(a, list_from(&[1, 2, 3]));
+        assert_eq!(b, list_from(&[4, 5]));
+
+        assert_eq!(a.map(|x| x * 10), list_from(&[10, 20, 30]));
+        assert_eq!(
+            format!("{:?}", a.map(|x| x.to_string())),
+            r#"["1", "2", "3"]"#
+        );
+    }
+
+    #[test]
+    fn equality() {
+        assert_eq!(List::<i32>::new(), List::new());
+        assert_eq!(list_from(&[1, 2]), list_from(&[1, 2]));
+        assert_ne!(list_from(&[1, 2]), list_from(&[1]));
+        assert_ne!(list_from(&[1]), list_from(&[1, 2]));
+        assert_ne!(list_from(&[1, 2]), list_from(&[2, 1]));
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        let history = list_from(&[3, 2, 1]);
+
+        let lists: Vec<List<i32>> = thread::scope(|s| {
+            let workers: Vec<_> = (0..4)
+                .map(|n| {
+                    let history = history.clone();
+                    s.spawn(move || history.prepend(10 + n))
+                })
+                .collect();
+            workers.into_iter().map(|w| w.join().unwrap()).collect()
+        });
+
+        for (n, list) in (0..).zip(&lists) {
+            assert_eq!(list.head(), Some(&(10 + n)));
+            assert_eq!(list.tail(), history);
+        }
+        assert_eq!(history, list_from(&[3, 2, 1]));
+    }
+
+    #[test]
+    fn long_list_drops_without_recursion() {
+        let list = (0..200_000).fold(List::new(), |list, n| list.prepend(n));
+        assert_eq!(list.iter().count(), 200_000);
+
+        // the last handles on the long shared tail are all dropped at once; a recursive drop
+        // of 200k nodes would overflow a spawned thread's stack
+        let handles: Vec<List<i32>> = (0..4).map(|n| list.prepend(n)).collect();
+        drop(list);
+        let barrier = Barrier::new(handles.len());
+        thread::scope(|s| {
+            for handle in handles {
+                let barrier = &barrier;
+                s.spawn(move || {
+                    barrier.wait();
+                    drop(handle);
+                });
+            }
+        });
+    }
+}
@@ -0,0 +1,343 @@
+use crate::third::{self, List};
+use std::fmt;
+
+/// A persistent double-ended queue: the front half is a list in order, the back half is a list
+/// in reverse, so both ends are a list head away. When one half outgrows the other by more than
+/// `BALANCE` times, the two are rebuilt to an even split.
+///
+/// Every operation returns a new deque and leaves `self` untouched. Pushes and pops are amortised
+/// O(1) as long as each version is only built on once; building on an old version just before a
+/// rebalance repeats that rebalance, which costs O(len).
+pub struct Deque<T> {
+    front: List<T>,
+    front_len: usize,
+    back: List<T>,
+    back_len: usize,
+}
+
+const BALANCE: usize = 3;
+
+impl<T> Deque<T> {
+    pub fn new() -> Self {
+        Deque {
+            front: List::new(),
+            front_len: 0,
+            back: List::new(),
+            back_len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.front_len + self.back_len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The balance invariant means that if one half is empty, the other has at most one element.
+    pub fn front(&self) -> Option<&T> {
+        self.front.head().or_else(|| self.back.head())
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        self.back.head().or_else(|| self.front.head())
+    }
+
+    /// Walks the front half in place. The back half is stored in reverse, so the first time
+    /// iteration reaches it, it is collected into a `Vec` of references: O(1) to start, O(len)
+    /// overall.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            front: self.front.iter(),
+            back: Some(&self.back),
+            reversed: Vec::new(),
+        }
+    }
+}
+
+impl<T: Clone> Deque<T> {
+    pub fn push_front(&self, elem: T) -> Deque<T> {
+        Deque {
+            front: self.front.prepend(elem),
+            front_len: self.front_len + 1,
+            back: self.back.clone(),
+            back_len: self.back_len,
+        }
+        .balance()
+    }
+
+    pub fn push_back(&self, elem: T) -> Deque<T> {
+        Deque {
+            front: self.front.clone(),
+            front_len: self.front_len,
+            back: self.back.prepend(elem),
+            back_len: self.back_len + 1,
+        }
+        .balance()
+    }
+
+    /// The deque without its front element; an empty deque stays empty.
+    pub fn pop_front(&self) -> Deque<T> {
+        if self.front_len == 0 {
+            // at most one element, and it is in the back half
+            return Deque::new();
+        }
+
+        Deque {
+            front: self.front.tail(),
+            front_len: self.front_len - 1,
+            back: self.back.clone(),
+            back_len: self.back_len,
+        }
+        .balance()
+    }
+
+    /// The deque without its back element; an empty deque stays empty.
+    pub fn pop_back(&self) -> Deque<T> {
+        if self.back_len == 0 {
+            return Deque::new();
+        }
+
+        Deque {
+            front: self.front.clone(),
+            front_len: self.front_len,
+            back: self.back.tail(),
+            back_len: self.back_len - 1,
+        }
+        .balance()
+    }
+
+    fn balance(self) -> Deque<T> {
+        let len = self.len();
+        if self.front_len > BALANCE * self.back_len + 1 {
+            // keep the first half of the front, and move the rest onto the end of the back:
+            //
+            //     front: 1 2 3 4 5    back: 6       ->    front: 1 2 3    back: 6 5 4
+            //
+            let front_len = len.div_ceil(2);
+            let moved = reverse(&skip(&self.front, front_len));
+            Deque {
+                front: take(&self.front, front_len),
+                front_len,
+                back: append(&self.back, &moved),
+                back_len: len - front_len,
+            }
+        } else if self.back_len > BALANCE * self.front_len + 1 {
+            let back_len = len.div_ceil(2);
+            let moved = reverse(&skip(&self.back, back_len));
+            Deque {
+                front: append(&self.front, &moved),
+                front_len: len - back_len,
+                back: take(&self.back, back_len),
+                back_len,
+            }
+        } else {
+            self
+        }
+    }
+}
+
+/// A copy of the first `n` elements of `list`
+fn take<T: Clone>(list: &List<T>, n: usize) -> List<T> {
+    let prefix: Vec<&T> = list.iter().take(n).collect();
+    prefix
+        .into_iter()
+        .rev()
+        .fold(List::new(), |list, elem| list.prepend(elem.clone()))
+}
+
+/// The list after its first `n` elements, sharing its nodes
+fn skip<T>(list: &List<T>, n: usize) -> List<T> {
+    (0..n).fold(list.clone(), |list, _| list.tail())
+}
+
+fn reverse<T: Clone>(list: &List<T>) -> List<T> {
+    list.iter()
+        .fold(List::new(), |list, elem| list.prepend(elem.clone()))
+}
+
+/// `a` followed by `b`, copying `a` and sharing `b`
+fn append<T: Clone>(a: &List<T>, b: &List<T>) -> List<T> {
+    let prefix: Vec<&T> = a.iter().collect();
+    prefix
+        .into_iter()
+        .rev()
+        .fold(b.clone(), |list, elem| list.prepend(elem.clone()))
+}
+
+impl<T> Default for Deque<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for Deque<T> {
+    fn clone(&self) -> Self {
+        Deque {
+            front: self.front.clone(),
+            front_len: self.front_len,
+            back: self.back.clone(),
+            back_len: self.back_len,
+        }
+    }
+}
+
+impl<T: Clone> FromIterator<T> for Deque<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(Deque::new(), |deque, elem| deque.push_back(elem))
+    }
+}
+
+impl<T: PartialEq> PartialEq for Deque<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other)
+    }
+}
+
+impl<T: Eq> Eq for Deque<T> {}
+
+impl<T: fmt::Debug> fmt::Debug for Deque<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self).finish()
+    }
+}
+
+pub struct Iter<'a, T> {
+    front: third::Iter<'a, T>,
+    /// The back half, until iteration reaches it
+    back: Option<&'a List<T>>,
+    /// The rest of the back half, last element first
+    reversed: Vec<&'a T>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(elem) = self.front.next() {
+            return Some(elem);
+        }
+        if let Some(back) = self.back.take() {
+            self.reversed = back.iter().collect();
+        }
+        self.reversed.pop()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Deque<T> {
+    type IntoIter = Iter<'a, T>;
+    type Item = &'a T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Deque;
+    use std::collections::VecDeque;
+
+    fn contents(deque: &Deque<i32>) -> Vec<i32> {
+        deque.iter().cloned().collect()
+    }
+
+    #[test]
+    fn basics() {
+        let deque = Deque::new();
+        assert_eq!(deque.front(), None);
+        assert_eq!(deque.back(), None);
+        assert!(deque.pop_front().is_empty());
+        assert!(deque.pop_back().is_empty());
+
+        let one = deque.push_back(1);
+        assert_eq!(one.front(), Some(&1));
+        assert_eq!(one.back(), Some(&1));
+        assert!(one.pop_front().is_empty());
+        assert!(one.pop_back().is_empty());
+
+        let deque = one.push_back(2).push_front(0).push_back(3);
+        assert_eq!(contents(&deque), &[0, 1, 2, 3]);
+        assert_eq!(deque.front(), Some(&0));
+        assert_eq!(deque.back(), Some(&3));
+
+        // stopping part way through either half
+        assert_eq!(deque.iter().next(), Some(&0));
+        let first_three: Vec<i32> = deque.iter().take(3).cloned().collect();
+        assert_eq!(first_three, &[0, 1, 2]);
+
+        let deque = deque.pop_front().pop_back();
+        assert_eq!(contents(&deque), &[1, 2]);
+        assert_eq!(deque.len(), 2);
+    }
+
+    #[test]
+    fn one_sided_use() {
+        // pushing at one end and popping at the other forces the halves to rebalance
+        let mut deque = Deque::new();
+        for n in 0..100 {
+            deque = deque.push_back(n);
+        }
+        for n in 0..100 {
+            assert_eq!(deque.front(), Some(&n));
+            deque = deque.pop_front();
+        }
+        assert!(deque.is_empty());
+
+        for n in 0..100 {
+            deque = deque.push_front(n);
+        }
+        for n in 0..100 {
+            assert_eq!(deque.back(), Some(&n));
+            deque = deque.pop_back();
+        }
+        assert!(deque.is_empty());
+    }
+
+    #[test]
+    fn old_versions_are_unchanged() {
+        let mut versions = vec![(Deque::new(), VecDeque::new())];
+
+        // each step builds on an earlier version, checking every version still matches its model
+        for n in 0..400 {
+            let (deque, mut model) = versions[(n * 7919) % versions.len()].clone();
+            let deque = match n % 5 {
+                0 | 1 => {
+                    model.push_back(n as i32);
+                    deque.push_back(n as i32)
+                }
+                2 => {
+                    model.push_front(n as i32);
+                    deque.push_front(n as i32)
+                }
+                3 => {
+                    model.pop_front();
+                    deque.pop_front()
+                }
+                _ => {
+                    model.pop_back();
+                    deque.pop_back()
+                }
+            };
+            assert_eq!(deque.front(), model.front());
+            assert_eq!(deque.back(), model.back());
+            versions.push((deque, model));
+        }
+
+        for (deque, model) in &versions {
+            assert_eq!(deque.len(), model.len());
+            assert!(deque.iter().eq(model));
+        }
+    }
+
+    #[test]
+    fn equality() {
+        let a: Deque<i32> = (1..=5).collect();
+        let b = Deque::new().push_front(3).push_front(2).push_front(1);
+        let b = b.push_back(4).push_back(5);
+        assert_eq!(a, b);
+        assert_ne!(a, b.pop_back());
+        assert_eq!(format!("{:?}", a), "[1, 2, 3, 4, 5]");
+    }
+}
@@ -0,0 +1,383 @@
+use crate::third::{self, List};
+use std::fmt;
+use std::rc::Rc;
+
+/// A persistent list with O(log n) indexing and update, as a skew binary number: a list of
+/// complete binary trees, each of size 2^k - 1, in increasing size. Only the two smallest
+/// trees may be the same size.
+///
+/// Pushing onto the front either merges the two smallest trees under the new element or adds a
+/// new tree of one; popping the front splits the first tree back into its two halves. Both are
+/// O(1).
+pub struct RandomAccessList<T> {
+    trees: List<Digit<T>>,
+    len: usize,
+}
+
+struct Digit<T> {
+    size: usize,
+    tree: Rc<Tree<T>>,
+}
+
+/// Elements are in preorder: a node's element comes before everything in its left half, which
+/// comes before everything in its right half.
+enum Tree<T> {
+    Leaf(T),
+    Node(T, Rc<Tree<T>>, Rc<Tree<T>>),
+}
+
+impl<T> Clone for Digit<T> {
+    fn clone(&self) -> Self {
+        Digit {
+            size: self.size,
+            tree: self.tree.clone(),
+        }
+    }
+}
+
+impl<T> Tree<T> {
+    fn elem(&self) -> &T {
+        match self {
+            Tree::Leaf(elem) | Tree::Node(elem, _, _) => elem,
+        }
+    }
+
+    fn get(&self, mut size: usize, mut index: usize) -> &T {
+        let mut tree = self;
+        while index > 0 {
+            match tree {
+                Tree::Leaf(_) => unreachable!("index past the end of a tree"),
+                Tree::Node(_, left, right) => {
+                    size /= 2;
+                    index -= 1;
+                    if index < size {
+                        tree = left;
+                    } else {
+                        index -= size;
+                        tree = right;
+                    }
+                }
+            }
+        }
+        tree.elem()
+    }
+
+    /// A copy of the path down to `index` with the element replaced, sharing everything else
+    fn update(&self, size: usize, index: usize, elem: T) -> Tree<T>
+    where
+        T: Clone,
+    {
+        match self {
+            Tree::Leaf(_) => Tree::Leaf(elem),
+            Tree::Node(_, left, right) if index == 0 => {
+                Tree::Node(elem, left.clone(), right.clone())
+            }
+            Tree::Node(x, left, right) => {
+                let half = size / 2;
+                if index - 1 < half {
+                    let left = left.update(half, index - 1, elem);
+                    Tree::Node(x.clone(), Rc::new(left), right.clone())
+                } else {
+                    let right = right.update(half, index - 1 - half, elem);
+                    Tree::Node(x.clone(), left.clone(), Rc::new(right))
+                }
+            }
+        }
+    }
+}
+
+impl<T> RandomAccessList<T> {
+    pub fn new() -> Self {
+        RandomAccessList {
+            trees: List::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// O(1)
+    pub fn push_front(&self, elem: T) -> RandomAccessList<T> {
+        let rest = self.trees.tail();
+        let trees = match (self.trees.head(), rest.head()) {
+            (Some(first), Some(second)) if first.size == second.size => {
+                rest.tail().prepend(Digit {
+                    size: 1 + first.size + second.size,
+                    tree: Rc::new(Tree::Node(elem, first.tree.clone(), second.tree.clone())),
+                })
+            }
+            _ => self.trees.prepend(Digit {
+                size: 1,
+                tree: Rc::new(Tree::Leaf(elem)),
+            }),
+        };
+
+        RandomAccessList {
+            trees,
+            len: self.len + 1,
+        }
+    }
+
+    /// O(1)
+    pub fn front(&self) -> Option<&T> {
+        self.trees.head().map(|digit| digit.tree.elem())
+    }
+
+    /// The list without its front element; an empty list stays empty. O(1)
+    pub fn pop_front(&self) -> RandomAccessList<T> {
+        let Some(first) = self.trees.head() else {
+            return RandomAccessList::new();
+        };
+
+        let trees = match &*first.tree {
+            Tree::Leaf(_) => self.trees.tail(),
+            Tree::Node(_, left, right) => {
+                let size = first.size / 2;
+                self.trees
+                    .tail()
+                    .prepend(Digit {
+                        size,
+                        tree: right.clone(),
+                    })
+                    .prepend(Digit {
+                        size,
+                        tree: left.clone(),
+                    })
+            }
+        };
+
+        RandomAccessList {
+            trees,
+            len: self.len - 1,
+        }
+    }
+
+    /// O(log n)
+    pub fn get(&self, mut index: usize) -> Option<&T> {
+        for digit in self.trees.iter() {
+            if index < digit.size {
+                return Some(digit.tree.get(digit.size, index));
+            }
+            index -= digit.size;
+        }
+        None
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            trees: self.trees.iter(),
+            stack: Vec::new(),
+        }
+    }
+}
+
+impl<T: Clone> RandomAccessList<T> {
+    /// A new list with the element at `index` replaced. Copies the O(log n) elements on the way
+    /// down to it and shares the rest. Panics if `index` is out of bounds.
+    pub fn update(&self, index: usize, elem: T) -> RandomAccessList<T> {
+        assert!(index < self.len, "index out of bounds");
+
+        // the trees before the one holding `index` are rebuilt in front of the new tree
+        let mut before = Vec::new();
+        let mut rest = self.trees.clone();
+        let mut index = index;
+        loop {
+            let digit = rest.head().unwrap().clone();
+            rest = rest.tail();
+            if index < digit.size {
+                let tree = digit.tree.update(digit.size, index, elem);
+                let mut trees = rest.prepend(Digit {
+                    size: digit.size,
+                    tree: Rc::new(tree),
+                });
+                for digit in before.into_iter().rev() {
+                    trees = trees.prepend(digit);
+                }
+                return RandomAccessList {
+                    trees,
+                    len: self.len,
+                };
+            }
+            index -= digit.size;
+            before.push(digit);
+        }
+    }
+}
+
+impl<T> Default for RandomAccessList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for RandomAccessList<T> {
+    fn clone(&self) -> Self {
+        RandomAccessList {
+            trees: self.trees.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<T> FromIterator<T> for RandomAccessList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let elems: Vec<T> = iter.into_iter().collect();
+        elems
+            .into_iter()
+            .rev()
+            .fold(RandomAccessList::new(), |list, elem| list.push_front(elem))
+    }
+}
+
+impl<T: PartialEq> PartialEq for RandomAccessList<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other)
+    }
+}
+
+impl<T: Eq> Eq for RandomAccessList<T> {}
+
+impl<T: fmt::Debug> fmt::Debug for RandomAccessList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self).finish()
+    }
+}
+
+pub struct Iter<'a, T> {
+    trees: third::Iter<'a, Digit<T>>,
+    stack: Vec<&'a Tree<T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        let tree = match self.stack.pop() {
+            Some(tree) => tree,
+            None => &self.trees.next()?.tree,
+        };
+
+        if let Tree::Node(_, left, right) = tree {
+            self.stack.push(right);
+            self.stack.push(left);
+        }
+        Some(tree.elem())
+    }
+}
+
+impl<'a, T> IntoIterator for &'a RandomAccessList<T> {
+    type IntoIter = Iter<'a, T>;
+    type Item = &'a T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RandomAccessList;
+
+    fn contents(list: &RandomAccessList<i32>) -> Vec<i32> {
+        list.iter().cloned().collect()
+    }
+
+    #[test]
+    fn basics() {
+        let list = RandomAccessList::new();
+        assert_eq!(list.front(), None);
+        assert_eq!(list.get(0), None);
+        assert!(list.pop_front().is_empty());
+
+        let list = list.push_front(3).push_front(2).push_front(1);
+        assert_eq!(list.front(), Some(&1));
+        assert_eq!(list.len(), 3);
+        assert_eq!(contents(&list), &[1, 2, 3]);
+
+        let list = list.pop_front();
+        assert_eq!(list.front(), Some(&2));
+        let list = list.pop_front().pop_front();
+        assert!(list.is_empty());
+        assert_eq!(list.front(), None);
+    }
+
+    #[test]
+    fn indexing() {
+        // every size up to a few full trees, so each shape of the spine is covered
+        for len in 0..70 {
+            let list: RandomAccessList<i32> = (0..len).collect();
+            assert_eq!(list.len(), len as usize);
+            assert_eq!(contents(&list), (0..len).collect::<Vec<_>>());
+            for i in 0..len {
+                assert_eq!(list.get(i as usize), Some(&i));
+            }
+            assert_eq!(list.get(len as usize), None);
+
+            for i in 0..len {
+                let updated = list.update(i as usize, -1);
+                let mut expected: Vec<i32> = (0..len).collect();
+                expected[i as usize] = -1;
+                assert_eq!(contents(&updated), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn old_versions_are_unchanged() {
+        let mut versions = vec![(RandomAccessList::new(), Vec::new())];
+
+        // each step builds on an earlier version, checking every version still matches its model
+        for n in 0..400 {
+            let (list, mut model) = versions[(n * 7919) % versions.len()].clone();
+            let list = match n % 4 {
+                0 | 1 => {
+                    model.insert(0, n as i32);
+                    list.push_front(n as i32)
+                }
+                2 if !model.is_empty() => {
+                    model.remove(0);
+                    list.pop_front()
+                }
+                _ if !model.is_empty() => {
+                    let i = n % model.len();
+                    model[i] = -(n as i32);
+                    list.update(i, -(n as i32))
+                }
+                _ => list,
+            };
+            assert_eq!(list.front(), model.first());
+            versions.push((list, model));
+        }
+
+        for (list, model) in &versions {
+            assert_eq!(list.len(), model.len());
+            assert!(list.iter().eq(model));
+            for (i, elem) in model.iter().enumerate() {
+                assert_eq!(list.get(i), Some(elem));
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn update_out_of_bounds() {
+        RandomAccessList::new().push_front(1).update(1, 2);
+    }
+
+    #[test]
+    fn equality() {
+        let a: RandomAccessList<i32> = (1..=5).collect();
+        let b = (1..=5)
+            .rev()
+            .fold(RandomAccessList::new(), |list, n| list.push_front(n));
+        assert_eq!(a, b);
+        assert_ne!(a, b.pop_front());
+        assert_ne!(a, b.update(4, 6));
+        assert_eq!(format!("{:?}", a), "[1, 2, 3, 4, 5]");
+    }
+}
@@ -0,0 +1,111 @@
+//! Interpreter loop benchmarks. Run with `cargo bench`.
+//!
+//! Each benchmark program is compiled once into a fresh heap with the prelude loaded and then
+//! evaluated, timing only the evaluation. The minimum and mean over a number of runs are
+//! reported.
+
+use std::time::{Duration, Instant};
+
+use writing_interpreters::interpreter::{
+    compiler::compile, memory::Memory, parser::parse_all, prelude::load_prelude, vm::Thread,
+    Mutator, MutatorView, RuntimeError,
+};
+
+/// How many times each program is evaluated
+const RUNS: usize = 10;
+
+/// Benchmark programs: a name, definitions to evaluate untimed and the expression to time
+const BENCHMARKS: &[(&str, &str, &str)] = &[
+    // deep recursion dominated by calls and returns
+    (
+        "fib",
+        "(def fib (n)
+           (cond (is? n 0) 0
+                 (is? n 1) 1
+                 true (+ (fib (- n 1)) (fib (- n 2)))))",
+        "(fib 22)",
+    ),
+    // allocating, walking and reversing lists
+    (
+        "list building",
+        "(def build (n acc)
+           (if (is? n 0) acc (build (- n 1) (cons n acc))))
+         (def churn (times)
+           (set (quote done) 0)
+           (while (nil? (is? done times))
+             (length (reverse (build 200 nil)))
+             (set (quote done) (+ done 1))))",
+        "(churn 1000)",
+    ),
+    // lambdas created per call that capture locals of their enclosing function as upvalues
+    (
+        "closures",
+        "(def adder (n) (lambda (x) (+ x n)))
+         (def scaler (k) (lambda (x) (* k x)))
+         (def apply-all (times xs)
+           (if (is? times 0)
+             xs
+             (apply-all (- times 1) (map (scaler 1) (map (adder times) xs)))))",
+        "(length (apply-all 200 (range 0 200)))",
+    ),
+    // functions that carry their first arguments as partial applications
+    (
+        "partial-application",
+        "(def add (a b) (+ a b))
+         (def scale (k x) (* k x))
+         (def apply-all (times xs)
+           (if (is? times 0)
+             xs
+             (apply-all (- times 1) (map (scale 1) (map (add 0) xs)))))",
+        "(length (apply-all 200 (range 0 200)))",
+    ),
+];
+
+/// Evaluates a program's definitions, then times evaluating its expression
+struct Bench {
+    setup: &'static str,
+    expr: &'static str,
+}
+
+impl Mutator for Bench {
+    type Input = ();
+    type Output = Duration;
+
+    fn run(&self, mem: &MutatorView, _input: ()) -> Result<Duration, RuntimeError> {
+        let thread = Thread::alloc(mem)?;
+        load_prelude(mem, thread)?;
+
+        for value in parse_all(mem, self.setup)? {
+            thread.quick_vm_eval(mem, compile(mem, value)?)?;
+        }
+
+        let mut exprs = parse_all(mem, self.expr)?;
+        let function = compile(mem, exprs.remove(0))?;
+
+        let start = Instant::now();
+        thread.quick_vm_eval(mem, function)?;
+        Ok(start.elapsed())
+    }
+}
+
+fn main() {
+    for (name, setup, expr) in BENCHMARKS {
+        let times: Vec<Duration> = (0..RUNS)
+            .map(|_| {
+                Memory::new()
+                    .mutate(&Bench { setup, expr }, ())
+                    .unwrap_or_else(|err| panic!("benchmark {} failed: {}", name, err))
+            })
+            .collect();
+
+        let min = times.iter().min().unwrap();
+        let mean = times.iter().sum::<Duration>() / RUNS as u32;
+        println!(
+            "{:<20} {:>3} runs   min {:>8.2} ms   mean {:>8.2} ms",
+            name,
+            RUNS,
+            min.as_secs_f64() * 1000.0,
+            mean.as_secs_f64() * 1000.0
+        );
+    }
+}
@@ -0,0 +1,17 @@
+use std::{io, process};
+
+use writing_interpreters::interpreter::lsp::serve;
+
+/// A language server for editors, speaking LSP over stdin and stdout
+fn main() {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+
+    match serve(&mut stdin.lock(), &mut stdout.lock()) {
+        Ok(code) => process::exit(code),
+        Err(err) => {
+            eprintln!("Terminated: {}", err);
+            process::exit(1);
+        }
+    }
+}
@@ -1,95 +1,263 @@
-use std::ptr::NonNull;
+use std::{
+    cell::UnsafeCell,
+    marker::PhantomData,
+    mem::size_of,
+    ptr::{write, NonNull},
+    slice::from_raw_parts_mut,
+};
 
 use crate::memory::{
-    AllocError, AllocHeader, AllocObject, AllocRaw, ArraySize, Mark, RawPtr, SizeClass,
-    StickyImmixHeap,
+    allocator::alloc_size_of, stickyimmix::BLOCK_SIZE, AllocError, AllocHeader, AllocObject,
+    AllocRaw, ArraySize, Block, HeapStats, Mark, RawPtr, SizeClass,
 };
 
-use super::{RuntimeError, TypeList};
+use super::TypeList;
+
+/// The size of the blocks that small and medium objects are allocated into. Objects larger than
+/// this get a block to themselves.
+const ARENA_BLOCK_SIZE: usize = BLOCK_SIZE;
 
-/// Allocation header for an Arena-allocated value
-pub struct ArenaHeader {}
+/// Allocation header for an Arena-allocated value. Arena objects are never collected so no
+/// mark is kept.
+pub struct ArenaHeader {
+    size_class: SizeClass,
+    type_id: TypeList,
+    size_bytes: u32,
+}
 
 impl AllocHeader for ArenaHeader {
     type TypeId = TypeList;
 
-    fn new<O: crate::memory::allocator::AllocObject<Self::TypeId>>(
-        size: u32,
-        size_class: SizeClass,
-        mark: Mark,
-    ) -> Self {
-        ArenaHeader {}
+    fn new<O: AllocObject<Self::TypeId>>(size: u32, size_class: SizeClass, _mark: Mark) -> Self {
+        ArenaHeader {
+            size_class,
+            type_id: O::TYPE_ID,
+            size_bytes: size,
+        }
     }
 
-    fn new_array(
-        size: crate::memory::allocator::ArraySize,
-        size_class: SizeClass,
-        mark: Mark,
-    ) -> Self {
-        ArenaHeader {}
+    fn new_array(size: ArraySize, size_class: SizeClass, _mark: Mark) -> Self {
+        ArenaHeader {
+            size_class,
+            type_id: TypeList::ArrayBackingBytes,
+            size_bytes: size,
+        }
     }
 
-    fn mark(&mut self) {}
+    fn mark(&mut self, _mark: Mark) {}
 
-    fn is_marked(&self) -> bool {
-        true
+    fn get_mark(&self) -> Mark {
+        Mark::Allocated
     }
 
     fn size_class(&self) -> SizeClass {
-        SizeClass::Small
+        self.size_class
     }
 
     fn size(&self) -> u32 {
-        1
+        self.size_bytes
     }
 
     fn type_id(&self) -> Self::TypeId {
-        TypeList::Symbol
+        self.type_id
     }
 }
 
-/// A non-garbage-collected pool of memory blocks for interned values.
+/// A non-garbage-collected pool of memory blocks for values that are never moved or freed
+/// until the Arena is dropped.
 /// These values are not dropped on Arena deallocation.
-/// Values must be "atomic", that is, not composed of other object
-/// pointers that need to be traced.
+/// Values are never traced, so an Arena that outlives other heaps must only
+/// hold "atomic" values, that is, not composed of other object pointers.
 ///
-/// Since symbols are unique strings that can be identified and compared
-/// by their pointer values, these pointer values must remain static
-/// throughout the program lifetime. Thus, Symbol objects cannot be managed
-/// by a heap that might perform object relocation. We need a separate
-/// heap type for objects that are never moved or freed unil the program ends,
-/// the Arena type.
-pub struct Arena {
-    heap: StickyImmixHeap<ArenaHeader>,
+/// The header type is a parameter so that the Arena can also stand in for the garbage collected
+/// heap, in which case it must write the same header type. As in `StickyImmixHeap`, a header
+/// immediately precedes its object.
+pub struct Arena<H = ArenaHeader> {
+    blocks: UnsafeCell<ArenaBlocks>,
+    _header_type: PhantomData<*const H>,
+}
+
+/// The blocks owned by an Arena
+struct ArenaBlocks {
+    /// the block currently being bump allocated into
+    head: Option<Block>,
+    /// offset of the next free byte in the head block
+    cursor: usize,
+    /// filled blocks and blocks holding a single large object
+    rest: Vec<Block>,
+    /// total bytes allocated, including headers
+    allocated: usize,
+}
+
+impl<H> Default for Arena<H> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl Arena {
-    pub fn new() -> Arena {
+impl<H> Arena<H> {
+    pub fn new() -> Arena<H> {
         Arena {
-            heap: StickyImmixHeap::new(),
+            blocks: UnsafeCell::new(ArenaBlocks {
+                head: None,
+                cursor: 0,
+                rest: Vec::new(),
+                allocated: 0,
+            }),
+            _header_type: PhantomData,
+        }
+    }
+
+    /// Return block usage information. Nothing is ever freed, so no block is free and every byte
+    /// allocated is still allocated.
+    pub fn stats(&self) -> HeapStats {
+        let blocks = unsafe { &*self.blocks.get() };
+
+        HeapStats {
+            blocks: blocks.rest.len() + blocks.head.iter().count(),
+            free_blocks: 0,
+            marked_lines: 0,
+            allocated_since_collection: blocks.allocated,
+        }
+    }
+
+    fn find_space(&self, alloc_size: usize) -> Result<*const u8, AllocError> {
+        let blocks = unsafe { &mut *self.blocks.get() };
+        blocks.allocated += alloc_size;
+
+        if alloc_size > ARENA_BLOCK_SIZE {
+            let block = Block::new(alloc_size.next_power_of_two())?;
+            let space = block.as_ptr();
+            blocks.rest.push(block);
+            return Ok(space);
         }
+
+        if let Some(ref head) = blocks.head {
+            if blocks.cursor + alloc_size <= ARENA_BLOCK_SIZE {
+                let space = unsafe { head.as_ptr().add(blocks.cursor) };
+                blocks.cursor += alloc_size;
+                return Ok(space);
+            }
+        }
+
+        let block = Block::new(ARENA_BLOCK_SIZE)?;
+        let space = block.as_ptr();
+        blocks.cursor = alloc_size;
+        if let Some(previous) = blocks.head.replace(block) {
+            blocks.rest.push(previous);
+        }
+
+        Ok(space)
     }
 }
 
-impl AllocRaw for Arena {
-    type Header = ArenaHeader;
+impl<H: AllocHeader> AllocRaw for Arena<H> {
+    type Header = H;
 
     fn alloc<T>(&self, object: T) -> Result<RawPtr<T>, AllocError>
     where
-        T: AllocObject<TypeList>,
+        T: AllocObject<H::TypeId>,
     {
-        self.heap.alloc(object)
+        let header_size = size_of::<H>();
+        let object_size = size_of::<T>();
+
+        let alloc_size = alloc_size_of(header_size + object_size);
+        let size_class = SizeClass::get_for_size(alloc_size)?;
+
+        let space = self.find_space(alloc_size)?;
+        let header = H::new::<T>(object_size as ArraySize, size_class, Mark::Allocated);
+
+        unsafe {
+            write(space as *mut H, header);
+            let object_space = space.add(header_size);
+            write(object_space as *mut T, object);
+            Ok(RawPtr::new(object_space as *const T))
+        }
+    }
+
+    fn alloc_array(&self, size_bytes: ArraySize) -> Result<RawPtr<u8>, AllocError> {
+        let header_size = size_of::<H>();
+
+        let alloc_size = alloc_size_of(header_size + size_bytes as usize);
+        let size_class = SizeClass::get_for_size(alloc_size)?;
+
+        let space = self.find_space(alloc_size)?;
+        let header = H::new_array(size_bytes, size_class, Mark::Allocated);
+
+        unsafe {
+            write(space as *mut H, header);
+            let array_space = space.add(header_size);
+
+            // blocks are not zeroed when they are allocated
+            let array = from_raw_parts_mut(array_space as *mut u8, size_bytes as usize);
+            array.fill(0);
+
+            Ok(RawPtr::new(array_space))
+        }
+    }
+
+    fn get_header(object: NonNull<()>) -> NonNull<Self::Header> {
+        unsafe { NonNull::new_unchecked(object.cast::<H>().as_ptr().offset(-1)) }
+    }
+
+    fn get_object(header: NonNull<Self::Header>) -> NonNull<()> {
+        unsafe { NonNull::new_unchecked(header.as_ptr().offset(1).cast::<()>()) }
     }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::interpreter::{number::Float, pair::Pair};
+
+    #[test]
+    fn alloc_writes_headers() {
+        let arena: Arena = Arena::new();
 
-    fn alloc_array(&self, _size_bytes: ArraySize) -> Result<RawPtr<u8>, AllocError> {
-        unimplemented!()
+        let float = arena.alloc(Float::new(1.5)).unwrap();
+        let header = unsafe { Arena::<ArenaHeader>::get_header(float.as_untyped()).as_ref() };
+        assert_eq!(header.type_id(), TypeList::Float);
+        assert_eq!(header.size() as usize, size_of::<Float>());
+        assert_eq!(header.size_class(), SizeClass::Small);
+
+        let object = Arena::<ArenaHeader>::get_object(Arena::get_header(float.as_untyped()));
+        assert_eq!(object, float.as_untyped());
+        assert_eq!(unsafe { &*float.as_ptr() }.value(), 1.5);
     }
 
-    fn get_header(_object: NonNull<()>) -> NonNull<Self::Header> {
-        unimplemented!()
+    #[test]
+    fn alloc_array_is_zeroed() {
+        let arena: Arena = Arena::new();
+
+        for size in [1, 100, 5000] {
+            let array = arena.alloc_array(size).unwrap();
+            let header = unsafe { Arena::<ArenaHeader>::get_header(array.as_untyped()).as_ref() };
+            assert_eq!(header.type_id(), TypeList::ArrayBackingBytes);
+            assert_eq!(header.size(), size);
+
+            let bytes = unsafe { from_raw_parts_mut(array.as_ptr() as *mut u8, size as usize) };
+            assert!(bytes.iter().all(|byte| *byte == 0));
+            bytes.fill(0xff);
+        }
     }
 
-    fn get_object(_header: NonNull<Self::Header>) -> NonNull<()> {
-        unimplemented!()
+    #[test]
+    fn alloc_large_objects() {
+        let arena: Arena = Arena::new();
+
+        let small = arena.alloc(Pair::new()).unwrap();
+        let large = arena
+            .alloc_array(ARENA_BLOCK_SIZE as ArraySize * 3)
+            .unwrap();
+        let header = unsafe { Arena::<ArenaHeader>::get_header(large.as_untyped()).as_ref() };
+        assert_eq!(header.size_class(), SizeClass::Large);
+
+        // the small object's block is still the head, the large object has its own block
+        let next = arena.alloc(Pair::new()).unwrap();
+        assert_eq!(
+            small.as_word() & !(ARENA_BLOCK_SIZE - 1),
+            next.as_word() & !(ARENA_BLOCK_SIZE - 1)
+        );
+        assert_eq!(arena.stats().blocks, 2);
     }
 }
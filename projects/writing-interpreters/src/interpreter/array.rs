@@ -14,9 +14,11 @@ use super::{
         StackContainer,
     },
     error::ErrorKind,
+    memory::write_barrier,
     printer::Print,
     rawarray::{default_array_growth, RawArray, DEFAULT_ARRAY_SIZE},
     safeptr::{MutatorScope, TaggedCellPtr, TaggedScopedPtr},
+    trace::{Trace, Tracer},
     MutatorView, RuntimeError, ScopedPtr, TypeList,
 };
 
@@ -38,7 +40,7 @@ pub struct Array<T: Sized + Clone> {
 }
 
 /// Internal implementation
-impl<T: Sized + Clone> Array<T> {
+impl<T: Sized + Clone + Trace> Array<T> {
     /// Allocate a new instance on the heap
     pub fn alloc<'guard>(
         mem: &'guard MutatorView,
@@ -122,6 +124,7 @@ impl<T: Sized + Clone> Array<T> {
         unsafe {
             let dest = self.get_offset(index)?;
             write(dest, item);
+            write_barrier(self);
             Ok(&*dest as &T)
         }
     }
@@ -152,7 +155,7 @@ impl<T: Sized + Clone> Array<T> {
     }
 }
 
-impl<T: Sized + Clone> Container<T> for Array<T> {
+impl<T: Sized + Clone + Trace> Container<T> for Array<T> {
     fn new() -> Array<T> {
         Array {
             length: Cell::new(0),
@@ -186,7 +189,7 @@ impl<T: Sized + Clone> Container<T> for Array<T> {
     }
 }
 
-impl<T: Sized + Clone> StackContainer<T> for Array<T> {
+impl<T: Sized + Clone + Trace> StackContainer<T> for Array<T> {
     /// Push can trigger an underlying array resize, hence it requires the ability to allocate    
     fn push<'guard>(&self, mem: &'guard MutatorView, item: T) -> Result<(), RuntimeError> {
         if self.borrow.get() != INTERIOR_ONLY {
@@ -246,7 +249,7 @@ impl<T: Sized + Clone> StackContainer<T> for Array<T> {
     }
 }
 
-impl<T: Sized + Clone> IndexedContainer<T> for Array<T> {
+impl<T: Sized + Clone + Trace> IndexedContainer<T> for Array<T> {
     fn get<'guard>(
         &self,
         guard: &'guard dyn MutatorScope,
@@ -266,7 +269,7 @@ impl<T: Sized + Clone> IndexedContainer<T> for Array<T> {
     }
 }
 
-impl<T: Clone + Sized> ContainerFromSlice<T> for Array<T>
+impl<T: Clone + Sized + Trace> ContainerFromSlice<T> for Array<T>
 where
     Array<T>: AllocObject<TypeList>,
 {
@@ -310,12 +313,14 @@ impl StackAnyContainer for Array<TaggedCellPtr> {
     }
 }
 
-impl<T: Sized + Clone> SliceableContainer<T> for Array<T> {
+impl<T: Sized + Clone + Trace> SliceableContainer<T> for Array<T> {
     fn access_slice<'guard, F, R>(&self, guard: &'guard dyn MutatorScope, f: F) -> R
     where
         F: FnOnce(&mut [T]) -> R,
     {
         self.borrow.set(EXPOSED_MUTABLY);
+        // items may be overwritten through the slice without going through a barrier
+        write_barrier(self);
         let slice = unsafe { self.as_slice(guard) };
         let result = f(slice);
         self.borrow.set(INTERIOR_ONLY);
@@ -341,6 +346,18 @@ impl AnyContainerFromSlice for Array<TaggedCellPtr> {
     }
 }
 
+impl<T: Sized + Clone + Trace> Trace for Array<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        if let Some(ptr) = self.data.get().as_ptr() {
+            tracer.mark_array_backing(ptr);
+        }
+
+        for item in unsafe { self.as_slice(tracer) }.iter() {
+            item.trace(tracer);
+        }
+    }
+}
+
 /// Array of u8
 pub type ArrayU8 = Array<u8>;
 /// Array of u16
@@ -400,7 +417,7 @@ impl IndexedAnyContainer for Array<TaggedCellPtr> {
     }
 }
 
-impl<T: Sized + Clone> FillContainer<T> for Array<T> {
+impl<T: Sized + Clone + Trace> FillContainer<T> for Array<T> {
     fn fill<'guard>(
         &self,
         mem: &'guard MutatorView,
@@ -418,9 +435,9 @@ impl<T: Sized + Clone> FillContainer<T> for Array<T> {
 
             if size > capacity {
                 if capacity == 0 {
-                    array.resize(mem, DEFAULT_ARRAY_SIZE)?;
+                    array.resize(mem, DEFAULT_ARRAY_SIZE.max(size))?;
                 } else {
-                    array.resize(mem, default_array_growth(capacity)?)?;
+                    array.resize(mem, default_array_growth(capacity)?.max(size))?;
                 }
                 // Replace the struct's copy with the resized RawArray object
                 self.data.set(array);
@@ -455,9 +472,9 @@ impl FillAnyContainer for Array<TaggedCellPtr> {
 
             if size > capacity {
                 if capacity == 0 {
-                    array.resize(mem, DEFAULT_ARRAY_SIZE)?;
+                    array.resize(mem, DEFAULT_ARRAY_SIZE.max(size))?;
                 } else {
-                    array.resize(mem, default_array_growth(capacity)?)?;
+                    array.resize(mem, default_array_growth(capacity)?.max(size))?;
                 }
                 // Replace the struct's copy with the resized RawArray object
                 self.data.set(array);
@@ -1,4 +1,4 @@
-use std::cell::Cell;
+use std::{cell::Cell, fmt};
 
 use crate::memory::ArraySize;
 
@@ -7,8 +7,8 @@ use super::{
     containers::{Container, IndexedContainer, StackAnyContainer, StackContainer},
     error::err_eval,
     list::List,
-    safeptr::{MutatorScope, TaggedScopedPtr},
-    taggedptr::TaggedPtr,
+    safeptr::{MutatorScope, TaggedCellPtr, TaggedScopedPtr},
+    trace::{Trace, Tracer},
     CellPtr, MutatorView, RuntimeError, ScopedPtr,
 };
 
@@ -72,6 +72,7 @@ impl ByteCode {
             Opcode::Jump { offset: _ } => Opcode::Jump { offset },
             Opcode::JumpIfTrue { test, offset: _ } => Opcode::JumpIfTrue { test, offset },
             Opcode::JumpIfNotTrue { test, offset: _ } => Opcode::JumpIfNotTrue { test, offset },
+            Opcode::Protect { dest, offset: _ } => Opcode::Protect { dest, offset },
             _ => {
                 return Err(err_eval(
                     "Cannot modify jump offset for non-jump instruction",
@@ -108,6 +109,31 @@ impl ByteCode {
         )
     }
 
+    /// Return the instruction sequence
+    pub fn opcodes(&self) -> &ArrayOpcode {
+        &self.code
+    }
+
+    /// Return the literals list
+    pub fn literals(&self) -> &Literals {
+        &self.literals
+    }
+
+    /// Return the instructions and literals as slices for the interpreter loop to index
+    /// directly.
+    ///
+    /// # Safety
+    ///
+    /// Pushing more instructions or literals may reallocate their arrays and invalidate the
+    /// slices, so nothing may be pushed while they are held. Compiled code is never modified,
+    /// so the slices of a Function's ByteCode remain valid for the mutator scope.
+    pub unsafe fn as_slices<'guard>(
+        &'guard self,
+        guard: &'guard dyn MutatorScope,
+    ) -> (&'guard [Opcode], &'guard [TaggedCellPtr]) {
+        (self.code.as_slice(guard), self.literals.as_slice(guard))
+    }
+
     /// Get the index into the bytecode array of the next instruction that will be pushed
     pub fn next_instruction(&self) -> ArraySize {
         self.code.length()
@@ -117,10 +143,43 @@ impl ByteCode {
     pub fn last_instruction(&self) -> ArraySize {
         self.code.length() - 1
     }
+
+    /// Write a listing of the instructions, one per line with its index, followed by the
+    /// literals. Jumps are annotated with the index of their target.
+    pub fn disassemble<'guard>(
+        &self,
+        guard: &'guard dyn MutatorScope,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        let (code, literals) = unsafe { self.as_slices(guard) };
+
+        for (index, opcode) in code.iter().enumerate() {
+            write!(f, "\n{:>5}  {:?}", index, opcode)?;
+
+            match opcode {
+                Opcode::Jump { offset }
+                | Opcode::JumpIfTrue { offset, .. }
+                | Opcode::JumpIfNotTrue { offset, .. }
+                | Opcode::Protect { offset, .. } => {
+                    write!(f, " -> {}", index as isize + 1 + *offset as isize)?
+                }
+                _ => (),
+            }
+        }
+
+        if !literals.is_empty() {
+            write!(f, "\n  literals:")?;
+            for (index, literal) in literals.iter().enumerate() {
+                write!(f, "\n{:>5}  {}", index, literal.get(guard))?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 // 4 bytes (1 byte enum tag + 3 bytes of data)
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug)]
 pub enum Opcode {
     Add {
         // 3 bytes
@@ -128,6 +187,37 @@ pub enum Opcode {
         left: Register,
         right: Register,
     },
+    Subtract {
+        dest: Register,
+        left: Register,
+        right: Register,
+    },
+    Multiply {
+        dest: Register,
+        left: Register,
+        right: Register,
+    },
+    Divide {
+        dest: Register,
+        left: Register,
+        right: Register,
+    },
+    Floor {
+        dest: Register,
+        reg: Register,
+    },
+    Round {
+        dest: Register,
+        reg: Register,
+    },
+    ToInteger {
+        dest: Register,
+        reg: Register,
+    },
+    ToFloat {
+        dest: Register,
+        reg: Register,
+    },
     LoadLiteral {
         // 3 bytes
         dest: Register,
@@ -210,10 +300,305 @@ pub enum Opcode {
         dest: Register,
         arg_count: NumArgs,
     },
+    IsEqual {
+        dest: Register,
+        test1: Register,
+        test2: Register,
+    },
+    MakeRecord {
+        dest: Register,
+        descriptor: Register,
+        first: Register,
+    },
+    IsRecord {
+        dest: Register,
+        test: Register,
+        descriptor: Register,
+    },
+    CheckRecord {
+        record: Register,
+        descriptor: Register,
+    },
+    GetField {
+        dest: Register,
+        record: Register,
+        index: u8,
+    },
+    SetField {
+        record: Register,
+        index: u8,
+        src: Register,
+    },
+    Yield {
+        dest: Register,
+        value: Register,
+    },
+    Protect {
+        dest: Register,
+        offset: JumpOffset,
+    },
+    Unprotect,
+    Fail {
+        reason: Register,
+        pos: Register,
+    },
+    RegisterTest {
+        src: Register,
+        name: Register,
+    },
+}
+
+impl Opcode {
+    /// Return the name of the instruction, without operands
+    pub fn name(&self) -> &'static str {
+        match self {
+            Opcode::Add { .. } => "Add",
+            Opcode::Subtract { .. } => "Subtract",
+            Opcode::Multiply { .. } => "Multiply",
+            Opcode::Divide { .. } => "Divide",
+            Opcode::Floor { .. } => "Floor",
+            Opcode::Round { .. } => "Round",
+            Opcode::ToInteger { .. } => "ToInteger",
+            Opcode::ToFloat { .. } => "ToFloat",
+            Opcode::LoadLiteral { .. } => "LoadLiteral",
+            Opcode::Jump { .. } => "Jump",
+            Opcode::JumpIfTrue { .. } => "JumpIfTrue",
+            Opcode::JumpIfNotTrue { .. } => "JumpIfNotTrue",
+            Opcode::MakeClosure { .. } => "MakeClosure",
+            Opcode::GetUpvalue { .. } => "GetUpvalue",
+            Opcode::SetUpvalue { .. } => "SetUpvalue",
+            Opcode::CloseUpvalues { .. } => "CloseUpvalues",
+            Opcode::Return { .. } => "Return",
+            Opcode::LoadNil { .. } => "LoadNil",
+            Opcode::LoadGlobal { .. } => "LoadGlobal",
+            Opcode::IsAtom { .. } => "IsAtom",
+            Opcode::IsNil { .. } => "IsNil",
+            Opcode::FirstOfPair { .. } => "FirstOfPair",
+            Opcode::SecondOfPair { .. } => "SecondOfPair",
+            Opcode::MakePair { .. } => "MakePair",
+            Opcode::IsIdentical { .. } => "IsIdentical",
+            Opcode::StoreGlobal { .. } => "StoreGlobal",
+            Opcode::CopyRegister { .. } => "CopyRegister",
+            Opcode::Call { .. } => "Call",
+            Opcode::IsEqual { .. } => "IsEqual",
+            Opcode::MakeRecord { .. } => "MakeRecord",
+            Opcode::IsRecord { .. } => "IsRecord",
+            Opcode::CheckRecord { .. } => "CheckRecord",
+            Opcode::GetField { .. } => "GetField",
+            Opcode::SetField { .. } => "SetField",
+            Opcode::Yield { .. } => "Yield",
+            Opcode::Protect { .. } => "Protect",
+            Opcode::Unprotect => "Unprotect",
+            Opcode::Fail { .. } => "Fail",
+            Opcode::RegisterTest { .. } => "RegisterTest",
+        }
+    }
+
+    /// Encode the instruction as four bytes: the variant followed by its operands, 16 bit operands
+    /// in little endian order
+    pub fn encode(&self) -> [u8; 4] {
+        let wide = |variant, reg: u8, operand: u16| {
+            let [low, high] = operand.to_le_bytes();
+            [variant, reg, low, high]
+        };
+
+        match *self {
+            Opcode::Add { dest, left, right } => [0, dest, left, right],
+            Opcode::Subtract { dest, left, right } => [1, dest, left, right],
+            Opcode::Multiply { dest, left, right } => [2, dest, left, right],
+            Opcode::Divide { dest, left, right } => [3, dest, left, right],
+            Opcode::Floor { dest, reg } => [4, dest, reg, 0],
+            Opcode::Round { dest, reg } => [5, dest, reg, 0],
+            Opcode::ToInteger { dest, reg } => [6, dest, reg, 0],
+            Opcode::ToFloat { dest, reg } => [7, dest, reg, 0],
+            Opcode::LoadLiteral { dest, literal } => wide(8, dest, literal),
+            Opcode::Jump { offset } => wide(9, 0, offset as u16),
+            Opcode::JumpIfTrue { test, offset } => wide(10, test, offset as u16),
+            Opcode::JumpIfNotTrue { test, offset } => wide(11, test, offset as u16),
+            Opcode::MakeClosure { dest, function } => [12, dest, function, 0],
+            Opcode::GetUpvalue { dest, src } => [13, dest, src, 0],
+            Opcode::SetUpvalue { dest, src } => [14, dest, src, 0],
+            Opcode::CloseUpvalues { reg1, reg2, reg3 } => [15, reg1, reg2, reg3],
+            Opcode::Return { reg } => [16, reg, 0, 0],
+            Opcode::LoadNil { dest } => [17, dest, 0, 0],
+            Opcode::LoadGlobal { dest, name } => [18, dest, name, 0],
+            Opcode::IsAtom { dest, test } => [19, dest, test, 0],
+            Opcode::IsNil { dest, test } => [20, dest, test, 0],
+            Opcode::FirstOfPair { dest, reg } => [21, dest, reg, 0],
+            Opcode::SecondOfPair { dest, reg } => [22, dest, reg, 0],
+            Opcode::MakePair { dest, reg1, reg2 } => [23, dest, reg1, reg2],
+            Opcode::IsIdentical { dest, test1, test2 } => [24, dest, test1, test2],
+            Opcode::StoreGlobal { src, name } => [25, src, name, 0],
+            Opcode::CopyRegister { dest, src } => [26, dest, src, 0],
+            Opcode::Call {
+                function,
+                dest,
+                arg_count,
+            } => [27, function, dest, arg_count],
+            Opcode::IsEqual { dest, test1, test2 } => [28, dest, test1, test2],
+            Opcode::MakeRecord {
+                dest,
+                descriptor,
+                first,
+            } => [29, dest, descriptor, first],
+            Opcode::IsRecord {
+                dest,
+                test,
+                descriptor,
+            } => [30, dest, test, descriptor],
+            Opcode::CheckRecord { record, descriptor } => [31, record, descriptor, 0],
+            Opcode::GetField {
+                dest,
+                record,
+                index,
+            } => [32, dest, record, index],
+            Opcode::SetField { record, index, src } => [33, record, index, src],
+            Opcode::Yield { dest, value } => [34, dest, value, 0],
+            Opcode::Protect { dest, offset } => wide(35, dest, offset as u16),
+            Opcode::Unprotect => [36, 0, 0, 0],
+            Opcode::Fail { reason, pos } => [37, reason, pos, 0],
+            Opcode::RegisterTest { src, name } => [38, src, name, 0],
+        }
+    }
+
+    /// Decode an instruction encoded by `encode()`
+    pub fn decode(bytes: [u8; 4]) -> Result<Opcode, RuntimeError> {
+        let [variant, a, b, c] = bytes;
+        let wide = u16::from_le_bytes([b, c]);
+
+        let opcode = match variant {
+            0 => Opcode::Add {
+                dest: a,
+                left: b,
+                right: c,
+            },
+            1 => Opcode::Subtract {
+                dest: a,
+                left: b,
+                right: c,
+            },
+            2 => Opcode::Multiply {
+                dest: a,
+                left: b,
+                right: c,
+            },
+            3 => Opcode::Divide {
+                dest: a,
+                left: b,
+                right: c,
+            },
+            4 => Opcode::Floor { dest: a, reg: b },
+            5 => Opcode::Round { dest: a, reg: b },
+            6 => Opcode::ToInteger { dest: a, reg: b },
+            7 => Opcode::ToFloat { dest: a, reg: b },
+            8 => Opcode::LoadLiteral {
+                dest: a,
+                literal: wide,
+            },
+            9 => Opcode::Jump {
+                offset: wide as JumpOffset,
+            },
+            10 => Opcode::JumpIfTrue {
+                test: a,
+                offset: wide as JumpOffset,
+            },
+            11 => Opcode::JumpIfNotTrue {
+                test: a,
+                offset: wide as JumpOffset,
+            },
+            12 => Opcode::MakeClosure {
+                dest: a,
+                function: b,
+            },
+            13 => Opcode::GetUpvalue { dest: a, src: b },
+            14 => Opcode::SetUpvalue { dest: a, src: b },
+            15 => Opcode::CloseUpvalues {
+                reg1: a,
+                reg2: b,
+                reg3: c,
+            },
+            16 => Opcode::Return { reg: a },
+            17 => Opcode::LoadNil { dest: a },
+            18 => Opcode::LoadGlobal { dest: a, name: b },
+            19 => Opcode::IsAtom { dest: a, test: b },
+            20 => Opcode::IsNil { dest: a, test: b },
+            21 => Opcode::FirstOfPair { dest: a, reg: b },
+            22 => Opcode::SecondOfPair { dest: a, reg: b },
+            23 => Opcode::MakePair {
+                dest: a,
+                reg1: b,
+                reg2: c,
+            },
+            24 => Opcode::IsIdentical {
+                dest: a,
+                test1: b,
+                test2: c,
+            },
+            25 => Opcode::StoreGlobal { src: a, name: b },
+            26 => Opcode::CopyRegister { dest: a, src: b },
+            27 => Opcode::Call {
+                function: a,
+                dest: b,
+                arg_count: c,
+            },
+            28 => Opcode::IsEqual {
+                dest: a,
+                test1: b,
+                test2: c,
+            },
+            29 => Opcode::MakeRecord {
+                dest: a,
+                descriptor: b,
+                first: c,
+            },
+            30 => Opcode::IsRecord {
+                dest: a,
+                test: b,
+                descriptor: c,
+            },
+            31 => Opcode::CheckRecord {
+                record: a,
+                descriptor: b,
+            },
+            32 => Opcode::GetField {
+                dest: a,
+                record: b,
+                index: c,
+            },
+            33 => Opcode::SetField {
+                record: a,
+                index: b,
+                src: c,
+            },
+            34 => Opcode::Yield { dest: a, value: b },
+            35 => Opcode::Protect {
+                dest: a,
+                offset: wide as JumpOffset,
+            },
+            36 => Opcode::Unprotect,
+            37 => Opcode::Fail { reason: a, pos: b },
+            38 => Opcode::RegisterTest { src: a, name: b },
+            _ => return Err(err_eval("Invalid instruction encoding")),
+        };
+
+        Ok(opcode)
+    }
+}
+
+impl Trace for Opcode {
+    fn trace(&self, _tracer: &mut Tracer) {}
+}
+
+impl Trace for ByteCode {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.code.trace(tracer);
+        self.literals.trace(tracer);
+    }
 }
 
 /// An InstructionStream is a pointer to a ByteCode instance and an instruction pointer giving the
-/// current index into the ByteCode
+/// current index into the ByteCode. It records where execution is between batches of
+/// instructions; while a batch runs, the interpreter loop keeps its position in locals.
 pub struct InstructionStream {
     instructions: CellPtr<ByteCode>,
     ip: Cell<ArraySize>,
@@ -236,40 +621,9 @@ impl InstructionStream {
         self.ip.set(ip);
     }
 
-    /// Retrieve the next instruction and return it, incrementing the instruction pointer
-    // TODO: https://github.com/rust-hosted-langs/book/issues/39
-    pub fn get_next_opcode<'guard>(
-        &self,
-        guard: &'guard dyn MutatorScope,
-    ) -> Result<Opcode, RuntimeError> {
-        let instr = self
-            .instructions
-            .get(guard)
-            .code
-            .get(guard, self.ip.get())?;
-        self.ip.set(self.ip.get() + 1);
-        Ok(instr)
-    }
-
-    /// Given an index into the literals list, return the pointer in the list at that index.
-    pub fn get_literal<'guard>(
-        &self,
-        guard: &'guard dyn MutatorScope,
-        lit_id: LiteralId,
-    ) -> Result<TaggedPtr, RuntimeError> {
-        Ok(IndexedContainer::get(
-            &self.instructions.get(guard).literals,
-            guard,
-            lit_id as ArraySize,
-        )?
-        .get_ptr())
-    }
-
-    /// Adjust the instruction pointer by the given signed offset from the current ip
-    pub fn jump(&self, offset: JumpOffset) {
-        let mut ip = self.ip.get() as i32;
-        ip += offset as i32;
-        self.ip.set(ip as ArraySize);
+    /// Return the ByteCode being executed
+    pub fn code<'guard>(&self, guard: &'guard dyn MutatorScope) -> ScopedPtr<'guard, ByteCode> {
+        self.instructions.get(guard)
     }
 
     /// Return the next instruction pointer
@@ -278,6 +632,12 @@ impl InstructionStream {
     }
 }
 
+impl Trace for InstructionStream {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.instructions.trace(tracer);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::Opcode;
@@ -8,23 +8,149 @@ use crate::memory::ArraySize;
 use super::{
     bytecode::{ByteCode, JumpOffset, Opcode, Register, UpvalueId, JUMP_UNKNOWN},
     containers::{AnyContainerFromSlice, StackContainer},
-    error::err_eval,
-    function::Function,
+    error::{err_eval, SourcePos},
+    function::{Function, Signature, UNSUPPLIED},
     list::List,
-    pair::{value_from_1_pair, values_from_2_pairs, vec_from_pairs},
-    safeptr::TaggedScopedPtr,
+    pair::{value_from_1_pair, values_from_2_pairs, vec_from_pairs, Pair},
+    printer::print,
+    safeptr::{MutatorScope, TaggedScopedPtr},
+    symbol::Symbol,
     taggedptr::Value,
     vm::FIRST_ARG_REG,
     ArrayU16, CellPtr, MutatorView, RuntimeError, ScopedPtr,
 };
 
+/// The names that `compile_apply` compiles as special forms rather than function calls
+pub const SPECIAL_FORMS: &[&str] = &[
+    "quote",
+    "atom?",
+    "nil?",
+    "car",
+    "cdr",
+    "cons",
+    "cond",
+    "if",
+    "begin",
+    "do",
+    "and",
+    "or",
+    "while",
+    "loop",
+    "is?",
+    "equal?",
+    "+",
+    "-",
+    "*",
+    "/",
+    "floor",
+    "round",
+    "->int",
+    "->float",
+    "set",
+    "def",
+    "defstruct",
+    "yield",
+    "lambda",
+    "\\",
+    "let",
+    "match",
+    "deftest",
+    "assert",
+    "assert-equal",
+    "assert-error",
+];
+
 /// Compile the given AST and return an anonymous Function object
 pub fn compile<'guard>(
     mem: &'guard MutatorView,
     ast: TaggedScopedPtr<'guard>,
 ) -> Result<ScopedPtr<'guard, Function>, RuntimeError> {
     let compiler = Compiler::new(mem, None)?;
-    compiler.compile_function(mem, mem.nil(), &[], &[ast])
+    compiler.compile_function(mem, mem.nil(), mem.nil(), &[ast])
+}
+
+/// Build one of the functions generated by `defstruct`. The record type descriptor is loaded into
+/// `descriptor_reg` before the given instructions run.
+fn record_function<'guard>(
+    mem: &'guard MutatorView,
+    name: &str,
+    params: &[TaggedScopedPtr<'guard>],
+    descriptor: TaggedScopedPtr<'guard>,
+    descriptor_reg: Register,
+    code: &[Opcode],
+) -> Result<ScopedPtr<'guard, Function>, RuntimeError> {
+    let bytecode = ByteCode::alloc(mem)?;
+    let literal = bytecode.push_lit(mem, descriptor)?;
+    bytecode.push_loadlit(mem, descriptor_reg, literal)?;
+    for op in code {
+        bytecode.push(mem, *op)?;
+    }
+
+    let param_names = List::from_slice(mem, params)?;
+    Function::alloc(mem, mem.lookup_sym(name), param_names, bytecode, None)
+}
+
+/// What a symbol means when it appears in a `match` pattern
+enum PatternSymbol {
+    /// `_` matches anything without binding it
+    Wildcard,
+    /// `nil` matches the empty list
+    Nil,
+    /// `true` and keywords such as `:name` match themselves
+    Literal,
+    /// Any other symbol matches anything and is bound to it
+    Variable,
+}
+
+impl PatternSymbol {
+    fn from_symbol(guard: &dyn MutatorScope, symbol: &Symbol) -> PatternSymbol {
+        match symbol.as_str(guard) {
+            "_" => PatternSymbol::Wildcard,
+            "nil" => PatternSymbol::Nil,
+            "true" => PatternSymbol::Literal,
+            _ if symbol.is_keyword() => PatternSymbol::Literal,
+            _ => PatternSymbol::Variable,
+        }
+    }
+}
+
+/// Return true if the pattern is a quoted literal, `(quote <value>)`
+fn is_quoted_pattern<'guard>(mem: &'guard MutatorView, pattern: TaggedScopedPtr<'guard>) -> bool {
+    match *pattern {
+        Value::Pair(p) => match *p.first.get(mem) {
+            Value::Symbol(s) => s.as_str(mem) == "quote",
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Collect the names of the variables a `match` pattern binds, in the order they appear
+fn pattern_variables<'guard>(
+    mem: &'guard MutatorView,
+    pattern: TaggedScopedPtr<'guard>,
+    names: &mut Vec<TaggedScopedPtr<'guard>>,
+) -> Result<(), RuntimeError> {
+    match *pattern {
+        Value::Symbol(s) => {
+            if let PatternSymbol::Variable = PatternSymbol::from_symbol(mem, &s) {
+                if names.contains(&pattern) {
+                    return Err(err_eval(&format!(
+                        "Pattern variable {} may only appear once",
+                        s.as_str(mem)
+                    )));
+                }
+                names.push(pattern);
+            }
+        }
+        Value::Pair(p) if !is_quoted_pattern(mem, pattern) => {
+            pattern_variables(mem, p.first.get(mem), names)?;
+            pattern_variables(mem, p.second.get(mem), names)?;
+        }
+        _ => (),
+    }
+
+    Ok(())
 }
 
 /// Compile a function - parameters and expression, returning a tagged Function object
@@ -32,7 +158,7 @@ fn compile_function<'guard, 'scope>(
     mem: &'guard MutatorView,
     parent: Option<&'scope Variables<'scope>>,
     name: TaggedScopedPtr<'guard>,
-    params: &[TaggedScopedPtr<'guard>],
+    params: TaggedScopedPtr<'guard>,
     exprs: &[TaggedScopedPtr<'guard>],
 ) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
     let compiler = Compiler::new(mem, parent)?;
@@ -41,6 +167,137 @@ fn compile_function<'guard, 'scope>(
         .as_tagged(mem))
 }
 
+/// A function's parameter list, sorted into the kinds of parameter
+/// (a b &optional c (d <default>) &key e (f <default>) . rest)
+struct Params<'guard> {
+    /// Required parameter names
+    required: Vec<TaggedScopedPtr<'guard>>,
+    /// Optional parameter names and default value expressions
+    optional: Vec<(TaggedScopedPtr<'guard>, Option<TaggedScopedPtr<'guard>>)>,
+    /// Rest parameter name
+    rest: Option<TaggedScopedPtr<'guard>>,
+    /// Keyword parameter names and default value expressions
+    keys: Vec<(TaggedScopedPtr<'guard>, Option<TaggedScopedPtr<'guard>>)>,
+}
+
+impl<'guard> Params<'guard> {
+    /// Parse a parameter list. A symbol in place of the list, or a dotted tail, names a rest
+    /// parameter.
+    fn parse(
+        mem: &'guard MutatorView,
+        params: TaggedScopedPtr<'guard>,
+    ) -> Result<Params<'guard>, RuntimeError> {
+        let mut parsed = Params {
+            required: Vec::new(),
+            optional: Vec::new(),
+            rest: None,
+            keys: Vec::new(),
+        };
+
+        // which section of the parameter list we are in: 0 required, 1 optional, 2 keyword
+        let mut section = 0;
+
+        let mut next = params;
+        loop {
+            let item = match *next {
+                Value::Nil => break,
+                Value::Symbol(_) => {
+                    parsed.rest = Some(next);
+                    break;
+                }
+                Value::Pair(pair) => {
+                    next = pair.second.get(mem);
+                    pair.first.get(mem)
+                }
+                _ => return Err(err_eval("A parameter list must be a list of symbols")),
+            };
+
+            match *item {
+                Value::Symbol(s) if s.as_str(mem) == "&optional" => {
+                    if section >= 1 {
+                        return Err(err_eval("&optional must come before &key, and only once"));
+                    }
+                    section = 1;
+                }
+                Value::Symbol(s) if s.as_str(mem) == "&key" => {
+                    if section >= 2 {
+                        return Err(err_eval("&key may only be given once"));
+                    }
+                    section = 2;
+                }
+                Value::Symbol(_) if section == 0 => parsed.required.push(item),
+                Value::Symbol(_) => {
+                    if section == 1 {
+                        parsed.optional.push((item, None));
+                    } else {
+                        parsed.keys.push((item, None));
+                    }
+                }
+                Value::Pair(_) if section > 0 => {
+                    let (name, default) = values_from_2_pairs(mem, item)?;
+                    if section == 1 {
+                        parsed.optional.push((name, Some(default)));
+                    } else {
+                        parsed.keys.push((name, Some(default)));
+                    }
+                }
+                _ => return Err(err_eval("A required parameter must be a symbol")),
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    /// Return all the parameter names in register order: required, optional, rest, keyword
+    fn names(&self) -> Vec<TaggedScopedPtr<'guard>> {
+        let mut names = self.required.clone();
+        names.extend(self.optional.iter().map(|(name, _)| *name));
+        names.extend(self.rest);
+        names.extend(self.keys.iter().map(|(name, _)| *name));
+        names
+    }
+
+    /// Return the default value expressions of the optional and keyword parameters, with the
+    /// offset of each parameter's register from the first parameter register
+    fn defaults(&self) -> Vec<(usize, Option<TaggedScopedPtr<'guard>>)> {
+        let optional_start = self.required.len();
+        let keys_start = optional_start + self.optional.len() + self.rest.iter().count();
+
+        let optional = self.optional.iter().enumerate();
+        let keys = self.keys.iter().enumerate();
+
+        optional
+            .map(|(index, (_, default))| (optional_start + index, *default))
+            .chain(keys.map(|(index, (_, default))| (keys_start + index, *default)))
+            .collect()
+    }
+
+    /// Describe how call arguments bind to these parameters. Each keyword parameter `name` is
+    /// selected at the call site by the symbol `:name`.
+    fn signature(&self, mem: &'guard MutatorView) -> Result<Signature<'guard>, RuntimeError> {
+        let keywords = if self.keys.is_empty() {
+            None
+        } else {
+            let mut keywords = Vec::new();
+            for (name, _) in &self.keys {
+                let name = match **name {
+                    Value::Symbol(s) => s.as_str(mem),
+                    _ => return Err(err_eval("A keyword parameter name must be a symbol")),
+                };
+                keywords.push(mem.lookup_sym(&format!(":{}", name)));
+            }
+            Some(List::from_slice(mem, &keywords)?)
+        };
+
+        Ok(Signature {
+            required: self.required.len() as u8,
+            optional: self.optional.len() as u8,
+            rest: self.rest.is_some(),
+            keywords,
+        })
+    }
+}
+
 struct Compiler<'parent> {
     bytecode: CellPtr<ByteCode>,
     /// Next available register slot.
@@ -124,7 +381,7 @@ impl<'parent> Compiler<'parent> {
         mut self,
         mem: &'guard MutatorView,
         name: TaggedScopedPtr<'guard>,
-        params: &[TaggedScopedPtr<'guard>],
+        params: TaggedScopedPtr<'guard>,
         exprs: &[TaggedScopedPtr<'guard>],
     ) -> Result<ScopedPtr<'guard, Function>, RuntimeError> {
         // validate function name
@@ -140,22 +397,38 @@ impl<'parent> Compiler<'parent> {
         let fn_name = name;
 
         // validate arity
-        if params.len() > 254 {
+        let params = Params::parse(mem, params)?;
+        let names = params.names();
+        if names.len() > 254 {
             return Err(err_eval("A function cannot have more than 254 parameters"));
         }
         // put params into a list for the Function object
-        let fn_params = List::from_slice(mem, params)?;
+        let fn_params = List::from_slice(mem, &names)?;
+        let signature = params.signature(mem)?;
 
         // also assign params to the first level function scope and give each one a register
+        let first_param_reg = self.next_reg;
         let mut param_scope = Scope::new();
-        self.next_reg = param_scope.push_bindings(params, self.next_reg)?;
+        self.next_reg = param_scope.push_bindings(&names, self.next_reg)?;
         self.vars.scopes.push(param_scope);
 
+        // optional and keyword parameters that were not given an argument get their default
+        for (offset, default) in params.defaults() {
+            self.compile_default(mem, first_param_reg + offset as Register, default)?;
+        }
+
         // validate expression list
         if exprs.len() == 0 {
             return Err(err_eval("A function must have at least one expression"));
         }
 
+        // a Text followed by more expressions is the function's docstring rather than part of
+        // the body
+        let (doc, exprs) = match *exprs[0] {
+            Value::Text(_) if exprs.len() > 1 => (exprs[0], &exprs[1..]),
+            _ => (mem.nil(), exprs),
+        };
+
         // compile expressions
         let mut result_reg = 0;
         for expr in exprs.iter() {
@@ -174,13 +447,67 @@ impl<'parent> Compiler<'parent> {
 
         let fn_nonlocals = self.vars.get_nonlocals(mem)?;
 
-        Ok(Function::alloc(
+        Function::alloc_with_signature(
             mem,
             fn_name,
             fn_params,
+            signature,
+            doc,
             fn_bytecode,
             fn_nonlocals,
-        )?)
+        )
+    }
+
+    /// Compile the evaluation of an optional or keyword parameter's default value, if the
+    /// parameter was not given an argument
+    fn compile_default<'guard>(
+        &mut self,
+        mem: &'guard MutatorView,
+        param: Register,
+        default: Option<TaggedScopedPtr<'guard>>,
+    ) -> Result<(), RuntimeError> {
+        //
+        //   test = param is unsupplied
+        //   if not true then jmp -> end
+        //   param = eval default (or nil)
+        //  end:
+        //
+        let bytecode = self.bytecode.get(mem);
+        let start_reg = self.next_reg;
+
+        let unsupplied = self.push_load_literal(mem, mem.lookup_sym(UNSUPPLIED))?;
+        let test = self.acquire_reg();
+        self.push(
+            mem,
+            Opcode::IsIdentical {
+                dest: test,
+                test1: param,
+                test2: unsupplied,
+            },
+        )?;
+        self.push(
+            mem,
+            Opcode::JumpIfNotTrue {
+                test,
+                offset: JUMP_UNKNOWN,
+            },
+        )?;
+        let end_jump = bytecode.last_instruction();
+
+        match default {
+            Some(default) => {
+                let src = self.compile_eval(mem, default)?;
+                if src != param {
+                    self.push(mem, Opcode::CopyRegister { dest: param, src })?;
+                }
+            }
+            None => self.push(mem, Opcode::LoadNil { dest: param })?,
+        }
+
+        self.patch_jump_to_next(mem, end_jump)?;
+
+        self.reset_reg(start_reg);
+        Ok(())
     }
 
     /// Compile an expression - this can be an 'atomic' value or a nested function application    
@@ -190,7 +517,9 @@ impl<'parent> Compiler<'parent> {
         ast_node: TaggedScopedPtr<'guard>,
     ) -> Result<Register, RuntimeError> {
         match *ast_node {
-            Value::Pair(p) => self.compile_apply(mem, p.first.get(mem), p.second.get(mem)),
+            Value::Pair(p) => {
+                self.compile_apply(mem, p.first.get(mem), p.second.get(mem), p.first_pos.get())
+            }
             Value::Symbol(s) => {
                 match s.as_str(mem) {
                     "nil" => {
@@ -201,6 +530,9 @@ impl<'parent> Compiler<'parent> {
 
                     "true" => self.push_load_literal(mem, mem.lookup_sym("true")),
 
+                    // keywords such as :name evaluate to themselves
+                    _ if s.is_keyword() => self.push_load_literal(mem, ast_node),
+
                     // Search scopes for a binding; if none do a global lookup
                     _ => {
                         match self.vars.lookup_binding(ast_node)? {
@@ -240,12 +572,14 @@ impl<'parent> Compiler<'parent> {
         self.bytecode.get(mem).push(mem, op)
     }
 
-    /// Compile a function or special-form application    
+    /// Compile a function or special-form application. The source position of the function
+    /// expression is given to assertions to report.
     fn compile_apply<'guard>(
         &mut self,
         mem: &'guard MutatorView,
         function: TaggedScopedPtr<'guard>,
         args: TaggedScopedPtr<'guard>,
+        pos: Option<SourcePos>,
     ) -> Result<Register, RuntimeError> {
         match *function {
             Value::Symbol(s) => match s.as_str(mem) {
@@ -262,23 +596,59 @@ impl<'parent> Compiler<'parent> {
                     reg2,
                 }),
                 "cond" => self.compile_apply_cond(mem, args),
+                "if" => self.compile_apply_if(mem, args),
+                "begin" | "do" => self.compile_apply_begin(mem, args),
+                "and" => self.compile_apply_and(mem, args),
+                "or" => self.compile_apply_or(mem, args),
+                "while" | "loop" => self.compile_apply_while(mem, args),
                 "is?" => self.push_op3(mem, args, |dest, test1, test2| Opcode::IsIdentical {
                     dest,
                     test1,
                     test2,
                 }),
+                "equal?" => self.push_op3(mem, args, |dest, test1, test2| Opcode::IsEqual {
+                    dest,
+                    test1,
+                    test2,
+                }),
                 "+" => self.push_op3(mem, args, |dest, reg1, reg2| Opcode::Add {
                     dest,
                     left: reg1,
                     right: reg2,
                 }),
+                "-" => self.push_op3(mem, args, |dest, reg1, reg2| Opcode::Subtract {
+                    dest,
+                    left: reg1,
+                    right: reg2,
+                }),
+                "*" => self.push_op3(mem, args, |dest, reg1, reg2| Opcode::Multiply {
+                    dest,
+                    left: reg1,
+                    right: reg2,
+                }),
+                "/" => self.push_op3(mem, args, |dest, reg1, reg2| Opcode::Divide {
+                    dest,
+                    left: reg1,
+                    right: reg2,
+                }),
+                "floor" => self.push_op2(mem, args, |dest, reg| Opcode::Floor { dest, reg }),
+                "round" => self.push_op2(mem, args, |dest, reg| Opcode::Round { dest, reg }),
+                "->int" => self.push_op2(mem, args, |dest, reg| Opcode::ToInteger { dest, reg }),
+                "->float" => self.push_op2(mem, args, |dest, reg| Opcode::ToFloat { dest, reg }),
                 "set" => self.compile_apply_assign(mem, args),
                 "def" => self.compile_named_function(mem, args),
+                "defstruct" => self.compile_defstruct(mem, args),
+                "yield" => self.push_op2(mem, args, |dest, value| Opcode::Yield { dest, value }),
                 // ANCHOR: DefCompileApplyLambda
                 "lambda" => self.compile_anonymous_function(mem, args),
                 // ANCHOR_END: DefCompileApplyLambda
                 "\\" => self.compile_anonymous_function(mem, args),
                 "let" => self.compile_apply_let(mem, args),
+                "match" => self.compile_apply_match(mem, args),
+                "deftest" => self.compile_deftest(mem, args),
+                "assert" => self.compile_apply_assert(mem, args, pos),
+                "assert-equal" => self.compile_apply_assert_equal(mem, args, pos),
+                "assert-error" => self.compile_apply_assert_error(mem, args, pos),
                 _ => self.compile_apply_call(mem, function, args),
             },
 
@@ -390,9 +760,8 @@ impl<'parent> Compiler<'parent> {
                     self.push(mem, Opcode::JumpIfNotTrue { test, offset })?;
                     last_cond_jump = Some(bytecode.last_instruction());
 
-                    // Compile the expression and jump to the end of the entire cond
-                    self.reset_reg(dest); // reuse this register for condition and dest
-                    let _expr_result = self.compile_eval(mem, expr)?;
+                    // Compile the expression into dest and jump to the end of the entire cond
+                    self.compile_eval_into(mem, expr, dest)?;
                     let offset = JUMP_UNKNOWN;
                     bytecode.push(mem, Opcode::Jump { offset })?;
                     end_jumps.push(bytecode.last_instruction());
@@ -416,6 +785,248 @@ impl<'parent> Compiler<'parent> {
             bytecode.update_jump_offset(mem, *address, offset as JumpOffset)?;
         }
 
+        self.reset_reg(dest + 1);
+        Ok(dest)
+    }
+
+    /// Compile an expression, making sure the result ends up in the given register, and release
+    /// any registers acquired beyond it
+    fn compile_eval_into<'guard>(
+        &mut self,
+        mem: &'guard MutatorView,
+        expr: TaggedScopedPtr<'guard>,
+        dest: Register,
+    ) -> Result<(), RuntimeError> {
+        self.reset_reg(dest + 1);
+        let src = self.compile_eval(mem, expr)?;
+        if src != dest {
+            self.push(mem, Opcode::CopyRegister { dest, src })?;
+        }
+        self.reset_reg(dest + 1);
+        Ok(())
+    }
+
+    /// Set the offset of a forward jump instruction so that it lands on the next instruction to
+    /// be pushed
+    fn patch_jump_to_next<'guard>(
+        &mut self,
+        mem: &'guard MutatorView,
+        address: ArraySize,
+    ) -> Result<(), RuntimeError> {
+        let bytecode = self.bytecode.get(mem);
+        let offset = bytecode.next_instruction() - address - 1;
+        bytecode.update_jump_offset(mem, address, offset as JumpOffset)
+    }
+
+    /// Compile an 'if' application
+    /// (if <test-expr> <then-expr> <else-expr>)
+    /// The else expression is optional, the result is nil if the test is not true and there is
+    /// no else expression
+    fn compile_apply_if<'guard>(
+        &mut self,
+        mem: &'guard MutatorView,
+        args: TaggedScopedPtr<'guard>,
+    ) -> Result<Register, RuntimeError> {
+        //
+        //   eval test
+        //   if not true then jmp -> else
+        //   eval then-expr
+        //   jmp -> end
+        //  else:
+        //   eval else-expr (or nil)
+        //  end:
+        //
+        let items = vec_from_pairs(mem, args)?;
+        if items.len() < 2 || items.len() > 3 {
+            return Err(err_eval(
+                "An if expression must be (if test then) or (if test then else)",
+            ));
+        }
+
+        let bytecode = self.bytecode.get(mem);
+        let dest = self.acquire_reg();
+
+        let test = self.compile_eval(mem, items[0])?;
+        self.push(
+            mem,
+            Opcode::JumpIfNotTrue {
+                test,
+                offset: JUMP_UNKNOWN,
+            },
+        )?;
+        let else_jump = bytecode.last_instruction();
+
+        self.compile_eval_into(mem, items[1], dest)?;
+        self.push(
+            mem,
+            Opcode::Jump {
+                offset: JUMP_UNKNOWN,
+            },
+        )?;
+        let end_jump = bytecode.last_instruction();
+
+        self.patch_jump_to_next(mem, else_jump)?;
+        match items.get(2) {
+            Some(else_expr) => self.compile_eval_into(mem, *else_expr, dest)?,
+            None => self.push(mem, Opcode::LoadNil { dest })?,
+        }
+
+        self.patch_jump_to_next(mem, end_jump)?;
+
+        self.reset_reg(dest + 1);
+        Ok(dest)
+    }
+
+    /// Compile a 'begin' or 'do' application, evaluating each expression in sequence
+    /// (begin <expr> <expr> ...)
+    /// The result is that of the last expression, or nil if there are none
+    fn compile_apply_begin<'guard>(
+        &mut self,
+        mem: &'guard MutatorView,
+        args: TaggedScopedPtr<'guard>,
+    ) -> Result<Register, RuntimeError> {
+        let exprs = vec_from_pairs(mem, args)?;
+        let dest = self.acquire_reg();
+
+        if exprs.is_empty() {
+            self.push(mem, Opcode::LoadNil { dest })?;
+        }
+
+        for expr in exprs {
+            self.compile_eval_into(mem, expr, dest)?;
+        }
+
+        Ok(dest)
+    }
+
+    /// Compile an 'and' application, evaluating expressions until one is not true
+    /// (and <expr> <expr> ...)
+    /// The result is the first value that is not true, otherwise the last value. An empty 'and'
+    /// is true.
+    fn compile_apply_and<'guard>(
+        &mut self,
+        mem: &'guard MutatorView,
+        args: TaggedScopedPtr<'guard>,
+    ) -> Result<Register, RuntimeError> {
+        let true_sym = mem.lookup_sym("true");
+        self.compile_short_circuit(mem, args, true_sym, |test, offset| Opcode::JumpIfNotTrue {
+            test,
+            offset,
+        })
+    }
+
+    /// Compile an 'or' application, evaluating expressions until one is true
+    /// (or <expr> <expr> ...)
+    /// The result is true if any expression is true, otherwise the last value. An empty 'or' is
+    /// nil.
+    fn compile_apply_or<'guard>(
+        &mut self,
+        mem: &'guard MutatorView,
+        args: TaggedScopedPtr<'guard>,
+    ) -> Result<Register, RuntimeError> {
+        self.compile_short_circuit(mem, args, mem.nil(), |test, offset| Opcode::JumpIfTrue {
+            test,
+            offset,
+        })
+    }
+
+    /// Evaluate each expression into the same register, jumping to the end as soon as the given
+    /// jump instruction is taken
+    fn compile_short_circuit<'guard, F>(
+        &mut self,
+        mem: &'guard MutatorView,
+        args: TaggedScopedPtr<'guard>,
+        empty_result: TaggedScopedPtr<'guard>,
+        jump: F,
+    ) -> Result<Register, RuntimeError>
+    where
+        F: Fn(Register, JumpOffset) -> Opcode,
+    {
+        let exprs = vec_from_pairs(mem, args)?;
+
+        if exprs.is_empty() {
+            return self.push_load_literal(mem, empty_result);
+        }
+
+        let bytecode = self.bytecode.get(mem);
+        let dest = self.acquire_reg();
+
+        let mut end_jumps = Vec::new();
+        for (index, expr) in exprs.iter().enumerate() {
+            self.compile_eval_into(mem, *expr, dest)?;
+
+            // the last expression's value is the result whatever it is
+            if index < exprs.len() - 1 {
+                self.push(mem, jump(dest, JUMP_UNKNOWN))?;
+                end_jumps.push(bytecode.last_instruction());
+            }
+        }
+
+        for address in end_jumps {
+            self.patch_jump_to_next(mem, address)?;
+        }
+
+        Ok(dest)
+    }
+
+    /// Compile a 'while' or 'loop' application, evaluating the body expressions for as long as
+    /// the test expression is true
+    /// (while <test-expr> <expr> ...)
+    /// The result is always nil
+    fn compile_apply_while<'guard>(
+        &mut self,
+        mem: &'guard MutatorView,
+        args: TaggedScopedPtr<'guard>,
+    ) -> Result<Register, RuntimeError> {
+        //
+        //  start:
+        //   eval test
+        //   if not true then jmp -> end
+        //   eval body
+        //   jmp -> start
+        //  end:
+        //   load nil
+        //
+        let items = vec_from_pairs(mem, args)?;
+        if items.is_empty() {
+            return Err(err_eval("A while expression must have a test expression"));
+        }
+
+        let bytecode = self.bytecode.get(mem);
+        let dest = self.acquire_reg();
+
+        let start = bytecode.next_instruction();
+
+        let test = self.compile_eval(mem, items[0])?;
+        self.push(
+            mem,
+            Opcode::JumpIfNotTrue {
+                test,
+                offset: JUMP_UNKNOWN,
+            },
+        )?;
+        let end_jump = bytecode.last_instruction();
+
+        for expr in &items[1..] {
+            self.compile_eval_into(mem, *expr, dest)?;
+        }
+
+        // jump back relative to the instruction after this one
+        let offset = start as i64 - bytecode.next_instruction() as i64 - 1;
+        if offset < JumpOffset::MIN as i64 {
+            return Err(err_eval("A while expression body is too long"));
+        }
+        self.push(
+            mem,
+            Opcode::Jump {
+                offset: offset as JumpOffset,
+            },
+        )?;
+
+        self.patch_jump_to_next(mem, end_jump)?;
+        self.push(mem, Opcode::LoadNil { dest })?;
+
+        self.reset_reg(dest + 1);
         Ok(dest)
     }
 
@@ -455,11 +1066,11 @@ impl<'parent> Compiler<'parent> {
 
         // a function consists of (name (params) expr1 .. exprn)
         let fn_name = items[0];
-        let fn_params = vec_from_pairs(mem, items[1])?;
+        let fn_params = items[1];
         let fn_exprs = &items[2..];
 
         // compile the function to a Function object
-        let fn_object = compile_function(mem, Some(&self.vars), fn_name, &fn_params, fn_exprs)?;
+        let fn_object = compile_function(mem, Some(&self.vars), fn_name, fn_params, fn_exprs)?;
 
         // load the function object as a literal and associate it with a global name
         // TODO store in local scope if we're nested in an expression
@@ -472,30 +1083,380 @@ impl<'parent> Compiler<'parent> {
         // TODO if fn_object has nonlocal refs, compile a MakeClosure instruction in addition
     }
 
-    /// (lambda (args) (exprs))
-    /// OR
-    /// (\ (args) (exprs))        
-    fn compile_anonymous_function<'guard>(
+    /// (deftest name expr1 .. exprn)
+    /// Registers a test, a function of no arguments, on the Thread under the name. The name is
+    /// not bound as a global. The test runner calls every test registered while a file is
+    /// loaded; a test passes if it returns without an error.
+    fn compile_deftest<'guard>(
         &mut self,
         mem: &'guard MutatorView,
-        params: TaggedScopedPtr<'guard>,
+        args: TaggedScopedPtr<'guard>,
     ) -> Result<Register, RuntimeError> {
-        let items = vec_from_pairs(mem, params)?;
+        let items = vec_from_pairs(mem, args)?;
 
-        if items.len() < 2 {
-            return Err(err_eval(
-                "An anonymous function definition must have at least (lambda (params) expr)",
-            ));
+        let (test_name, test_exprs) = match items.split_first() {
+            Some((name, exprs)) if !exprs.is_empty() => (*name, exprs),
+            _ => return Err(err_eval("A test must have at least (deftest name expr)")),
+        };
+        if !matches!(*test_name, Value::Symbol(_)) {
+            return Err(err_eval("A test name must be a symbol"));
         }
 
-        // a function consists of (name (params) expr1 .. exprn)
-        let fn_params = vec_from_pairs(mem, items[0])?;
-        let fn_exprs = &items[1..];
+        let test_object =
+            compile_function(mem, Some(&self.vars), test_name, mem.nil(), test_exprs)?;
 
-        // compile the function to a Function object
-        let fn_object = compile_function(mem, Some(&self.vars), mem.nil(), &fn_params, fn_exprs)?;
+        let name = self.push_load_literal(mem, test_name)?;
+        let src = self.push_load_literal(mem, test_object)?;
+        self.push(mem, Opcode::RegisterTest { src, name })?;
 
-        // load the function object as a literal
+        Ok(src)
+    }
+
+    /// (assert <expr>)
+    /// Fails with the source position of the assertion unless the expression is true. The
+    /// result is true.
+    fn compile_apply_assert<'guard>(
+        &mut self,
+        mem: &'guard MutatorView,
+        args: TaggedScopedPtr<'guard>,
+        pos: Option<SourcePos>,
+    ) -> Result<Register, RuntimeError> {
+        //
+        //   eval expr
+        //   if true then jmp -> end
+        //   fail
+        //  end:
+        //
+        let expr = value_from_1_pair(mem, args)?;
+
+        let dest = self.acquire_reg();
+        self.compile_eval_into(mem, expr, dest)?;
+        self.push(
+            mem,
+            Opcode::JumpIfTrue {
+                test: dest,
+                offset: JUMP_UNKNOWN,
+            },
+        )?;
+        let end_jump = self.bytecode.get(mem).last_instruction();
+
+        let reason = mem.text(&format!("{} is not true", print(*expr)))?;
+        let reason = self.push_load_literal(mem, reason)?;
+        self.push_fail(mem, reason, pos)?;
+
+        self.patch_jump_to_next(mem, end_jump)?;
+
+        self.reset_reg(dest + 1);
+        Ok(dest)
+    }
+
+    /// (assert-equal <expected-expr> <actual-expr>)
+    /// Fails with the source position of the assertion and both values unless they are equal as
+    /// `equal?` compares them. The result is true.
+    fn compile_apply_assert_equal<'guard>(
+        &mut self,
+        mem: &'guard MutatorView,
+        args: TaggedScopedPtr<'guard>,
+        pos: Option<SourcePos>,
+    ) -> Result<Register, RuntimeError> {
+        //
+        //   eval expected
+        //   eval actual
+        //   test = expected equal? actual
+        //   if true then jmp -> end
+        //   fail with both values
+        //  end:
+        //
+        let (expected_expr, actual_expr) = values_from_2_pairs(mem, args)?;
+
+        let dest = self.acquire_reg();
+        let expected = self.acquire_reg();
+        self.compile_eval_into(mem, expected_expr, expected)?;
+        let actual = self.acquire_reg();
+        self.compile_eval_into(mem, actual_expr, actual)?;
+
+        self.push(
+            mem,
+            Opcode::IsEqual {
+                dest,
+                test1: expected,
+                test2: actual,
+            },
+        )?;
+        self.push(
+            mem,
+            Opcode::JumpIfTrue {
+                test: dest,
+                offset: JUMP_UNKNOWN,
+            },
+        )?;
+        let end_jump = self.bytecode.get(mem).last_instruction();
+
+        // the reason is a list of a format string and the values to fill it with
+        let format = mem.text(&format!("{}: expected {{}}, got {{}}", print(*actual_expr)))?;
+        let reason = self.acquire_reg();
+        self.push(mem, Opcode::LoadNil { dest: reason })?;
+        self.push(
+            mem,
+            Opcode::MakePair {
+                dest: reason,
+                reg1: actual,
+                reg2: reason,
+            },
+        )?;
+        self.push(
+            mem,
+            Opcode::MakePair {
+                dest: reason,
+                reg1: expected,
+                reg2: reason,
+            },
+        )?;
+        let format = self.push_load_literal(mem, format)?;
+        self.push(
+            mem,
+            Opcode::MakePair {
+                dest: reason,
+                reg1: format,
+                reg2: reason,
+            },
+        )?;
+        self.push_fail(mem, reason, pos)?;
+
+        self.patch_jump_to_next(mem, end_jump)?;
+
+        self.reset_reg(dest + 1);
+        Ok(dest)
+    }
+
+    /// (assert-error <expr>)
+    /// Fails with the source position of the assertion unless evaluating the expression raises
+    /// an error. The result is the error message as text.
+    fn compile_apply_assert_error<'guard>(
+        &mut self,
+        mem: &'guard MutatorView,
+        args: TaggedScopedPtr<'guard>,
+        pos: Option<SourcePos>,
+    ) -> Result<Register, RuntimeError> {
+        //
+        //   protect, on error put the message in dest and jmp -> end
+        //   eval expr
+        //   unprotect
+        //   fail
+        //  end:
+        //
+        let expr = value_from_1_pair(mem, args)?;
+
+        let dest = self.acquire_reg();
+        self.push(
+            mem,
+            Opcode::Protect {
+                dest,
+                offset: JUMP_UNKNOWN,
+            },
+        )?;
+        let handler_jump = self.bytecode.get(mem).last_instruction();
+
+        self.compile_eval(mem, expr)?;
+        self.push(mem, Opcode::Unprotect)?;
+        self.reset_reg(dest + 1);
+
+        let reason = mem.text(&format!("{} did not raise an error", print(*expr)))?;
+        let reason = self.push_load_literal(mem, reason)?;
+        self.push_fail(mem, reason, pos)?;
+
+        self.patch_jump_to_next(mem, handler_jump)?;
+
+        self.reset_reg(dest + 1);
+        Ok(dest)
+    }
+
+    /// Push a `Fail` instruction with the reason in the given register, loading the source
+    /// position of the assertion as a pair of line and column
+    fn push_fail<'guard>(
+        &mut self,
+        mem: &'guard MutatorView,
+        reason: Register,
+        pos: Option<SourcePos>,
+    ) -> Result<(), RuntimeError> {
+        let pos = match pos {
+            Some(pos) => {
+                let line = mem.number(pos.line as isize);
+                let column = mem.number(pos.column as isize);
+                self.push_load_literal(mem, Pair::cons(mem, line, column)?)?
+            }
+            None => {
+                let dest = self.acquire_reg();
+                self.push(mem, Opcode::LoadNil { dest })?;
+                dest
+            }
+        };
+
+        self.push(mem, Opcode::Fail { reason, pos })
+    }
+
+    /// (defstruct name field1 .. fieldn)
+    ///
+    /// Defines a record type by binding these generated functions as globals:
+    ///  - (make-name field1 .. fieldn) returns a new record
+    ///  - (name? value) returns true if the value is a record of this type
+    ///  - (name-field record) returns the value of a field
+    ///  - (set-name-field! record value) sets the value of a field and returns the value
+    ///
+    /// The expression evaluates to the type name.
+    fn compile_defstruct<'guard>(
+        &mut self,
+        mem: &'guard MutatorView,
+        params: TaggedScopedPtr<'guard>,
+    ) -> Result<Register, RuntimeError> {
+        let items = vec_from_pairs(mem, params)?;
+
+        let mut names = Vec::with_capacity(items.len());
+        for item in &items {
+            match **item {
+                Value::Symbol(s) => names.push(String::from(s.as_str(mem))),
+                _ => {
+                    return Err(err_eval(
+                        "A record definition must be (defstruct name field1 .. fieldn) of symbols",
+                    ))
+                }
+            }
+        }
+
+        if names.is_empty() {
+            return Err(err_eval("A record definition must have a name"));
+        }
+        // the constructor needs a register for each field plus one for the descriptor
+        if items.len() > 253 - FIRST_ARG_REG {
+            return Err(err_eval("A record type cannot have more than 250 fields"));
+        }
+
+        // every record of this type refers to this descriptor: the name followed by the fields
+        let descriptor = List::from_slice(mem, &items)?.as_tagged(mem);
+        let type_name = &names[0];
+        let fields = &items[1..];
+
+        let arg = FIRST_ARG_REG as Register;
+        let mut functions = Vec::with_capacity(fields.len() * 2 + 2);
+
+        // (make-name field1 .. fieldn)
+        let descriptor_reg = arg + fields.len() as Register;
+        functions.push(record_function(
+            mem,
+            &format!("make-{}", type_name),
+            fields,
+            descriptor,
+            descriptor_reg,
+            &[
+                Opcode::MakeRecord {
+                    dest: 0,
+                    descriptor: descriptor_reg,
+                    first: arg,
+                },
+                Opcode::Return { reg: 0 },
+            ],
+        )?);
+
+        // (name? value)
+        functions.push(record_function(
+            mem,
+            &format!("{}?", type_name),
+            &items[..1],
+            descriptor,
+            arg + 1,
+            &[
+                Opcode::IsRecord {
+                    dest: 0,
+                    test: arg,
+                    descriptor: arg + 1,
+                },
+                Opcode::Return { reg: 0 },
+            ],
+        )?);
+
+        for (index, field) in names[1..].iter().enumerate() {
+            let index = index as u8;
+            let field_params = [items[0], fields[index as usize]];
+
+            // (name-field record)
+            functions.push(record_function(
+                mem,
+                &format!("{}-{}", type_name, field),
+                &items[..1],
+                descriptor,
+                arg + 1,
+                &[
+                    Opcode::CheckRecord {
+                        record: arg,
+                        descriptor: arg + 1,
+                    },
+                    Opcode::GetField {
+                        dest: 0,
+                        record: arg,
+                        index,
+                    },
+                    Opcode::Return { reg: 0 },
+                ],
+            )?);
+
+            // (set-name-field! record value)
+            functions.push(record_function(
+                mem,
+                &format!("set-{}-{}!", type_name, field),
+                &field_params,
+                descriptor,
+                arg + 2,
+                &[
+                    Opcode::CheckRecord {
+                        record: arg,
+                        descriptor: arg + 2,
+                    },
+                    Opcode::SetField {
+                        record: arg,
+                        index,
+                        src: arg + 1,
+                    },
+                    Opcode::Return { reg: arg + 1 },
+                ],
+            )?);
+        }
+
+        // bind each generated function to its name, reusing the same two registers for each
+        let first_reg = self.next_reg;
+        for function in functions {
+            let name = self.push_load_literal(mem, function.name_symbol(mem))?;
+            let src = self.push_load_literal(mem, function.as_tagged(mem))?;
+            self.push(mem, Opcode::StoreGlobal { src, name })?;
+            self.reset_reg(first_reg);
+        }
+
+        self.push_load_literal(mem, items[0])
+    }
+
+    /// (lambda (args) (exprs))
+    /// OR
+    /// (\ (args) (exprs))        
+    fn compile_anonymous_function<'guard>(
+        &mut self,
+        mem: &'guard MutatorView,
+        params: TaggedScopedPtr<'guard>,
+    ) -> Result<Register, RuntimeError> {
+        let items = vec_from_pairs(mem, params)?;
+
+        if items.len() < 2 {
+            return Err(err_eval(
+                "An anonymous function definition must have at least (lambda (params) expr)",
+            ));
+        }
+
+        // a function consists of (name (params) expr1 .. exprn)
+        let fn_params = items[0];
+        let fn_exprs = &items[1..];
+
+        // compile the function to a Function object
+        let fn_object = compile_function(mem, Some(&self.vars), mem.nil(), fn_params, fn_exprs)?;
+
+        // load the function object as a literal
         let dest = self.push_load_literal(mem, fn_object)?;
 
         // if fn_object has nonlocal refs, compile a MakeClosure instruction in addition, replacing
@@ -584,6 +1545,288 @@ impl<'parent> Compiler<'parent> {
         Ok(dest)
     }
 
+    /// Compile a 'match' application, evaluating the body of the first clause whose pattern
+    /// matches the value of the subject expression
+    /// (match <subject-expr>
+    ///   (<pattern> <expr> ...)
+    ///   (<pattern> :when <guard-expr> <expr> ...))
+    ///
+    /// A pattern is one of:
+    ///  - `_`, matching anything
+    ///  - a symbol, matching anything and binding it to the symbol in the clause body
+    ///  - `nil`, matching the empty list
+    ///  - a number, text, `true`, a keyword or `(quote <value>)`, matching an equal value
+    ///  - a list of patterns, matching a list of the same length whose items match in turn
+    ///  - a dotted list `(<pattern> ... . <pattern>)`, the last pattern matching the rest of the
+    ///    list
+    ///
+    /// If a guard expression is given, the clause only matches if it evaluates to true with the
+    /// pattern variables bound. The result is nil if no clause matches.
+    fn compile_apply_match<'guard>(
+        &mut self,
+        mem: &'guard MutatorView,
+        args: TaggedScopedPtr<'guard>,
+    ) -> Result<Register, RuntimeError> {
+        //
+        //   eval subject
+        //   for each clause:
+        //     test pattern against subject, binding variables
+        //       (on any failed test jmp -> next)
+        //     eval guard
+        //     if not true then jmp -> next
+        //     eval body
+        //     jmp -> end
+        //    next:
+        //   load nil
+        //  end:
+        //
+        let items = vec_from_pairs(mem, args)?;
+        if items.is_empty() {
+            return Err(err_eval(
+                "A match expression must have a subject expression",
+            ));
+        }
+
+        let bytecode = self.bytecode.get(mem);
+        let dest = self.acquire_reg();
+        let subject = self.acquire_reg();
+        self.compile_eval_into(mem, items[0], subject)?;
+
+        let mut end_jumps = Vec::new();
+
+        for clause in &items[1..] {
+            let clause = vec_from_pairs(mem, *clause)?;
+            let (pattern, body) = match clause.split_first() {
+                Some((pattern, body)) => (*pattern, body),
+                None => return Err(err_eval("A match clause must have a pattern")),
+            };
+
+            // a guard is introduced by the keyword :when straight after the pattern
+            let (guard, body) = match body {
+                [keyword, guard, body @ ..] if *keyword == mem.lookup_sym(":when") => {
+                    (Some(*guard), body)
+                }
+                [keyword] if *keyword == mem.lookup_sym(":when") => {
+                    return Err(err_eval("A match clause :when must be followed by a guard"))
+                }
+                _ => (None, body),
+            };
+
+            // bind the pattern variables in a new scope, as let does
+            let mut names = Vec::new();
+            pattern_variables(mem, pattern, &mut names)?;
+
+            self.reset_reg(subject + 1);
+            let mut match_scope = Scope::new();
+            self.next_reg = match_scope.push_bindings(&names, self.next_reg)?;
+            self.vars.scopes.push(match_scope);
+
+            let mut next_jumps = Vec::new();
+            self.compile_pattern(mem, pattern, subject, &mut next_jumps)?;
+
+            if let Some(guard) = guard {
+                let test = self.compile_eval(mem, guard)?;
+                self.push(
+                    mem,
+                    Opcode::JumpIfNotTrue {
+                        test,
+                        offset: JUMP_UNKNOWN,
+                    },
+                )?;
+                next_jumps.push(bytecode.last_instruction());
+            }
+
+            if body.is_empty() {
+                self.push(mem, Opcode::LoadNil { dest })?;
+            }
+            for expr in body {
+                let src = self.compile_eval(mem, *expr)?;
+                if src != dest {
+                    self.push(mem, Opcode::CopyRegister { dest, src })?;
+                }
+            }
+
+            let closing_instructions = self.vars.pop_scope();
+            for opcode in &closing_instructions {
+                self.push(mem, *opcode)?;
+            }
+
+            self.push(
+                mem,
+                Opcode::Jump {
+                    offset: JUMP_UNKNOWN,
+                },
+            )?;
+            end_jumps.push(bytecode.last_instruction());
+
+            for address in next_jumps {
+                self.patch_jump_to_next(mem, address)?;
+            }
+        }
+
+        // no clause matched
+        self.push(mem, Opcode::LoadNil { dest })?;
+
+        for address in end_jumps {
+            self.patch_jump_to_next(mem, address)?;
+        }
+
+        self.reset_reg(dest + 1);
+        Ok(dest)
+    }
+
+    /// Compile the tests of a `match` pattern against the value in the `value` register, copying
+    /// matched values into the registers of the pattern variables. The address of every jump
+    /// taken when a test fails is added to `fail_jumps`.
+    fn compile_pattern<'guard>(
+        &mut self,
+        mem: &'guard MutatorView,
+        pattern: TaggedScopedPtr<'guard>,
+        value: Register,
+        fail_jumps: &mut Vec<ArraySize>,
+    ) -> Result<(), RuntimeError> {
+        match *pattern {
+            Value::Nil => {
+                let test = self.acquire_reg();
+                self.push(
+                    mem,
+                    Opcode::IsNil {
+                        dest: test,
+                        test: value,
+                    },
+                )?;
+                self.push_fail_jump(mem, test, fail_jumps)?;
+            }
+
+            Value::Symbol(s) => match PatternSymbol::from_symbol(mem, &s) {
+                PatternSymbol::Wildcard => (),
+                PatternSymbol::Nil => self.compile_pattern(mem, mem.nil(), value, fail_jumps)?,
+                PatternSymbol::Literal => {
+                    self.compile_literal_pattern(mem, pattern, value, fail_jumps)?
+                }
+                PatternSymbol::Variable => match self.vars.lookup_binding(pattern)? {
+                    Some(Binding::Local(dest)) => {
+                        self.push(mem, Opcode::CopyRegister { dest, src: value })?
+                    }
+                    _ => return Err(err_eval("Pattern variable is not bound in the clause")),
+                },
+            },
+
+            Value::Pair(p) if is_quoted_pattern(mem, pattern) => {
+                let literal = value_from_1_pair(mem, p.second.get(mem))?;
+                self.compile_literal_pattern(mem, literal, value, fail_jumps)?;
+            }
+
+            Value::Pair(p) => {
+                // the value must be a pair: neither an atom nor nil
+                let test = self.acquire_reg();
+                self.push(
+                    mem,
+                    Opcode::IsAtom {
+                        dest: test,
+                        test: value,
+                    },
+                )?;
+                self.push(
+                    mem,
+                    Opcode::JumpIfTrue {
+                        test,
+                        offset: JUMP_UNKNOWN,
+                    },
+                )?;
+                fail_jumps.push(self.bytecode.get(mem).last_instruction());
+                self.push(
+                    mem,
+                    Opcode::IsNil {
+                        dest: test,
+                        test: value,
+                    },
+                )?;
+                self.push(
+                    mem,
+                    Opcode::JumpIfTrue {
+                        test,
+                        offset: JUMP_UNKNOWN,
+                    },
+                )?;
+                fail_jumps.push(self.bytecode.get(mem).last_instruction());
+
+                let first = self.acquire_reg();
+                self.push(
+                    mem,
+                    Opcode::FirstOfPair {
+                        dest: first,
+                        reg: value,
+                    },
+                )?;
+                self.compile_pattern(mem, p.first.get(mem), first, fail_jumps)?;
+
+                let second = self.acquire_reg();
+                self.push(
+                    mem,
+                    Opcode::SecondOfPair {
+                        dest: second,
+                        reg: value,
+                    },
+                )?;
+                self.compile_pattern(mem, p.second.get(mem), second, fail_jumps)?;
+            }
+
+            _ => self.compile_literal_pattern(mem, pattern, value, fail_jumps)?,
+        }
+
+        Ok(())
+    }
+
+    /// Compile a test that the value in the `value` register equals a literal. Numbers and
+    /// symbols are compared by identity, anything else structurally.
+    fn compile_literal_pattern<'guard>(
+        &mut self,
+        mem: &'guard MutatorView,
+        literal: TaggedScopedPtr<'guard>,
+        value: Register,
+        fail_jumps: &mut Vec<ArraySize>,
+    ) -> Result<(), RuntimeError> {
+        if let Value::Nil = *literal {
+            return self.compile_pattern(mem, literal, value, fail_jumps);
+        }
+
+        let expected = self.push_load_literal(mem, literal)?;
+        let test = self.acquire_reg();
+        let op = match *literal {
+            Value::Number(_) | Value::Symbol(_) => Opcode::IsIdentical {
+                dest: test,
+                test1: value,
+                test2: expected,
+            },
+            _ => Opcode::IsEqual {
+                dest: test,
+                test1: value,
+                test2: expected,
+            },
+        };
+        self.push(mem, op)?;
+        self.push_fail_jump(mem, test, fail_jumps)
+    }
+
+    /// Push a jump taken if the `test` register is not true, recording its address
+    fn push_fail_jump<'guard>(
+        &mut self,
+        mem: &'guard MutatorView,
+        test: Register,
+        fail_jumps: &mut Vec<ArraySize>,
+    ) -> Result<(), RuntimeError> {
+        self.push(
+            mem,
+            Opcode::JumpIfNotTrue {
+                test,
+                offset: JUMP_UNKNOWN,
+            },
+        )?;
+        fail_jumps.push(self.bytecode.get(mem).last_instruction());
+        Ok(())
+    }
+
     /// (name <arg-expr-1> <arg-expr-n>)
     fn compile_apply_call<'guard>(
         &mut self,
@@ -600,16 +1843,13 @@ impl<'parent> Compiler<'parent> {
         let arg_list = vec_from_pairs(mem, args)?;
         let arg_count = arg_list.len() as u8;
 
-        for arg in arg_list {
-            let src = self.compile_eval(mem, arg)?;
-            // if a local variable register was returned, we need to copy the register to the arg
-            // list. Bound registers are necessarily lower indexes than where the function call is
-            // situated because expression scope and register acquisition progresses the register
-            // index in use.
-            if src <= dest {
-                let dest = self.acquire_reg();
-                self.push(mem, Opcode::CopyRegister { dest, src })?;
-            }
+        // each argument must end up in its own register following the closure environment. An
+        // argument expression may use scratch registers beyond its own, so the result is copied
+        // into place if it landed elsewhere - as does a local variable register, which is
+        // necessarily lower than the call's registers
+        for (index, arg) in arg_list.into_iter().enumerate() {
+            let arg_reg = dest + FIRST_ARG_REG as Register + index as Register;
+            self.compile_eval_into(mem, arg, arg_reg)?;
         }
 
         // put the function pointer in the last register of the call so it'll be discarded
@@ -690,6 +1930,9 @@ impl<'parent> Variables<'parent> {
     ) -> Result<Option<Binding>, RuntimeError> {
         //  return value should be (count-of-parent-functions-followed, Variable)
         let name_string = match *name {
+            Value::Symbol(s) if s.is_keyword() => {
+                return Err(err_eval("A keyword cannot be bound to a value"))
+            }
             Value::Symbol(s) => String::from(s.as_str(&name)),
             _ => {
                 return Err(err_eval(
@@ -781,6 +2024,9 @@ impl Scope {
         reg: Register,
     ) -> Result<(), RuntimeError> {
         let name_string = match *name {
+            Value::Symbol(s) if s.is_keyword() => {
+                return Err(err_eval("A keyword cannot be bound to a value"))
+            }
             Value::Symbol(s) => String::from(s.as_str(&name)),
             _ => return Err(err_eval("A binding name must be a symbol")),
         };
@@ -832,8 +2078,11 @@ impl Nonlocal {
 #[cfg(test)]
 mod integration {
     use super::*;
+    use crate::interpreter::containers::{Container, IndexedAnyContainer};
     use crate::interpreter::memory::Memory;
+    use crate::interpreter::number::MIN_INTEGER;
     use crate::interpreter::parser::parse;
+    use crate::interpreter::printer::print;
     use crate::interpreter::vm::Thread;
     use crate::interpreter::Mutator;
 
@@ -886,4 +2135,542 @@ mod integration {
 
         test_helper(test_inner);
     }
+
+    #[test]
+    fn compile_float_arithmetic() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+
+            let result = eval_helper(mem, t, "(+ 1 2.5)")?;
+            assert_eq!(print(*result), "3.5");
+
+            let result = eval_helper(mem, t, "(* 0.5 (- 10 4))")?;
+            assert_eq!(print(*result), "3.0");
+
+            let result = eval_helper(mem, t, "(/ 7 2)")?;
+            assert!(result == mem.number(3));
+
+            let result = eval_helper(mem, t, "(/ 7 2.0)")?;
+            assert_eq!(print(*result), "3.5");
+
+            assert!(eval_helper(mem, t, "(/ 1 0)").is_err());
+            assert!(eval_helper(mem, t, "(+ 1 (quote a))").is_err());
+
+            // results that could not be printed and read back in are errors
+            assert!(eval_helper(mem, t, "(/ 1.0 0)").is_err());
+            assert!(eval_helper(mem, t, "(/ 0 0.0)").is_err());
+            assert!(eval_helper(mem, t, "(* 1e300 1e300)").is_err());
+            assert!(eval_helper(mem, t, "(- -1e308 1e308)").is_err());
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn compile_numeric_conversions() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+
+            let result = eval_helper(mem, t, "(floor -2.5)")?;
+            assert_eq!(print(*result), "-3.0");
+
+            let result = eval_helper(mem, t, "(round 2.5)")?;
+            assert_eq!(print(*result), "3.0");
+
+            let result = eval_helper(mem, t, "(->int -2.7)")?;
+            assert!(result == mem.number(-2));
+
+            let result = eval_helper(mem, t, "(->float 4)")?;
+            assert_eq!(print(*result), "4.0");
+
+            let result = eval_helper(mem, t, "(floor 4)")?;
+            assert!(result == mem.number(4));
+
+            assert!(eval_helper(mem, t, "(->int 1e300)").is_err());
+
+            // 2^61 is one past MAX_INTEGER, the largest float below it is 2^61 - 256
+            assert!(eval_helper(mem, t, "(->int 2305843009213693952.0)").is_err());
+            let result = eval_helper(mem, t, "(->int 2305843009213693696.0)")?;
+            assert!(result == mem.number(2305843009213693696));
+
+            // -2^61 - 1 has no float of its own and rounds to -2^61, which is MIN_INTEGER, the
+            // next float down is -2^61 - 512
+            let result = eval_helper(mem, t, "(->int -2305843009213693953.0)")?;
+            assert!(result == mem.number(MIN_INTEGER));
+            assert!(eval_helper(mem, t, "(->int -2305843009213694464.0)").is_err());
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn compile_if() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+
+            let result = eval_helper(mem, t, "(if (is? 1 1) (quote yes) (quote no))")?;
+            assert!(result == mem.lookup_sym("yes"));
+
+            let result = eval_helper(mem, t, "(if (is? 1 2) (quote yes) (quote no))")?;
+            assert!(result == mem.lookup_sym("no"));
+
+            let result = eval_helper(mem, t, "(if (is? 1 2) (quote yes))")?;
+            assert!(result == mem.nil());
+
+            // a local variable in either branch must still land in the result register
+            eval_helper(mem, t, "(def pick (test a b) (if test a b))")?;
+            let result = eval_helper(mem, t, "(pick true 1 2)")?;
+            assert!(result == mem.number(1));
+            let result = eval_helper(mem, t, "(pick nil 1 2)")?;
+            assert!(result == mem.number(2));
+
+            assert!(eval_helper(mem, t, "(if true)").is_err());
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn compile_begin() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+
+            let result = eval_helper(
+                mem,
+                t,
+                "(begin (set (quote a) 1) (set (quote b) 2) (+ a b))",
+            )?;
+            assert!(result == mem.number(3));
+
+            let result = eval_helper(mem, t, "(do 1 2)")?;
+            assert!(result == mem.number(2));
+
+            let result = eval_helper(mem, t, "(begin)")?;
+            assert!(result == mem.nil());
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn compile_and_or() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+
+            let result = eval_helper(mem, t, "(and true (is? 1 1) 3)")?;
+            assert!(result == mem.number(3));
+
+            let result = eval_helper(mem, t, "(and true nil 3)")?;
+            assert!(result == mem.nil());
+
+            let result = eval_helper(mem, t, "(and)")?;
+            assert!(result == mem.lookup_sym("true"));
+
+            let result = eval_helper(mem, t, "(or nil (is? 1 1) 3)")?;
+            assert!(result == mem.lookup_sym("true"));
+
+            let result = eval_helper(mem, t, "(or nil 3)")?;
+            assert!(result == mem.number(3));
+
+            let result = eval_helper(mem, t, "(or)")?;
+            assert!(result == mem.nil());
+
+            // evaluation stops at the first expression that decides the result
+            eval_helper(mem, t, "(set (quote touched) nil)")?;
+            eval_helper(mem, t, "(and nil (set (quote touched) true))")?;
+            eval_helper(mem, t, "(or true (set (quote touched) true))")?;
+            let result = eval_helper(mem, t, "touched")?;
+            assert!(result == mem.nil());
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn compile_while() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+
+            eval_helper(mem, t, "(set (quote i) 0)")?;
+            eval_helper(mem, t, "(set (quote total) 0)")?;
+
+            // enough iterations to run well past a single batch of VM instructions
+            let result = eval_helper(
+                mem,
+                t,
+                "(while (nil? (is? i 1000))
+                   (set (quote total) (+ total i))
+                   (set (quote i) (+ i 1)))",
+            )?;
+            assert!(result == mem.nil());
+
+            let result = eval_helper(mem, t, "total")?;
+            assert!(result == mem.number(499500));
+
+            // a loop whose test is false to begin with never runs its body
+            let result = eval_helper(mem, t, "(loop nil (set (quote i) 0))")?;
+            assert!(result == mem.nil());
+            let result = eval_helper(mem, t, "i")?;
+            assert!(result == mem.number(1000));
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn compile_closures() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+
+            // the captured parameter outlives the call that created the closure
+            eval_helper(mem, t, "(def adder (n) (lambda (x) (+ x n)))")?;
+            let result = eval_helper(mem, t, "((adder 1) 2)")?;
+            assert!(result == mem.number(3));
+
+            // each call captures its own variable
+            eval_helper(mem, t, "(set (quote add-ten) (adder 10))")?;
+            eval_helper(mem, t, "(set (quote add-two) (adder 2))")?;
+            let result = eval_helper(mem, t, "(cons (add-ten 1) (add-two 1))")?;
+            assert_eq!(print(*result), "(11 . 3)");
+
+            // closures can be partially applied and take no arguments
+            eval_helper(
+                mem,
+                t,
+                "(def between (n) (lambda (a b) (cons a (cons n b))))",
+            )?;
+            let result = eval_helper(mem, t, "(((between 2) 1) 3)")?;
+            assert_eq!(print(*result), "(1 2 . 3)");
+            let result = eval_helper(mem, t, "(((lambda (n) (lambda () n)) 5))")?;
+            assert!(result == mem.number(5));
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn compile_rest_params() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+
+            eval_helper(mem, t, "(def tail (a b . rest) rest)")?;
+            let result = eval_helper(mem, t, "(tail 1 2 3 4)")?;
+            assert_eq!(print(*result), "(3 4)");
+            let result = eval_helper(mem, t, "(tail 1 2)")?;
+            assert!(result == mem.nil());
+
+            // a symbol in place of the parameter list takes all the arguments
+            let result = eval_helper(mem, t, "((lambda args args) 1 2 3)")?;
+            assert_eq!(print(*result), "(1 2 3)");
+            let result = eval_helper(mem, t, "((lambda args args))")?;
+            assert!(result == mem.nil());
+
+            // too few arguments is still a partial application
+            let result = eval_helper(mem, t, "((tail 1) 2 3)")?;
+            assert_eq!(print(*result), "(3)");
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn compile_optional_params() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+
+            eval_helper(
+                mem,
+                t,
+                "(def f (a &optional (b (+ a 1)) c) (cons a (cons b c)))",
+            )?;
+            let result = eval_helper(mem, t, "(f 1)")?;
+            assert_eq!(print(*result), "(1 2)");
+            let result = eval_helper(mem, t, "(f 1 5)")?;
+            assert_eq!(print(*result), "(1 5)");
+            let result = eval_helper(mem, t, "(f 1 5 (quote (6)))")?;
+            assert_eq!(print(*result), "(1 5 6)");
+
+            // an explicit nil is an argument, not a request for the default
+            let result = eval_helper(mem, t, "(f 1 nil)")?;
+            assert_eq!(print(*result), "(1 nil)");
+
+            assert!(eval_helper(mem, t, "(f 1 2 3 4)").is_err());
+
+            // optionals with a rest parameter
+            eval_helper(mem, t, "(def g (&optional (a 10) . rest) (cons a rest))")?;
+            let result = eval_helper(mem, t, "(g)")?;
+            assert_eq!(print(*result), "(10)");
+            let result = eval_helper(mem, t, "(g 1 2 3)")?;
+            assert_eq!(print(*result), "(1 2 3)");
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn compile_keyword_params() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+
+            let result = eval_helper(mem, t, ":size")?;
+            assert!(result == mem.lookup_sym(":size"));
+
+            eval_helper(
+                mem,
+                t,
+                "(def rect (x &key (width 1) height) (cons x (cons width height)))",
+            )?;
+            let result = eval_helper(mem, t, "(rect 0)")?;
+            assert_eq!(print(*result), "(0 1)");
+            let result = eval_helper(mem, t, "(rect 0 :height (quote (3)) :width 2)")?;
+            assert_eq!(print(*result), "(0 2 3)");
+
+            assert!(eval_helper(mem, t, "(rect 0 :depth 3)").is_err());
+            assert!(eval_helper(mem, t, "(rect 0 :width)").is_err());
+
+            // partial applications pass keywords through once the required args are given
+            eval_helper(mem, t, "(set (quote r) (rect))")?;
+            let result = eval_helper(mem, t, "(r 5 :width 4)")?;
+            assert_eq!(print(*result), "(5 4)");
+
+            eval_helper(mem, t, "(def sum (a b &optional (c 0)) (+ a (+ b c)))")?;
+            eval_helper(mem, t, "(set (quote add1) (sum 1))")?;
+            let result = eval_helper(mem, t, "(add1 2)")?;
+            assert!(result == mem.number(3));
+            let result = eval_helper(mem, t, "(add1 2 3)")?;
+            assert!(result == mem.number(6));
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn compile_keywords() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+            let eval = |code| eval_helper(mem, t, code).map(|value| print(*value));
+
+            assert_eq!(eval("(cons :a (cons (quote :b) nil))")?, "(:a :b)");
+            assert_eq!(eval("(is? :a (string->symbol \":a\"))")?, "true");
+            assert_eq!(eval("(keyword? :a)")?, "true");
+            assert_eq!(eval("(keyword? (quote a))")?, "nil");
+            assert_eq!(eval("(keyword? (quote :))")?, "nil");
+            assert_eq!(eval("(symbol? :a)")?, "true");
+            assert_eq!(eval("(symbol? 1)")?, "nil");
+            assert_eq!(eval("(symbol->string :a)")?, "\":a\"");
+            assert!(eval("(string->symbol \"\")").is_err());
+
+            // keywords can't be bound, whether globally, as parameters or by let
+            assert!(eval("(set (quote :a) 1)").is_err());
+            assert!(eval("(def :f () 1)").is_err());
+            assert!(eval("(def f (:x) :x)").is_err());
+            assert!(eval("(let ((:x 1)) :x)").is_err());
+            assert_eq!(eval(":a")?, ":a");
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn compile_defstruct() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+
+            let result = eval_helper(mem, t, "(defstruct point x y)")?;
+            assert!(result == mem.lookup_sym("point"));
+
+            eval_helper(mem, t, "(set (quote p) (make-point 1 2))")?;
+            let result = eval_helper(mem, t, "p")?;
+            assert_eq!(print(*result), "#<point x=1 y=2>");
+
+            let result = eval_helper(mem, t, "(point-y p)")?;
+            assert!(result == mem.number(2));
+            let result = eval_helper(mem, t, "(point? p)")?;
+            assert!(result == mem.lookup_sym("true"));
+            let result = eval_helper(mem, t, "(point? (quote (1 2)))")?;
+            assert!(result == mem.nil());
+
+            let result = eval_helper(mem, t, "(set-point-x! p 5)")?;
+            assert!(result == mem.number(5));
+            let result = eval_helper(mem, t, "(point-x p)")?;
+            assert!(result == mem.number(5));
+
+            // records of the same type compare by content, other types never compare equal
+            eval_helper(mem, t, "(defstruct pair x y)")?;
+            let result = eval_helper(mem, t, "(equal? p (make-point 5 2))")?;
+            assert!(result == mem.lookup_sym("true"));
+            let result = eval_helper(mem, t, "(equal? p (make-pair 5 2))")?;
+            assert!(result == mem.nil());
+
+            assert!(eval_helper(mem, t, "(point-x (make-pair 1 2))").is_err());
+            assert!(eval_helper(mem, t, "(point-x 1)").is_err());
+            assert!(eval_helper(mem, t, "(defstruct)").is_err());
+            assert!(eval_helper(mem, t, "(defstruct point 1)").is_err());
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn compile_match() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+
+            eval_helper(
+                mem,
+                t,
+                "(def describe (x)
+                   (match x
+                     (nil (quote empty))
+                     (0 (quote zero))
+                     (\"hi\" (quote greeting))
+                     ((quote sym) (quote quoted))
+                     ((:tag v) v)
+                     ((a b) :when (is? a b) (quote same))
+                     ((a b) (cons b a))
+                     ((a _ . rest) rest)
+                     (n :when (is? n 5) (quote five))
+                     (_ (quote other))))",
+            )?;
+
+            let cases = [
+                ("nil", "empty"),
+                ("0", "zero"),
+                ("\"hi\"", "greeting"),
+                ("(quote sym)", "quoted"),
+                ("(quote (:tag 7))", "7"),
+                ("(quote (1 1))", "same"),
+                ("(quote (1 2))", "(2 . 1)"),
+                ("(quote (1 2 3 4))", "(3 4)"),
+                ("5", "five"),
+                ("6", "other"),
+                ("(quote other-sym)", "other"),
+            ];
+            for (arg, expected) in cases.iter() {
+                let result = eval_helper(mem, t, &format!("(describe {})", arg))?;
+                assert_eq!(print(*result), *expected);
+            }
+
+            // nested patterns and no matching clause
+            let result = eval_helper(
+                mem,
+                t,
+                "(match (quote ((1 2) 3)) (((a b) c) (+ a (+ b c))))",
+            )?;
+            assert!(result == mem.number(6));
+            let result = eval_helper(mem, t, "(match 1 (2 true))")?;
+            assert!(result == mem.nil());
+
+            // pattern variables are scoped to their clause
+            let result = eval_helper(mem, t, "(let ((a 10)) (match 1 (a a)) a)")?;
+            assert!(result == mem.number(10));
+
+            assert!(eval_helper(mem, t, "(match)").is_err());
+            assert!(eval_helper(mem, t, "(match 1 ((a a) a))").is_err());
+            assert!(eval_helper(mem, t, "(match 1 (a :when))").is_err());
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn compile_assertions() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            use crate::interpreter::error::{spos, ErrorKind};
+
+            let t = Thread::alloc(mem)?;
+            let true_sym = mem.lookup_sym("true");
+
+            assert!(eval_helper(mem, t, "(assert (is? 1 1))")? == true_sym);
+            assert!(
+                eval_helper(mem, t, "(assert-equal (quote (1 2)) (cons 1 (cons 2 nil)))")?
+                    == true_sym
+            );
+
+            // failures carry the position of the assertion
+            let error = eval_helper(mem, t, "(begin\n  (assert (is? 1 2)))").unwrap_err();
+            let kind = ErrorKind::AssertionFailed(String::from("(is? 1 2) is not true"));
+            assert_eq!(error, RuntimeError::with_pos(kind, spos(2, 3)));
+
+            let error = eval_helper(mem, t, "(assert-equal \"a\" (car (quote (1))))").unwrap_err();
+            let kind = ErrorKind::AssertionFailed(String::from(
+                "(car (quote (1))): expected \"a\", got 1",
+            ));
+            assert_eq!(error, RuntimeError::with_pos(kind, spos(1, 1)));
+
+            // assert-error catches errors raised in nested calls and evaluates to the message
+            eval_helper(mem, t, "(def inner (x) (car x))")?;
+            eval_helper(mem, t, "(def outer (x) (cons 1 (inner x)))")?;
+            let message = eval_helper(mem, t, "(assert-error (outer 1))")?;
+            assert!(matches!(*message, Value::Text(_)));
+            let error = eval_helper(mem, t, "(assert-error (outer (quote (2))))").unwrap_err();
+            let kind = ErrorKind::AssertionFailed(String::from(
+                "(outer (quote (2))) did not raise an error",
+            ));
+            assert_eq!(error, RuntimeError::with_pos(kind, spos(1, 1)));
+
+            // the thread carries on normally after a caught error
+            let result = eval_helper(
+                mem,
+                t,
+                "(begin (assert-error (outer 1)) (outer (quote (2))))",
+            )?;
+            assert_eq!(print(*result), "(1 . 2)");
+
+            // a test is a function of no arguments, registered on the thread rather than bound
+            // as a global
+            eval_helper(mem, t, "(deftest passes (assert-equal 2 (+ 1 1)))")?;
+            eval_helper(mem, t, "(deftest fails (assert nil))")?;
+            assert!(eval_helper(mem, t, "(passes)").is_err());
+            let tests = t.tests(mem);
+            assert_eq!(tests.length(), 2);
+            for (index, passes) in [(0, true), (1, false)] {
+                let Value::Pair(test) = *IndexedAnyContainer::get(&*tests, mem, index)? else {
+                    panic!("a test should be a (name . function) pair");
+                };
+                let Value::Function(function) = *test.second.get(mem) else {
+                    panic!("a test should be a function");
+                };
+                assert_eq!(t.quick_vm_eval(mem, function).is_ok(), passes);
+            }
+
+            // defining a test again replaces it
+            eval_helper(mem, t, "(deftest fails (assert true))")?;
+            assert_eq!(t.tests(mem).length(), 2);
+
+            assert!(eval_helper(mem, t, "(deftest no-body)").is_err());
+            assert!(eval_helper(mem, t, "(deftest (x) 1)").is_err());
+            assert!(eval_helper(mem, t, "(assert-equal 1)").is_err());
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
 }
@@ -6,12 +6,14 @@ use crate::memory::ArraySize;
 
 use super::{
     containers::{Container, HashIndexedAnyContainer},
+    equality::equal,
     error::ErrorKind,
-    hashable::Hashable,
+    hashable::hash_value,
+    memory::write_barrier,
     printer::Print,
     rawarray::{default_array_growth, RawArray},
     safeptr::{MutatorScope, TaggedCellPtr, TaggedScopedPtr},
-    taggedptr::Value,
+    trace::{Trace, Tracer},
     MutatorView, RuntimeError, ScopedPtr,
 };
 
@@ -58,38 +60,35 @@ impl Dict {
                 let entry =
                     unsafe { &mut *(ptr.offset(index as isize) as *mut DictItem) as &mut DictItem };
                 if !entry.key.is_nil() {
-                    let new_entry = find_entry(mem, &new_data, entry.hash)?;
+                    let new_entry = find_entry(mem, &new_data, entry.key.get(mem), entry.hash)?;
                     *new_entry = entry.clone();
                 }
             }
         }
 
         self.data.set(new_data);
+        write_barrier(self);
         Ok(())
     }
 }
 
-/// Generate a hash value for a key
+/// Generate a hash value for a key. Keys are hashed structurally so that keys that are `equal?`
+/// find the same entry.
 fn hash_key<'guard>(
     guard: &'guard dyn MutatorScope,
     key: TaggedScopedPtr<'guard>,
 ) -> Result<u64, RuntimeError> {
-    match *key {
-        Value::Symbol(sym) => {
-            let mut hasher = FnvHasher::default();
-            sym.hash(guard, &mut hasher);
-            Ok(hasher.finish())
-        }
-        Value::Number(n) => Ok(n as u64),
-        _ => Err(RuntimeError::new(ErrorKind::UnhashableError)),
-    }
+    let mut hasher = FnvHasher::default();
+    hash_value(guard, key, &mut hasher);
+    Ok(hasher.finish())
 }
 
-/// Given a key, generate the hash and search for an entry that either matches this hash
+/// Given a key and its hash, search for an entry whose key is equal to the given key
 /// or the next available blank entry.
 fn find_entry<'guard>(
-    _guard: &'guard dyn MutatorScope,
+    guard: &'guard dyn MutatorScope,
     data: &RawArray<DictItem>,
+    key: TaggedScopedPtr<'guard>,
     hash: u64,
 ) -> Result<&'guard mut DictItem, RuntimeError> {
     // get raw pointer to base of array
@@ -110,7 +109,10 @@ fn find_entry<'guard>(
                 // Keep tombstone for now in case we find an exact match later
                 tombstone = Some(entry);
             }
-        } else if entry.hash == hash {
+        } else if entry.hash == hash
+            && !entry.key.is_nil()
+            && equal(guard, entry.key.get(guard), key)
+        {
             // this is an exact match slot
             return Ok(entry);
         } else if entry.key.is_nil() {
@@ -155,6 +157,40 @@ fn fill_with_blank_entries<'guard>(
     Ok(())
 }
 
+impl Dict {
+    /// Return every key and value in the Dict, in no particular order
+    pub fn items<'guard>(
+        &self,
+        guard: &'guard dyn MutatorScope,
+    ) -> Vec<(TaggedScopedPtr<'guard>, TaggedScopedPtr<'guard>)> {
+        let data = self.data.get();
+        let mut items = Vec::with_capacity(self.length.get() as usize);
+
+        if let Some(ptr) = data.as_ptr() {
+            for index in 0..data.capacity() {
+                let entry = unsafe { &*ptr.offset(index as isize) };
+                if !entry.key.is_nil() {
+                    items.push((entry.key.get(guard), entry.value.get(guard)));
+                }
+            }
+        }
+
+        items
+    }
+
+    /// Allocate a new Dict holding the same keys and values
+    pub fn copy<'guard>(
+        &self,
+        mem: &'guard MutatorView,
+    ) -> Result<ScopedPtr<'guard, Dict>, RuntimeError> {
+        let copy = Dict::alloc(mem)?;
+        for (key, value) in self.items(mem) {
+            copy.assoc(mem, key, value)?;
+        }
+        Ok(copy)
+    }
+}
+
 /// Hashable-indexed interface. Objects used as keys must implement Hashable.
 impl HashIndexedAnyContainer for Dict {
     fn lookup<'guard>(
@@ -164,7 +200,7 @@ impl HashIndexedAnyContainer for Dict {
     ) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
         let hash = hash_key(guard, key)?;
         let data = self.data.get();
-        let entry = find_entry(guard, &data, hash)?;
+        let entry = find_entry(guard, &data, key, hash)?;
 
         if entry.key.is_nil() {
             // a nil key means the key was not found in the Dict
@@ -188,7 +224,7 @@ impl HashIndexedAnyContainer for Dict {
             data = self.data.get();
         }
 
-        let entry = find_entry(mem, &data, hash)?;
+        let entry = find_entry(mem, &data, key, hash)?;
         if entry.key.is_nil() {
             self.length.set(self.length.get() + 1);
             if entry.hash == 0 {
@@ -212,7 +248,7 @@ impl HashIndexedAnyContainer for Dict {
         let hash = hash_key(guard, key)?;
 
         let data = self.data.get();
-        let entry = find_entry(guard, &data, hash)?;
+        let entry = find_entry(guard, &data, key, hash)?;
 
         if entry.key.is_nil() {
             // a nil key means the key was not found in the Dict
@@ -234,7 +270,7 @@ impl HashIndexedAnyContainer for Dict {
     ) -> Result<bool, RuntimeError> {
         let hash = hash_key(guard, key)?;
         let data = self.data.get();
-        let entry = find_entry(guard, &data, hash)?;
+        let entry = find_entry(guard, &data, key, hash)?;
 
         Ok(!entry.key.is_nil())
     }
@@ -288,6 +324,28 @@ impl Print for Dict {
     }
 }
 
+impl Trace for Dict {
+    fn trace(&self, tracer: &mut Tracer) {
+        let data = self.data.get();
+
+        if let Some(ptr) = data.as_ptr() {
+            tracer.mark_array_backing(ptr);
+
+            for index in 0..data.capacity() {
+                let entry = unsafe { &*ptr.offset(index as isize) };
+                entry.trace(tracer);
+            }
+        }
+    }
+}
+
+impl Trace for DictItem {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.key.trace(tracer);
+        self.value.trace(tracer);
+    }
+}
+
 impl DictItem {
     fn blank() -> DictItem {
         DictItem {
@@ -0,0 +1,248 @@
+use std::collections::HashSet;
+
+use super::{
+    containers::{Container, HashIndexedAnyContainer, IndexedAnyContainer},
+    safeptr::{MutatorScope, TaggedScopedPtr},
+    taggedptr::Value,
+};
+
+/// Structural equality. Pairs, Lists, Dicts and records of the same type are equal if their
+/// contents are equal, Text if the strings are the same, and Floats if their values are. Integers
+/// and Floats are never equal to each other. Any other values are only equal to themselves.
+///
+/// Cyclic structures are compared without looping forever: if the comparison of two containers
+/// comes back around to the same two containers, no difference has been found along the cycle.
+pub fn equal<'guard>(
+    guard: &'guard dyn MutatorScope,
+    left: TaggedScopedPtr<'guard>,
+    right: TaggedScopedPtr<'guard>,
+) -> bool {
+    let mut visited = HashSet::new();
+    equal_values(guard, left, right, &mut visited)
+}
+
+/// The address of a heap object, used to recognize containers that have already been visited
+fn address<T>(object: &T) -> usize {
+    object as *const T as usize
+}
+
+/// Compare two values. `visited` holds the address pairs of containers whose comparison has
+/// already begun; a difference between them will be found by that comparison, so meeting them
+/// again can be taken as equal.
+fn equal_values<'guard>(
+    guard: &'guard dyn MutatorScope,
+    left: TaggedScopedPtr<'guard>,
+    right: TaggedScopedPtr<'guard>,
+    visited: &mut HashSet<(usize, usize)>,
+) -> bool {
+    if left == right {
+        return true;
+    }
+
+    match (*left, *right) {
+        (Value::Float(l), Value::Float(r)) => l.value() == r.value(),
+
+        (Value::Text(l), Value::Text(r)) => l.as_string(guard) == r.as_string(guard),
+
+        (Value::Pair(_), Value::Pair(_)) => {
+            // follow the chain of second values iteratively so long lists don't exhaust the stack
+            let mut left = left;
+            let mut right = right;
+
+            while let (Value::Pair(l), Value::Pair(r)) = (*left, *right) {
+                if left == right || !visited.insert((address(&*l), address(&*r))) {
+                    return true;
+                }
+
+                if !equal_values(guard, l.first.get(guard), r.first.get(guard), visited) {
+                    return false;
+                }
+
+                left = l.second.get(guard);
+                right = r.second.get(guard);
+            }
+
+            equal_values(guard, left, right, visited)
+        }
+
+        (Value::List(l), Value::List(r)) => {
+            if !visited.insert((address(&*l), address(&*r))) {
+                return true;
+            }
+
+            if l.length() != r.length() {
+                return false;
+            }
+
+            (0..l.length()).all(|index| match (l.get(guard, index), r.get(guard, index)) {
+                (Ok(l_item), Ok(r_item)) => equal_values(guard, l_item, r_item, visited),
+                _ => false,
+            })
+        }
+
+        (Value::Dict(l), Value::Dict(r)) => {
+            if !visited.insert((address(&*l), address(&*r))) {
+                return true;
+            }
+
+            if l.length() != r.length() {
+                return false;
+            }
+
+            l.items(guard)
+                .into_iter()
+                .all(|(key, l_value)| match r.lookup(guard, key) {
+                    Ok(r_value) => equal_values(guard, l_value, r_value, visited),
+                    Err(_) => false,
+                })
+        }
+
+        (Value::Record(l), Value::Record(r)) => {
+            l.is_instance(guard, r.descriptor(guard).as_tagged(guard))
+                && equal_values(
+                    guard,
+                    l.values(guard).as_tagged(guard),
+                    r.values(guard).as_tagged(guard),
+                    visited,
+                )
+        }
+
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::interpreter::compiler::compile;
+    use crate::interpreter::dict::Dict;
+    use crate::interpreter::memory::Memory;
+    use crate::interpreter::pair::Pair;
+    use crate::interpreter::parser::parse;
+    use crate::interpreter::vm::Thread;
+    use crate::interpreter::{Mutator, MutatorView, RuntimeError, ScopedPtr};
+
+    fn eval_helper<'guard>(
+        mem: &'guard MutatorView,
+        thread: ScopedPtr<'guard, Thread>,
+        code: &str,
+    ) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+        let function = compile(mem, parse(mem, code)?)?;
+        thread.quick_vm_eval(mem, function)
+    }
+
+    fn test_helper(test_fn: fn(&MutatorView) -> Result<(), RuntimeError>) {
+        let mem = Memory::new();
+
+        struct Test {}
+        impl Mutator for Test {
+            type Input = fn(&MutatorView) -> Result<(), RuntimeError>;
+            type Output = ();
+
+            fn run(
+                &self,
+                mem: &MutatorView,
+                test_fn: Self::Input,
+            ) -> Result<Self::Output, RuntimeError> {
+                test_fn(mem)
+            }
+        }
+
+        let test = Test {};
+        mem.mutate(&test, test_fn).unwrap();
+    }
+
+    #[test]
+    fn equal_values() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+            let yes = mem.lookup_sym("true");
+
+            for code in &[
+                "(equal? (quote (1 (2 3) a)) (quote (1 (2 3) a)))",
+                "(equal? (quote (1 . 2)) (quote (1 . 2)))",
+                "(equal? \"abc\" \"abc\")",
+                "(equal? 1.5 1.5)",
+                "(equal? nil nil)",
+            ] {
+                assert!(eval_helper(mem, t, code)? == yes, "{}", code);
+            }
+
+            for code in &[
+                "(equal? (quote (1 2 3)) (quote (1 2)))",
+                "(equal? (quote (1 2)) (quote (1 . 2)))",
+                "(equal? \"abc\" \"abd\")",
+                "(equal? 1 1.0)",
+                "(is? \"abc\" \"abc\")",
+            ] {
+                assert!(eval_helper(mem, t, code)? == mem.nil(), "{}", code);
+            }
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn equal_cycles() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            // two separate circular lists of (1 2 1 2 ...) and one of (1 3 1 3 ...)
+            let circular = |second| -> Result<TaggedScopedPtr, RuntimeError> {
+                let tail = Pair::cons(mem, mem.number(second), mem.nil())?;
+                let head = Pair::cons(mem, mem.number(1), tail)?;
+                if let Value::Pair(pair) = *tail {
+                    pair.dot(head);
+                }
+                Ok(head)
+            };
+
+            let a = circular(2)?;
+            let b = circular(2)?;
+            let c = circular(3)?;
+
+            assert!(equal(mem, a, b));
+            assert!(!equal(mem, a, c));
+
+            // a Dict that contains itself
+            let dict = Dict::alloc(mem)?;
+            let d = dict.as_tagged(mem);
+            dict.assoc(mem, mem.lookup_sym("self"), d)?;
+            assert!(equal(mem, d, d));
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn structural_dict_keys() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let dict = Dict::alloc(mem)?;
+
+            let key = Pair::cons(mem, mem.number(1), mem.text("one")?)?;
+            dict.assoc(mem, key, mem.number(1))?;
+            dict.assoc(mem, mem.text("two")?, mem.number(2))?;
+            dict.assoc(mem, mem.float(3.0)?, mem.number(3))?;
+
+            let same_key = Pair::cons(mem, mem.number(1), mem.text("one")?)?;
+            assert!(dict.lookup(mem, same_key)? == mem.number(1));
+            assert!(dict.lookup(mem, mem.text("two")?)? == mem.number(2));
+            assert!(dict.lookup(mem, mem.float(3.0)?)? == mem.number(3));
+            assert!(dict.lookup(mem, mem.text("three")?).is_err());
+
+            // replacing the value for an equal key does not add an entry
+            dict.assoc(mem, mem.text("two")?, mem.number(4))?;
+            assert!(dict.length() == 3);
+            assert!(dict.lookup(mem, mem.text("two")?)? == mem.number(4));
+
+            dict.dissoc(mem, same_key)?;
+            assert!(!dict.exists(mem, key)?);
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+}
@@ -1,10 +1,10 @@
-use std::fmt;
+use std::{fmt, io};
 
 use rustyline::error::ReadlineError;
 
 use crate::memory::AllocError;
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum ErrorKind {
     OutOfMemory,
     BadAllocationRequest,
@@ -16,6 +16,14 @@ pub enum ErrorKind {
     UnhashableError,
     KeyError,
     IOError(String),
+    FileNotFound(String),
+    PermissionDenied(String),
+    ImageError(String),
+    InstructionLimit,
+    CallDepthLimit,
+    HeapLimit,
+    Timeout,
+    AssertionFailed(String),
 }
 
 /// Source code position
@@ -41,42 +49,86 @@ pub fn spos(line: u32, column: u32) -> SourcePos {
 pub struct RuntimeError {
     kind: ErrorKind,
     pos: Option<SourcePos>,
+    /// The function call frames that were active when the error was raised, outermost first
+    traceback: Vec<String>,
 }
 
 impl RuntimeError {
     pub fn new(kind: ErrorKind) -> Self {
-        Self { kind, pos: None }
+        Self {
+            kind,
+            pos: None,
+            traceback: Vec::new(),
+        }
     }
 
     pub fn with_pos(kind: ErrorKind, pos: SourcePos) -> RuntimeError {
         RuntimeError {
             kind: kind,
             pos: Some(pos),
+            traceback: Vec::new(),
         }
     }
 
+    /// Attach a description of the call frames the error was raised in
+    pub fn with_traceback(mut self, traceback: Vec<String>) -> RuntimeError {
+        self.traceback = traceback;
+        self
+    }
+
     pub fn error_kind(&self) -> &ErrorKind {
         &self.kind
     }
 
+    pub fn error_pos(&self) -> Option<SourcePos> {
+        self.pos
+    }
+
+    pub fn traceback(&self) -> &[String] {
+        &self.traceback
+    }
+
+    /// Show the error in context like `print_with_source()`, preceded by the call frames it was
+    /// raised in if it was raised inside a function
+    pub fn print_with_traceback(&self, source: &str) {
+        if !self.traceback.is_empty() {
+            println!("Error traceback:");
+            for frame in &self.traceback {
+                println!("  {}", frame);
+            }
+        }
+
+        self.print_with_source(source);
+    }
+
     /// Given the relevant source code string, show the error in context
     pub fn print_with_source(&self, source: &str) {
+        print!("{}", self.format_with_source(source));
+    }
+
+    /// Return the text `print_with_source()` shows, one or more lines each ending in a newline
+    pub fn format_with_source(&self, source: &str) -> String {
         if let Some(ref pos) = self.pos {
             let mut iter = source.lines().enumerate();
 
             while let Some((count, line)) = iter.next() {
                 // count starts at 0, line numbers start at 1
                 if count + 1 == pos.line as usize {
-                    println!("error: {}", self);
-                    println!("{:5}|{}", pos.line, line);
-                    println!("{:5}|{:width$}^", " ", " ", width = pos.column as usize);
-                    println!("{:5}|", " ");
-                    return;
+                    return format!(
+                        "error: {}\n{:5}|{}\n{:5}|{:width$}^\n{:5}|\n",
+                        self,
+                        pos.line,
+                        line,
+                        " ",
+                        " ",
+                        " ",
+                        width = pos.column as usize
+                    );
                 }
             }
-        } else {
-            println!("error: {}", self);
         }
+
+        format!("error: {}\n", self)
     }
 }
 
@@ -84,6 +136,9 @@ impl fmt::Display for RuntimeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.kind {
             ErrorKind::IOError(ref reason) => write!(f, "IO Error: {}", reason),
+            ErrorKind::FileNotFound(ref path) => write!(f, "File not found: {}", path),
+            ErrorKind::PermissionDenied(ref path) => write!(f, "Permission denied: {}", path),
+            ErrorKind::ImageError(ref reason) => write!(f, "Image error: {}", reason),
             ErrorKind::LexerError(ref reason) => write!(f, "Parse error: {}", reason),
             ErrorKind::ParseError(ref reason) => write!(f, "Parse error: {}", reason),
             ErrorKind::EvalError(ref reason) => write!(f, "Evaluation error: {}", reason),
@@ -94,6 +149,11 @@ impl fmt::Display for RuntimeError {
             ErrorKind::BoundsError => write!(f, "Indexing bounds error"),
             ErrorKind::KeyError => write!(f, "Key does not exist in Dict"),
             ErrorKind::UnhashableError => write!(f, "Attempt to access Dict with unhashable key"),
+            ErrorKind::InstructionLimit => write!(f, "Instruction limit exceeded"),
+            ErrorKind::CallDepthLimit => write!(f, "Call depth limit exceeded"),
+            ErrorKind::HeapLimit => write!(f, "Heap size limit exceeded"),
+            ErrorKind::Timeout => write!(f, "Evaluation timed out"),
+            ErrorKind::AssertionFailed(ref reason) => write!(f, "Assertion failed: {}", reason),
             ErrorKind::MutableBorrowError => write!(
                 f,
                 "Attempt to modify a container that is already mutably borrowed"
@@ -109,6 +169,13 @@ impl From<ReadlineError> for RuntimeError {
     }
 }
 
+/// Convert from io::Error
+impl From<io::Error> for RuntimeError {
+    fn from(other: io::Error) -> RuntimeError {
+        RuntimeError::new(ErrorKind::IOError(format!("{}", other)))
+    }
+}
+
 /// Convert from AllocError
 impl From<AllocError> for RuntimeError {
     fn from(other: AllocError) -> RuntimeError {
@@ -138,3 +205,18 @@ pub fn err_parser_wpos(pos: SourcePos, reason: &str) -> RuntimeError {
 pub fn err_eval(reason: &str) -> RuntimeError {
     RuntimeError::new(ErrorKind::EvalError(String::from(reason)))
 }
+
+/// Convenience shorthand function for building a heap image error
+pub fn err_image(reason: &str) -> RuntimeError {
+    RuntimeError::new(ErrorKind::ImageError(String::from(reason)))
+}
+
+/// Convenience shorthand function for building an error for a failed operation on a file
+pub fn err_file(path: &str, error: io::Error) -> RuntimeError {
+    let kind = match error.kind() {
+        io::ErrorKind::NotFound => ErrorKind::FileNotFound(String::from(path)),
+        io::ErrorKind::PermissionDenied => ErrorKind::PermissionDenied(String::from(path)),
+        _ => ErrorKind::IOError(format!("{}: {}", path, error)),
+    };
+    RuntimeError::new(kind)
+}
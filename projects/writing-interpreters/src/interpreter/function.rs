@@ -1,15 +1,54 @@
 use std::fmt;
 
+use crate::memory::ArraySize;
+
 use super::{
     bytecode::ByteCode,
-    containers::{Container, ContainerFromSlice, StackContainer},
+    containers::{
+        Container, ContainerFromSlice, IndexedAnyContainer, SliceableContainer, StackContainer,
+    },
+    error::err_eval,
     list::List,
-    printer::Print,
+    lsp::Arity,
+    native::NativeSpec,
+    pair::Pair,
+    printer::{debug, Print},
     safeptr::{MutatorScope, TaggedCellPtr, TaggedScopedPtr},
     taggedptr::Value,
+    trace::{Trace, Tracer},
     ArrayU16, CellPtr, MutatorView, RuntimeError, ScopedPtr,
 };
 
+/// The name of the Symbol the VM binds to optional and keyword parameters that were not given an
+/// argument. Function code replaces it with the parameter's default value. The name contains a
+/// space so the lexer can never produce it.
+pub const UNSUPPLIED: &str = "#<unsupplied argument>";
+
+/// Describes how call arguments are bound to a Function's parameters. Parameters take registers
+/// in the order required, optional, rest, keyword.
+pub struct Signature<'guard> {
+    /// Number of arguments required to activate the function
+    pub required: u8,
+    /// Number of optional positional parameters following the required ones
+    pub optional: u8,
+    /// Whether arguments beyond the positional parameters are collected into a list
+    pub rest: bool,
+    /// The keyword Symbols, such as `:name`, that select each keyword parameter
+    pub keywords: Option<ScopedPtr<'guard, List>>,
+}
+
+impl<'guard> Signature<'guard> {
+    /// A signature of required parameters only
+    pub fn fixed(required: u8) -> Signature<'guard> {
+        Signature {
+            required,
+            optional: 0,
+            rest: false,
+            keywords: None,
+        }
+    }
+}
+
 /// A function object type
 #[derive(Clone)]
 pub struct Function {
@@ -17,10 +56,18 @@ pub struct Function {
     name: TaggedCellPtr,
     /// Number of arguments required to activate the function
     arity: u8,
+    /// Number of optional positional parameters following the required ones
+    optional: u8,
+    /// Whether arguments beyond the positional parameters are collected into a list
+    rest: bool,
+    /// List of keyword Symbols selecting the keyword parameters, in register order. May be nil
+    keywords: TaggedCellPtr,
     /// Instructions comprising the function code
     code: CellPtr<ByteCode>,
     /// Param names are stored for introspection of a function signature
     param_names: CellPtr<List>,
+    /// The docstring given as the first body form, a Text, or nil if there is none
+    doc: TaggedCellPtr,
     /// List of (CallFrame-index: u8 | Window-index: u8) relative offsets from this function's
     /// declaration where nonlocal variables will be found. Needed when creating a closure. May be
     /// nil
@@ -39,6 +86,30 @@ impl Function {
         param_names: ScopedPtr<'guard, List>,
         code: ScopedPtr<'guard, ByteCode>,
         nonlocal_refs: Option<ScopedPtr<'guard, ArrayU16>>,
+    ) -> Result<ScopedPtr<'guard, Function>, RuntimeError> {
+        let signature = Signature::fixed(param_names.length() as u8);
+        Function::alloc_with_signature(
+            mem,
+            name,
+            param_names,
+            signature,
+            mem.nil(),
+            code,
+            nonlocal_refs,
+        )
+    }
+
+    /// Allocate a Function object on the heap that may take optional, rest or keyword
+    /// parameters. The param_names must be given in register order, as described by `Signature`.
+    /// The doc must be a Text or nil.
+    pub fn alloc_with_signature<'guard>(
+        mem: &'guard MutatorView,
+        name: TaggedScopedPtr<'guard>,
+        param_names: ScopedPtr<'guard, List>,
+        signature: Signature<'guard>,
+        doc: TaggedScopedPtr<'guard>,
+        code: ScopedPtr<'guard, ByteCode>,
+        nonlocal_refs: Option<ScopedPtr<'guard, ArrayU16>>,
     ) -> Result<ScopedPtr<'guard, Function>, RuntimeError> {
         // Store a nil ptr if no nonlocal references are given
         let nonlocal_refs = if let Some(refs_ptr) = nonlocal_refs {
@@ -49,13 +120,41 @@ impl Function {
 
         mem.alloc(Function {
             name: TaggedCellPtr::new_with(name),
-            arity: param_names.length() as u8,
+            arity: signature.required,
+            optional: signature.optional,
+            rest: signature.rest,
+            keywords: keywords_ptr(mem, signature.keywords),
             code: CellPtr::new_with(code),
             param_names: CellPtr::new_with(param_names),
+            doc: TaggedCellPtr::new_with(doc),
             nonlocal_refs,
         })
     }
 
+    /// Allocate a Function from the parts of one saved in a heap image. The param_names list may
+    /// not be filled in yet.
+    pub fn restore<'guard>(
+        mem: &'guard MutatorView,
+        name: TaggedScopedPtr<'guard>,
+        signature: Signature<'guard>,
+        param_names: ScopedPtr<'guard, List>,
+        doc: TaggedScopedPtr<'guard>,
+        code: ScopedPtr<'guard, ByteCode>,
+        nonlocal_refs: TaggedScopedPtr<'guard>,
+    ) -> Result<ScopedPtr<'guard, Function>, RuntimeError> {
+        mem.alloc(Function {
+            name: TaggedCellPtr::new_with(name),
+            arity: signature.required,
+            optional: signature.optional,
+            rest: signature.rest,
+            keywords: keywords_ptr(mem, signature.keywords),
+            code: CellPtr::new_with(code),
+            param_names: CellPtr::new_with(param_names),
+            doc: TaggedCellPtr::new_with(doc),
+            nonlocal_refs: TaggedCellPtr::new_with(nonlocal_refs),
+        })
+    }
+
     /// Return a list of nonlocal stack references referenced by the function. It is a panickable
     /// offense to call this when there are no nonlocals referenced by the function. This would
     /// indicate a compiler bug.
@@ -79,10 +178,81 @@ impl Function {
         !self.nonlocal_refs.is_nil()
     }
 
-    /// Return the number of arguments the Function can take
+    /// Return the number of arguments the Function requires
     pub fn arity(&self) -> u8 {
         self.arity
     }
+
+    /// Return the number of optional positional parameters
+    pub fn optional(&self) -> u8 {
+        self.optional
+    }
+
+    /// Return true if extra arguments are collected into a rest parameter
+    pub fn has_rest(&self) -> bool {
+        self.rest
+    }
+
+    /// Return the keyword Symbols selecting the keyword parameters, if there are any
+    pub fn keywords<'guard>(
+        &self,
+        guard: &'guard dyn MutatorScope,
+    ) -> Option<ScopedPtr<'guard, List>> {
+        match *self.keywords.get(guard) {
+            Value::List(keywords) => Some(keywords),
+            _ => None,
+        }
+    }
+
+    /// Return true if the Function can be called with more arguments than it requires
+    pub fn takes_extra_args(&self) -> bool {
+        self.optional > 0 || self.rest || !self.keywords.is_nil()
+    }
+
+    /// Return the list of parameter names
+    pub fn param_names<'guard>(&self, guard: &'guard dyn MutatorScope) -> ScopedPtr<'guard, List> {
+        self.param_names.get(guard)
+    }
+
+    /// Return the docstring Text, or nil if the Function was not given one
+    pub fn doc<'guard>(&self, guard: &'guard dyn MutatorScope) -> TaggedScopedPtr<'guard> {
+        self.doc.get(guard)
+    }
+
+    /// Return the Symbol the Function is named by, or nil if it is anonymous
+    pub fn name_symbol<'guard>(&self, guard: &'guard dyn MutatorScope) -> TaggedScopedPtr<'guard> {
+        self.name.get(guard)
+    }
+
+    /// Return the name of the Function, or "<lambda>" if it is anonymous
+    pub fn name<'guard>(&self, guard: &'guard dyn MutatorScope) -> &'guard str {
+        match *self.name.get(guard) {
+            Value::Symbol(name) => name.as_str(guard),
+            _ => "<lambda>",
+        }
+    }
+}
+
+impl Trace for Function {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.name.trace(tracer);
+        self.code.trace(tracer);
+        self.param_names.trace(tracer);
+        self.doc.trace(tracer);
+        self.keywords.trace(tracer);
+        self.nonlocal_refs.trace(tracer);
+    }
+}
+
+/// Store a nil ptr if no keywords are given
+fn keywords_ptr<'guard>(
+    mem: &'guard MutatorView,
+    keywords: Option<ScopedPtr<'guard, List>>,
+) -> TaggedCellPtr {
+    match keywords {
+        Some(keywords) => TaggedCellPtr::new_with(keywords.as_tagged(mem)),
+        None => TaggedCellPtr::new_nil(),
+    }
 }
 
 impl Print for Function {
@@ -101,7 +271,15 @@ impl Print for Function {
         guard: &'guard dyn MutatorScope,
         f: &mut fmt::Formatter,
     ) -> fmt::Result {
-        write!(f, "(Function unimplemented)")
+        write!(f, "(Function {}", self.name(guard))?;
+        self.param_names(guard).access_slice(guard, |names| {
+            names
+                .iter()
+                .try_for_each(|name| write!(f, " {}", name.get(guard)))
+        })?;
+        write!(f, ")")?;
+
+        self.code(guard).disassemble(guard, f)
     }
 }
 
@@ -150,6 +328,25 @@ impl Partial {
         })
     }
 
+    /// Allocate a Partial from the parts of one saved in a heap image. The args list is used as
+    /// given rather than copied, so it may be filled in afterwards.
+    pub fn restore<'guard>(
+        mem: &'guard MutatorView,
+        function: ScopedPtr<'guard, Function>,
+        arity: u8,
+        used: u8,
+        args: ScopedPtr<'guard, List>,
+        env: TaggedScopedPtr<'guard>,
+    ) -> Result<ScopedPtr<'guard, Partial>, RuntimeError> {
+        mem.alloc(Partial {
+            arity,
+            used,
+            args: CellPtr::new_with(args),
+            env: TaggedCellPtr::new_with(env),
+            func: CellPtr::new_with(function),
+        })
+    }
+
     /// Clone an existing Partial application, appending the given arguments to the list
     pub fn alloc_clone<'guard>(
         mem: &'guard MutatorView,
@@ -202,6 +399,14 @@ impl Partial {
     }
 }
 
+impl Trace for Partial {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.args.trace(tracer);
+        self.env.trace(tracer);
+        self.func.trace(tracer);
+    }
+}
+
 impl Print for Partial {
     /// Prints a string representation of the Partial object
     fn print<'guard>(
@@ -218,6 +423,192 @@ impl Print for Partial {
         guard: &'guard dyn MutatorScope,
         f: &mut fmt::Formatter,
     ) -> fmt::Result {
-        write!(f, "(Partial function unimplemented)")
+        self.function(guard).debug(guard, f)
+    }
+}
+
+/// Native functions for inspecting Functions at runtime
+pub static INTROSPECTION_NATIVES: [NativeSpec; 4] = [
+    NativeSpec {
+        name: "doc",
+        min_args: 1,
+        max_args: Some(1),
+        function: function_doc,
+    },
+    NativeSpec {
+        name: "arity",
+        min_args: 1,
+        max_args: Some(1),
+        function: function_arity,
+    },
+    NativeSpec {
+        name: "params",
+        min_args: 1,
+        max_args: Some(1),
+        function: function_params,
+    },
+    NativeSpec {
+        name: "disassemble",
+        min_args: 1,
+        max_args: Some(1),
+        function: function_disassemble,
+    },
+];
+
+/// (doc function) returns the function's docstring, or nil if it has none
+fn function_doc<'guard>(
+    mem: &'guard MutatorView,
+    args: &[TaggedScopedPtr<'guard>],
+) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+    match *args[0] {
+        Value::Function(function) => Ok(function.doc(mem)),
+        Value::Partial(partial) => Ok(partial.function(mem).doc(mem)),
+        Value::NativeFunction(_) => Ok(mem.nil()),
+        _ => Err(err_eval("doc expected a function")),
+    }
+}
+
+/// Return the least and, unless a rest parameter takes any number, the most arguments a Function
+/// can be called with. Each keyword parameter takes two arguments, the keyword and the value.
+fn function_range(mem: &MutatorView, function: &Function) -> (u8, Option<u8>) {
+    let max = if function.has_rest() {
+        None
+    } else {
+        let keywords = function
+            .keywords(mem)
+            .map_or(0, |keywords| keywords.length());
+        Some(function.arity() + function.optional() + 2 * keywords as u8)
+    };
+
+    (function.arity(), max)
+}
+
+/// Return the least and most arguments any kind of function can be called with, or an error
+/// naming the builtin if the value is not a function
+fn argument_range(
+    mem: &MutatorView,
+    value: TaggedScopedPtr,
+    builtin: &str,
+) -> Result<(u8, Option<u8>), RuntimeError> {
+    match *value {
+        Value::Function(function) => Ok(function_range(mem, &function)),
+        Value::Partial(partial) => {
+            let (_, max) = function_range(mem, &partial.function(mem));
+            Ok((partial.arity(), max.map(|max| max - partial.used())))
+        }
+        Value::NativeFunction(function) => Ok((function.spec().min_args, function.spec().max_args)),
+        _ => Err(err_eval(&format!("{} expected a function", builtin))),
+    }
+}
+
+/// (arity function) returns the number of arguments the function takes, or a pair of the least
+/// and most it takes if that varies. The most is nil if there is no limit.
+fn function_arity<'guard>(
+    mem: &'guard MutatorView,
+    args: &[TaggedScopedPtr<'guard>],
+) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+    let (min, max) = argument_range(mem, args[0], "arity")?;
+
+    match max {
+        Some(max) if max == min => Ok(mem.number(min as isize)),
+        Some(max) => Pair::cons(mem, mem.number(min as isize), mem.number(max as isize)),
+        None => Pair::cons(mem, mem.number(min as isize), mem.nil()),
+    }
+}
+
+/// (params function) returns the function's parameter list as it was written, without default
+/// value expressions. Arguments already applied to a partial function are left out, and a native
+/// function's parameters are not named so it returns nil.
+fn function_params<'guard>(
+    mem: &'guard MutatorView,
+    args: &[TaggedScopedPtr<'guard>],
+) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+    let (function, used) = match *args[0] {
+        Value::Function(function) => (function, 0),
+        Value::Partial(partial) => (partial.function(mem), partial.used()),
+        Value::NativeFunction(_) => return Ok(mem.nil()),
+        _ => return Err(err_eval("params expected a function")),
+    };
+
+    // names are in register order: required, optional, rest, keyword
+    let names = function.param_names(mem);
+    let name = |index: u8| IndexedAnyContainer::get(&*names, mem, index as ArraySize);
+
+    let required = function.arity();
+    let optional = required + function.optional();
+    let keywords = optional + function.has_rest() as u8;
+
+    let mut items = Vec::new();
+    for index in used..required {
+        items.push(name(index)?);
+    }
+    if function.optional() > 0 {
+        items.push(mem.lookup_sym("&optional"));
+        for index in required..optional {
+            items.push(name(index)?);
+        }
+    }
+    if function.keywords(mem).is_some() {
+        items.push(mem.lookup_sym("&key"));
+        for index in keywords..names.length() as u8 {
+            items.push(name(index)?);
+        }
+    }
+
+    // a rest parameter is the dotted tail of the list
+    let mut list = if function.has_rest() {
+        name(optional)?
+    } else {
+        mem.nil()
+    };
+    for item in items.into_iter().rev() {
+        list = Pair::cons(mem, item, list)?;
+    }
+
+    Ok(list)
+}
+
+/// (disassemble function) returns a Text listing the function's bytecode instructions and
+/// literals
+fn function_disassemble<'guard>(
+    mem: &'guard MutatorView,
+    args: &[TaggedScopedPtr<'guard>],
+) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+    match *args[0] {
+        Value::Function(_) | Value::Partial(_) => mem.text(&debug(*args[0])),
+        _ => Err(err_eval("disassemble expected a compiled function")),
+    }
+}
+
+/// Describe a function for the REPL's `:doc` command: how it is called, the number of arguments
+/// it takes and its docstring
+pub fn describe<'guard>(
+    mem: &'guard MutatorView,
+    value: TaggedScopedPtr<'guard>,
+) -> Result<String, RuntimeError> {
+    let (min, max) = argument_range(mem, value, ":doc")?;
+    let arity = Arity {
+        min: min as usize,
+        max: max.map(|max| max as usize),
+    };
+
+    let usage = match *value {
+        Value::Function(function) => Pair::cons(
+            mem,
+            mem.lookup_sym(function.name(mem)),
+            function_params(mem, &[value])?,
+        )?,
+        Value::Partial(partial) => Pair::cons(
+            mem,
+            mem.lookup_sym(partial.function(mem).name(mem)),
+            function_params(mem, &[value])?,
+        )?,
+        _ => value,
+    };
+
+    let mut text = format!("{}\n{}", usage, arity);
+    if let Value::Text(doc) = *function_doc(mem, &[value])? {
+        text.push_str(&format!("\n\n{}", doc.as_string(mem)));
     }
+    Ok(text)
 }
@@ -1,8 +1,140 @@
-use std::hash::Hasher;
+use std::hash::{Hash, Hasher};
 
-use super::safeptr::MutatorScope;
+use super::{
+    containers::{Container, IndexedAnyContainer},
+    dict::Dict,
+    list::List,
+    pair::Pair,
+    safeptr::{MutatorScope, TaggedScopedPtr},
+    taggedptr::Value,
+};
 
 /// Similar to Hash but for use in a mutator lifetime-limited scope
 pub trait Hashable {
     fn hash<'guard, H: Hasher>(&self, _guard: &'guard dyn MutatorScope, hasher: &mut H);
 }
+
+/// The maximum number of values visited when hashing a structure. Anything beyond the limit does
+/// not contribute to the hash, which keeps hashing of long or cyclic structures bounded.
+const HASH_VALUE_LIMIT: usize = 64;
+
+/// Hash any value consistently with `equality::equal`: values that are `equal` hash the same.
+/// Pairs, Lists and Texts are hashed by content, a Dict only by its length, and values that are
+/// compared by identity by their address.
+pub fn hash_value<'guard, H: Hasher>(
+    guard: &'guard dyn MutatorScope,
+    value: TaggedScopedPtr<'guard>,
+    hasher: &mut H,
+) {
+    let mut budget = HASH_VALUE_LIMIT;
+    hash_bounded(guard, value, hasher, &mut budget);
+}
+
+fn hash_bounded<'guard, H: Hasher>(
+    guard: &'guard dyn MutatorScope,
+    value: TaggedScopedPtr<'guard>,
+    hasher: &mut H,
+    budget: &mut usize,
+) {
+    if *budget == 0 {
+        return;
+    }
+    *budget -= 1;
+
+    match *value {
+        Value::Nil => 0u8.hash(hasher),
+        Value::Number(n) => {
+            1u8.hash(hasher);
+            n.hash(hasher);
+        }
+        Value::Symbol(s) => {
+            2u8.hash(hasher);
+            s.hash(guard, hasher);
+        }
+        Value::Float(f) => {
+            3u8.hash(hasher);
+            f.hash(guard, hasher);
+        }
+        Value::Text(t) => {
+            4u8.hash(hasher);
+            t.hash(guard, hasher);
+        }
+        Value::Pair(p) => hash_pair(guard, &p, hasher, budget),
+        Value::List(l) => hash_list(guard, &l, hasher, budget),
+        Value::Dict(d) => hash_dict(&d, hasher),
+        Value::Record(r) => {
+            // records of different types with equal values may collide, which equality resolves
+            9u8.hash(hasher);
+            hash_list(guard, &r.values(guard), hasher, budget);
+        }
+        _ => {
+            8u8.hash(hasher);
+            value.get_ptr().as_object_ptr().hash(hasher);
+        }
+    }
+}
+
+/// Hash the items of a chain of Pairs in order, ending with whatever terminates the chain
+fn hash_pair<'guard, H: Hasher>(
+    guard: &'guard dyn MutatorScope,
+    pair: &Pair,
+    hasher: &mut H,
+    budget: &mut usize,
+) {
+    5u8.hash(hasher);
+    hash_bounded(guard, pair.first.get(guard), hasher, budget);
+
+    let mut next = pair.second.get(guard);
+    while let Value::Pair(p) = *next {
+        if *budget == 0 {
+            return;
+        }
+        hash_bounded(guard, p.first.get(guard), hasher, budget);
+        next = p.second.get(guard);
+    }
+    hash_bounded(guard, next, hasher, budget);
+}
+
+fn hash_list<'guard, H: Hasher>(
+    guard: &'guard dyn MutatorScope,
+    list: &List,
+    hasher: &mut H,
+    budget: &mut usize,
+) {
+    6u8.hash(hasher);
+    list.length().hash(hasher);
+    for index in 0..list.length() {
+        if *budget == 0 {
+            return;
+        }
+        if let Ok(item) = list.get(guard, index) {
+            hash_bounded(guard, item, hasher, budget);
+        }
+    }
+}
+
+/// Dict entries are unordered, so only the length is hashed
+fn hash_dict<H: Hasher>(dict: &Dict, hasher: &mut H) {
+    7u8.hash(hasher);
+    dict.length().hash(hasher);
+}
+
+impl Hashable for Pair {
+    fn hash<'guard, H: Hasher>(&self, guard: &'guard dyn MutatorScope, hasher: &mut H) {
+        let mut budget = HASH_VALUE_LIMIT;
+        hash_pair(guard, self, hasher, &mut budget);
+    }
+}
+
+impl Hashable for List {
+    fn hash<'guard, H: Hasher>(&self, guard: &'guard dyn MutatorScope, hasher: &mut H) {
+        let mut budget = HASH_VALUE_LIMIT;
+        hash_list(guard, self, hasher, &mut budget);
+    }
+}
+
+impl Hashable for Dict {
+    fn hash<'guard, H: Hasher>(&self, _guard: &'guard dyn MutatorScope, hasher: &mut H) {
+        hash_dict(self, hasher);
+    }
+}
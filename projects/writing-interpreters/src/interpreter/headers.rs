@@ -6,13 +6,15 @@ use super::{
     function::{Function, Partial},
     list::List,
     memory::HeapStorage,
-    number::NumberObject,
+    native::NativeFunction,
+    number::{Float, NumberObject},
     pair::Pair,
     pointerops::{AsNonNull, Tagged},
+    record::Record,
     symbol::Symbol,
     taggedptr::FatPtr,
     text::Text,
-    vm::{CallFrameList, Thread, Upvalue},
+    vm::{CallFrameList, Generator, Thread, Upvalue},
     ArrayU16, ArrayU32, ArrayU8,
 };
 
@@ -30,12 +32,16 @@ pub enum TypeList {
     ByteCode,
     CallFrameList,
     Dict,
+    Float,
     Function,
+    Generator,
     InstructionStream,
     List,
+    NativeFunction,
     NumberObject,
     Pair,
     Partial,
+    Record,
     Symbol,
     Text,
     Thread,
@@ -68,13 +74,21 @@ impl ObjectHeader {
             TypeList::ArrayU16 => FatPtr::ArrayU16(RawPtr::untag(object_addr.cast::<ArrayU16>())),
             TypeList::ArrayU32 => FatPtr::ArrayU32(RawPtr::untag(object_addr.cast::<ArrayU32>())),
             TypeList::Dict => FatPtr::Dict(RawPtr::untag(object_addr.cast::<Dict>())),
+            TypeList::Float => FatPtr::Float(RawPtr::untag(object_addr.cast::<Float>())),
             TypeList::Function => FatPtr::Function(RawPtr::untag(object_addr.cast::<Function>())),
+            TypeList::Generator => {
+                FatPtr::Generator(RawPtr::untag(object_addr.cast::<Generator>()))
+            }
             TypeList::List => FatPtr::List(RawPtr::untag(object_addr.cast::<List>())),
+            TypeList::NativeFunction => {
+                FatPtr::NativeFunction(RawPtr::untag(object_addr.cast::<NativeFunction>()))
+            }
             TypeList::NumberObject => {
                 FatPtr::NumberObject(RawPtr::untag(object_addr.cast::<NumberObject>()))
             }
             TypeList::Pair => FatPtr::Pair(RawPtr::untag(object_addr.cast::<Pair>())),
             TypeList::Partial => FatPtr::Partial(RawPtr::untag(object_addr.cast::<Partial>())),
+            TypeList::Record => FatPtr::Record(RawPtr::untag(object_addr.cast::<Record>())),
             TypeList::Symbol => FatPtr::Symbol(RawPtr::untag(object_addr.cast::<Symbol>())),
             TypeList::Text => FatPtr::Text(RawPtr::untag(object_addr.cast::<Text>())),
             TypeList::Upvalue => FatPtr::Upvalue(RawPtr::untag(object_addr.cast::<Upvalue>())),
@@ -115,12 +129,12 @@ impl AllocHeader for ObjectHeader {
         }
     }
 
-    fn mark(&mut self) {
-        self.mark = Mark::Marked;
+    fn mark(&mut self, mark: Mark) {
+        self.mark = mark;
     }
 
-    fn is_marked(&self) -> bool {
-        self.mark == Mark::Marked
+    fn get_mark(&self) -> Mark {
+        self.mark
     }
 
     fn size_class(&self) -> SizeClass {
@@ -145,15 +159,22 @@ macro_rules! declare_allocobject {
     };
 }
 
+declare_allocobject!(ArrayU8, ArrayU8);
 declare_allocobject!(ArrayU16, ArrayU16);
+declare_allocobject!(ArrayU32, ArrayU32);
 declare_allocobject!(ByteCode, ByteCode);
 declare_allocobject!(CallFrameList, CallFrameList);
 declare_allocobject!(Dict, Dict);
+declare_allocobject!(Float, Float);
 declare_allocobject!(Function, Function);
+declare_allocobject!(Generator, Generator);
 declare_allocobject!(InstructionStream, InstructionStream);
 declare_allocobject!(List, List);
+declare_allocobject!(NativeFunction, NativeFunction);
 declare_allocobject!(Pair, Pair);
 declare_allocobject!(Partial, Partial);
+declare_allocobject!(Record, Record);
 declare_allocobject!(Symbol, Symbol);
+declare_allocobject!(Text, Text);
 declare_allocobject!(Thread, Thread);
 declare_allocobject!(Upvalue, Upvalue);
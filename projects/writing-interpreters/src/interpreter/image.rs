@@ -0,0 +1,1155 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use crate::memory::ArraySize;
+
+use super::{
+    bytecode::{ByteCode, Opcode},
+    containers::{HashIndexedAnyContainer, SliceableContainer, StackAnyContainer, StackContainer},
+    dict::Dict,
+    error::err_image,
+    function::{Function, Partial, Signature},
+    list::List,
+    native::{find_native, NativeFunction},
+    pair::Pair,
+    record,
+    safeptr::{MutatorScope, TaggedScopedPtr},
+    taggedptr::Value,
+    vm::Upvalue,
+    ArrayU16, ArrayU32, ArrayU8, MutatorView, RuntimeError, ScopedPtr,
+};
+
+/// Identifies a file as a heap image
+const MAGIC: &[u8; 8] = b"EVALRUS\0";
+
+/// Image format version, to be incremented whenever the record layout changes
+const VERSION: u32 = 7;
+
+/// The tag written before each record, identifying its kind. These are part of the file format,
+/// so they are numbered explicitly rather than taken from `TypeList`, and must not be reused.
+mod tag {
+    pub const ARRAY_U8: u16 = 0;
+    pub const ARRAY_U16: u16 = 1;
+    pub const ARRAY_U32: u16 = 2;
+    pub const BYTE_CODE: u16 = 3;
+    pub const DICT: u16 = 4;
+    pub const FLOAT: u16 = 5;
+    pub const FUNCTION: u16 = 6;
+    pub const LIST: u16 = 7;
+    pub const NATIVE_FUNCTION: u16 = 8;
+    pub const PAIR: u16 = 9;
+    pub const PARTIAL: u16 = 10;
+    pub const STRUCT: u16 = 11;
+    pub const SYMBOL: u16 = 12;
+    pub const TEXT: u16 = 13;
+    pub const UPVALUE: u16 = 14;
+}
+
+/// A reference from a record to a value. Inline values are stored directly, heap objects by their
+/// index in the record table.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Ref {
+    Nil,
+    Number(isize),
+    Object(u32),
+}
+
+/// A single heap object with its references to other objects replaced by record indices
+#[derive(Debug, PartialEq)]
+enum Record {
+    ArrayU8(Vec<u8>),
+    ArrayU16(Vec<u16>),
+    ArrayU32(Vec<u32>),
+    ByteCode {
+        opcodes: Vec<[u8; 4]>,
+        literals: Vec<Ref>,
+    },
+    Dict(Vec<(Ref, Ref)>),
+    Float(f64),
+    Function {
+        name: Ref,
+        arity: u8,
+        optional: u8,
+        rest: bool,
+        keywords: Ref,
+        code: Ref,
+        param_names: Ref,
+        doc: Ref,
+        nonlocal_refs: Ref,
+    },
+    List(Vec<Ref>),
+    /// Native functions are saved by name and bound to the implementation of that name on restore
+    NativeFunction(String),
+    Pair(Ref, Ref),
+    Partial {
+        arity: u8,
+        used: u8,
+        args: Ref,
+        env: Ref,
+        function: Ref,
+    },
+    /// A record of a type defined with defstruct
+    Struct {
+        descriptor: Ref,
+        values: Ref,
+    },
+    Symbol(String),
+    Text(String),
+    Upvalue {
+        value: Ref,
+        closed: bool,
+        location: ArraySize,
+    },
+}
+
+/// A relocatable copy of every object reachable from a root value.
+///
+/// Objects refer to each other by index into the record table rather than by address, so an Image
+/// can be written to a file and restored into any heap. Symbols are stored by name and re-interned
+/// on restore, so restored code compares symbols by identity just as the original did.
+#[derive(Debug, PartialEq)]
+pub struct Image {
+    root: Ref,
+    records: Vec<Record>,
+}
+
+impl Image {
+    /// Copy every object reachable from `root` into a new Image
+    pub fn capture<'guard>(
+        guard: &'guard dyn MutatorScope,
+        root: TaggedScopedPtr<'guard>,
+    ) -> Result<Image, RuntimeError> {
+        let mut capture = Capture {
+            guard,
+            indices: HashMap::new(),
+            records: Vec::new(),
+            worklist: Vec::new(),
+        };
+
+        let root = capture.reference(root);
+
+        while let Some((index, object)) = capture.worklist.pop() {
+            let record = capture.record(object)?;
+            capture.records[index as usize] = Some(record);
+        }
+
+        Ok(Image {
+            root,
+            // every index handed out was pushed onto the worklist
+            records: capture.records.into_iter().map(Option::unwrap).collect(),
+        })
+    }
+
+    /// Allocate a copy of every object in the Image and return the root value
+    pub fn restore<'guard>(
+        &self,
+        mem: &'guard MutatorView,
+    ) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+        let mut objects = Restore {
+            mem,
+            objects: vec![None; self.records.len()],
+        };
+
+        // Allocate every object whose contents can be filled in afterwards. After this, anything
+        // can be referred to except Functions, Partials and Upvalues.
+        for (index, record) in self.records.iter().enumerate() {
+            let object = match record {
+                Record::ArrayU8(_) => Restored::Tagged(ArrayU8::alloc(mem)?.as_tagged(mem)),
+                Record::ArrayU16(_) => Restored::Tagged(ArrayU16::alloc(mem)?.as_tagged(mem)),
+                Record::ArrayU32(_) => Restored::Tagged(ArrayU32::alloc(mem)?.as_tagged(mem)),
+                Record::ByteCode { .. } => Restored::ByteCode(ByteCode::alloc(mem)?),
+                Record::Dict(_) => Restored::Tagged(Dict::alloc(mem)?.as_tagged(mem)),
+                Record::Float(value) => Restored::Tagged(mem.float(*value)?),
+                Record::List(_) => Restored::Tagged(List::alloc(mem)?.as_tagged(mem)),
+                Record::NativeFunction(name) => match find_native(mem, name) {
+                    Some(spec) => {
+                        Restored::Tagged(NativeFunction::alloc(mem, spec)?.as_tagged(mem))
+                    }
+                    None => return Err(err_image(&format!("Unknown native function {}", name))),
+                },
+                Record::Pair(_, _) => Restored::Tagged(mem.alloc_tagged(Pair::new())?),
+                Record::Symbol(name) => Restored::Tagged(mem.lookup_sym(name)),
+                Record::Text(content) => Restored::Tagged(mem.text(content)?),
+                Record::Function { .. }
+                | Record::Partial { .. }
+                | Record::Struct { .. }
+                | Record::Upvalue { .. } => continue,
+            };
+            objects.objects[index] = Some(object);
+        }
+
+        // Functions only refer to ByteCode, Lists and Symbols
+        for (index, record) in self.records.iter().enumerate() {
+            if let Record::Function {
+                name,
+                arity,
+                optional,
+                rest,
+                keywords,
+                code,
+                param_names,
+                doc,
+                nonlocal_refs,
+            } = *record
+            {
+                let signature = Signature {
+                    required: arity,
+                    optional,
+                    rest,
+                    keywords: match keywords {
+                        Ref::Nil => None,
+                        keywords => Some(objects.list(keywords)?),
+                    },
+                };
+
+                let function = Function::restore(
+                    mem,
+                    objects.get(name)?,
+                    signature,
+                    objects.list(param_names)?,
+                    objects.get(doc)?,
+                    objects.bytecode(code)?,
+                    objects.get(nonlocal_refs)?,
+                )?;
+                objects.objects[index] = Some(Restored::Tagged(function.as_tagged(mem)));
+            }
+        }
+
+        // Partials refer to Functions
+        for (index, record) in self.records.iter().enumerate() {
+            if let Record::Partial {
+                arity,
+                used,
+                args,
+                env,
+                function,
+            } = *record
+            {
+                let partial = Partial::restore(
+                    mem,
+                    objects.function(function)?,
+                    arity,
+                    used,
+                    objects.list(args)?,
+                    objects.get(env)?,
+                )?;
+                objects.objects[index] = Some(Restored::Tagged(partial.as_tagged(mem)));
+            }
+        }
+
+        // Records only refer to Lists
+        for (index, record) in self.records.iter().enumerate() {
+            if let Record::Struct { descriptor, values } = *record {
+                let record =
+                    record::Record::restore(mem, objects.list(descriptor)?, objects.list(values)?)?;
+                objects.objects[index] = Some(Restored::Tagged(record.as_tagged(mem)));
+            }
+        }
+
+        // Upvalues may refer to anything but another Upvalue
+        for (index, record) in self.records.iter().enumerate() {
+            if let Record::Upvalue {
+                value,
+                closed,
+                location,
+            } = *record
+            {
+                let upvalue = Upvalue::restore(mem, objects.get(value)?, closed, location)?;
+                objects.objects[index] = Some(Restored::Tagged(upvalue.as_tagged(mem)));
+            }
+        }
+
+        // Every object now exists, fill in the contents of containers
+        for (index, record) in self.records.iter().enumerate() {
+            let object = Ref::Object(index as u32);
+
+            match record {
+                Record::ArrayU8(items) => {
+                    let array = objects.array_u8(object)?;
+                    for item in items {
+                        StackContainer::push(&*array, mem, *item)?;
+                    }
+                }
+
+                Record::ArrayU16(items) => {
+                    let array = objects.array_u16(object)?;
+                    for item in items {
+                        StackContainer::push(&*array, mem, *item)?;
+                    }
+                }
+
+                Record::ArrayU32(items) => {
+                    let array = objects.array_u32(object)?;
+                    for item in items {
+                        StackContainer::push(&*array, mem, *item)?;
+                    }
+                }
+
+                Record::ByteCode { opcodes, literals } => {
+                    let code = objects.bytecode(object)?;
+                    for opcode in opcodes {
+                        code.push(mem, Opcode::decode(*opcode)?)?;
+                    }
+                    for literal in literals {
+                        code.push_lit(mem, objects.get(*literal)?)?;
+                    }
+                }
+
+                Record::List(items) => {
+                    let list = objects.list(object)?;
+                    for item in items {
+                        StackAnyContainer::push(&*list, mem, objects.get(*item)?)?;
+                    }
+                }
+
+                Record::Pair(first, second) => {
+                    let pair = objects.pair(object)?;
+                    pair.first.set(objects.get(*first)?);
+                    pair.second.set(objects.get(*second)?);
+                }
+
+                _ => (),
+            }
+        }
+
+        // Keys are hashed by content, so Dicts are filled only once every other container is
+        for (index, record) in self.records.iter().enumerate() {
+            if let Record::Dict(items) = record {
+                let dict = objects.dict(Ref::Object(index as u32))?;
+                for (key, value) in items {
+                    dict.assoc(mem, objects.get(*key)?, objects.get(*value)?)?;
+                }
+            }
+        }
+
+        objects.get(self.root)
+    }
+
+    /// Write the Image to a file
+    pub fn save(&self, path: &Path) -> Result<(), RuntimeError> {
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    /// Read an Image from a file
+    pub fn load(path: &Path) -> Result<Image, RuntimeError> {
+        Image::from_bytes(&fs::read(path)?)
+    }
+
+    /// Serialize the Image. All values are written little endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer { bytes: Vec::new() };
+
+        writer.bytes.extend_from_slice(MAGIC);
+        writer.u32(VERSION);
+        writer.u32(self.records.len() as u32);
+        writer.reference(self.root);
+
+        for record in &self.records {
+            writer.record(record);
+        }
+
+        writer.bytes
+    }
+
+    /// Deserialize an Image, checking that every reference is within the record table
+    pub fn from_bytes(bytes: &[u8]) -> Result<Image, RuntimeError> {
+        let mut reader = Reader { bytes, offset: 0 };
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(err_image("Not a heap image"));
+        }
+
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(err_image(&format!(
+                "Unsupported image version {}, expected {}",
+                version, VERSION
+            )));
+        }
+
+        let count = reader.u32()?;
+        let root = reader.reference(count)?;
+
+        let mut records = Vec::new();
+        for _ in 0..count {
+            records.push(reader.record(count)?);
+        }
+
+        if reader.offset != bytes.len() {
+            return Err(err_image("Trailing data after the last record"));
+        }
+
+        Ok(Image { root, records })
+    }
+}
+
+/// An object waiting to be captured
+enum Object<'guard> {
+    Value(Value<'guard>),
+    ByteCode(ScopedPtr<'guard, ByteCode>),
+}
+
+/// State of an in-progress capture: objects already assigned a record index, by address
+struct Capture<'guard> {
+    guard: &'guard dyn MutatorScope,
+    indices: HashMap<usize, u32>,
+    records: Vec<Option<Record>>,
+    worklist: Vec<(u32, Object<'guard>)>,
+}
+
+/// Return the address of a heap object, used to identify objects referred to more than once
+fn address<T>(object: &T) -> usize {
+    object as *const T as usize
+}
+
+impl<'guard> Capture<'guard> {
+    /// Return a reference to a value, assigning a record index if it is a heap object that has
+    /// not been seen yet
+    fn reference(&mut self, value: TaggedScopedPtr<'guard>) -> Ref {
+        let object_address = match *value {
+            Value::Nil => return Ref::Nil,
+            Value::Number(number) => return Ref::Number(number),
+            Value::ArrayU8(array) => address(&*array),
+            Value::ArrayU16(array) => address(&*array),
+            Value::ArrayU32(array) => address(&*array),
+            Value::Dict(dict) => address(&*dict),
+            Value::Float(float) => address(&*float),
+            Value::Function(function) => address(&*function),
+            Value::Generator(generator) => address(&*generator),
+            Value::List(list) => address(&*list),
+            Value::NativeFunction(function) => address(&*function),
+            Value::NumberObject(number) => address(&*number),
+            Value::Pair(pair) => address(&*pair),
+            Value::Partial(partial) => address(&*partial),
+            Value::Record(record) => address(&*record),
+            Value::Symbol(symbol) => address(&*symbol),
+            Value::Text(text) => address(&*text),
+            Value::Upvalue(upvalue) => address(&*upvalue),
+        };
+
+        Ref::Object(self.object(object_address, Object::Value(*value)))
+    }
+
+    /// Return the record index of an object, queueing it for capture if it has not been seen
+    fn object(&mut self, object_address: usize, object: Object<'guard>) -> u32 {
+        if let Some(index) = self.indices.get(&object_address) {
+            return *index;
+        }
+
+        let index = self.records.len() as u32;
+        self.records.push(None);
+        self.indices.insert(object_address, index);
+        self.worklist.push((index, object));
+        index
+    }
+
+    /// Return references to the items of a List
+    fn list(&mut self, list: &List) -> Vec<Ref> {
+        let guard = self.guard;
+        let items: Vec<TaggedScopedPtr<'guard>> = list.access_slice(guard, |items| {
+            items.iter().map(|item| item.get(guard)).collect()
+        });
+
+        items.into_iter().map(|item| self.reference(item)).collect()
+    }
+
+    /// Copy an object into a record
+    fn record(&mut self, object: Object<'guard>) -> Result<Record, RuntimeError> {
+        let guard = self.guard;
+
+        let value = match object {
+            Object::ByteCode(code) => {
+                return Ok(Record::ByteCode {
+                    opcodes: code.opcodes().access_slice(guard, |opcodes| {
+                        opcodes.iter().map(Opcode::encode).collect()
+                    }),
+                    literals: self.list(code.literals()),
+                })
+            }
+
+            Object::Value(value) => value,
+        };
+
+        let record = match value {
+            Value::ArrayU8(array) => {
+                Record::ArrayU8(array.access_slice(guard, |items| items.to_vec()))
+            }
+            Value::ArrayU16(array) => {
+                Record::ArrayU16(array.access_slice(guard, |items| items.to_vec()))
+            }
+            Value::ArrayU32(array) => {
+                Record::ArrayU32(array.access_slice(guard, |items| items.to_vec()))
+            }
+
+            Value::Dict(dict) => Record::Dict(
+                dict.items(guard)
+                    .into_iter()
+                    .map(|(key, value)| (self.reference(key), self.reference(value)))
+                    .collect(),
+            ),
+
+            Value::Float(float) => Record::Float(float.value()),
+
+            Value::Function(function) => {
+                let code = function.code(guard);
+
+                Record::Function {
+                    name: self.reference(function.name_symbol(guard)),
+                    arity: function.arity(),
+                    optional: function.optional(),
+                    rest: function.has_rest(),
+                    keywords: match function.keywords(guard) {
+                        Some(keywords) => self.reference(keywords.as_tagged(guard)),
+                        None => Ref::Nil,
+                    },
+                    code: Ref::Object(self.object(address(&*code), Object::ByteCode(code))),
+                    param_names: self.reference(function.param_names(guard).as_tagged(guard)),
+                    doc: self.reference(function.doc(guard)),
+                    nonlocal_refs: if function.is_closure() {
+                        self.reference(function.nonlocals(guard).as_tagged(guard))
+                    } else {
+                        Ref::Nil
+                    },
+                }
+            }
+
+            Value::List(list) => Record::List(self.list(&list)),
+
+            Value::NativeFunction(function) => {
+                Record::NativeFunction(String::from(function.name()))
+            }
+
+            Value::Pair(pair) => Record::Pair(
+                self.reference(pair.first.get(guard)),
+                self.reference(pair.second.get(guard)),
+            ),
+
+            Value::Partial(partial) => Record::Partial {
+                arity: partial.arity(),
+                used: partial.used(),
+                args: self.reference(partial.args(guard).as_tagged(guard)),
+                env: self.reference(partial.closure_env().get(guard)),
+                function: self.reference(partial.function(guard).as_tagged(guard)),
+            },
+
+            Value::Record(record) => Record::Struct {
+                descriptor: self.reference(record.descriptor(guard).as_tagged(guard)),
+                values: self.reference(record.values(guard).as_tagged(guard)),
+            },
+
+            Value::Symbol(symbol) => Record::Symbol(String::from(symbol.as_str(guard))),
+
+            Value::Text(text) => Record::Text(text.as_string(guard)),
+
+            Value::Upvalue(upvalue) => Record::Upvalue {
+                value: self.reference(upvalue.closed_value(guard)),
+                closed: upvalue.is_closed(),
+                location: upvalue.location(),
+            },
+
+            Value::NumberObject(_) => return Err(err_image("NumberObject cannot be saved")),
+
+            Value::Generator(_) => return Err(err_image("A generator cannot be saved")),
+
+            // inline values never get a record index
+            Value::Nil | Value::Number(_) => unreachable!(),
+        };
+
+        Ok(record)
+    }
+}
+
+/// An object allocated while restoring an Image
+#[derive(Copy, Clone)]
+enum Restored<'guard> {
+    Tagged(TaggedScopedPtr<'guard>),
+    ByteCode(ScopedPtr<'guard, ByteCode>),
+}
+
+/// Objects allocated so far while restoring an Image, by record index
+struct Restore<'guard> {
+    mem: &'guard MutatorView<'guard>,
+    objects: Vec<Option<Restored<'guard>>>,
+}
+
+/// Define a `Restore` method that returns a restored object of the given `Value` type
+macro_rules! restored_as {
+    ($name:ident, $T:ty, $V:ident) => {
+        fn $name(&self, reference: Ref) -> Result<ScopedPtr<'guard, $T>, RuntimeError> {
+            match *self.get(reference)? {
+                Value::$V(object) => Ok(object),
+                _ => Err(err_image(concat!(
+                    "Expected a reference to a ",
+                    stringify!($V)
+                ))),
+            }
+        }
+    };
+}
+
+impl<'guard> Restore<'guard> {
+    /// Return the value a reference refers to. The object must already have been allocated.
+    fn get(&self, reference: Ref) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+        match reference {
+            Ref::Nil => Ok(self.mem.nil()),
+            Ref::Number(number) => Ok(self.mem.number(number)),
+            Ref::Object(index) => match self.objects[index as usize] {
+                Some(Restored::Tagged(object)) => Ok(object),
+                _ => Err(err_image("Invalid reference between records")),
+            },
+        }
+    }
+
+    fn bytecode(&self, reference: Ref) -> Result<ScopedPtr<'guard, ByteCode>, RuntimeError> {
+        match reference {
+            Ref::Object(index) => match self.objects[index as usize] {
+                Some(Restored::ByteCode(code)) => Ok(code),
+                _ => Err(err_image("Expected a reference to a ByteCode")),
+            },
+            _ => Err(err_image("Expected a reference to a ByteCode")),
+        }
+    }
+
+    restored_as!(array_u8, ArrayU8, ArrayU8);
+    restored_as!(array_u16, ArrayU16, ArrayU16);
+    restored_as!(array_u32, ArrayU32, ArrayU32);
+    restored_as!(dict, Dict, Dict);
+    restored_as!(function, Function, Function);
+    restored_as!(list, List, List);
+    restored_as!(pair, Pair, Pair);
+}
+
+/// Serializes records into a byte buffer
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn reference(&mut self, reference: Ref) {
+        match reference {
+            Ref::Nil => self.u8(0),
+            Ref::Number(number) => {
+                self.u8(1);
+                self.u64(number as i64 as u64);
+            }
+            Ref::Object(index) => {
+                self.u8(2);
+                self.u32(index);
+            }
+        }
+    }
+
+    fn references(&mut self, references: &[Ref]) {
+        self.u32(references.len() as u32);
+        for reference in references {
+            self.reference(*reference);
+        }
+    }
+
+    fn record(&mut self, record: &Record) {
+        match record {
+            Record::ArrayU8(items) => {
+                self.u16(tag::ARRAY_U8);
+                self.u32(items.len() as u32);
+                self.bytes.extend_from_slice(items);
+            }
+
+            Record::ArrayU16(items) => {
+                self.u16(tag::ARRAY_U16);
+                self.u32(items.len() as u32);
+                for item in items {
+                    self.u16(*item);
+                }
+            }
+
+            Record::ArrayU32(items) => {
+                self.u16(tag::ARRAY_U32);
+                self.u32(items.len() as u32);
+                for item in items {
+                    self.u32(*item);
+                }
+            }
+
+            Record::ByteCode { opcodes, literals } => {
+                self.u16(tag::BYTE_CODE);
+                self.u32(opcodes.len() as u32);
+                for opcode in opcodes {
+                    self.bytes.extend_from_slice(opcode);
+                }
+                self.references(literals);
+            }
+
+            Record::Dict(items) => {
+                self.u16(tag::DICT);
+                self.u32(items.len() as u32);
+                for (key, value) in items {
+                    self.reference(*key);
+                    self.reference(*value);
+                }
+            }
+
+            Record::Float(value) => {
+                self.u16(tag::FLOAT);
+                self.u64(value.to_bits());
+            }
+
+            Record::Function {
+                name,
+                arity,
+                optional,
+                rest,
+                keywords,
+                code,
+                param_names,
+                doc,
+                nonlocal_refs,
+            } => {
+                self.u16(tag::FUNCTION);
+                self.u8(*arity);
+                self.u8(*optional);
+                self.u8(*rest as u8);
+                self.reference(*keywords);
+                self.reference(*name);
+                self.reference(*code);
+                self.reference(*param_names);
+                self.reference(*doc);
+                self.reference(*nonlocal_refs);
+            }
+
+            Record::List(items) => {
+                self.u16(tag::LIST);
+                self.references(items);
+            }
+
+            Record::NativeFunction(name) => {
+                self.u16(tag::NATIVE_FUNCTION);
+                self.u32(name.len() as u32);
+                self.bytes.extend_from_slice(name.as_bytes());
+            }
+
+            Record::Pair(first, second) => {
+                self.u16(tag::PAIR);
+                self.reference(*first);
+                self.reference(*second);
+            }
+
+            Record::Partial {
+                arity,
+                used,
+                args,
+                env,
+                function,
+            } => {
+                self.u16(tag::PARTIAL);
+                self.u8(*arity);
+                self.u8(*used);
+                self.reference(*args);
+                self.reference(*env);
+                self.reference(*function);
+            }
+
+            Record::Struct { descriptor, values } => {
+                self.u16(tag::STRUCT);
+                self.reference(*descriptor);
+                self.reference(*values);
+            }
+
+            Record::Symbol(name) => {
+                self.u16(tag::SYMBOL);
+                self.u32(name.len() as u32);
+                self.bytes.extend_from_slice(name.as_bytes());
+            }
+
+            Record::Text(content) => {
+                self.u16(tag::TEXT);
+                self.u32(content.len() as u32);
+                self.bytes.extend_from_slice(content.as_bytes());
+            }
+
+            Record::Upvalue {
+                value,
+                closed,
+                location,
+            } => {
+                self.u16(tag::UPVALUE);
+                self.u8(*closed as u8);
+                self.u32(*location);
+                self.reference(*value);
+            }
+        }
+    }
+}
+
+/// Deserializes records from a byte buffer
+struct Reader<'bytes> {
+    bytes: &'bytes [u8],
+    offset: usize,
+}
+
+impl<'bytes> Reader<'bytes> {
+    fn take(&mut self, count: usize) -> Result<&'bytes [u8], RuntimeError> {
+        let end = self.offset + count;
+        if end > self.bytes.len() {
+            return Err(err_image("Unexpected end of image"));
+        }
+
+        let bytes = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, RuntimeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, RuntimeError> {
+        let mut bytes = [0; 2];
+        bytes.copy_from_slice(self.take(2)?);
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    fn u32(&mut self) -> Result<u32, RuntimeError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> Result<u64, RuntimeError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    /// Read a length prefix, checking that at least that many items of the given size remain so
+    /// that a corrupt length cannot cause a huge allocation
+    fn length(&mut self, item_size: usize) -> Result<usize, RuntimeError> {
+        let length = self.u32()? as usize;
+        if length * item_size > self.bytes.len() - self.offset {
+            return Err(err_image("Unexpected end of image"));
+        }
+        Ok(length)
+    }
+
+    /// Read a reference, checking it is within a table of `count` records
+    fn reference(&mut self, count: u32) -> Result<Ref, RuntimeError> {
+        match self.u8()? {
+            0 => Ok(Ref::Nil),
+            1 => Ok(Ref::Number(self.u64()? as i64 as isize)),
+            2 => {
+                let index = self.u32()?;
+                if index >= count {
+                    return Err(err_image("Reference to a record that does not exist"));
+                }
+                Ok(Ref::Object(index))
+            }
+            _ => Err(err_image("Invalid reference")),
+        }
+    }
+
+    fn references(&mut self, count: u32) -> Result<Vec<Ref>, RuntimeError> {
+        let length = self.length(1)?;
+        (0..length).map(|_| self.reference(count)).collect()
+    }
+
+    fn record(&mut self, count: u32) -> Result<Record, RuntimeError> {
+        let record = match self.u16()? {
+            tag::ARRAY_U8 => {
+                let length = self.length(1)?;
+                Record::ArrayU8(self.take(length)?.to_vec())
+            }
+
+            tag::ARRAY_U16 => {
+                let length = self.length(2)?;
+                Record::ArrayU16((0..length).map(|_| self.u16()).collect::<Result<_, _>>()?)
+            }
+
+            tag::ARRAY_U32 => {
+                let length = self.length(4)?;
+                Record::ArrayU32((0..length).map(|_| self.u32()).collect::<Result<_, _>>()?)
+            }
+
+            tag::BYTE_CODE => {
+                let length = self.length(4)?;
+                let mut opcodes = Vec::with_capacity(length);
+                for _ in 0..length {
+                    let mut opcode = [0; 4];
+                    opcode.copy_from_slice(self.take(4)?);
+                    opcodes.push(opcode);
+                }
+
+                Record::ByteCode {
+                    opcodes,
+                    literals: self.references(count)?,
+                }
+            }
+
+            tag::DICT => {
+                let length = self.length(2)?;
+                let mut items = Vec::with_capacity(length);
+                for _ in 0..length {
+                    items.push((self.reference(count)?, self.reference(count)?));
+                }
+                Record::Dict(items)
+            }
+
+            tag::FLOAT => Record::Float(f64::from_bits(self.u64()?)),
+
+            tag::FUNCTION => Record::Function {
+                arity: self.u8()?,
+                optional: self.u8()?,
+                rest: self.u8()? != 0,
+                keywords: self.reference(count)?,
+                name: self.reference(count)?,
+                code: self.reference(count)?,
+                param_names: self.reference(count)?,
+                doc: self.reference(count)?,
+                nonlocal_refs: self.reference(count)?,
+            },
+
+            tag::LIST => Record::List(self.references(count)?),
+
+            tag::NATIVE_FUNCTION => {
+                let length = self.length(1)?;
+                let name = String::from_utf8(self.take(length)?.to_vec())
+                    .map_err(|_| err_image("Native function name is not valid UTF-8"))?;
+                Record::NativeFunction(name)
+            }
+
+            tag::PAIR => Record::Pair(self.reference(count)?, self.reference(count)?),
+
+            tag::PARTIAL => Record::Partial {
+                arity: self.u8()?,
+                used: self.u8()?,
+                args: self.reference(count)?,
+                env: self.reference(count)?,
+                function: self.reference(count)?,
+            },
+
+            tag::STRUCT => Record::Struct {
+                descriptor: self.reference(count)?,
+                values: self.reference(count)?,
+            },
+
+            tag::SYMBOL => {
+                let length = self.length(1)?;
+                let name = String::from_utf8(self.take(length)?.to_vec())
+                    .map_err(|_| err_image("Symbol name is not valid UTF-8"))?;
+                Record::Symbol(name)
+            }
+
+            tag::TEXT => {
+                let length = self.length(1)?;
+                let content = String::from_utf8(self.take(length)?.to_vec())
+                    .map_err(|_| err_image("Text is not valid UTF-8"))?;
+                Record::Text(content)
+            }
+
+            tag::UPVALUE => Record::Upvalue {
+                closed: self.u8()? != 0,
+                location: self.u32()?,
+                value: self.reference(count)?,
+            },
+
+            _ => return Err(err_image("Unknown record type")),
+        };
+
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::interpreter::{
+        compiler::compile, memory::Memory, parser::parse, printer::print, vm::Thread, Mutator,
+    };
+
+    /// Evaluate each line on a Thread, capture its globals and return the Image
+    fn capture_session(lines: &[&str]) -> Image {
+        struct Session<'a> {
+            lines: &'a [&'a str],
+        }
+
+        impl<'a> Mutator for Session<'a> {
+            type Input = ();
+            type Output = Image;
+
+            fn run(&self, mem: &MutatorView, _: ()) -> Result<Image, RuntimeError> {
+                let thread = Thread::alloc(mem)?;
+                for line in self.lines {
+                    let function = compile(mem, parse(mem, line)?)?;
+                    thread.quick_vm_eval(mem, function)?;
+                }
+
+                Image::capture(mem, thread.globals(mem).as_tagged(mem))
+            }
+        }
+
+        Memory::new().mutate(&Session { lines }, ()).unwrap()
+    }
+
+    /// Restore an Image as the globals of a fresh Thread in a new heap and evaluate each line,
+    /// returning the printed results
+    fn eval_restored(image: &Image, lines: &[&str]) -> Vec<String> {
+        struct Restored<'a> {
+            image: &'a Image,
+            lines: &'a [&'a str],
+        }
+
+        impl<'a> Mutator for Restored<'a> {
+            type Input = ();
+            type Output = Vec<String>;
+
+            fn run(&self, mem: &MutatorView, _: ()) -> Result<Vec<String>, RuntimeError> {
+                let globals = match *self.image.restore(mem)? {
+                    Value::Dict(globals) => globals,
+                    _ => panic!("root is not a Dict"),
+                };
+
+                let thread = Thread::alloc_with_globals(mem, globals)?;
+
+                let mut results = Vec::new();
+                for line in self.lines {
+                    let function = compile(mem, parse(mem, line)?)?;
+                    results.push(print(*thread.quick_vm_eval(mem, function)?));
+                }
+                Ok(results)
+            }
+        }
+
+        Memory::new()
+            .mutate(&Restored { image, lines }, ())
+            .unwrap()
+    }
+
+    #[test]
+    fn session_survives_save_and_restore() {
+        let image = capture_session(&[
+            "(def double (n) (* n 2))",
+            "(def add (a b) \"Adds two numbers\" (+ a b))",
+            "(set (quote add3) (add 3))",
+            "(set (quote xs) (cons 1 (cons 2.5 (cons (quote a) nil))))",
+        ]);
+
+        let image = Image::from_bytes(&image.to_bytes()).unwrap();
+
+        let results = eval_restored(
+            &image,
+            &[
+                "(double 21)",
+                "(add3 4)",
+                "xs",
+                "(is? (car (cdr (cdr xs))) (quote a))",
+                "(doc add3)",
+            ],
+        );
+
+        assert_eq!(
+            results,
+            vec!["42", "7", "(1 2.5 a)", "true", "\"Adds two numbers\""]
+        );
+    }
+
+    #[test]
+    fn records_survive_save_and_restore() {
+        let image = capture_session(&[
+            "(defstruct point x y)",
+            "(set (quote p) (make-point 1 (quote (2 3))))",
+        ]);
+
+        let image = Image::from_bytes(&image.to_bytes()).unwrap();
+
+        let results = eval_restored(
+            &image,
+            &[
+                "p",
+                "(point? p)",
+                "(point-y p)",
+                "(point? (make-point 4 5))",
+            ],
+        );
+
+        assert_eq!(
+            results,
+            vec!["#<point x=1 y=(2 3)>", "true", "(2 3)", "true"]
+        );
+    }
+
+    #[test]
+    fn shared_objects_are_captured_once() {
+        let image = capture_session(&[
+            "(set (quote shared) (cons 1 nil))",
+            "(set (quote a) (cons shared shared))",
+        ]);
+
+        let pairs = image
+            .records
+            .iter()
+            .filter(|record| matches!(record, Record::Pair(_, _)))
+            .count();
+        assert_eq!(pairs, 2);
+
+        let results = eval_restored(&image, &["(is? (car a) (cdr a))", "(is? (car a) shared)"]);
+        assert_eq!(results, vec!["true", "true"]);
+    }
+
+    #[test]
+    fn record_tags_are_stable() {
+        // the tags are part of the file format and must not change when heap types are added
+        let records = [
+            (Record::Float(1.5), 5),
+            (Record::Pair(Ref::Nil, Ref::Number(2)), 9),
+            (Record::Symbol(String::from("a")), 12),
+        ];
+
+        for (record, tag) in records {
+            let mut writer = Writer { bytes: Vec::new() };
+            writer.record(&record);
+            assert_eq!(writer.bytes[..2], u16::to_le_bytes(tag));
+
+            let mut reader = Reader {
+                bytes: &writer.bytes,
+                offset: 0,
+            };
+            assert_eq!(reader.record(1).unwrap(), record);
+        }
+
+        let mut reader = Reader {
+            bytes: &u16::to_le_bytes(15),
+            offset: 0,
+        };
+        assert!(reader.record(1).is_err());
+    }
+
+    #[test]
+    fn corrupt_images_are_rejected() {
+        let bytes = capture_session(&["(def double (n) (* n 2))"]).to_bytes();
+
+        assert!(Image::from_bytes(b"not an image").is_err());
+        assert!(Image::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        let mut extended = bytes.clone();
+        extended.push(0);
+        assert!(Image::from_bytes(&extended).is_err());
+
+        let mut version = bytes;
+        version[MAGIC.len()] = 99;
+        assert!(Image::from_bytes(&version).is_err());
+    }
+}
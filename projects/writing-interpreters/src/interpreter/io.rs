@@ -0,0 +1,315 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+use super::{
+    error::{err_eval, err_file},
+    native::NativeSpec,
+    printer::display,
+    safeptr::TaggedScopedPtr,
+    taggedptr::Value,
+    MutatorView, RuntimeError,
+};
+
+/// Host I/O functions: console output and input, and whole-file reads and writes
+pub static IO_NATIVES: [NativeSpec; 8] = [
+    NativeSpec {
+        name: "print",
+        min_args: 0,
+        max_args: None,
+        function: native_print,
+    },
+    NativeSpec {
+        name: "println",
+        min_args: 0,
+        max_args: None,
+        function: native_println,
+    },
+    NativeSpec {
+        name: "display",
+        min_args: 1,
+        max_args: Some(1),
+        function: native_display,
+    },
+    NativeSpec {
+        name: "read-line",
+        min_args: 0,
+        max_args: Some(0),
+        function: native_read_line,
+    },
+    NativeSpec {
+        name: "read-file",
+        min_args: 1,
+        max_args: Some(1),
+        function: native_read_file,
+    },
+    NativeSpec {
+        name: "write-file",
+        min_args: 2,
+        max_args: Some(2),
+        function: native_write_file,
+    },
+    NativeSpec {
+        name: "append-file",
+        min_args: 2,
+        max_args: Some(2),
+        function: native_append_file,
+    },
+    NativeSpec {
+        name: "file-exists?",
+        min_args: 1,
+        max_args: Some(1),
+        function: native_file_exists,
+    },
+];
+
+/// Write values to the console output separated by spaces, optionally followed by a newline
+fn write_values(
+    mem: &MutatorView,
+    args: &[TaggedScopedPtr],
+    newline: bool,
+) -> Result<(), RuntimeError> {
+    let mut line = args
+        .iter()
+        .map(|arg| display(**arg))
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    if newline {
+        line.push('\n');
+    }
+    mem.write_output(line.as_bytes())
+}
+
+/// Return the path named by a Text or Symbol argument
+fn path_arg(function: &str, arg: TaggedScopedPtr) -> Result<String, RuntimeError> {
+    match *arg {
+        Value::Text(text) => Ok(text.as_string(&arg)),
+        Value::Symbol(symbol) => Ok(String::from(symbol.as_str(&arg))),
+        _ => Err(err_eval(&format!(
+            "Function {} expected a path, got {}",
+            function, arg
+        ))),
+    }
+}
+
+/// (print value ...) writes the values to the console output, Text without quotes. Returns nil.
+fn native_print<'guard>(
+    mem: &'guard MutatorView,
+    args: &[TaggedScopedPtr<'guard>],
+) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+    write_values(mem, args, false)?;
+    Ok(mem.nil())
+}
+
+/// (println value ...) writes the values to the console output followed by a newline. Returns nil.
+fn native_println<'guard>(
+    mem: &'guard MutatorView,
+    args: &[TaggedScopedPtr<'guard>],
+) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+    write_values(mem, args, true)?;
+    Ok(mem.nil())
+}
+
+/// (display value) writes a single value to the console output and returns it, so that it can be used to
+/// trace the value of an expression
+fn native_display<'guard>(
+    mem: &'guard MutatorView,
+    args: &[TaggedScopedPtr<'guard>],
+) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+    write_values(mem, args, false)?;
+    Ok(args[0])
+}
+
+/// (read-line) reads a line from stdin, returning it as Text without the line ending, or nil at
+/// the end of input
+fn native_read_line<'guard>(
+    mem: &'guard MutatorView,
+    _args: &[TaggedScopedPtr<'guard>],
+) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+    let mut line = String::new();
+    if io::stdin().lock().read_line(&mut line)? == 0 {
+        return Ok(mem.nil());
+    }
+
+    let line = line.strip_suffix('\n').unwrap_or(&line);
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    mem.text(line)
+}
+
+/// (read-file path) returns the whole content of a file as Text
+fn native_read_file<'guard>(
+    mem: &'guard MutatorView,
+    args: &[TaggedScopedPtr<'guard>],
+) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+    let path = path_arg("read-file", args[0])?;
+    let content = fs::read_to_string(&path).map_err(|e| err_file(&path, e))?;
+    mem.text(&content)
+}
+
+/// (write-file path value) replaces the content of a file, creating it if necessary. Returns nil.
+fn native_write_file<'guard>(
+    mem: &'guard MutatorView,
+    args: &[TaggedScopedPtr<'guard>],
+) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+    let path = path_arg("write-file", args[0])?;
+    fs::write(&path, display(*args[1])).map_err(|e| err_file(&path, e))?;
+    Ok(mem.nil())
+}
+
+/// (append-file path value) adds to the end of a file, creating it if necessary. Returns nil.
+fn native_append_file<'guard>(
+    mem: &'guard MutatorView,
+    args: &[TaggedScopedPtr<'guard>],
+) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+    let path = path_arg("append-file", args[0])?;
+    OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(display(*args[1]).as_bytes()))
+        .map_err(|e| err_file(&path, e))?;
+    Ok(mem.nil())
+}
+
+/// (file-exists? path) returns true if there is a file or directory at the path, nil otherwise
+fn native_file_exists<'guard>(
+    mem: &'guard MutatorView,
+    args: &[TaggedScopedPtr<'guard>],
+) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+    let path = path_arg("file-exists?", args[0])?;
+    if Path::new(&path).exists() {
+        Ok(mem.lookup_sym("true"))
+    } else {
+        Ok(mem.nil())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+
+    use super::*;
+    use crate::interpreter::compiler::compile;
+    use crate::interpreter::error::ErrorKind;
+    use crate::interpreter::memory::Memory;
+    use crate::interpreter::parser::parse;
+    use crate::interpreter::printer::print;
+    use crate::interpreter::vm::Thread;
+    use crate::interpreter::Mutator;
+
+    fn eval_helper<'guard>(
+        mem: &'guard MutatorView,
+        thread: crate::interpreter::ScopedPtr<'guard, Thread>,
+        code: &str,
+    ) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+        let function = compile(mem, parse(mem, code)?)?;
+        thread.quick_vm_eval(mem, function)
+    }
+
+    fn test_helper(test_fn: fn(&MutatorView) -> Result<(), RuntimeError>) {
+        let mem = Memory::new();
+
+        struct Test {}
+        impl Mutator for Test {
+            type Input = fn(&MutatorView) -> Result<(), RuntimeError>;
+            type Output = ();
+
+            fn run(
+                &self,
+                mem: &MutatorView,
+                test_fn: Self::Input,
+            ) -> Result<Self::Output, RuntimeError> {
+                test_fn(mem)
+            }
+        }
+
+        let test = Test {};
+        mem.mutate(&test, test_fn).unwrap();
+    }
+
+    #[test]
+    fn file_round_trip() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+
+            let path = env::temp_dir().join(format!("evalrus-io-{}.txt", std::process::id()));
+            let path = path.to_str().unwrap();
+            let _ = fs::remove_file(path);
+
+            let exists = format!("(file-exists? \"{}\")", path);
+            assert!(eval_helper(mem, t, &exists)? == mem.nil());
+
+            eval_helper(mem, t, &format!("(write-file \"{}\" \"one\\n\")", path))?;
+            eval_helper(mem, t, &format!("(append-file \"{}\" (quote (2 3)))", path))?;
+            assert!(eval_helper(mem, t, &exists)? == mem.lookup_sym("true"));
+
+            let content = eval_helper(mem, t, &format!("(read-file \"{}\")", path))?;
+            assert_eq!(print(*content), "\"one\\n(2 3)\"");
+
+            // writing replaces the previous content
+            eval_helper(mem, t, &format!("(write-file \"{}\" 4)", path))?;
+            let content = eval_helper(mem, t, &format!("(read-file \"{}\")", path))?;
+            assert_eq!(print(*content), "\"4\"");
+
+            fs::remove_file(path)?;
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn file_errors() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+
+            let path = env::temp_dir().join("evalrus-io-does-not-exist.txt");
+            let path = path.to_str().unwrap();
+
+            let error = eval_helper(mem, t, &format!("(read-file \"{}\")", path)).unwrap_err();
+            assert_eq!(
+                *error.error_kind(),
+                ErrorKind::FileNotFound(String::from(path))
+            );
+
+            // a directory cannot be read as a file
+            let dir = env::temp_dir();
+            let code = format!("(read-file \"{}\")", dir.to_str().unwrap());
+            let error = eval_helper(mem, t, &code).unwrap_err();
+            assert!(matches!(*error.error_kind(), ErrorKind::IOError(_)));
+
+            // paths must be Text or Symbols, and argument counts are checked
+            assert!(eval_helper(mem, t, "(read-file 1)").is_err());
+            assert!(eval_helper(mem, t, "(write-file \"x\")").is_err());
+            assert!(eval_helper(mem, t, "(display 1 2)").is_err());
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn output_returns() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+            mem.capture_output();
+
+            assert!(eval_helper(mem, t, "(println \"a\" 1)")? == mem.nil());
+            assert!(eval_helper(mem, t, "(print)")? == mem.nil());
+            let result = eval_helper(mem, t, "(+ 1 (display 2))")?;
+            assert!(result == mem.number(3));
+            eval_helper(mem, t, "(print (quote (b \"c\")) 3.5)")?;
+
+            // Text arguments are written without quotes, Text inside a list is printed
+            assert_eq!(mem.take_output(), "a 1\n2(b \"c\") 3.5");
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+}
@@ -0,0 +1,344 @@
+use std::{fmt, iter::Peekable, str::Chars};
+
+use super::{error::err_parser, RuntimeError};
+
+/// The deepest nesting of arrays and objects that will be parsed, so that a hostile document
+/// can't overflow the stack
+const MAX_DEPTH: usize = 128;
+
+/// A JSON value, as exchanged with editors by the language server
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// Members are kept in the order they were given
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Build an object from a list of members
+    pub fn object(members: Vec<(&str, Json)>) -> Json {
+        Json::Object(
+            members
+                .into_iter()
+                .map(|(key, value)| (String::from(key), value))
+                .collect(),
+        )
+    }
+
+    /// Build a string value
+    pub fn string(value: &str) -> Json {
+        Json::String(String::from(value))
+    }
+
+    /// Parse a complete JSON document
+    pub fn parse(input: &str) -> Result<Json, RuntimeError> {
+        let mut chars = input.chars().peekable();
+        let value = parse_value(&mut chars, 0)?;
+
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            None => Ok(value),
+            Some(c) => Err(err_parser(&format!("Unexpected '{}' after JSON value", c))),
+        }
+    }
+
+    /// Look up an object member by key, returning None if this is not an object or the key is
+    /// not present
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Look up a member by following a path of object keys
+    pub fn path(&self, keys: &[&str]) -> Option<&Json> {
+        keys.iter().try_fold(self, |value, key| value.get(key))
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Json {
+    /// Write the value as compact JSON text
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(value) if value.fract() == 0.0 && value.abs() < 1e15 => {
+                write!(f, "{}", *value as i64)
+            }
+            Json::Number(value) if value.is_finite() => write!(f, "{}", value),
+            Json::Number(_) => write!(f, "null"),
+            Json::String(value) => write_string(f, value),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (index, (key, value)) in members.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// Write a string literal, escaping quotes, backslashes and control characters
+fn write_string(f: &mut fmt::Formatter, value: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in value.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+}
+
+/// Parse a value nested inside `depth` arrays and objects
+fn parse_value(chars: &mut Peekable<Chars>, depth: usize) -> Result<Json, RuntimeError> {
+    skip_whitespace(chars);
+
+    match chars.peek() {
+        Some('{') | Some('[') if depth >= MAX_DEPTH => Err(err_parser("JSON is nested too deeply")),
+        Some('{') => parse_object(chars, depth + 1),
+        Some('[') => parse_array(chars, depth + 1),
+        Some('"') => Ok(Json::String(parse_string(chars)?)),
+        Some('t') => parse_word(chars, "true", Json::Bool(true)),
+        Some('f') => parse_word(chars, "false", Json::Bool(false)),
+        Some('n') => parse_word(chars, "null", Json::Null),
+        Some(c) if *c == '-' || c.is_ascii_digit() => parse_number(chars),
+        Some(c) => Err(err_parser(&format!("Unexpected '{}' in JSON", c))),
+        None => Err(err_parser("Unexpected end of JSON")),
+    }
+}
+
+fn parse_word(chars: &mut Peekable<Chars>, word: &str, value: Json) -> Result<Json, RuntimeError> {
+    for expected in word.chars() {
+        if chars.next() != Some(expected) {
+            return Err(err_parser(&format!("Expected '{}' in JSON", word)));
+        }
+    }
+    Ok(value)
+}
+
+fn parse_number(chars: &mut Peekable<Chars>) -> Result<Json, RuntimeError> {
+    let mut text = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_digit() || "+-.eE".contains(*c)) {
+        text.push(c);
+    }
+
+    text.parse()
+        .map(Json::Number)
+        .map_err(|_| err_parser(&format!("Invalid JSON number '{}'", text)))
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, RuntimeError> {
+    // opening quote
+    chars.next();
+
+    let mut value = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(value),
+            Some('\\') => match chars.next() {
+                Some('"') => value.push('"'),
+                Some('\\') => value.push('\\'),
+                Some('/') => value.push('/'),
+                Some('b') => value.push('\u{8}'),
+                Some('f') => value.push('\u{c}'),
+                Some('n') => value.push('\n'),
+                Some('r') => value.push('\r'),
+                Some('t') => value.push('\t'),
+                Some('u') => value.push(parse_unicode_escape(chars)?),
+                _ => return Err(err_parser("Invalid escape in JSON string")),
+            },
+            Some(c) => value.push(c),
+            None => return Err(err_parser("Unterminated JSON string")),
+        }
+    }
+}
+
+/// Parse the hex digits following `\u`, combining a surrogate pair if one is given
+fn parse_unicode_escape(chars: &mut Peekable<Chars>) -> Result<char, RuntimeError> {
+    let high = parse_hex4(chars)?;
+
+    let code = if (0xd800..0xdc00).contains(&high) {
+        if chars.next() != Some('\\') || chars.next() != Some('u') {
+            return Err(err_parser("Unpaired surrogate in JSON string"));
+        }
+        let low = parse_hex4(chars)?;
+        0x10000 + ((high - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff)
+    } else {
+        high
+    };
+
+    char::from_u32(code).ok_or_else(|| err_parser("Invalid unicode escape in JSON string"))
+}
+
+fn parse_hex4(chars: &mut Peekable<Chars>) -> Result<u32, RuntimeError> {
+    let mut code = 0;
+    for _ in 0..4 {
+        let digit = chars
+            .next()
+            .and_then(|c| c.to_digit(16))
+            .ok_or_else(|| err_parser("Invalid unicode escape in JSON string"))?;
+        code = code * 16 + digit;
+    }
+    Ok(code)
+}
+
+fn parse_array(chars: &mut Peekable<Chars>, depth: usize) -> Result<Json, RuntimeError> {
+    // opening bracket
+    chars.next();
+
+    let mut items = Vec::new();
+    skip_whitespace(chars);
+    if chars.next_if_eq(&']').is_some() {
+        return Ok(Json::Array(items));
+    }
+
+    loop {
+        items.push(parse_value(chars, depth)?);
+
+        skip_whitespace(chars);
+        match chars.next() {
+            Some(',') => continue,
+            Some(']') => return Ok(Json::Array(items)),
+            _ => return Err(err_parser("Expected ',' or ']' in JSON array")),
+        }
+    }
+}
+
+fn parse_object(chars: &mut Peekable<Chars>, depth: usize) -> Result<Json, RuntimeError> {
+    // opening brace
+    chars.next();
+
+    let mut members = Vec::new();
+    skip_whitespace(chars);
+    if chars.next_if_eq(&'}').is_some() {
+        return Ok(Json::Object(members));
+    }
+
+    loop {
+        skip_whitespace(chars);
+        if chars.peek() != Some(&'"') {
+            return Err(err_parser("Expected a string key in JSON object"));
+        }
+        let key = parse_string(chars)?;
+
+        skip_whitespace(chars);
+        if chars.next() != Some(':') {
+            return Err(err_parser("Expected ':' in JSON object"));
+        }
+        members.push((key, parse_value(chars, depth)?));
+
+        skip_whitespace(chars);
+        match chars.next() {
+            Some(',') => continue,
+            Some('}') => return Ok(Json::Object(members)),
+            _ => return Err(err_parser("Expected ',' or '}' in JSON object")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_and_write() {
+        let text = r#"{"id":1,"params":{"text":"a \"b\"\n\u00e9\ud83d\ude00","list":[true,false,null,-2.5]}}"#;
+        let value = Json::parse(text).unwrap();
+
+        assert_eq!(value.get("id").and_then(Json::as_f64), Some(1.0));
+        assert_eq!(
+            value.path(&["params", "text"]).and_then(Json::as_str),
+            Some("a \"b\"\n\u{e9}\u{1f600}")
+        );
+        assert_eq!(
+            value.path(&["params", "list"]).and_then(Json::as_array),
+            Some(
+                &[
+                    Json::Bool(true),
+                    Json::Bool(false),
+                    Json::Null,
+                    Json::Number(-2.5)
+                ][..]
+            )
+        );
+
+        // writing and reading back gives the same value
+        assert_eq!(Json::parse(&format!("{}", value)).unwrap(), value);
+
+        assert_eq!(format!("{}", Json::parse(" { } ").unwrap()), "{}",);
+        assert!(Json::parse("{\"a\" 1}").is_err());
+        assert!(Json::parse("[1, 2").is_err());
+        assert!(Json::parse("1 2").is_err());
+    }
+
+    #[test]
+    fn nesting_depth() {
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(Json::parse(&nested(MAX_DEPTH)).is_ok());
+        assert!(Json::parse(&nested(MAX_DEPTH + 1)).is_err());
+
+        let objects = format!(
+            "{}1{}",
+            r#"{"a":"#.repeat(MAX_DEPTH + 1),
+            "}".repeat(MAX_DEPTH + 1)
+        );
+        assert!(Json::parse(&objects).is_err());
+
+        // a document deep enough to overflow the stack is rejected rather than crashing
+        assert!(Json::parse(&"[".repeat(1_000_000)).is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use super::{
     error::{err_lexer, spos, SourcePos},
+    number::{MAX_INTEGER, MIN_INTEGER},
     RuntimeError,
 };
 
@@ -13,6 +14,7 @@ const LF: char = '\n';
 const DOT: char = '.';
 const DOUBLE_QUOTE: char = '"';
 const SINGLE_QUOTE: char = '\'';
+const SEMICOLON: char = ';';
 
 #[derive(Debug, PartialEq)]
 pub enum TokenType {
@@ -21,7 +23,11 @@ pub enum TokenType {
     Symbol(String),
     Dot,
     Number(isize),
-    // Text(String),
+    Float(f64),
+    Text(String),
+    /// The text of a comment, including its leading semicolons. Only produced by
+    /// `tokenize_with_comments()`.
+    Comment(String),
     // Quote,
 }
 
@@ -39,6 +45,16 @@ impl Token {
 
 // tokenize a String
 pub fn tokenize(input: &str) -> Result<Vec<Token>, RuntimeError> {
+    tokenize_source(input, false)
+}
+
+/// Tokenize a String, keeping comments as tokens so that source can be rewritten without losing
+/// them
+pub fn tokenize_with_comments(input: &str) -> Result<Vec<Token>, RuntimeError> {
+    tokenize_source(input, true)
+}
+
+fn tokenize_source(input: &str, keep_comments: bool) -> Result<Vec<Token>, RuntimeError> {
     let mut tokens = Vec::new();
 
     // start line numbering at 1, the first character of each line being number 0
@@ -69,6 +85,63 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, RuntimeError> {
                 column += 1;
                 current = chars.next();
             }
+            Some(DOUBLE_QUOTE) => {
+                let text_start = spos(line, column);
+                let mut text = String::new();
+                loop {
+                    current = chars.next();
+                    column += 1;
+                    match current {
+                        Some(DOUBLE_QUOTE) => break,
+                        Some('\\') => {
+                            current = chars.next();
+                            column += 1;
+                            match current {
+                                Some('n') => text.push(LF),
+                                Some('r') => text.push(CR),
+                                Some('t') => text.push(TAB),
+                                Some('\\') => text.push('\\'),
+                                Some(DOUBLE_QUOTE) => text.push(DOUBLE_QUOTE),
+                                _ => {
+                                    return Err(err_lexer(
+                                        spos(line, column),
+                                        "unknown escape sequence in string",
+                                    ))
+                                }
+                            }
+                        }
+                        Some(LF) => {
+                            text.push(LF);
+                            line += 1;
+                            column = 0;
+                        }
+                        Some(c) => text.push(c),
+                        None => return Err(err_lexer(text_start, "unterminated string")),
+                    }
+                }
+
+                tokens.push(Token::new(text_start, TokenType::Text(text)));
+                current = chars.next();
+                column += 1;
+            }
+            Some(SEMICOLON) => {
+                // a comment runs to the end of the line
+                let comment_start = spos(line, column);
+                let mut comment = String::new();
+                while let Some(c) = current {
+                    if c == CR || c == LF {
+                        break;
+                    }
+                    comment.push(c);
+                    column += 1;
+                    current = chars.next();
+                }
+
+                if keep_comments {
+                    let text = String::from(comment.trim_end());
+                    tokens.push(Token::new(comment_start, TokenType::Comment(text)));
+                }
+            }
             Some(TAB) => {
                 return Err(err_lexer(
                     spos(line, column),
@@ -111,10 +184,28 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, RuntimeError> {
                     }
                 }
 
-                if let Ok(number) = symbol.parse::<isize>() {
+                if is_integer_literal(&symbol) {
+                    match symbol.parse::<isize>() {
+                        Ok(number) if (MIN_INTEGER..=MAX_INTEGER).contains(&number) => tokens.push(
+                            Token::new(spos(line, symbol_start_column), TokenType::Number(number)),
+                        ),
+                        _ => {
+                            return Err(err_lexer(
+                                spos(line, symbol_start_column),
+                                "integer literal out of range",
+                            ))
+                        }
+                    }
+                } else if let Some(number) = parse_float(&symbol) {
+                    if !number.is_finite() {
+                        return Err(err_lexer(
+                            spos(line, symbol_start_column),
+                            "float literal out of range",
+                        ));
+                    }
                     tokens.push(Token::new(
                         spos(line, symbol_start_column),
-                        TokenType::Number(number),
+                        TokenType::Float(number),
                     ));
                 } else {
                     tokens.push(Token::new(
@@ -133,7 +224,35 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, RuntimeError> {
     Ok(tokens)
 }
 
-fn is_terminating(c: char) -> bool {
+/// Return true if the symbol is an optionally signed run of digits. These are always integer
+/// literals, never symbols or floats, even when they do not fit into a tagged integer.
+fn is_integer_literal(symbol: &str) -> bool {
+    let digits = symbol.strip_prefix(['-', '+']).unwrap_or(symbol);
+    !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Parse a decimal or exponent float literal such as `1.5`, `-0.25` or `6.02e23`.
+/// `f64::from_str` also accepts words like `inf` and `NaN`, which must remain symbols here, so
+/// only digits, signs, decimal points and exponent markers are allowed.
+fn parse_float(symbol: &str) -> Option<f64> {
+    let is_float_char = |c: char| c.is_ascii_digit() || "+-.eE".contains(c);
+    let starts_numeric = symbol
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_digit() || c == '-' || c == '+');
+
+    if starts_numeric
+        && symbol.chars().any(|c| c.is_ascii_digit())
+        && symbol.chars().all(is_float_char)
+    {
+        symbol.parse::<f64>().ok()
+    } else {
+        None
+    }
+}
+
+/// Return true if the character ends a symbol or number
+pub fn is_terminating(c: char) -> bool {
     let terminating = [OPEN_PAREN, CLOSE_PAREN, SPACE, TAB, CR, LF, DOUBLE_QUOTE];
     terminating.iter().any(|t| *t == c)
 }
@@ -196,4 +315,90 @@ mod test {
             assert!(false, "unexpected error");
         }
     }
+
+    #[test]
+    fn lexer_comments() {
+        if let Ok(tokens) = tokenize("; leading\n(foo ; trailing (\r\nbar;\n)") {
+            assert!(tokens.len() == 4);
+            assert_eq!(tokens[0], Token::new(spos(2, 0), TokenType::OpenParen));
+            assert_eq!(
+                tokens[1],
+                Token::new(spos(2, 1), TokenType::Symbol(String::from("foo")))
+            );
+            // a semicolon inside a symbol does not begin a comment
+            assert_eq!(
+                tokens[2],
+                Token::new(spos(3, 0), TokenType::Symbol(String::from("bar;")))
+            );
+            assert_eq!(tokens[3], Token::new(spos(4, 0), TokenType::CloseParen));
+        } else {
+            assert!(false, "unexpected error");
+        }
+    }
+
+    #[test]
+    fn lexer_strings() {
+        if let Ok(tokens) = tokenize("(\"a b\" \"say \\\"hi\\\"\\n\")") {
+            assert!(tokens.len() == 4);
+            assert_eq!(
+                tokens[1],
+                Token::new(spos(1, 1), TokenType::Text(String::from("a b")))
+            );
+            assert_eq!(
+                tokens[2],
+                Token::new(spos(1, 7), TokenType::Text(String::from("say \"hi\"\n")))
+            );
+            assert_eq!(tokens[3], Token::new(spos(1, 21), TokenType::CloseParen));
+        } else {
+            assert!(false, "unexpected error");
+        }
+
+        assert!(tokenize("\"unterminated").is_err());
+        assert!(tokenize("\"\\q\"").is_err());
+    }
+
+    #[test]
+    fn lexer_numbers() {
+        if let Ok(tokens) = tokenize("(1 -2 1.5 -0.25 6.02e23 1e-3)") {
+            assert!(tokens.len() == 8);
+            assert_eq!(tokens[1], Token::new(spos(1, 1), TokenType::Number(1)));
+            assert_eq!(tokens[2], Token::new(spos(1, 3), TokenType::Number(-2)));
+            assert_eq!(tokens[3], Token::new(spos(1, 6), TokenType::Float(1.5)));
+            assert_eq!(tokens[4], Token::new(spos(1, 10), TokenType::Float(-0.25)));
+            assert_eq!(
+                tokens[5],
+                Token::new(spos(1, 16), TokenType::Float(6.02e23))
+            );
+            assert_eq!(tokens[6], Token::new(spos(1, 24), TokenType::Float(1e-3)));
+        } else {
+            assert!(false, "unexpected error");
+        }
+    }
+
+    #[test]
+    fn lexer_number_ranges() {
+        let tokens = tokenize("2305843009213693951 -2305843009213693952").unwrap();
+        assert_eq!(tokens[0].token, TokenType::Number(MAX_INTEGER));
+        assert_eq!(tokens[1].token, TokenType::Number(MIN_INTEGER));
+
+        // too big for a tagged integer, or for an isize at all
+        assert!(tokenize("2305843009213693952").is_err());
+        assert!(tokenize("-2305843009213693953").is_err());
+        assert!(tokenize("(+ 1 99999999999999999999)").is_err());
+
+        assert!(tokenize("1e999").is_err());
+        assert!(tokenize("-1e999").is_err());
+    }
+
+    #[test]
+    fn lexer_float_like_symbols() {
+        if let Ok(tokens) = tokenize("(inf nan - e1 1e)") {
+            assert!(tokens.len() == 7);
+            for (token, name) in tokens[1..6].iter().zip(["inf", "nan", "-", "e1", "1e"]) {
+                assert_eq!(token.token, TokenType::Symbol(String::from(name)));
+            }
+        } else {
+            assert!(false, "unexpected error");
+        }
+    }
 }
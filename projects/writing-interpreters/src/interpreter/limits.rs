@@ -0,0 +1,36 @@
+use std::time::Duration;
+
+/// Resource limits for embedding the interpreter where the code being evaluated is not trusted.
+///
+/// Limits are set on a `Memory` instance with `Memory::set_limits()` and apply to every Thread
+/// that evaluates in it. Each limit that is exceeded stops evaluation with its own `ErrorKind`.
+/// `None` means unlimited.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Limits {
+    /// Maximum number of instructions a single evaluation may execute
+    pub max_instructions: Option<u64>,
+    /// Maximum number of call frames on a Thread's frame stack, including the main frame
+    pub max_call_depth: Option<usize>,
+    /// Maximum number of heap bytes in use. Garbage counts toward this until it is collected.
+    pub max_heap_bytes: Option<usize>,
+    /// Maximum wall-clock time a single evaluation may run for
+    pub timeout: Option<Duration>,
+    /// When set, the host I/O functions are not bound in new Threads and cannot be restored from
+    /// a heap image
+    pub sandboxed: bool,
+}
+
+impl Limits {
+    /// No limits and host I/O allowed
+    pub fn none() -> Limits {
+        Limits::default()
+    }
+
+    /// No resource limits but no host I/O either
+    pub fn sandboxed() -> Limits {
+        Limits {
+            sandboxed: true,
+            ..Limits::default()
+        }
+    }
+}
@@ -0,0 +1,999 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{BufRead, Write},
+};
+
+use super::{
+    compiler::{compile, SPECIAL_FORMS},
+    error::{err_parser, SourcePos},
+    json::Json,
+    lexer::{is_terminating, tokenize, Token, TokenType},
+    memory::Memory,
+    native::{all_natives, NativeSpec},
+    parser::parse_all,
+    prelude::PRELUDE,
+    Mutator, MutatorView, RuntimeError,
+};
+
+/// JSON-RPC error code for a message body that is not valid JSON
+const PARSE_ERROR: f64 = -32700.0;
+/// JSON-RPC error code for a request made after shutdown
+const INVALID_REQUEST: f64 = -32600.0;
+/// JSON-RPC error code for a request the server does not implement
+const METHOD_NOT_FOUND: f64 = -32601.0;
+
+// LSP enumeration values
+const TEXT_DOCUMENT_SYNC_FULL: f64 = 1.0;
+const SEVERITY_ERROR: f64 = 1.0;
+const COMPLETION_FUNCTION: f64 = 3.0;
+const COMPLETION_VARIABLE: f64 = 6.0;
+const COMPLETION_KEYWORD: f64 = 14.0;
+
+/// The number of arguments a function accepts
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Arity {
+    pub min: usize,
+    /// None if any number of arguments beyond the minimum may be given
+    pub max: Option<usize>,
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let plural = |count: usize| if count == 1 { "" } else { "s" };
+
+        match self.max {
+            Some(0) => write!(f, "takes no arguments"),
+            Some(max) if max == self.min => write!(f, "takes {} argument{}", max, plural(max)),
+            Some(max) => write!(f, "takes {} to {} arguments", self.min, max),
+            None => write!(
+                f,
+                "takes at least {} argument{}",
+                self.min,
+                plural(self.min)
+            ),
+        }
+    }
+}
+
+/// A global name bound by a top-level form in a source file
+#[derive(Clone, Debug, PartialEq)]
+pub struct Definition {
+    pub name: String,
+    /// Position of the name in the source
+    pub pos: SourcePos,
+    /// How the definition is shown, such as `(nth n xs)` for a function
+    pub signature: String,
+    /// None if the name is bound to a value rather than a function
+    pub arity: Option<Arity>,
+    /// A string given as the first of several expressions in a function body
+    pub doc: Option<String>,
+}
+
+/// A source file grouped into lists just far enough to find definitions. Unbalanced parentheses
+/// are tolerated, as source being edited is rarely complete.
+enum Form<'t> {
+    Atom(&'t Token),
+    List(Vec<Form<'t>>),
+}
+
+impl<'t> Form<'t> {
+    fn symbol(&self) -> Option<&'t str> {
+        match self {
+            Form::Atom(Token {
+                token: TokenType::Symbol(name),
+                ..
+            }) => Some(name),
+            _ => None,
+        }
+    }
+
+    fn is_dot(&self) -> bool {
+        matches!(self, Form::Atom(token) if token.token == TokenType::Dot)
+    }
+
+    fn pos(&self) -> Option<SourcePos> {
+        match self {
+            Form::Atom(token) => Some(token.pos),
+            Form::List(_) => None,
+        }
+    }
+
+    /// Write the form back out as source code on one line
+    fn render(&self) -> String {
+        match self {
+            Form::Atom(token) => match token.token {
+                TokenType::Symbol(ref name) => name.clone(),
+                TokenType::Number(n) => format!("{}", n),
+                TokenType::Float(n) => format!("{}", n),
+                TokenType::Text(ref text) => format!("{:?}", text),
+                TokenType::Dot => String::from("."),
+                _ => String::new(),
+            },
+            Form::List(items) => {
+                let items: Vec<String> = items.iter().map(Form::render).collect();
+                format!("({})", items.join(" "))
+            }
+        }
+    }
+}
+
+/// Group tokens into a sequence of top-level forms
+fn forms(tokens: &[Token]) -> Vec<Form<'_>> {
+    let mut open: Vec<Vec<Form>> = vec![Vec::new()];
+
+    for token in tokens {
+        match token.token {
+            TokenType::OpenParen => open.push(Vec::new()),
+            TokenType::CloseParen if open.len() > 1 => {
+                let list = Form::List(open.pop().unwrap());
+                open.last_mut().unwrap().push(list);
+            }
+            TokenType::CloseParen | TokenType::Comment(_) => (),
+            _ => open.last_mut().unwrap().push(Form::Atom(token)),
+        }
+    }
+
+    // close any lists left open at the end of the source
+    while open.len() > 1 {
+        let list = Form::List(open.pop().unwrap());
+        open.last_mut().unwrap().push(list);
+    }
+
+    open.pop().unwrap()
+}
+
+/// Count the arguments a parameter list accepts:
+/// (a b &optional c (d <default>) &key e (f <default>) . rest)
+fn params_arity(params: &Form) -> Arity {
+    let items = match params {
+        Form::List(items) => items,
+        // a symbol in place of the list collects every argument
+        _ => return Arity { min: 0, max: None },
+    };
+
+    let (mut required, mut optional, mut keys, mut rest) = (0, 0, 0, false);
+    let mut section = "";
+    for item in items {
+        match item.symbol() {
+            Some(marker @ ("&optional" | "&key")) => section = marker,
+            _ if item.is_dot() => rest = true,
+            _ if rest => (),
+            _ => match section {
+                "&optional" => optional += 1,
+                // keyword arguments are given as :name value
+                "&key" => keys += 2,
+                _ => required += 1,
+            },
+        }
+    }
+
+    Arity {
+        min: required,
+        max: if rest {
+            None
+        } else {
+            Some(required + optional + keys)
+        },
+    }
+}
+
+/// Find the definitions made by the forms, including those nested inside other forms as
+/// definitions always bind globals
+fn collect_definitions(forms: &[Form], definitions: &mut Vec<Definition>) {
+    for form in forms {
+        let items = match form {
+            Form::List(items) => items,
+            Form::Atom(_) => continue,
+        };
+
+        let head = items.first().and_then(Form::symbol);
+        let name = items
+            .get(1)
+            .and_then(|item| Some((item.symbol()?, item.pos()?)));
+
+        match (head, name) {
+            // (def name (params) expr1 .. exprn)
+            (Some("def"), Some((name, pos))) if items.len() > 2 => {
+                let params = &items[2];
+                let signature = match params {
+                    Form::List(params) if params.is_empty() => format!("({})", name),
+                    Form::List(_) => {
+                        let rendered = params.render();
+                        format!("({} {})", name, &rendered[1..rendered.len() - 1])
+                    }
+                    _ => format!("({} . {})", name, params.render()),
+                };
+
+                let body = &items[3..];
+                let doc = match body.first() {
+                    Some(Form::Atom(Token {
+                        token: TokenType::Text(text),
+                        ..
+                    })) if body.len() > 1 => Some(text.clone()),
+                    _ => None,
+                };
+
+                definitions.push(Definition {
+                    name: String::from(name),
+                    pos,
+                    signature,
+                    arity: Some(params_arity(params)),
+                    doc,
+                });
+            }
+
+            // (defstruct name field1 .. fieldn)
+            (Some("defstruct"), Some((name, pos))) => {
+                let fields: Vec<&str> = items[2..].iter().filter_map(Form::symbol).collect();
+                let mut function = |fn_name: String, params: &[&str]| {
+                    definitions.push(Definition {
+                        signature: format!("({} {})", fn_name, params.join(" ")),
+                        name: fn_name,
+                        pos,
+                        arity: Some(Arity {
+                            min: params.len(),
+                            max: Some(params.len()),
+                        }),
+                        doc: None,
+                    })
+                };
+
+                function(format!("make-{}", name), &fields);
+                function(format!("{}?", name), &["value"]);
+                for field in &fields {
+                    function(format!("{}-{}", name, field), &["record"]);
+                    function(format!("set-{}-{}!", name, field), &["record", "value"]);
+                }
+            }
+
+            // (set (quote name) value)
+            (Some("set"), None) => {
+                if let Some(Form::List(quoted)) = items.get(1) {
+                    if let [quote, symbol] = &quoted[..] {
+                        if let (Some("quote"), Some(name), Some(pos)) =
+                            (quote.symbol(), symbol.symbol(), symbol.pos())
+                        {
+                            definitions.push(Definition {
+                                name: String::from(name),
+                                pos,
+                                signature: String::from(name),
+                                arity: None,
+                                doc: None,
+                            });
+                        }
+                    }
+                }
+            }
+
+            _ => (),
+        }
+
+        collect_definitions(items, definitions);
+    }
+}
+
+/// Find the global definitions in a source file. Source that cannot be tokenized has none.
+pub fn definitions(source: &str) -> Vec<Definition> {
+    let mut found = Vec::new();
+    if let Ok(tokens) = tokenize(source) {
+        collect_definitions(&forms(&tokens), &mut found);
+    }
+    found
+}
+
+/// Find the symbol at a position in a source file, returning its name
+pub fn symbol_at(source: &str, pos: SourcePos) -> Option<String> {
+    let tokens = tokenize(source).ok()?;
+
+    tokens.into_iter().find_map(|token| match token.token {
+        TokenType::Symbol(name)
+            if token.pos.line == pos.line
+                && token.pos.column <= pos.column
+                && pos.column <= token.pos.column + name.chars().count() as u32 =>
+        {
+            Some(name)
+        }
+        _ => None,
+    })
+}
+
+/// A mutator that parses and compiles each top-level form in a source file without evaluating
+/// anything, returning the errors found
+struct Check<'a> {
+    source: &'a str,
+}
+
+impl<'a> Mutator for Check<'a> {
+    type Input = ();
+    type Output = Vec<RuntimeError>;
+
+    fn run(&self, mem: &MutatorView, _input: ()) -> Result<Vec<RuntimeError>, RuntimeError> {
+        let exprs = match parse_all(mem, self.source) {
+            Ok(exprs) => exprs,
+            Err(e) => return Ok(vec![e]),
+        };
+
+        // compiler errors are mostly reported without a position, so fall back on the position
+        // of the form being compiled
+        let tokens = tokenize(self.source)?;
+        let starts = tokens.iter().scan(0, |depth, token| {
+            let top_level = *depth == 0;
+            match token.token {
+                TokenType::OpenParen => *depth += 1,
+                TokenType::CloseParen => *depth -= 1,
+                _ => (),
+            }
+            Some(if top_level { Some(token.pos) } else { None })
+        });
+
+        let mut errors = Vec::new();
+        for (expr, start) in exprs.into_iter().zip(starts.flatten()) {
+            if let Err(e) = compile(mem, expr) {
+                errors.push(match e.error_pos() {
+                    Some(_) => e,
+                    None => RuntimeError::with_pos(e.error_kind().clone(), start),
+                });
+            }
+        }
+
+        Ok(errors)
+    }
+}
+
+/// Parse and compile a source file, returning the errors found
+pub fn check(source: &str) -> Vec<RuntimeError> {
+    let mem = Memory::new();
+    mem.mutate(&Check { source }, ())
+        .unwrap_or_else(|e| vec![e])
+}
+
+/// Convert an LSP position, which counts lines and characters from 0, to a SourcePos
+fn source_pos(position: &Json) -> Option<SourcePos> {
+    Some(SourcePos {
+        line: position.get("line")?.as_f64()? as u32 + 1,
+        column: position.get("character")?.as_f64()? as u32,
+    })
+}
+
+/// Convert a SourcePos to an LSP position
+fn lsp_position(line: u32, column: u32) -> Json {
+    Json::object(vec![
+        ("line", Json::Number(line.saturating_sub(1) as f64)),
+        ("character", Json::Number(column as f64)),
+    ])
+}
+
+/// The LSP range of the word starting at a position, or of its first character if it does not
+/// start a word
+fn word_range(source: &str, pos: SourcePos) -> Json {
+    let length = source
+        .lines()
+        .nth(pos.line.saturating_sub(1) as usize)
+        .map(|line| {
+            line.chars()
+                .skip(pos.column as usize)
+                .take_while(|c| !is_terminating(*c))
+                .count()
+        })
+        .unwrap_or(0)
+        .max(1);
+
+    Json::object(vec![
+        ("start", lsp_position(pos.line, pos.column)),
+        ("end", lsp_position(pos.line, pos.column + length as u32)),
+    ])
+}
+
+/// Show a function's signature, arity and docstring as markdown
+fn hover_text(signature: &str, arity: Option<Arity>, doc: Option<&str>) -> String {
+    let mut text = format!("```evalrus\n{}\n```", signature);
+    if let Some(arity) = arity {
+        text.push_str(&format!("\n\n{}", arity));
+    }
+    if let Some(doc) = doc {
+        text.push_str(&format!("\n\n{}", doc));
+    }
+    text
+}
+
+fn native_arity(spec: &NativeSpec) -> Arity {
+    Arity {
+        min: spec.min_args as usize,
+        max: spec.max_args.map(|max| max as usize),
+    }
+}
+
+/// A language server: holds the open documents and answers editor requests about them
+pub struct LanguageServer {
+    /// Text of each open document, by URI
+    documents: HashMap<String, String>,
+    /// Definitions made by the prelude, which every session has
+    prelude: Vec<Definition>,
+    shutdown: bool,
+    /// Set once the client asks the server to exit, to the process exit code
+    exit_code: Option<i32>,
+}
+
+impl Default for LanguageServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LanguageServer {
+    pub fn new() -> LanguageServer {
+        LanguageServer {
+            documents: HashMap::new(),
+            prelude: definitions(PRELUDE),
+            shutdown: false,
+            exit_code: None,
+        }
+    }
+
+    /// The process exit code, once the client has asked the server to exit
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    /// Handle a request or notification, returning the messages to send back to the client
+    pub fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let method = message.get("method").and_then(Json::as_str).unwrap_or("");
+        let params = message.get("params").unwrap_or(&Json::Null);
+        let id = message.get("id");
+
+        // notifications
+        match method {
+            "exit" => {
+                self.exit_code = Some(if self.shutdown { 0 } else { 1 });
+                return Vec::new();
+            }
+            "textDocument/didOpen" => {
+                let uri = params.path(&["textDocument", "uri"]).and_then(Json::as_str);
+                let text = params
+                    .path(&["textDocument", "text"])
+                    .and_then(Json::as_str);
+                return match (uri, text) {
+                    (Some(uri), Some(text)) => self.update(uri, text),
+                    _ => Vec::new(),
+                };
+            }
+            "textDocument/didChange" => {
+                // the whole text is sent on every change
+                let uri = params.path(&["textDocument", "uri"]).and_then(Json::as_str);
+                let text = params
+                    .get("contentChanges")
+                    .and_then(Json::as_array)
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change.get("text"))
+                    .and_then(Json::as_str);
+                return match (uri, text) {
+                    (Some(uri), Some(text)) => self.update(uri, text),
+                    _ => Vec::new(),
+                };
+            }
+            "textDocument/didClose" => {
+                return match params.path(&["textDocument", "uri"]).and_then(Json::as_str) {
+                    Some(uri) => {
+                        self.documents.remove(uri);
+                        vec![publish_diagnostics(uri, Vec::new())]
+                    }
+                    None => Vec::new(),
+                };
+            }
+            _ => (),
+        }
+
+        // anything else without an id is a notification that needs no answer
+        let id = match id {
+            Some(id) => id.clone(),
+            None => return Vec::new(),
+        };
+
+        if self.shutdown {
+            return vec![error_response(id, INVALID_REQUEST, "Server is shut down")];
+        }
+
+        let result = match method {
+            "initialize" => Json::object(vec![
+                (
+                    "capabilities",
+                    Json::object(vec![
+                        ("textDocumentSync", Json::Number(TEXT_DOCUMENT_SYNC_FULL)),
+                        ("completionProvider", Json::object(Vec::new())),
+                        ("definitionProvider", Json::Bool(true)),
+                        ("hoverProvider", Json::Bool(true)),
+                    ]),
+                ),
+                (
+                    "serverInfo",
+                    Json::object(vec![("name", Json::string("evalrus-lsp"))]),
+                ),
+            ]),
+            "shutdown" => {
+                self.shutdown = true;
+                Json::Null
+            }
+            "textDocument/completion" => self.completion(params),
+            "textDocument/definition" => self.definition(params),
+            "textDocument/hover" => self.hover(params),
+            _ => {
+                return vec![error_response(
+                    id,
+                    METHOD_NOT_FOUND,
+                    &format!("Unknown method {}", method),
+                )]
+            }
+        };
+
+        vec![Json::object(vec![
+            ("jsonrpc", Json::string("2.0")),
+            ("id", id),
+            ("result", result),
+        ])]
+    }
+
+    /// Store a document's new text and report the errors in it
+    fn update(&mut self, uri: &str, text: &str) -> Vec<Json> {
+        let diagnostics = check(text)
+            .into_iter()
+            .map(|error| {
+                let pos = error
+                    .error_pos()
+                    .unwrap_or(SourcePos { line: 1, column: 0 });
+                Json::object(vec![
+                    ("range", word_range(text, pos)),
+                    ("severity", Json::Number(SEVERITY_ERROR)),
+                    ("source", Json::string("evalrus")),
+                    ("message", Json::String(format!("{}", error))),
+                ])
+            })
+            .collect();
+
+        self.documents.insert(String::from(uri), String::from(text));
+        vec![publish_diagnostics(uri, diagnostics)]
+    }
+
+    /// The document and position a request refers to
+    fn document_position(&self, params: &Json) -> Option<(&str, &str, SourcePos)> {
+        let uri = params.path(&["textDocument", "uri"])?.as_str()?;
+        let (uri, text) = self.documents.get_key_value(uri)?;
+        Some((uri, text, source_pos(params.get("position")?)?))
+    }
+
+    /// Offer every special form, native function and global defined by the prelude or the
+    /// document
+    fn completion(&self, params: &Json) -> Json {
+        let mut items = Vec::new();
+        let mut seen = Vec::new();
+        let mut item = |label: &str, kind: f64, detail: &str| {
+            if !seen.contains(&String::from(label)) {
+                seen.push(String::from(label));
+                items.push(Json::object(vec![
+                    ("label", Json::string(label)),
+                    ("kind", Json::Number(kind)),
+                    ("detail", Json::string(detail)),
+                ]));
+            }
+        };
+
+        let document = self
+            .document_position(params)
+            .map(|(_, text, _)| definitions(text))
+            .unwrap_or_default();
+
+        for definition in document.iter().chain(self.prelude.iter()) {
+            let kind = match definition.arity {
+                Some(_) => COMPLETION_FUNCTION,
+                None => COMPLETION_VARIABLE,
+            };
+            item(&definition.name, kind, &definition.signature);
+        }
+        for spec in all_natives() {
+            item(spec.name, COMPLETION_FUNCTION, "native function");
+        }
+        for name in SPECIAL_FORMS {
+            item(name, COMPLETION_KEYWORD, "special form");
+        }
+
+        Json::Array(items)
+    }
+
+    /// Find where the symbol at a position is defined, looking in the same document first and
+    /// then in the other open documents
+    fn definition(&self, params: &Json) -> Json {
+        let (uri, text, pos) = match self.document_position(params) {
+            Some(found) => found,
+            None => return Json::Null,
+        };
+        let name = match symbol_at(text, pos) {
+            Some(name) => name,
+            None => return Json::Null,
+        };
+
+        let others = self
+            .documents
+            .iter()
+            .filter(|(other, _)| *other != uri)
+            .map(|(uri, text)| (uri.as_str(), text.as_str()));
+        for (uri, text) in Some((uri, text)).into_iter().chain(others) {
+            if let Some(definition) = definitions(text).into_iter().find(|d| d.name == name) {
+                return Json::object(vec![
+                    ("uri", Json::string(uri)),
+                    ("range", word_range(text, definition.pos)),
+                ]);
+            }
+        }
+
+        Json::Null
+    }
+
+    /// Describe the symbol at a position: its signature, arity and docstring if it is a
+    /// function
+    fn hover(&self, params: &Json) -> Json {
+        let (_, text, pos) = match self.document_position(params) {
+            Some(found) => found,
+            None => return Json::Null,
+        };
+        let name = match symbol_at(text, pos) {
+            Some(name) => name,
+            None => return Json::Null,
+        };
+
+        let contents = if SPECIAL_FORMS.contains(&name.as_str()) {
+            hover_text(&name, None, Some("special form"))
+        } else if let Some(definition) = definitions(text)
+            .into_iter()
+            .chain(self.prelude.iter().cloned())
+            .find(|d| d.name == name)
+        {
+            hover_text(
+                &definition.signature,
+                definition.arity,
+                definition.doc.as_deref(),
+            )
+        } else if let Some(spec) = all_natives().find(|spec| spec.name == name) {
+            hover_text(&name, Some(native_arity(spec)), Some("native function"))
+        } else {
+            return Json::Null;
+        };
+
+        Json::object(vec![(
+            "contents",
+            Json::object(vec![
+                ("kind", Json::string("markdown")),
+                ("value", Json::String(contents)),
+            ]),
+        )])
+    }
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Json>) -> Json {
+    Json::object(vec![
+        ("jsonrpc", Json::string("2.0")),
+        ("method", Json::string("textDocument/publishDiagnostics")),
+        (
+            "params",
+            Json::object(vec![
+                ("uri", Json::string(uri)),
+                ("diagnostics", Json::Array(diagnostics)),
+            ]),
+        ),
+    ])
+}
+
+fn error_response(id: Json, code: f64, message: &str) -> Json {
+    Json::object(vec![
+        ("jsonrpc", Json::string("2.0")),
+        ("id", id),
+        (
+            "error",
+            Json::object(vec![
+                ("code", Json::Number(code)),
+                ("message", Json::string(message)),
+            ]),
+        ),
+    ])
+}
+
+/// Read one message, framed by a Content-Length header. Returns None at the end of the input.
+pub fn read_message<R: BufRead>(input: &mut R) -> Result<Option<Json>, RuntimeError> {
+    match read_body(input)? {
+        Some(body) => parse_body(body).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Read the body of one message without parsing it, so that a body that is not valid JSON
+/// leaves the input positioned at the start of the next message
+fn read_body<R: BufRead>(input: &mut R) -> Result<Option<Vec<u8>>, RuntimeError> {
+    let mut length = None;
+
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let length = length.ok_or_else(|| err_parser("Message has no Content-Length header"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+fn parse_body(body: Vec<u8>) -> Result<Json, RuntimeError> {
+    let body = String::from_utf8(body).map_err(|_| err_parser("Message is not valid UTF-8"))?;
+    Json::parse(&body)
+}
+
+/// Write one message, framed by a Content-Length header
+pub fn write_message<W: Write>(output: &mut W, message: &Json) -> Result<(), RuntimeError> {
+    let body = format!("{}", message);
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Serve LSP requests until the client asks the server to exit or closes the input, returning
+/// the process exit code
+pub fn serve<R: BufRead, W: Write>(input: &mut R, output: &mut W) -> Result<i32, RuntimeError> {
+    let mut server = LanguageServer::new();
+
+    while let Some(body) = read_body(input)? {
+        let replies = match parse_body(body) {
+            Ok(message) => server.handle(&message),
+            // the id of a message that can't be parsed is unknown, so the reply has a null id
+            Err(e) => vec![error_response(Json::Null, PARSE_ERROR, &e.to_string())],
+        };
+        for reply in replies {
+            write_message(output, &reply)?;
+        }
+
+        if let Some(code) = server.exit_code() {
+            return Ok(code);
+        }
+    }
+
+    Ok(1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::interpreter::error::{spos, ErrorKind};
+
+    #[test]
+    fn find_definitions() {
+        let source = "\
+(def add (a b) (+ a b))
+(def greet (name &optional (greeting \"hello\"))
+  \"Greet someone by name\"
+  (cons greeting name))
+(def list-of (first . rest) (cons first rest))
+(begin (set (quote counter) 0) (deftest adds (assert-equal 3 (add 1 2))))
+(defstruct point x y)
+(def unfinished (x) (car x)";
+
+        let found: Vec<(String, String, Option<Arity>)> = definitions(source)
+            .into_iter()
+            .map(|d| (d.name, d.signature, d.arity))
+            .collect();
+        let arity = |min, max| Some(Arity { min, max });
+
+        let expected = vec![
+            ("add", "(add a b)", arity(2, Some(2))),
+            (
+                "greet",
+                "(greet name &optional (greeting \"hello\"))",
+                arity(1, Some(2)),
+            ),
+            ("list-of", "(list-of first . rest)", arity(1, None)),
+            ("counter", "counter", None),
+            ("make-point", "(make-point x y)", arity(2, Some(2))),
+            ("point?", "(point? value)", arity(1, Some(1))),
+            ("point-x", "(point-x record)", arity(1, Some(1))),
+            (
+                "set-point-x!",
+                "(set-point-x! record value)",
+                arity(2, Some(2)),
+            ),
+            ("point-y", "(point-y record)", arity(1, Some(1))),
+            (
+                "set-point-y!",
+                "(set-point-y! record value)",
+                arity(2, Some(2)),
+            ),
+            ("unfinished", "(unfinished x)", arity(1, Some(1))),
+        ];
+        let expected: Vec<(String, String, Option<Arity>)> = expected
+            .into_iter()
+            .map(|(name, signature, arity)| (String::from(name), String::from(signature), arity))
+            .collect();
+        assert_eq!(found, expected);
+
+        let greet = &definitions(source)[1];
+        assert_eq!(greet.pos, spos(2, 5));
+        assert_eq!(greet.doc.as_deref(), Some("Greet someone by name"));
+
+        assert_eq!(symbol_at(source, spos(6, 63)), Some(String::from("add")));
+        assert_eq!(symbol_at(source, spos(1, 9)), None);
+
+        assert_eq!(
+            format!("{}", Arity { min: 1, max: None }),
+            "takes at least 1 argument"
+        );
+        assert_eq!(
+            format!(
+                "{}",
+                Arity {
+                    min: 1,
+                    max: Some(3)
+                }
+            ),
+            "takes 1 to 3 arguments"
+        );
+    }
+
+    #[test]
+    fn check_errors() {
+        assert_eq!(check("(def f (x) x)\n(f 1)"), Vec::new());
+
+        let errors = check("(def f (x) x)\n(f \"unterminated)");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].error_pos(), Some(spos(2, 3)));
+        assert!(matches!(errors[0].error_kind(), ErrorKind::LexerError(_)));
+
+        // compile errors are reported at the start of the form they are in
+        let errors = check("(def f (x) x)\n\n  (def g)\n(if)");
+        let positions: Vec<Option<SourcePos>> = errors.iter().map(|e| e.error_pos()).collect();
+        assert_eq!(positions, vec![Some(spos(3, 2)), Some(spos(4, 0))]);
+
+        // an unclosed list is reported at its open paren rather than the start of the file
+        let errors = check(
+            "(def f (x) x)
+(def g (x)
+  (+ (f x) 1)",
+        );
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].error_pos(), Some(spos(2, 0)));
+        let errors = check(
+            "(def f (x)
+  (car x)
+  (cons x",
+        );
+        assert_eq!(errors[0].error_pos(), Some(spos(3, 2)));
+    }
+
+    /// Frame a sequence of messages as a client would send them
+    fn client_messages(messages: &[&str]) -> Vec<u8> {
+        let mut input = Vec::new();
+        for message in messages {
+            write_message(&mut input, &Json::parse(message).unwrap()).unwrap();
+        }
+        input
+    }
+
+    #[test]
+    fn serve_session() {
+        let source = "(def double (x)\\n  \\\"Twice x\\\"\\n  (+ x x))\\n(double (car 1 2))";
+        let open = format!(
+            r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"file:///a.evalrus","text":"{}"}}}}}}"#,
+            source
+        );
+        let at = |id: u32, method: &str, line: u32, character: u32| {
+            format!(
+                r#"{{"jsonrpc":"2.0","id":{},"method":"{}","params":{{"textDocument":{{"uri":"file:///a.evalrus"}},"position":{{"line":{},"character":{}}}}}}}"#,
+                id, method, line, character
+            )
+        };
+
+        let input = client_messages(&[
+            r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#,
+            r#"{"jsonrpc":"2.0","method":"initialized","params":{}}"#,
+            &open,
+            &at(2, "textDocument/hover", 3, 2),
+            &at(3, "textDocument/definition", 3, 2),
+            &at(4, "textDocument/hover", 3, 9),
+            &at(5, "textDocument/completion", 0, 0),
+            r#"{"jsonrpc":"2.0","id":6,"method":"workspace/symbol","params":{}}"#,
+            r#"{"jsonrpc":"2.0","id":7,"method":"shutdown"}"#,
+            r#"{"jsonrpc":"2.0","method":"exit"}"#,
+        ]);
+
+        let mut output = Vec::new();
+        let code = serve(&mut &input[..], &mut output).unwrap();
+        assert_eq!(code, 0);
+
+        let mut replies = Vec::new();
+        let mut output = &output[..];
+        while let Some(reply) = read_message(&mut output).unwrap() {
+            replies.push(reply);
+        }
+        assert_eq!(replies.len(), 8);
+
+        let capabilities = replies[0].path(&["result", "capabilities"]).unwrap();
+        assert_eq!(capabilities.get("hoverProvider"), Some(&Json::Bool(true)));
+
+        // (car 1 2) has too many arguments
+        let diagnostics = replies[1].path(&["params", "diagnostics"]).unwrap();
+        let diagnostics = diagnostics.as_array().unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].path(&["range", "start", "line"]),
+            Some(&Json::Number(3.0))
+        );
+
+        let hover = replies[2].path(&["result", "contents", "value"]).unwrap();
+        assert_eq!(
+            hover.as_str(),
+            Some("```evalrus\n(double x)\n```\n\ntakes 1 argument\n\nTwice x")
+        );
+
+        let definition = replies[3].get("result").unwrap();
+        assert_eq!(
+            definition.path(&["range", "start"]),
+            Some(&Json::parse(r#"{"line":0,"character":5}"#).unwrap())
+        );
+
+        let hover = replies[4].path(&["result", "contents", "value"]).unwrap();
+        assert!(hover.as_str().unwrap().contains("special form"));
+
+        let completions = replies[5].get("result").and_then(Json::as_array).unwrap();
+        let labels: Vec<&str> = completions
+            .iter()
+            .filter_map(|item| item.get("label").and_then(Json::as_str))
+            .collect();
+        for label in ["double", "map", "println", "match"] {
+            assert!(labels.contains(&label), "missing completion {}", label);
+        }
+
+        assert_eq!(
+            replies[6].path(&["error", "code"]),
+            Some(&Json::Number(METHOD_NOT_FOUND))
+        );
+        assert_eq!(replies[7].get("result"), Some(&Json::Null));
+    }
+
+    #[test]
+    fn serve_bad_message() {
+        // a body that is not JSON, then one nested too deeply, then a valid request
+        let mut input = Vec::new();
+        for body in [String::from("{\"id\": 1,"), "[".repeat(10_000)] {
+            write!(input, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        }
+        input.extend(client_messages(&[
+            r#"{"jsonrpc":"2.0","id":2,"method":"shutdown"}"#,
+            r#"{"jsonrpc":"2.0","method":"exit"}"#,
+        ]));
+
+        let mut output = Vec::new();
+        let code = serve(&mut &input[..], &mut output).unwrap();
+        assert_eq!(code, 0);
+
+        let mut replies = Vec::new();
+        let mut output = &output[..];
+        while let Some(reply) = read_message(&mut output).unwrap() {
+            replies.push(reply);
+        }
+        assert_eq!(replies.len(), 3);
+
+        for reply in &replies[..2] {
+            assert_eq!(reply.get("id"), Some(&Json::Null));
+            assert_eq!(
+                reply.path(&["error", "code"]),
+                Some(&Json::Number(PARSE_ERROR))
+            );
+        }
+        assert_eq!(replies[2].get("id"), Some(&Json::Number(2.0)));
+        assert_eq!(replies[2].get("result"), Some(&Json::Null));
+    }
+}
@@ -1,16 +1,74 @@
-use crate::memory::{allocator::AllocObject, AllocRaw, ArraySize, RawPtr, StickyImmixHeap};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashSet,
+    io::{self, Write},
+    mem::size_of,
+    path::Path,
+    ptr,
+};
+
+use crate::memory::{
+    allocator::{alloc_size_of, AllocObject},
+    stickyimmix::LINE_SIZE,
+    AllocRaw, ArraySize, CollectionKind, HeapStats, RawPtr, StickyImmixHeap,
+};
 
 // GC and Rust: https://blog.pnkfx.org/blog/categories/gc/
 
 use super::{
-    error::RuntimeError,
+    arena::Arena,
+    error::{ErrorKind, RuntimeError},
     headers::{ObjectHeader, TypeList},
+    image::Image,
+    limits::Limits,
+    number::Float,
     pointerops::ScopedRef,
+    repl::{ImageLoader, ReadEvalPrint},
     safeptr::{MutatorScope, ScopedPtr, TaggedScopedPtr},
     symbolmap::SymbolMap,
     taggedptr::{FatPtr, TaggedPtr},
+    text::Text,
+    trace::{Trace, Tracer},
 };
 
+/// Bytes allocated since the last collection beyond which `Memory::collect_if_needed()` collects
+const COLLECTION_THRESHOLD: usize = 1 << 20;
+
+/// `Memory::collect_if_needed()` runs a major collection instead of a minor one after this many
+/// minor collections, to reclaim garbage that has become old
+const MINOR_COLLECTIONS_PER_MAJOR: usize = 8;
+
+thread_local! {
+    /// The heap of the mutator that is currently running, which the write barrier records into
+    static ACTIVE_HEAP: Cell<*const Heap> = const { Cell::new(ptr::null()) };
+}
+
+/// The write barrier. Must be called after a pointer is written into `container`.
+///
+/// Minor collections do not trace objects that survived an earlier collection. If such an old
+/// object is made to point at a young object, the container is remembered and traced as an extra
+/// root by the next minor collection so that the young object is found.
+pub fn write_barrier<T: Trace>(container: &T) {
+    ACTIVE_HEAP.with(|heap| {
+        let heap = heap.get();
+        if !heap.is_null() {
+            unsafe { &*heap }.remember(container);
+        }
+    })
+}
+
+/// A container recorded by the write barrier, along with the function that knows how to trace
+/// its concrete type
+struct Remembered {
+    address: usize,
+    trace: unsafe fn(usize, &mut Tracer),
+}
+
+/// Trace the container of type T at the given address
+unsafe fn trace_remembered<T: Trace>(address: usize, tracer: &mut Tracer) {
+    (*(address as *const T)).trace(tracer);
+}
+
 /// This type describes the mutator's view into memory - the heap and symbol name/ptr lookup.
 ///
 /// It implements `MutatorScope` such that any `TaggedScopedPtr` or `Value` instances must be lifetime-
@@ -58,35 +116,198 @@ impl<'memory> MutatorView<'memory> {
         TaggedScopedPtr::new(self, TaggedPtr::number(value))
     }
 
+    /// Allocate a floating point number on the heap
+    pub fn float(&self, value: f64) -> Result<TaggedScopedPtr<'_>, RuntimeError> {
+        self.alloc_tagged(Float::new(value))
+    }
+
+    /// Allocate a Text object on the heap
+    pub fn text(&self, value: &str) -> Result<TaggedScopedPtr<'_>, RuntimeError> {
+        Ok(Text::new_from_str(self, value)?.as_tagged(self))
+    }
+
     /// Return a nil-initialized runtime-tagged pointer
     pub fn nil(&self) -> TaggedScopedPtr<'_> {
         TaggedScopedPtr::new(self, TaggedPtr::nil())
     }
+
+    /// Return the resource limits evaluation must keep within
+    pub fn limits(&self) -> Limits {
+        self.heap.limits.get()
+    }
+
+    /// Write console output, to stdout or to the capture buffer if output is being captured
+    pub fn write_output(&self, bytes: &[u8]) -> Result<(), RuntimeError> {
+        match *self.heap.output.borrow_mut() {
+            Some(ref mut buffer) => buffer.extend_from_slice(bytes),
+            None => {
+                let mut stdout = io::stdout().lock();
+                stdout.write_all(bytes)?;
+                stdout.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Capture console output in a buffer instead of writing it to stdout
+    pub fn capture_output(&self) {
+        self.heap.output.replace(Some(Vec::new()));
+    }
+
+    /// Stop capturing console output, returning everything written since `capture_output()`
+    pub fn take_output(&self) -> String {
+        let buffer = self.heap.output.take().unwrap_or_default();
+        String::from_utf8_lossy(&buffer).into_owned()
+    }
 }
 
 impl<'memory> MutatorScope for MutatorView<'memory> {}
 
 pub type HeapStorage = StickyImmixHeap<ObjectHeader>;
 
+/// The allocator that backs a `Memory` instance
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Backend {
+    /// The garbage collected Sticky Immix heap
+    StickyImmix,
+    /// An arena that never frees anything. Useful for bootstrapping and for cross-checking the
+    /// behaviour of the collected heap.
+    Arena,
+}
+
+/// Object storage for one of the backends. Both write an `ObjectHeader` immediately before each
+/// object so that tagged pointers can be resolved the same way for either.
+enum Storage {
+    StickyImmix(HeapStorage),
+    Arena(Arena<ObjectHeader>),
+}
+
 /// Heap memory types.
 struct Heap {
-    heap: HeapStorage,
+    heap: Storage,
     syms: SymbolMap,
+    /// Old containers that may point at young objects
+    remembered: RefCell<Vec<Remembered>>,
+    remembered_addresses: RefCell<HashSet<usize>>,
+    /// No object can be old before the first collection, so the write barrier has nothing to do
+    has_old_objects: Cell<bool>,
+    limits: Cell<Limits>,
+    /// Console output is collected here instead of being written to stdout, if set
+    output: RefCell<Option<Vec<u8>>>,
+    /// Bytes in lines holding objects that survived the last collection
+    live_bytes: Cell<usize>,
 }
 
 impl Heap {
-    fn new() -> Heap {
+    fn new(backend: Backend) -> Heap {
+        let heap = match backend {
+            Backend::StickyImmix => Storage::StickyImmix(HeapStorage::new()),
+            Backend::Arena => Storage::Arena(Arena::new()),
+        };
+
         Heap {
-            heap: HeapStorage::new(),
+            heap,
             syms: SymbolMap::new(),
+            remembered: RefCell::new(Vec::new()),
+            remembered_addresses: RefCell::new(HashSet::new()),
+            has_old_objects: Cell::new(false),
+            limits: Cell::new(Limits::none()),
+            output: RefCell::new(None),
+            live_bytes: Cell::new(0),
+        }
+    }
+
+    /// Record the container in the remembered set if it is an old object
+    fn remember<T: Trace>(&self, container: &T) {
+        if !self.has_old_objects.get() {
+            return;
+        }
+
+        let heap = match self.heap {
+            Storage::StickyImmix(ref heap) => heap,
+            Storage::Arena(_) => return,
+        };
+
+        let address = container as *const T as usize;
+        if heap.is_old(address) && self.remembered_addresses.borrow_mut().insert(address) {
+            self.remembered.borrow_mut().push(Remembered {
+                address,
+                trace: trace_remembered::<T>,
+            });
         }
     }
 
+    /// Mark everything reachable from the roots and make the space of everything else available
+    /// for allocation. The arena backend never frees anything.
+    fn collect<R: Trace>(&self, roots: &R, kind: CollectionKind) {
+        let heap = match self.heap {
+            Storage::StickyImmix(ref heap) => heap,
+            Storage::Arena(_) => return,
+        };
+
+        heap.start_collection(kind);
+
+        // Symbols only reachable from old objects are not found by a minor collection, so only
+        // a major collection may free Symbols
+        if kind == CollectionKind::Major {
+            self.syms.unmark_all();
+        }
+
+        let mut tracer = Tracer::new(heap);
+        roots.trace(&mut tracer);
+
+        if kind == CollectionKind::Minor {
+            for remembered in self.remembered.borrow().iter() {
+                unsafe { (remembered.trace)(remembered.address, &mut tracer) };
+            }
+        }
+
+        tracer.trace_all();
+        heap.finish_collection();
+
+        if kind == CollectionKind::Major {
+            self.syms.sweep();
+        }
+
+        // every surviving object is now old, so no old-to-young pointers remain
+        self.remembered.borrow_mut().clear();
+        self.remembered_addresses.borrow_mut().clear();
+        self.has_old_objects.set(true);
+        self.live_bytes.set(heap.stats().marked_lines * LINE_SIZE);
+    }
+
+    /// Return an error if allocating an object or array of `size` bytes would take the heap past
+    /// its limit. The allocation is charged what the allocator reserves for it: the object header
+    /// as well, rounded up to a word boundary.
+    fn check_heap_limit(&self, size: usize) -> Result<(), RuntimeError> {
+        let size = alloc_size_of(size_of::<ObjectHeader>() + size);
+
+        if let Some(max) = self.limits.get().max_heap_bytes {
+            let in_use = match self.heap {
+                Storage::StickyImmix(ref heap) => {
+                    self.live_bytes.get() + heap.allocated_since_collection()
+                }
+                Storage::Arena(ref arena) => arena.stats().allocated_since_collection,
+            };
+
+            if in_use + size > max {
+                return Err(RuntimeError::new(ErrorKind::HeapLimit));
+            }
+        }
+
+        Ok(())
+    }
+
     fn alloc<T>(&self, object: T) -> Result<RawPtr<T>, RuntimeError>
     where
         T: AllocObject<TypeList>,
     {
-        Ok(self.heap.alloc(object)?)
+        self.check_heap_limit(size_of::<T>())?;
+
+        match self.heap {
+            Storage::StickyImmix(ref heap) => Ok(heap.alloc(object)?),
+            Storage::Arena(ref arena) => Ok(arena.alloc(object)?),
+        }
     }
 
     fn alloc_tagged<T>(&self, object: T) -> Result<TaggedPtr, RuntimeError>
@@ -94,7 +315,7 @@ impl Heap {
         FatPtr: From<RawPtr<T>>,
         T: AllocObject<TypeList>,
     {
-        Ok(TaggedPtr::from(FatPtr::from(self.heap.alloc(object)?)))
+        Ok(TaggedPtr::from(FatPtr::from(self.alloc(object)?)))
     }
 
     fn lookup_sym(&self, name: &str) -> TaggedPtr {
@@ -102,24 +323,128 @@ impl Heap {
     }
 
     fn alloc_array(&self, capacity: ArraySize) -> Result<RawPtr<u8>, RuntimeError> {
-        Ok(self.heap.alloc_array(capacity)?)
+        self.check_heap_limit(capacity as usize)?;
+
+        match self.heap {
+            Storage::StickyImmix(ref heap) => Ok(heap.alloc_array(capacity)?),
+            Storage::Arena(ref arena) => Ok(arena.alloc_array(capacity)?),
+        }
+    }
+
+    /// Return allocation information
+    fn stats(&self) -> HeapStats {
+        match self.heap {
+            Storage::StickyImmix(ref heap) => heap.stats(),
+            Storage::Arena(ref arena) => arena.stats(),
+        }
+    }
+
+    /// Return true if enough has been allocated since the last collection to warrant another
+    fn needs_collection(&self) -> bool {
+        match self.heap {
+            Storage::StickyImmix(ref heap) => {
+                heap.allocated_since_collection() >= COLLECTION_THRESHOLD
+            }
+            Storage::Arena(_) => false,
+        }
     }
 }
 
 /// Wraps a heap and provides scope-limited access to the heap
 pub struct Memory {
     heap: Heap,
+    minor_collections: Cell<usize>,
 }
 
 impl Memory {
     /// Instantiate a new memory environment
     pub fn new() -> Memory {
-        Memory { heap: Heap::new() }
+        Memory::with_backend(Backend::StickyImmix)
+    }
+
+    /// Instantiate a new memory environment that allocates with the given backend
+    pub fn with_backend(backend: Backend) -> Memory {
+        Memory {
+            heap: Heap::new(backend),
+            minor_collections: Cell::new(0),
+        }
+    }
+
+    /// Instantiate a new memory environment holding the objects saved in a heap image file,
+    /// returning it along with a REPL whose globals are those of the saved session. The limits
+    /// apply from the start so that a sandboxed session can't restore host I/O functions.
+    pub fn from_image(
+        path: &Path,
+        limits: Limits,
+    ) -> Result<(Memory, ReadEvalPrint), RuntimeError> {
+        let image = Image::load(path)?;
+
+        let mem = Memory::new();
+        mem.set_limits(limits);
+        let rep = mem.mutate(&ImageLoader::new(&image), ())?;
+
+        Ok((mem, rep))
     }
 
     pub fn mutate<M: Mutator>(&self, m: &M, input: M::Input) -> Result<M::Output, RuntimeError> {
         let mut guard = MutatorView::new(self);
-        m.run(&mut guard, input)
+
+        let previous = ACTIVE_HEAP.with(|heap| heap.replace(&self.heap));
+        let result = m.run(&mut guard, input);
+        ACTIVE_HEAP.with(|heap| heap.set(previous));
+
+        result
+    }
+
+    /// Run a garbage collection. Every object that is not reachable from `roots` is reclaimed.
+    ///
+    /// This can only be called between mutator runs, so that no pointers into the heap are held
+    /// anywhere except by the roots.
+    pub fn collect<R: Trace>(&self, roots: &R, kind: CollectionKind) {
+        self.heap.collect(roots, kind);
+
+        match kind {
+            CollectionKind::Minor => self.minor_collections.set(self.minor_collections.get() + 1),
+            CollectionKind::Major => self.minor_collections.set(0),
+        }
+    }
+
+    /// Run a collection if enough has been allocated since the last one. Mostly minor collections
+    /// are run, with a periodic major collection. Returns the kind of collection that was run.
+    pub fn collect_if_needed<R: Trace>(&self, roots: &R) -> Option<CollectionKind> {
+        if !self.heap.needs_collection() {
+            return None;
+        }
+
+        let kind = if self.minor_collections.get() >= MINOR_COLLECTIONS_PER_MAJOR {
+            CollectionKind::Major
+        } else {
+            CollectionKind::Minor
+        };
+
+        self.collect(roots, kind);
+        Some(kind)
+    }
+
+    /// Return block usage information for the heap
+    pub fn stats(&self) -> HeapStats {
+        self.heap.stats()
+    }
+
+    /// Set the resource limits for evaluation in this memory. Limits take effect from the next
+    /// allocation or evaluation, and sandboxing from the next Thread created.
+    pub fn set_limits(&self, limits: Limits) {
+        self.heap.limits.set(limits);
+    }
+
+    /// Return the resource limits for evaluation in this memory
+    pub fn limits(&self) -> Limits {
+        self.heap.limits.get()
+    }
+
+    /// Return the number of Symbols currently interned
+    pub fn symbol_count(&self) -> usize {
+        self.heap.syms.count()
     }
 }
 
@@ -131,3 +456,320 @@ pub trait Mutator: Sized {
 
     fn run(&self, mem: &MutatorView, input: Self::Input) -> Result<Self::Output, RuntimeError>;
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::interpreter::{
+        compiler::compile, pair::Pair, parser::parse, printer::print, safeptr::TaggedCellPtr,
+        taggedptr::Value, vm::Thread, CellPtr,
+    };
+
+    /// A mutator that runs a closure
+    struct Run<F>(F);
+
+    impl<F, O> Mutator for Run<F>
+    where
+        F: Fn(&MutatorView) -> Result<O, RuntimeError>,
+    {
+        type Input = ();
+        type Output = O;
+
+        fn run(&self, mem: &MutatorView, _input: ()) -> Result<O, RuntimeError> {
+            (self.0)(mem)
+        }
+    }
+
+    /// Allocate a number of pairs that are immediately garbage
+    fn alloc_garbage(mem: &MutatorView, count: usize) -> Result<(), RuntimeError> {
+        for n in 0..count {
+            Pair::cons(mem, mem.number(n as isize), mem.nil())?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn major_collection_reclaims_blocks() {
+        let mem = Memory::new();
+        let root = TaggedCellPtr::new_nil();
+
+        let fill = Run(|view: &MutatorView| {
+            root.set(Pair::cons(view, view.number(1), view.nil())?);
+            alloc_garbage(view, 10000)
+        });
+
+        mem.mutate(&fill, ()).unwrap();
+        mem.collect(&root, CollectionKind::Major);
+        let after_first = mem.stats();
+        assert!(after_first.free_blocks > 0);
+        assert_eq!(after_first.allocated_since_collection, 0);
+
+        // the same amount of garbage again must fit in the reclaimed blocks
+        mem.mutate(&fill, ()).unwrap();
+        mem.collect(&root, CollectionKind::Major);
+        assert_eq!(mem.stats().blocks, after_first.blocks);
+
+        let check = Run(|view: &MutatorView| {
+            assert_eq!(print(*root.get(view)), "(1)");
+            Ok(())
+        });
+        mem.mutate(&check, ()).unwrap();
+    }
+
+    #[test]
+    fn minor_collection_reclaims_young_garbage() {
+        let mem = Memory::new();
+        let root = TaggedCellPtr::new_nil();
+
+        mem.mutate(
+            &Run(|view: &MutatorView| {
+                root.set(Pair::cons(view, view.number(1), view.nil())?);
+                Ok(())
+            }),
+            (),
+        )
+        .unwrap();
+        mem.collect(&root, CollectionKind::Minor);
+        let marked_lines = mem.stats().marked_lines;
+
+        mem.mutate(&Run(|view: &MutatorView| alloc_garbage(view, 10000)), ())
+            .unwrap();
+        mem.collect(&root, CollectionKind::Minor);
+
+        assert_eq!(mem.stats().marked_lines, marked_lines);
+    }
+
+    #[test]
+    fn write_barrier_keeps_young_object_alive() {
+        let mem = Memory::new();
+        let root = TaggedCellPtr::new_nil();
+
+        // make an old pair
+        mem.mutate(
+            &Run(|view: &MutatorView| {
+                root.set(Pair::cons(view, view.nil(), view.nil())?);
+                Ok(())
+            }),
+            (),
+        )
+        .unwrap();
+        mem.collect(&root, CollectionKind::Minor);
+
+        // point the old pair at a young pair, which is only reachable through the old pair
+        mem.mutate(
+            &Run(|view: &MutatorView| {
+                if let Value::Pair(old) = *root.get(view) {
+                    let young = Pair::cons(view, view.number(42), view.nil())?;
+                    old.first.set(young);
+                }
+                alloc_garbage(view, 10000)
+            }),
+            (),
+        )
+        .unwrap();
+        mem.collect(&root, CollectionKind::Minor);
+
+        // reuse the reclaimed space, which would overwrite the young pair had it not been kept
+        mem.mutate(
+            &Run(|view: &MutatorView| {
+                for n in 0..10000 {
+                    Pair::cons(view, view.number(-n), view.nil())?;
+                }
+                assert_eq!(print(*root.get(view)), "((42))");
+                Ok(())
+            }),
+            (),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn eval_with_collections_between_lines() {
+        let mem = Memory::new();
+
+        let thread = mem
+            .mutate(
+                &Run(|view: &MutatorView| Ok(CellPtr::new_with(Thread::alloc(view)?))),
+                (),
+            )
+            .unwrap();
+
+        let thread = &thread;
+        let eval = |code: &'static str| {
+            mem.mutate(
+                &Run(move |view: &MutatorView| {
+                    let function = compile(view, parse(view, code)?)?;
+                    let result = thread.get(view).quick_vm_eval(view, function)?;
+                    Ok(print(*result))
+                }),
+                (),
+            )
+            .unwrap()
+        };
+
+        eval("(set (quote xs) (cons 1 (cons 2.5 nil)))");
+        mem.collect(thread, CollectionKind::Minor);
+
+        eval("(def second (l) (car (cdr l)))");
+        for n in 0..100 {
+            // leave garbage behind on every line
+            eval("(cons 1 (cons 2 (cons 3 nil)))");
+            if n % 10 == 0 {
+                mem.collect(thread, CollectionKind::Minor);
+            }
+        }
+        mem.collect(thread, CollectionKind::Major);
+
+        eval("(set (quote ys) (cons 0 xs))");
+        mem.collect(thread, CollectionKind::Minor);
+
+        assert_eq!(eval("(second xs)"), "2.5");
+        assert_eq!(eval("ys"), "(0 1 2.5)");
+    }
+
+    #[test]
+    fn major_collection_reclaims_symbols() {
+        let mem = Memory::new();
+        let root = TaggedCellPtr::new_nil();
+
+        mem.mutate(
+            &Run(|view: &MutatorView| {
+                root.set(Pair::cons(view, view.lookup_sym("kept"), view.nil())?);
+                for n in 0..1000 {
+                    view.lookup_sym(&format!("garbage-{}", n));
+                }
+                Ok(())
+            }),
+            (),
+        )
+        .unwrap();
+        let before = mem.symbol_count();
+        assert!(before >= 1001);
+
+        // a minor collection can't tell whether an old object refers to a symbol
+        mem.collect(&root, CollectionKind::Minor);
+        assert_eq!(mem.symbol_count(), before);
+
+        mem.collect(&root, CollectionKind::Major);
+        assert_eq!(mem.symbol_count(), before - 1000);
+
+        // the surviving symbol is still the one that is interned under its name
+        mem.mutate(
+            &Run(|view: &MutatorView| {
+                if let Value::Pair(pair) = *root.get(view) {
+                    assert!(pair.first.get(view) == view.lookup_sym("kept"));
+                }
+                Ok(())
+            }),
+            (),
+        )
+        .unwrap();
+        mem.collect(&root, CollectionKind::Major);
+        assert_eq!(mem.symbol_count(), before - 1000);
+    }
+
+    /// Evaluate each line in a fresh thread, collecting between lines, and return the printed
+    /// results
+    fn eval_lines(mem: &Memory, lines: &[&'static str]) -> Vec<String> {
+        let thread = mem
+            .mutate(
+                &Run(|view: &MutatorView| Ok(CellPtr::new_with(Thread::alloc(view)?))),
+                (),
+            )
+            .unwrap();
+
+        let mut results = Vec::new();
+        for (n, line) in lines.iter().enumerate() {
+            let result = mem.mutate(
+                &Run(|view: &MutatorView| {
+                    let function = compile(view, parse(view, line)?)?;
+                    let result = thread.get(view).quick_vm_eval(view, function)?;
+                    Ok(print(*result))
+                }),
+                (),
+            );
+
+            results.push(match result {
+                Ok(printed) => printed,
+                Err(e) => format!("error: {:?}", e.error_kind()),
+            });
+
+            let kind = if n % 3 == 0 {
+                CollectionKind::Major
+            } else {
+                CollectionKind::Minor
+            };
+            mem.collect(&thread, kind);
+        }
+
+        results
+    }
+
+    #[test]
+    fn heap_limit_counts_headers_and_arrays() {
+        let object_size = alloc_size_of(size_of::<ObjectHeader>() + size_of::<Float>());
+        let array_size = alloc_size_of(size_of::<ObjectHeader>() + 1000);
+        let needed = 10 * object_size + array_size;
+
+        let fill = Run(|view: &MutatorView| {
+            for n in 0..10 {
+                view.float(n as f64)?;
+            }
+            view.alloc_array(1000)?;
+            Ok(())
+        });
+
+        for backend in [Backend::StickyImmix, Backend::Arena] {
+            // the last allocation only fits if its header and padding aren't counted
+            for short in [1, array_size - 1000] {
+                let mem = Memory::with_backend(backend);
+                mem.set_limits(Limits {
+                    max_heap_bytes: Some(needed - short),
+                    ..Limits::none()
+                });
+                let error = mem.mutate(&fill, ()).unwrap_err();
+                assert_eq!(*error.error_kind(), ErrorKind::HeapLimit);
+            }
+
+            // room for exactly ten floats and the array, and nothing more
+            let mem = Memory::with_backend(backend);
+            mem.set_limits(Limits {
+                max_heap_bytes: Some(needed),
+                ..Limits::none()
+            });
+            mem.mutate(&fill, ()).unwrap();
+
+            let overflow = Run(|view: &MutatorView| view.alloc_array(1).map(|_| ()));
+            let error = mem.mutate(&overflow, ()).unwrap_err();
+            assert_eq!(*error.error_kind(), ErrorKind::HeapLimit);
+        }
+    }
+
+    #[test]
+    fn backends_evaluate_identically() {
+        let lines = [
+            "(set (quote xs) (cons 1 (cons 2.5 nil)))",
+            "(def second (l) (car (cdr l)))",
+            "(second xs)",
+            "(def add (a b) (+ a b))",
+            "(set (quote add3) (add 3))",
+            "(add3 4)",
+            "(let ((a 1) (b (cons 2 nil))) (cons a b))",
+            "(cond (is? 1 2) (quote no) (is? 1 1) (quote yes))",
+            "(/ 7 0)",
+            "(+ 1.5 xs)",
+            "(* (->float 3) 2)",
+            "xs",
+        ];
+
+        let immix = eval_lines(&Memory::with_backend(Backend::StickyImmix), &lines);
+        let arena_memory = Memory::with_backend(Backend::Arena);
+        let arena = eval_lines(&arena_memory, &lines);
+
+        assert_eq!(immix, arena);
+        assert_eq!(immix[2], "2.5");
+        assert_eq!(immix[5], "7");
+        assert_eq!(immix[11], "(1 2.5)");
+        assert_eq!(arena_memory.stats().free_blocks, 0);
+    }
+}
@@ -4,25 +4,37 @@ pub mod bytecode;
 pub mod compiler;
 pub mod containers;
 pub mod dict;
+pub mod equality;
 pub mod error;
 pub mod function;
 pub mod hashable;
 pub mod headers;
+pub mod image;
+pub mod io;
+pub mod json;
 pub mod lexer;
+pub mod limits;
 pub mod list;
+pub mod lsp;
 pub mod memory;
+pub mod native;
 pub mod number;
 pub mod pair;
 pub mod parser;
 pub mod pointerops;
+pub mod prelude;
+pub mod pretty;
 pub mod printer;
+pub mod profiler;
 pub mod rawarray;
+pub mod record;
 pub mod repl;
 pub mod safeptr;
 pub mod symbol;
 pub mod symbolmap;
 pub mod taggedptr;
 pub mod text;
+pub mod trace;
 pub mod vm;
 
 pub use array::{ArrayU16, ArrayU32, ArrayU8};
@@ -0,0 +1,134 @@
+use std::fmt;
+
+use super::{
+    containers::HashIndexedAnyContainer,
+    dict::Dict,
+    error::err_eval,
+    function::INTROSPECTION_NATIVES,
+    io::IO_NATIVES,
+    printer::Print,
+    safeptr::{MutatorScope, TaggedScopedPtr},
+    symbol::SYMBOL_NATIVES,
+    vm::GENERATOR_NATIVES,
+    MutatorView, RuntimeError, ScopedPtr,
+};
+
+/// A Rust function that can be called from the language. It is given its arguments in order and
+/// returns a single value.
+pub type NativeFn = for<'guard> fn(
+    &'guard MutatorView,
+    &[TaggedScopedPtr<'guard>],
+) -> Result<TaggedScopedPtr<'guard>, RuntimeError>;
+
+/// Describes a native function: the global name it is bound to, how many arguments it accepts
+/// and its implementation
+pub struct NativeSpec {
+    pub name: &'static str,
+    /// Minimum number of arguments
+    pub min_args: u8,
+    /// Maximum number of arguments, or None if any number beyond the minimum may be given
+    pub max_args: Option<u8>,
+    pub function: NativeFn,
+}
+
+/// A callable heap object through which the language calls a native function
+#[derive(Clone)]
+pub struct NativeFunction {
+    spec: &'static NativeSpec,
+}
+
+impl NativeFunction {
+    /// Allocate a NativeFunction object on the heap
+    pub fn alloc<'guard>(
+        mem: &'guard MutatorView,
+        spec: &'static NativeSpec,
+    ) -> Result<ScopedPtr<'guard, NativeFunction>, RuntimeError> {
+        mem.alloc(NativeFunction { spec })
+    }
+
+    /// Return the name the function is bound to
+    pub fn name(&self) -> &'static str {
+        self.spec.name
+    }
+
+    /// Return the description of the function
+    pub fn spec(&self) -> &'static NativeSpec {
+        self.spec
+    }
+
+    /// Check the number of arguments and call the native function
+    pub fn call<'guard>(
+        &self,
+        mem: &'guard MutatorView,
+        args: &[TaggedScopedPtr<'guard>],
+    ) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+        let count = args.len() as u8;
+        let too_many = self.spec.max_args.is_some_and(|max| count > max);
+
+        if count < self.spec.min_args || too_many {
+            let expected = match self.spec.max_args {
+                Some(max) if max == self.spec.min_args => format!("{}", max),
+                Some(max) => format!("{} to {}", self.spec.min_args, max),
+                None => format!("at least {}", self.spec.min_args),
+            };
+
+            return Err(err_eval(&format!(
+                "Function {} expected {} arguments, got {}",
+                self.spec.name, expected, count
+            )));
+        }
+
+        (self.spec.function)(mem, args)
+    }
+}
+
+impl Print for NativeFunction {
+    fn print<'guard>(
+        &self,
+        _guard: &'guard dyn MutatorScope,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        write!(f, "(NativeFunction {})", self.spec.name)
+    }
+}
+
+/// Return every native function available to the language. The host I/O functions are left out
+/// if the memory is sandboxed.
+pub fn natives(mem: &MutatorView) -> impl Iterator<Item = &'static NativeSpec> {
+    let host_io = !mem.limits().sandboxed;
+
+    IO_NATIVES
+        .iter()
+        .filter(move |_| host_io)
+        .chain(GENERATOR_NATIVES.iter())
+        .chain(INTROSPECTION_NATIVES.iter())
+        .chain(SYMBOL_NATIVES.iter())
+}
+
+/// Return every native function, including the host I/O functions whether or not they are
+/// available
+pub fn all_natives() -> impl Iterator<Item = &'static NativeSpec> {
+    IO_NATIVES
+        .iter()
+        .chain(GENERATOR_NATIVES.iter())
+        .chain(INTROSPECTION_NATIVES.iter())
+        .chain(SYMBOL_NATIVES.iter())
+}
+
+/// Look up a native function available to the language by the name it is bound to
+pub fn find_native(mem: &MutatorView, name: &str) -> Option<&'static NativeSpec> {
+    natives(mem).find(|spec| spec.name == name)
+}
+
+/// Bind every native function to its name in a globals Dict
+pub fn define_natives<'guard>(
+    mem: &'guard MutatorView,
+    globals: ScopedPtr<'guard, Dict>,
+) -> Result<(), RuntimeError> {
+    for spec in natives(mem) {
+        let function = NativeFunction::alloc(mem, spec)?;
+        globals.assoc(mem, mem.lookup_sym(spec.name), function.as_tagged(mem))?;
+    }
+
+    Ok(())
+}
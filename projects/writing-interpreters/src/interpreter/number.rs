@@ -1,5 +1,207 @@
-use super::array::Array;
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+use super::{
+    array::Array,
+    error::err_eval,
+    hashable::Hashable,
+    printer::Print,
+    safeptr::{MutatorScope, TaggedScopedPtr},
+    taggedptr::Value,
+    trace::{Trace, Tracer},
+    MutatorView, RuntimeError,
+};
+
+/// The largest integer that fits in a tagged pointer
+pub const MAX_INTEGER: isize = isize::MAX >> 2;
+/// The smallest integer that fits in a tagged pointer
+pub const MIN_INTEGER: isize = isize::MIN >> 2;
 
 pub struct NumberObject {
     _value: Array<u64>,
 }
+
+impl Trace for NumberObject {
+    fn trace(&self, tracer: &mut Tracer) {
+        self._value.trace(tracer);
+    }
+}
+
+/// A heap-allocated double precision floating point number
+#[derive(Copy, Clone)]
+pub struct Float {
+    value: f64,
+}
+
+impl Float {
+    pub fn new(value: f64) -> Float {
+        Float { value }
+    }
+
+    /// Return the wrapped f64 value
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+}
+
+impl Hashable for Float {
+    /// Hashes the bits of the value, with -0.0 hashing the same as 0.0 since they compare equal
+    fn hash<'guard, H: Hasher>(&self, _guard: &'guard dyn MutatorScope, h: &mut H) {
+        let value = if self.value == 0.0 { 0.0 } else { self.value };
+        value.to_bits().hash(h)
+    }
+}
+
+impl Print for Float {
+    fn print<'guard>(
+        &self,
+        _guard: &'guard dyn MutatorScope,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        // the Debug format of f64 always includes a decimal point or an exponent, which keeps
+        // floats distinguishable from integers when printed
+        write!(f, "{:?}", self.value)
+    }
+}
+
+/// A numeric operand, unpacked from a `Value` for arithmetic
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Numeric {
+    Integer(isize),
+    Float(f64),
+}
+
+/// Binary arithmetic operations supported on `Numeric` operands
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ArithmeticOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl Numeric {
+    /// Unpack a number from a Value, returning an error for non-numeric types
+    pub fn from_value(value: Value) -> Result<Numeric, RuntimeError> {
+        match value {
+            Value::Number(n) => Ok(Numeric::Integer(n)),
+            Value::Float(f) => Ok(Numeric::Float(f.value())),
+            _ => Err(err_eval(&format!("Expected a number, got {}", value))),
+        }
+    }
+
+    /// Return the operand as an f64, converting integers
+    pub fn as_float(self) -> f64 {
+        match self {
+            Numeric::Integer(n) => n as f64,
+            Numeric::Float(f) => f,
+        }
+    }
+
+    /// Convert back to a runtime value. Integers are inline tagged values, floats must be
+    /// allocated on the heap.
+    pub fn as_tagged<'guard>(
+        self,
+        mem: &'guard MutatorView,
+    ) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+        match self {
+            Numeric::Integer(n) => Ok(mem.number(n)),
+            Numeric::Float(f) => mem.float(f),
+        }
+    }
+
+    /// Apply an arithmetic operation. Integer operands produce an integer result, if either
+    /// operand is a float the result is a float. Integer division truncates toward zero.
+    /// A float result that is infinite or NaN is an error, since it could not be read back in.
+    pub fn arithmetic(
+        op: ArithmeticOp,
+        left: Numeric,
+        right: Numeric,
+    ) -> Result<Numeric, RuntimeError> {
+        match (left, right) {
+            (Numeric::Integer(l), Numeric::Integer(r)) => {
+                let result = match op {
+                    ArithmeticOp::Add => l.checked_add(r),
+                    ArithmeticOp::Subtract => l.checked_sub(r),
+                    ArithmeticOp::Multiply => l.checked_mul(r),
+                    ArithmeticOp::Divide => {
+                        if r == 0 {
+                            return Err(err_eval("Division by zero"));
+                        }
+                        l.checked_div(r)
+                    }
+                };
+
+                match result {
+                    Some(n) if (MIN_INTEGER..=MAX_INTEGER).contains(&n) => Ok(Numeric::Integer(n)),
+                    _ => Err(err_eval("Integer overflow")),
+                }
+            }
+
+            _ => {
+                let (l, r) = (left.as_float(), right.as_float());
+                let result = match op {
+                    ArithmeticOp::Add => l + r,
+                    ArithmeticOp::Subtract => l - r,
+                    ArithmeticOp::Multiply => l * r,
+                    ArithmeticOp::Divide => {
+                        if r == 0.0 {
+                            return Err(err_eval("Division by zero"));
+                        }
+                        l / r
+                    }
+                };
+
+                if result.is_finite() {
+                    Ok(Numeric::Float(result))
+                } else {
+                    Err(err_eval("Floating point overflow"))
+                }
+            }
+        }
+    }
+
+    /// Round down to the nearest whole number, keeping the operand type
+    pub fn floor(self) -> Numeric {
+        match self {
+            Numeric::Integer(n) => Numeric::Integer(n),
+            Numeric::Float(f) => Numeric::Float(f.floor()),
+        }
+    }
+
+    /// Round to the nearest whole number, half away from zero, keeping the operand type
+    pub fn round(self) -> Numeric {
+        match self {
+            Numeric::Integer(n) => Numeric::Integer(n),
+            Numeric::Float(f) => Numeric::Float(f.round()),
+        }
+    }
+
+    /// Convert to an integer, truncating toward zero. Fails if the value is not finite or does
+    /// not fit into a tagged integer.
+    pub fn to_integer(self) -> Result<Numeric, RuntimeError> {
+        match self {
+            Numeric::Integer(n) => Ok(Numeric::Integer(n)),
+            Numeric::Float(f) => {
+                // MIN_INTEGER is -2^61 and converts exactly, but MAX_INTEGER rounds up to 2^61
+                // as an f64, so the upper bound must be exclusive
+                let truncated = f.trunc();
+                if truncated.is_finite()
+                    && truncated >= MIN_INTEGER as f64
+                    && truncated < MAX_INTEGER as f64
+                {
+                    Ok(Numeric::Integer(truncated as isize))
+                } else {
+                    Err(err_eval(&format!("Cannot convert {:?} to an integer", f)))
+                }
+            }
+        }
+    }
+
+    /// Convert to a float
+    pub fn to_float(self) -> Numeric {
+        Numeric::Float(self.as_float())
+    }
+}
@@ -6,6 +6,7 @@ use super::{
     error::{err_eval, SourcePos},
     printer::Print,
     safeptr::{MutatorScope, TaggedCellPtr, TaggedScopedPtr},
+    trace::{Trace, Tracer},
     MutatorView, RuntimeError,
 };
 
@@ -67,6 +68,13 @@ impl Pair {
     }
 }
 
+impl Trace for Pair {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.first.trace(tracer);
+        self.second.trace(tracer);
+    }
+}
+
 impl Print for Pair {
     fn print<'guard>(
         &self,
@@ -1,7 +1,7 @@
 use core::panic;
 use std::{iter::Peekable, marker::PhantomData};
 
-use crate::interpreter::error::{err_parser, err_parser_wpos};
+use crate::interpreter::error::err_parser_wpos;
 
 use super::{
     error::SourcePos,
@@ -20,6 +20,22 @@ pub fn parse<'guard>(
     parse_tokens(mem, tokenize(input)?)
 }
 
+/// Parse every s-expression in the given string, as in a source file, into a sequence of ASTs
+pub fn parse_all<'guard>(
+    mem: &'guard MutatorView,
+    input: &str,
+) -> Result<Vec<TaggedScopedPtr<'guard>>, RuntimeError> {
+    let tokens = tokenize(input)?;
+    let mut peekable = tokens.iter().peekable();
+
+    let mut exprs = Vec::new();
+    while peekable.peek().is_some() {
+        exprs.push(parse_sexpr(mem, &mut peekable)?);
+    }
+
+    Ok(exprs)
+}
+
 fn parse_tokens<'guard>(
     mem: &'guard MutatorView,
     tokens: Vec<Token>,
@@ -48,10 +64,10 @@ where
         // '('
         Some(&&Token {
             token: OpenParen,
-            pos: _,
+            pos,
         }) => {
             tokens.next();
-            parse_list(mem, tokens)
+            parse_list(mem, tokens, pos)
         }
         // Symbol
         Some(&&Token {
@@ -74,10 +90,34 @@ where
             tokens.next();
             Ok(mem.number(*number))
         }
+        // Float
+        Some(&&Token {
+            token: Float(ref number),
+            pos: _,
+        }) => {
+            tokens.next();
+            mem.float(*number)
+        }
+        // Text
+        Some(&&Token {
+            token: Text(ref text),
+            pos: _,
+        }) => {
+            tokens.next();
+            mem.text(text)
+        }
         None => {
             tokens.next();
             Ok(mem.nil())
         }
+        // Comments have no value
+        Some(&&Token {
+            token: Comment(_),
+            pos: _,
+        }) => {
+            tokens.next();
+            parse_sexpr(mem, tokens)
+        }
         // '.'
         Some(&&Token { token: Dot, pos }) => Err(err_parser_wpos(pos, "Invalid symbol '.'")),
         // ')'
@@ -100,9 +140,12 @@ where
 // If a list token is:
 //  * a Dot, it must be followed by an s-expression and a CloseParen
 //
+// `open` is the position of the OpenParen, where a list that is never closed is reported
+//
 fn parse_list<'guard, 'i, I: 'i>(
     mem: &'guard MutatorView,
     tokens: &mut Peekable<I>,
+    open: SourcePos,
 ) -> Result<TaggedScopedPtr<'guard>, RuntimeError>
 where
     I: Iterator<Item = &'i Token>,
@@ -137,7 +180,7 @@ where
                 pos,
             }) => {
                 tokens.next();
-                list.push(mem, parse_list(mem, tokens)?, pos)?;
+                list.push(mem, parse_list(mem, tokens, pos)?, pos)?;
             }
             // Symbol
             Some(&&Token {
@@ -153,6 +196,27 @@ where
             }) => {
                 list.push(mem, parse_sexpr(mem, tokens)?, pos)?;
             }
+            // Float
+            Some(&&Token {
+                token: Float(_),
+                pos,
+            }) => {
+                list.push(mem, parse_sexpr(mem, tokens)?, pos)?;
+            }
+            // Text
+            Some(&&Token {
+                token: Text(_),
+                pos,
+            }) => {
+                list.push(mem, parse_sexpr(mem, tokens)?, pos)?;
+            }
+            // Comments have no value
+            Some(&&Token {
+                token: Comment(_),
+                pos: _,
+            }) => {
+                tokens.next();
+            }
             // ')' - End of the current list
             Some(&&Token {
                 token: CloseParen,
@@ -180,11 +244,11 @@ where
                         ));
                     }
 
-                    None => return Err(err_parser("Unexpected end of code stream")),
+                    None => return Err(err_parser_wpos(open, "Unmatched open parenthesis")),
                 }
             }
             None => {
-                return Err(err_parser("Unexpected end of code stream"));
+                return Err(err_parser_wpos(open, "Unmatched open parenthesis"));
             }
         }
     }
@@ -234,6 +298,7 @@ impl<'guard> PairList<'guard> {
             // Initially pushed
             let pair = Pair::new();
             pair.first.set(value);
+            pair.set_first_source_code_pos(pos);
 
             self.head.set(mem.alloc_tagged(pair)?);
             self.tail.copy_from(&self.head);
@@ -375,4 +440,11 @@ mod test {
         let expect = String::from("(+ 1 2)");
         check(&input, &expect);
     }
+
+    #[test]
+    fn parse_float() {
+        let input = String::from("(+ 1.5 -2e3)");
+        let expect = String::from("(+ 1.5 -2000.0)");
+        check(&input, &expect);
+    }
 }
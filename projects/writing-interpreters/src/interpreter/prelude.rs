@@ -0,0 +1,128 @@
+use super::{
+    compiler::compile, parser::parse_all, vm::Thread, MutatorView, RuntimeError, ScopedPtr,
+};
+
+/// Source of the library of list functions, predicates and higher-order helpers that REPL
+/// sessions start with
+pub const PRELUDE: &str = include_str!("prelude.evalrus");
+
+/// Compile and evaluate the prelude, defining its functions as globals of the given Thread
+pub fn load_prelude<'guard>(
+    mem: &'guard MutatorView,
+    thread: ScopedPtr<'guard, Thread>,
+) -> Result<(), RuntimeError> {
+    for value in parse_all(mem, PRELUDE)? {
+        let function = compile(mem, value)?;
+        thread.quick_vm_eval(mem, function)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::interpreter::compiler::compile;
+    use crate::interpreter::memory::Memory;
+    use crate::interpreter::parser::parse;
+    use crate::interpreter::printer::print;
+    use crate::interpreter::Mutator;
+
+    /// Evaluate each expression on a thread with the prelude loaded, checking the printed result
+    fn check_prelude(cases: &'static [(&'static str, &'static str)]) {
+        struct Test {}
+        impl Mutator for Test {
+            type Input = &'static [(&'static str, &'static str)];
+            type Output = ();
+
+            fn run(&self, mem: &MutatorView, cases: Self::Input) -> Result<(), RuntimeError> {
+                let thread = Thread::alloc(mem)?;
+                load_prelude(mem, thread)?;
+
+                for (code, expected) in cases {
+                    let function = compile(mem, parse(mem, code)?)?;
+                    let result = thread.quick_vm_eval(mem, function)?;
+                    assert_eq!(print(*result), *expected, "evaluating {}", code);
+                }
+
+                Ok(())
+            }
+        }
+
+        let mem = Memory::new();
+        mem.mutate(&Test {}, cases).unwrap();
+    }
+
+    #[test]
+    fn prelude_predicates() {
+        check_prelude(&[
+            ("(not nil)", "true"),
+            ("(not 1)", "nil"),
+            ("(pair? (list 1))", "true"),
+            ("(pair? nil)", "nil"),
+            ("(pair? 1)", "nil"),
+            ("(list? nil)", "true"),
+            ("(list? (quote a))", "nil"),
+            ("(zero? 0)", "true"),
+            ("(zero? 1)", "nil"),
+        ]);
+    }
+
+    #[test]
+    fn prelude_list_functions() {
+        check_prelude(&[
+            ("(list)", "nil"),
+            ("(list 1 2 3)", "(1 2 3)"),
+            ("(length nil)", "0"),
+            ("(length (list 1 2 3))", "3"),
+            ("(nth 0 (list 1 2 3))", "1"),
+            ("(nth 2 (list 1 2 3))", "3"),
+            ("(first (list 1 2))", "1"),
+            ("(second (list 1 2))", "2"),
+            ("(rest (list 1 2))", "(2)"),
+            ("(last (list 1 2 3))", "3"),
+            ("(take 2 (list 1 2 3))", "(1 2)"),
+            ("(take 5 (list 1 2 3))", "(1 2 3)"),
+            ("(drop 2 (list 1 2 3))", "(3)"),
+            ("(range 0 4)", "(0 1 2 3)"),
+            ("(range 2 2)", "nil"),
+            ("(append (list 1 2) (list 3 4))", "(1 2 3 4)"),
+            ("(append nil (list 1))", "(1)"),
+            ("(reverse (list 1 2 3))", "(3 2 1)"),
+            ("(reverse nil)", "nil"),
+            ("(member 2 (list 1 2 3))", "(2 3)"),
+            ("(member 4 (list 1 2 3))", "nil"),
+            (
+                "(assoc (quote b) (list (cons (quote a) 1) (cons (quote b) 2)))",
+                "(b . 2)",
+            ),
+            ("(assoc (quote c) (list (cons (quote a) 1)))", "nil"),
+        ]);
+    }
+
+    #[test]
+    fn prelude_higher_order_functions() {
+        check_prelude(&[
+            ("(map (lambda (x) (* x x)) (range 0 4))", "(0 1 4 9)"),
+            ("(map identity nil)", "nil"),
+            ("(filter zero? (list 0 1 0 2))", "(0 0)"),
+            ("(filter pair? (list 1 (list 2) 3))", "((2))"),
+            ("(fold (lambda (acc x) (+ acc x)) 0 (range 0 10))", "45"),
+            (
+                "(fold (lambda (acc x) (cons x acc)) nil (list 1 2 3))",
+                "(3 2 1)",
+            ),
+            (
+                "(fold-right (lambda (x acc) (cons x acc)) nil (list 1 2 3))",
+                "(1 2 3)",
+            ),
+            ("(any? zero? (list 1 0))", "true"),
+            ("(any? zero? (list 1 2))", "nil"),
+            ("(every? zero? (list 0 0))", "true"),
+            ("(every? zero? (list 0 1))", "nil"),
+            ("((compose not zero?) 1)", "true"),
+            ("(flip list 1 2)", "(2 1)"),
+            ("((constantly 7) 1)", "7"),
+        ]);
+    }
+}
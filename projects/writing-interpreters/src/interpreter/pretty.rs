@@ -0,0 +1,568 @@
+use super::{
+    error::{err_parser_wpos, SourcePos},
+    lexer::{is_terminating, tokenize_with_comments, TokenType},
+    printer::print,
+    safeptr::{MutatorScope, TaggedScopedPtr},
+    taggedptr::Value,
+    RuntimeError,
+};
+
+/// The number of list items converted by `pretty_print()` before the rest of a value is elided,
+/// so that a cyclic structure can't be expanded forever
+const PRETTY_ITEM_LIMIT: usize = 100_000;
+
+/// A tree of text to be laid out within a width. Built either from a value or from source code.
+#[derive(Debug, PartialEq)]
+pub enum Doc {
+    /// Text that is never broken across lines: a symbol, number, text literal or the dot of a
+    /// dotted list
+    Atom(String),
+    /// A comment, including its semicolons. It always ends a line. An inline comment followed
+    /// other code on the same line in the source.
+    Comment { text: String, inline: bool },
+    /// A parenthesized list
+    List(Vec<Doc>),
+    /// An empty line that separated items in the source
+    Blank,
+}
+
+impl Doc {
+    /// Return the width of the doc written on a single line, or None if it can't be written on
+    /// one line. Only the first line of a text literal spanning lines counts.
+    fn flat_width(&self) -> Option<usize> {
+        match self {
+            Doc::Atom(text) => Some(text.chars().take_while(|c| *c != '\n').count()),
+            Doc::Comment { .. } | Doc::Blank => None,
+            Doc::List(items) => {
+                let mut width = 2 + items.len().saturating_sub(1);
+                for item in items {
+                    width += item.flat_width()?;
+                }
+                Some(width)
+            }
+        }
+    }
+
+    /// Return true if the doc is code rather than a comment or spacing
+    fn is_code(&self) -> bool {
+        matches!(self, Doc::Atom(_) | Doc::List(_))
+    }
+}
+
+/// How the items of a list that doesn't fit on one line are laid out, chosen by its first item
+enum ListStyle {
+    /// The head and this many following items on the first line, the rest indented by two. Used
+    /// for special forms such as `def` and `let`.
+    Special(usize),
+    /// `cond`: each following test and expression pair on its own line, indented by two
+    Cond,
+    /// A function call: the head and first argument on the first line, the other arguments
+    /// aligned with the first
+    Call,
+    /// Data: every item aligned with the first
+    Data,
+}
+
+impl ListStyle {
+    fn for_items(items: &[Doc]) -> ListStyle {
+        match items.first() {
+            Some(Doc::Atom(head)) => match head.as_str() {
+                "def" => ListStyle::Special(2),
+                "lambda" | "\\" | "let" | "match" | "if" | "while" | "loop" | "defstruct" => {
+                    ListStyle::Special(1)
+                }
+                "begin" | "do" => ListStyle::Special(0),
+                "cond" => ListStyle::Cond,
+                _ => ListStyle::Call,
+            },
+            _ => ListStyle::Data,
+        }
+    }
+}
+
+/// Writes Docs to a String, breaking lists over lines where they don't fit in the width
+struct Layout {
+    out: String,
+    column: usize,
+    width: usize,
+}
+
+impl Layout {
+    fn new(width: usize) -> Layout {
+        Layout {
+            out: String::new(),
+            column: 0,
+            width,
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        self.out.push_str(text);
+        self.column = match text.rfind('\n') {
+            Some(index) => text[index + 1..].chars().count(),
+            None => self.column + text.chars().count(),
+        };
+    }
+
+    /// Start a new line indented to the given column
+    fn newline(&mut self, indent: usize) {
+        self.end_line();
+        self.out.push('\n');
+        self.out.extend(std::iter::repeat_n(' ', indent));
+        self.column = indent;
+    }
+
+    /// Leave an empty line. The next item written still starts with a `newline()`.
+    fn blank_line(&mut self) {
+        self.end_line();
+        self.out.push('\n');
+    }
+
+    fn end_line(&mut self) {
+        let trimmed = self.out.trim_end_matches(' ').len();
+        self.out.truncate(trimmed);
+    }
+
+    /// Return true if the doc fits on the rest of the current line, after `extra` more columns
+    fn fits(&self, doc: &Doc, extra: usize) -> bool {
+        doc.flat_width()
+            .is_some_and(|width| self.column + extra + width <= self.width)
+    }
+
+    fn doc(&mut self, doc: &Doc) {
+        match doc {
+            Doc::Atom(text) => self.text(text),
+            Doc::Comment { text, .. } => self.text(text),
+            Doc::Blank => self.blank_line(),
+            Doc::List(items) => {
+                if self.fits(doc, 0) {
+                    self.flat(doc)
+                } else {
+                    self.broken_list(items)
+                }
+            }
+        }
+    }
+
+    fn flat(&mut self, doc: &Doc) {
+        match doc {
+            Doc::List(items) => {
+                self.text("(");
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        self.text(" ");
+                    }
+                    self.flat(item);
+                }
+                self.text(")");
+            }
+            _ => self.doc(doc),
+        }
+    }
+
+    fn broken_list(&mut self, items: &[Doc]) {
+        let start = self.column;
+        self.text("(");
+
+        // the number of items on the first line and the indent of those on following lines
+        let style = ListStyle::for_items(items);
+        let (first_line, indent) = match style {
+            ListStyle::Special(count) => (1 + count, start + 2),
+            ListStyle::Cond => (1, start + 2),
+            ListStyle::Call => {
+                // align the arguments only if each of them fits after the head
+                let arg_column = self.column + items[0].flat_width().unwrap_or(0) + 1;
+                let args_fit = items[1..].iter().filter(|item| item.is_code()).all(|item| {
+                    item.flat_width()
+                        .is_some_and(|width| arg_column + width < self.width)
+                });
+                if items.len() > 1 && args_fit {
+                    (2, arg_column)
+                } else {
+                    (1, start + 2)
+                }
+            }
+            ListStyle::Data => (1, start + 1),
+        };
+
+        // a comment or blank line ends the first line early
+        let first_line = items
+            .iter()
+            .take(first_line)
+            .take_while(|item| item.is_code())
+            .count();
+
+        for (index, item) in items[..first_line].iter().enumerate() {
+            if index > 0 {
+                self.text(" ");
+            }
+            self.doc(item);
+        }
+
+        let pairs = matches!(style, ListStyle::Cond);
+        let ends_with_comment = self.items(&items[first_line..], indent, pairs);
+
+        if ends_with_comment {
+            self.newline(start);
+        }
+        self.text(")");
+    }
+
+    /// Write each item on a new line at the indent, keeping inline comments on the line before.
+    /// If `pairs` is set, code items are test and expression pairs, and the expression is kept
+    /// on the line of its test if it fits. Returns true if the last item was a comment.
+    fn items(&mut self, items: &[Doc], indent: usize, pairs: bool) -> bool {
+        let mut is_test = true;
+        let mut last_was_comment = false;
+
+        for item in items {
+            match item {
+                Doc::Comment { inline: true, .. } if !self.out.is_empty() => {
+                    self.text(" ");
+                    self.doc(item);
+                }
+                Doc::Blank => self.blank_line(),
+                _ if pairs && item.is_code() && !is_test => {
+                    if self.fits(item, 1) && !last_was_comment {
+                        self.text(" ");
+                    } else {
+                        self.newline(indent + 2);
+                    }
+                    self.doc(item);
+                }
+                _ => {
+                    self.newline(indent);
+                    self.doc(item);
+                }
+            }
+
+            if pairs && item.is_code() {
+                is_test = !is_test;
+            }
+            if !matches!(item, Doc::Blank) {
+                last_was_comment = matches!(item, Doc::Comment { .. });
+            }
+        }
+
+        last_was_comment
+    }
+}
+
+/// Convert a value into a Doc, spending one item of the budget for each list item
+fn value_doc<'guard>(
+    guard: &'guard dyn MutatorScope,
+    value: TaggedScopedPtr<'guard>,
+    budget: &mut usize,
+) -> Doc {
+    let mut pair = match *value {
+        Value::Pair(pair) => pair,
+        _ => return Doc::Atom(print(*value)),
+    };
+
+    let mut items = Vec::new();
+    loop {
+        if *budget == 0 {
+            items.push(Doc::Atom(String::from("...")));
+            break;
+        }
+        *budget -= 1;
+
+        items.push(value_doc(guard, pair.first.get(guard), budget));
+
+        match *pair.second.get(guard) {
+            Value::Pair(next) => pair = next,
+            Value::Nil => break,
+            _ => {
+                items.push(Doc::Atom(String::from(".")));
+                items.push(value_doc(guard, pair.second.get(guard), budget));
+                break;
+            }
+        }
+    }
+
+    Doc::List(items)
+}
+
+/// Render a value like `print()`, but breaking nested lists over lines so that they fit in the
+/// given width where possible
+pub fn pretty_print<'guard>(
+    guard: &'guard dyn MutatorScope,
+    value: TaggedScopedPtr<'guard>,
+    width: usize,
+) -> String {
+    let mut budget = PRETTY_ITEM_LIMIT;
+    let doc = value_doc(guard, value, &mut budget);
+
+    let mut layout = Layout::new(width);
+    layout.doc(&doc);
+    layout.out
+}
+
+/// Source code held as characters, for finding the original text of tokens
+struct Source {
+    chars: Vec<char>,
+    /// The index into `chars` of the start of each line
+    line_starts: Vec<usize>,
+}
+
+impl Source {
+    fn new(source: &str) -> Source {
+        let chars: Vec<char> = source.chars().collect();
+
+        let mut line_starts = vec![0];
+        for (index, c) in chars.iter().enumerate() {
+            if *c == '\n' {
+                line_starts.push(index + 1);
+            }
+        }
+
+        Source { chars, line_starts }
+    }
+
+    fn offset(&self, pos: SourcePos) -> usize {
+        self.line_starts[pos.line as usize - 1] + pos.column as usize
+    }
+
+    /// Return the text of the symbol or number token at the position
+    fn atom(&self, pos: SourcePos) -> String {
+        self.chars[self.offset(pos)..]
+            .iter()
+            .take_while(|c| !is_terminating(**c))
+            .collect()
+    }
+
+    /// Return the text of the text literal at the position, including its quotes and escapes
+    fn text_literal(&self, pos: SourcePos) -> String {
+        let start = self.offset(pos);
+
+        let mut end = start + 1;
+        while end < self.chars.len() && self.chars[end] != '"' {
+            if self.chars[end] == '\\' {
+                end += 1;
+            }
+            end += 1;
+        }
+
+        self.chars[start..(end + 1).min(self.chars.len())]
+            .iter()
+            .collect()
+    }
+}
+
+/// Read source code into Docs, one for each top level expression or comment
+pub fn source_docs(source: &str) -> Result<Vec<Doc>, RuntimeError> {
+    let tokens = tokenize_with_comments(source)?;
+    let text = Source::new(source);
+
+    // the items of the lists being read, the outermost being the top level
+    let mut lists: Vec<Vec<Doc>> = vec![Vec::new()];
+    // where each list below the top level was opened
+    let mut opened = Vec::new();
+    // the line the previous token ended on
+    let mut last_line = 0;
+
+    for token in &tokens {
+        let line = token.pos.line;
+        let items = lists.last_mut().expect("No list being read");
+
+        if last_line > 0 && line > last_line + 1 && items.last().is_some_and(|d| d != &Doc::Blank) {
+            items.push(Doc::Blank);
+        }
+
+        let doc = match token.token {
+            TokenType::OpenParen => {
+                lists.push(Vec::new());
+                opened.push(token.pos);
+                last_line = line;
+                continue;
+            }
+            TokenType::CloseParen => {
+                if lists.len() == 1 {
+                    return Err(err_parser_wpos(token.pos, "Unmatched close parenthesis"));
+                }
+
+                opened.pop();
+                let mut items = lists.pop().expect("No list being read");
+                if items.last() == Some(&Doc::Blank) {
+                    items.pop();
+                }
+                Doc::List(items)
+            }
+            TokenType::Dot => Doc::Atom(String::from(".")),
+            TokenType::Symbol(_) | TokenType::Number(_) | TokenType::Float(_) => {
+                Doc::Atom(text.atom(token.pos))
+            }
+            TokenType::Text(_) => {
+                let literal = text.text_literal(token.pos);
+                last_line = line + literal.matches('\n').count() as u32;
+                lists
+                    .last_mut()
+                    .expect("No list being read")
+                    .push(Doc::Atom(literal));
+                continue;
+            }
+            TokenType::Comment(ref comment) => Doc::Comment {
+                text: comment.clone(),
+                inline: line == last_line,
+            },
+        };
+
+        lists.last_mut().expect("No list being read").push(doc);
+        last_line = line;
+    }
+
+    if let Some(pos) = opened.pop() {
+        return Err(err_parser_wpos(pos, "Unmatched open parenthesis"));
+    }
+
+    let mut docs = lists.pop().unwrap_or_default();
+    if docs.last() == Some(&Doc::Blank) {
+        docs.pop();
+    }
+    Ok(docs)
+}
+
+/// Reformat source code to fit in the given width, keeping comments and the blank lines between
+/// expressions
+pub fn format_source(source: &str, width: usize) -> Result<String, RuntimeError> {
+    let docs = source_docs(source)?;
+
+    let mut layout = Layout::new(width);
+    for (index, doc) in docs.iter().enumerate() {
+        match doc {
+            Doc::Comment { inline: true, .. } if index > 0 => {
+                layout.text(" ");
+                layout.doc(doc);
+            }
+            Doc::Blank => layout.blank_line(),
+            _ => {
+                if index > 0 {
+                    layout.newline(0);
+                }
+                layout.doc(doc);
+            }
+        }
+    }
+
+    let mut formatted = layout.out;
+    if !formatted.is_empty() {
+        formatted.push('\n');
+    }
+    Ok(formatted)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::interpreter::memory::Memory;
+    use crate::interpreter::parser::parse;
+    use crate::interpreter::{Mutator, MutatorView};
+
+    fn test_helper(test_fn: fn(&MutatorView) -> Result<(), RuntimeError>) {
+        let mem = Memory::new();
+
+        struct Test {}
+        impl Mutator for Test {
+            type Input = fn(&MutatorView) -> Result<(), RuntimeError>;
+            type Output = ();
+
+            fn run(
+                &self,
+                mem: &MutatorView,
+                test_fn: Self::Input,
+            ) -> Result<Self::Output, RuntimeError> {
+                test_fn(mem)
+            }
+        }
+
+        let test = Test {};
+        mem.mutate(&test, test_fn).unwrap();
+    }
+
+    #[test]
+    fn pretty_print_values() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            // values that fit are printed as print() would
+            let value = parse(mem, "(a (b . c) \"text\" 1.5)")?;
+            assert_eq!(pretty_print(mem, value, 80), print(*value));
+
+            // call arguments are aligned with the first if they all fit there
+            let value = parse(mem, "(alpha (beta gamma delta) (epsilon zeta) eta)")?;
+            assert_eq!(
+                pretty_print(mem, value, 30),
+                "(alpha (beta gamma delta)\n       (epsilon zeta)\n       eta)"
+            );
+            assert_eq!(
+                pretty_print(mem, value, 20),
+                "(alpha\n  (beta gamma delta)\n  (epsilon zeta)\n  eta)"
+            );
+
+            let value = parse(mem, "((one two) (three four) (five six))")?;
+            assert_eq!(
+                pretty_print(mem, value, 16),
+                "((one two)\n (three four)\n (five six))"
+            );
+
+            for line in pretty_print(mem, value, 16).lines() {
+                assert!(line.len() <= 16);
+            }
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn format_special_forms() {
+        let source =
+            "(def f (x y) (let ((sum (+ x y)) (diff (- x y))) (cond (is? sum 0) diff true sum)))";
+        let expected = "\
+(def f (x y)
+  (let ((sum (+ x y))
+        (diff (- x y)))
+    (cond
+      (is? sum 0) diff
+      true sum)))
+";
+        assert_eq!(format_source(source, 30).unwrap(), expected);
+
+        // formatting is idempotent
+        assert_eq!(format_source(expected, 30).unwrap(), expected);
+    }
+
+    #[test]
+    fn format_keeps_comments() {
+        let source = "\
+;; helpers
+
+(def twice (f x) ; apply f two times
+    ; inner first
+    (f (f x)))
+
+
+(set (quote y)   \"a \\\"quoted\\\"\nline\") ; done
+";
+        let expected = "\
+;; helpers
+
+(def twice (f x) ; apply f two times
+  ; inner first
+  (f (f x)))
+
+(set (quote y) \"a \\\"quoted\\\"\nline\") ; done
+";
+        assert_eq!(format_source(source, 80).unwrap(), expected);
+        assert_eq!(format_source(expected, 80).unwrap(), expected);
+
+        // a comment before a close paren pushes it onto the next line
+        assert_eq!(
+            format_source("(f 1 ; one\n)", 80).unwrap(),
+            "(f 1 ; one\n)\n"
+        );
+
+        let error = format_source("(f 1\n  (g", 80).unwrap_err();
+        assert_eq!(error.error_pos(), Some(SourcePos { line: 2, column: 2 }));
+        assert!(format_source("f)", 80).is_err());
+    }
+}
@@ -31,6 +31,15 @@ pub fn print(value: Value) -> String {
     format!("{}", value)
 }
 
+/// Render a value for output to a user: like `print()` except that Text is written as-is rather
+/// than as a quoted literal
+pub fn display(value: Value) -> String {
+    match value {
+        Value::Text(text) => text.as_string(&value),
+        _ => print(value),
+    }
+}
+
 pub fn debug(value: Value) -> String {
     format!("{:?}", value)
 }
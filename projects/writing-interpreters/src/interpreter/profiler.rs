@@ -0,0 +1,253 @@
+use std::{collections::HashMap, fmt::Write, fs, path::Path};
+
+use super::{bytecode::Opcode, RuntimeError};
+
+/// Execution counts attributed to a single function, by name
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FunctionProfile {
+    /// Number of times the function was entered
+    pub calls: u64,
+    /// Instructions executed in the function's own code
+    pub exclusive: u64,
+    /// Instructions executed while the function was on the call stack, including its callees.
+    /// Recursive calls are only counted once.
+    pub inclusive: u64,
+}
+
+/// A call stack entry shadowing a VM CallFrame
+struct ProfileFrame {
+    name: String,
+    /// The names of all functions on the stack up to this one, separated by semicolons
+    path: String,
+    /// Total instruction count when the frame was entered
+    entered_at: u64,
+}
+
+/// Instruction-level profiler. The VM reports every instruction it executes and every function
+/// call and return; the profiler attributes instruction counts to opcodes, functions and call
+/// stacks.
+#[derive(Default)]
+pub struct Profiler {
+    instructions: u64,
+    opcodes: HashMap<&'static str, u64>,
+    functions: HashMap<String, FunctionProfile>,
+    stack: Vec<ProfileFrame>,
+    /// Number of frames on the stack for each function name, so that recursion doesn't count
+    /// instructions toward the inclusive total more than once
+    active: HashMap<String, usize>,
+    /// Exclusive instruction counts by call stack path
+    folded: HashMap<String, u64>,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    /// Record an instruction about to be executed in the current function
+    pub fn instruction(&mut self, opcode: &Opcode) {
+        self.instructions += 1;
+        *self.opcodes.entry(opcode.name()).or_insert(0) += 1;
+
+        if let Some(frame) = self.stack.last() {
+            if let Some(function) = self.functions.get_mut(&frame.name) {
+                function.exclusive += 1;
+            }
+
+            match self.folded.get_mut(&frame.path) {
+                Some(count) => *count += 1,
+                None => {
+                    self.folded.insert(frame.path.clone(), 1);
+                }
+            }
+        }
+    }
+
+    /// Record entry into a function
+    pub fn enter(&mut self, name: &str) {
+        self.functions.entry(String::from(name)).or_default().calls += 1;
+        *self.active.entry(String::from(name)).or_insert(0) += 1;
+
+        let path = match self.stack.last() {
+            Some(caller) => format!("{};{}", caller.path, name),
+            None => String::from(name),
+        };
+
+        self.stack.push(ProfileFrame {
+            name: String::from(name),
+            path,
+            entered_at: self.instructions,
+        });
+    }
+
+    /// Record a return from the current function
+    pub fn leave(&mut self) {
+        if let Some(frame) = self.stack.pop() {
+            if let Some(active) = self.active.get_mut(&frame.name) {
+                *active -= 1;
+
+                // only the outermost activation of a function counts toward the inclusive total
+                if *active == 0 {
+                    if let Some(function) = self.functions.get_mut(&frame.name) {
+                        function.inclusive += self.instructions - frame.entered_at;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Return from every function on the stack, as when evaluation is aborted by an error
+    pub fn unwind(&mut self) {
+        while !self.stack.is_empty() {
+            self.leave();
+        }
+    }
+
+    /// Return the total number of instructions executed
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    /// Return the number of times an opcode, given by name, was executed
+    pub fn opcode_count(&self, name: &str) -> u64 {
+        self.opcodes.get(name).copied().unwrap_or(0)
+    }
+
+    /// Return the execution counts for a function, given by name
+    pub fn function(&self, name: &str) -> Option<&FunctionProfile> {
+        self.functions.get(name)
+    }
+
+    /// Render a text table of opcode and function counts, most expensive first
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+
+        let mut opcodes: Vec<_> = self.opcodes.iter().collect();
+        opcodes.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+        let mut functions: Vec<_> = self.functions.iter().collect();
+        functions.sort_by(|a, b| {
+            b.1.inclusive
+                .cmp(&a.1.inclusive)
+                .then(b.1.exclusive.cmp(&a.1.exclusive))
+                .then(a.0.cmp(b.0))
+        });
+
+        // writing to a String cannot fail
+        writeln!(report, "Instructions executed: {}", self.instructions).unwrap();
+        writeln!(report).unwrap();
+
+        writeln!(report, "{:<16} {:>12} {:>7}", "Opcode", "Count", "%").unwrap();
+        for (name, count) in opcodes {
+            let percent = 100.0 * *count as f64 / self.instructions as f64;
+            writeln!(report, "{:<16} {:>12} {:>6.1}%", name, count, percent).unwrap();
+        }
+        writeln!(report).unwrap();
+
+        writeln!(
+            report,
+            "{:<24} {:>8} {:>12} {:>12}",
+            "Function", "Calls", "Inclusive", "Exclusive"
+        )
+        .unwrap();
+        for (name, function) in functions {
+            writeln!(
+                report,
+                "{:<24} {:>8} {:>12} {:>12}",
+                name, function.calls, function.inclusive, function.exclusive
+            )
+            .unwrap();
+        }
+
+        report
+    }
+
+    /// Render exclusive instruction counts per call stack in the folded format read by
+    /// flamegraph tools: one `outer;inner count` line per distinct stack
+    pub fn folded_stacks(&self) -> String {
+        let mut stacks: Vec<_> = self.folded.iter().collect();
+        stacks.sort();
+
+        let mut folded = String::new();
+        for (path, count) in stacks {
+            writeln!(folded, "{} {}", path, count).unwrap();
+        }
+        folded
+    }
+
+    /// Write the folded call stacks to a file
+    pub fn write_folded(&self, path: &Path) -> Result<(), RuntimeError> {
+        fs::write(path, self.folded_stacks())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn execute(profiler: &mut Profiler, count: usize) {
+        for _ in 0..count {
+            profiler.instruction(&Opcode::Jump { offset: 0 });
+        }
+        profiler.instruction(&Opcode::Return { reg: 0 });
+    }
+
+    #[test]
+    fn inclusive_and_exclusive_counts() {
+        let mut profiler = Profiler::new();
+
+        // main calls f, which recurses once and then calls g
+        profiler.enter("main");
+        execute(&mut profiler, 1);
+        profiler.enter("f");
+        execute(&mut profiler, 2);
+        profiler.enter("f");
+        execute(&mut profiler, 3);
+        profiler.enter("g");
+        execute(&mut profiler, 4);
+        profiler.leave();
+        profiler.leave();
+        profiler.leave();
+        profiler.leave();
+
+        assert_eq!(profiler.instructions(), 14);
+        assert_eq!(profiler.opcode_count("Jump"), 10);
+        assert_eq!(profiler.opcode_count("Return"), 4);
+        assert_eq!(profiler.opcode_count("Call"), 0);
+
+        let f = profiler.function("f").unwrap();
+        assert_eq!(f.calls, 2);
+        assert_eq!(f.exclusive, 7);
+        // the recursive call does not count g's instructions twice
+        assert_eq!(f.inclusive, 12);
+
+        let main = profiler.function("main").unwrap();
+        assert_eq!((main.calls, main.inclusive, main.exclusive), (1, 14, 2));
+
+        assert_eq!(
+            profiler.folded_stacks(),
+            "main 2\nmain;f 3\nmain;f;f 4\nmain;f;f;g 5\n"
+        );
+    }
+
+    #[test]
+    fn unwind_closes_all_frames() {
+        let mut profiler = Profiler::new();
+
+        profiler.enter("main");
+        profiler.enter("f");
+        execute(&mut profiler, 2);
+        profiler.unwind();
+
+        // a fresh evaluation starts from an empty stack
+        profiler.enter("main");
+        execute(&mut profiler, 0);
+        profiler.leave();
+
+        assert_eq!(profiler.function("f").unwrap().inclusive, 3);
+        assert_eq!(profiler.function("main").unwrap().calls, 2);
+        assert_eq!(profiler.function("main").unwrap().inclusive, 4);
+        assert_eq!(profiler.folded_stacks(), "main 1\nmain;f 3\n");
+    }
+}
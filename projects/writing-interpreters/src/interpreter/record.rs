@@ -0,0 +1,328 @@
+use std::fmt;
+
+use crate::memory::ArraySize;
+
+use super::{
+    containers::{AnyContainerFromSlice, Container, IndexedAnyContainer},
+    error::err_eval,
+    list::List,
+    printer::Print,
+    safeptr::{CellPtr, MutatorScope, TaggedScopedPtr},
+    taggedptr::Value,
+    trace::{Trace, Tracer},
+    MutatorView, RuntimeError, ScopedPtr,
+};
+
+/// An instance of a record type defined with `defstruct`.
+///
+/// A record type is described by a List of the type name followed by the field names. Every
+/// instance of the type refers to the same descriptor List, so two records are of the same type
+/// if their descriptors are identical.
+#[derive(Clone)]
+pub struct Record {
+    descriptor: CellPtr<List>,
+    values: CellPtr<List>,
+}
+
+impl Record {
+    /// Allocate a record of the type described by `descriptor` with the given field values, in
+    /// field order
+    pub fn alloc<'guard>(
+        mem: &'guard MutatorView,
+        descriptor: ScopedPtr<'guard, List>,
+        values: &[TaggedScopedPtr<'guard>],
+    ) -> Result<ScopedPtr<'guard, Record>, RuntimeError> {
+        if values.len() as ArraySize + 1 != descriptor.length() {
+            return Err(err_eval("Record value count does not match its type"));
+        }
+
+        Record::restore(mem, descriptor, List::from_slice(mem, values)?)
+    }
+
+    /// Allocate a record from the parts of one saved in a heap image. The lists may not be filled
+    /// in yet.
+    pub fn restore<'guard>(
+        mem: &'guard MutatorView,
+        descriptor: ScopedPtr<'guard, List>,
+        values: ScopedPtr<'guard, List>,
+    ) -> Result<ScopedPtr<'guard, Record>, RuntimeError> {
+        mem.alloc(Record {
+            descriptor: CellPtr::new_with(descriptor),
+            values: CellPtr::new_with(values),
+        })
+    }
+
+    /// Return the List describing the record type
+    pub fn descriptor<'guard>(&self, guard: &'guard dyn MutatorScope) -> ScopedPtr<'guard, List> {
+        self.descriptor.get(guard)
+    }
+
+    /// Return the List of field values
+    pub fn values<'guard>(&self, guard: &'guard dyn MutatorScope) -> ScopedPtr<'guard, List> {
+        self.values.get(guard)
+    }
+
+    /// Return the name the record type was defined with
+    pub fn type_name<'guard>(
+        &self,
+        guard: &'guard dyn MutatorScope,
+    ) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+        IndexedAnyContainer::get(&*self.descriptor.get(guard), guard, 0)
+    }
+
+    /// Return true if this record is of the type described by `descriptor`
+    pub fn is_instance<'guard>(
+        &self,
+        guard: &'guard dyn MutatorScope,
+        descriptor: TaggedScopedPtr<'guard>,
+    ) -> bool {
+        self.descriptor.get(guard).as_tagged(guard) == descriptor
+    }
+
+    /// Return the value of the field at `index`
+    pub fn get<'guard>(
+        &self,
+        guard: &'guard dyn MutatorScope,
+        index: ArraySize,
+    ) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+        IndexedAnyContainer::get(&*self.values.get(guard), guard, index)
+    }
+
+    /// Return the index of the field with the given name, if the record type has one
+    pub fn field_index(&self, guard: &dyn MutatorScope, name: &str) -> Option<ArraySize> {
+        let descriptor = self.descriptor.get(guard);
+        (1..descriptor.length()).find_map(|index| {
+            match *IndexedAnyContainer::get(&*descriptor, guard, index).ok()? {
+                Value::Symbol(field) if field.as_str(guard) == name => Some(index - 1),
+                _ => None,
+            }
+        })
+    }
+
+    /// Return the value of the field with the given name
+    pub fn get_field<'guard>(
+        &self,
+        guard: &'guard dyn MutatorScope,
+        name: &str,
+    ) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+        match self.field_index(guard, name) {
+            Some(index) => self.get(guard, index),
+            None => Err(err_eval(&format!(
+                "A {} record has no field {}",
+                self.type_name(guard)?,
+                name
+            ))),
+        }
+    }
+
+    /// Set the value of the field at `index`
+    pub fn set<'guard>(
+        &self,
+        guard: &'guard dyn MutatorScope,
+        index: ArraySize,
+        value: TaggedScopedPtr<'guard>,
+    ) -> Result<(), RuntimeError> {
+        IndexedAnyContainer::set(&*self.values.get(guard), guard, index, value)
+    }
+}
+
+impl Trace for Record {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.descriptor.trace(tracer);
+        self.values.trace(tracer);
+    }
+}
+
+impl Print for Record {
+    /// Prints the type name followed by each field name and value, e.g. `#<point x=1 y=2>`
+    fn print<'guard>(
+        &self,
+        guard: &'guard dyn MutatorScope,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        let descriptor = self.descriptor.get(guard);
+        let values = self.values.get(guard);
+
+        match self.type_name(guard) {
+            Ok(name) => write!(f, "#<{}", name)?,
+            Err(_) => write!(f, "#<record")?,
+        }
+
+        for index in 0..values.length() {
+            if let (Ok(field), Ok(value)) = (
+                IndexedAnyContainer::get(&*descriptor, guard, index + 1),
+                IndexedAnyContainer::get(&*values, guard, index),
+            ) {
+                write!(f, " {}={}", field, value)?;
+            }
+        }
+
+        write!(f, ">")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::interpreter::compiler::compile;
+    use crate::interpreter::memory::Memory;
+    use crate::interpreter::parser::parse;
+    use crate::interpreter::printer::print;
+    use crate::interpreter::vm::Thread;
+    use crate::interpreter::Mutator;
+
+    fn eval_helper<'guard>(
+        mem: &'guard MutatorView,
+        thread: ScopedPtr<'guard, Thread>,
+        code: &str,
+    ) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+        let function = compile(mem, parse(mem, code)?)?;
+        thread.quick_vm_eval(mem, function)
+    }
+
+    fn test_helper(test_fn: fn(&MutatorView) -> Result<(), RuntimeError>) {
+        let mem = Memory::new();
+
+        struct Test {}
+        impl Mutator for Test {
+            type Input = fn(&MutatorView) -> Result<(), RuntimeError>;
+            type Output = ();
+
+            fn run(
+                &self,
+                mem: &MutatorView,
+                test_fn: Self::Input,
+            ) -> Result<Self::Output, RuntimeError> {
+                test_fn(mem)
+            }
+        }
+
+        let test = Test {};
+        mem.mutate(&test, test_fn).unwrap();
+    }
+
+    fn point_descriptor<'guard>(
+        mem: &'guard MutatorView,
+    ) -> Result<ScopedPtr<'guard, List>, RuntimeError> {
+        let names = [
+            mem.lookup_sym("point"),
+            mem.lookup_sym("x"),
+            mem.lookup_sym("y"),
+        ];
+        List::from_slice(mem, &names)
+    }
+
+    #[test]
+    fn field_access_by_name() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let descriptor = point_descriptor(mem)?;
+            let point = Record::alloc(mem, descriptor, &[mem.number(1), mem.number(2)])?;
+
+            assert_eq!(point.field_index(mem, "x"), Some(0));
+            assert_eq!(point.field_index(mem, "y"), Some(1));
+            // the type name is not a field
+            assert_eq!(point.field_index(mem, "point"), None);
+
+            assert!(point.get_field(mem, "y")? == mem.number(2));
+            point.set(mem, 1, mem.number(5))?;
+            assert!(point.get_field(mem, "y")? == mem.number(5));
+
+            let error = point.get_field(mem, "z").unwrap_err();
+            assert_eq!(
+                format!("{}", error),
+                "Evaluation error: A point record has no field z"
+            );
+
+            assert!(point.is_instance(mem, descriptor.as_tagged(mem)));
+            let other = point_descriptor(mem)?;
+            assert!(!point.is_instance(mem, other.as_tagged(mem)));
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn constructor_arity() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let descriptor = point_descriptor(mem)?;
+            assert!(Record::alloc(mem, descriptor, &[mem.number(1)]).is_err());
+            assert!(Record::alloc(mem, descriptor, &[mem.number(1); 3]).is_err());
+
+            let t = Thread::alloc(mem)?;
+            eval_helper(mem, t, "(defstruct point x y)")?;
+            assert!(eval_helper(mem, t, "(make-point 1 2)").is_ok());
+            let error = eval_helper(mem, t, "(make-point 1 2 3)").unwrap_err();
+            let message = format!("{}", error);
+            assert!(
+                message.ends_with("expected 2 arguments, got 3"),
+                "{}",
+                message
+            );
+
+            // too few arguments is a partial application, like any other function
+            eval_helper(mem, t, "(set (quote make-x1) (make-point 1))")?;
+            let point = eval_helper(mem, t, "(make-x1 2)")?;
+            assert_eq!(print(*point), "#<point x=1 y=2>");
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn accessor_type_errors() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+            eval_helper(mem, t, "(defstruct point x y)")?;
+            eval_helper(mem, t, "(defstruct size x y)")?;
+
+            // a record of another type with the same fields is still the wrong type
+            let error = eval_helper(mem, t, "(point-x (make-size 1 2))").unwrap_err();
+            assert_eq!(
+                format!("{}", error),
+                "Evaluation error: Expected a point record, got #<size x=1 y=2>"
+            );
+
+            let error = eval_helper(mem, t, "(set-point-y! 1 2)").unwrap_err();
+            assert_eq!(
+                format!("{}", error),
+                "Evaluation error: Expected a point record, got 1"
+            );
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn printing() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let descriptor = point_descriptor(mem)?;
+            let values = [mem.text("a")?, mem.lookup_sym("nil")];
+            let point = Record::alloc(mem, descriptor, &values)?;
+            assert_eq!(print(*point.as_tagged(mem)), "#<point x=\"a\" y=nil>");
+
+            let empty = List::from_slice(mem, &[mem.lookup_sym("empty")])?;
+            let record = Record::alloc(mem, empty, &[])?;
+            assert_eq!(print(*record.as_tagged(mem)), "#<empty>");
+
+            // records nest
+            let t = Thread::alloc(mem)?;
+            eval_helper(mem, t, "(defstruct line from to)")?;
+            eval_helper(mem, t, "(defstruct point x y)")?;
+            let line = eval_helper(mem, t, "(make-line (make-point 0 0) (make-point 1 2))")?;
+            assert_eq!(
+                print(*line),
+                "#<line from=#<point x=0 y=0> to=#<point x=1 y=2>>"
+            );
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+}
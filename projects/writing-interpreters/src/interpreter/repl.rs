@@ -1,38 +1,155 @@
+use std::path::Path;
+
 use super::{
-    compiler::compile, error::ErrorKind, parser::parse, safeptr::TaggedScopedPtr, vm::Thread,
-    CellPtr, Mutator, MutatorView, RuntimeError,
+    compiler::compile,
+    containers::{HashIndexedAnyContainer, SliceableContainer},
+    error::{err_eval, err_image, ErrorKind},
+    function::describe,
+    image::Image,
+    parser::{parse, parse_all},
+    prelude::load_prelude,
+    pretty::pretty_print,
+    profiler::Profiler,
+    safeptr::TaggedScopedPtr,
+    taggedptr::Value,
+    trace::{Trace, Tracer},
+    vm::Thread,
+    CellPtr, Mutator, MutatorView, RuntimeError, ScopedPtr,
 };
 
 /// A mutator that returns a Repl instance
-pub struct RepMaker {}
+pub struct RepMaker {
+    /// Whether to define the prelude functions in the new session
+    pub prelude: bool,
+}
 
 impl Mutator for RepMaker {
     type Input = ();
     type Output = ReadEvalPrint;
 
     fn run(&self, mem: &MutatorView, _input: ()) -> Result<ReadEvalPrint, RuntimeError> {
-        ReadEvalPrint::alloc(mem)
+        ReadEvalPrint::alloc(mem, self.prelude)
+    }
+}
+
+/// A mutator that returns a Repl instance whose globals are restored from a heap image
+pub struct ImageLoader<'a> {
+    image: &'a Image,
+}
+
+impl<'a> ImageLoader<'a> {
+    pub fn new(image: &'a Image) -> ImageLoader<'a> {
+        ImageLoader { image }
     }
 }
 
+impl<'a> Mutator for ImageLoader<'a> {
+    type Input = ();
+    type Output = ReadEvalPrint;
+
+    fn run(&self, mem: &MutatorView, _input: ()) -> Result<ReadEvalPrint, RuntimeError> {
+        Ok(ReadEvalPrint {
+            main_thread: CellPtr::new_with(restore_thread(mem, self.image)?),
+        })
+    }
+}
+
+/// The file the REPL `:profile` command writes folded call stacks to
+const PROFILE_FOLDED_FILE: &str = "evalrus-profile.folded";
+
+/// Results longer than this are broken over lines to fit
+const RESULT_WIDTH: usize = 80;
+
 /// Mutator that implements the VM
 pub struct ReadEvalPrint {
     main_thread: CellPtr<Thread>,
 }
 
 impl ReadEvalPrint {
-    pub fn alloc(mem: &MutatorView) -> Result<ReadEvalPrint, RuntimeError> {
+    /// Allocate a session, with the prelude functions defined as globals unless skipped
+    pub fn alloc(mem: &MutatorView, prelude: bool) -> Result<ReadEvalPrint, RuntimeError> {
+        let thread = Thread::alloc(mem)?;
+
+        if prelude {
+            load_prelude(mem, thread)?;
+        }
+
         Ok(ReadEvalPrint {
-            main_thread: CellPtr::new_with(Thread::alloc(mem)?),
+            main_thread: CellPtr::new_with(thread),
         })
     }
 }
 
+/// Allocate a Thread whose globals are restored from a heap image
+fn restore_thread<'guard>(
+    mem: &'guard MutatorView,
+    image: &Image,
+) -> Result<ScopedPtr<'guard, Thread>, RuntimeError> {
+    match *image.restore(mem)? {
+        Value::Dict(globals) => Thread::alloc_with_globals(mem, globals),
+        _ => Err(err_image("Image does not contain a globals Dict")),
+    }
+}
+
+/// The main thread is the root of everything the REPL session allocates
+impl Trace for ReadEvalPrint {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.main_thread.trace(tracer);
+    }
+}
+
 impl Mutator for ReadEvalPrint {
     type Input = String;
     type Output = ();
 
     fn run(&self, mem: &MutatorView, line: String) -> Result<(), RuntimeError> {
+        // ":save <file>" writes the session's globals and everything they refer to to an image
+        if let Some(path) = line.strip_prefix(":save ") {
+            let path = Path::new(path.trim());
+            let globals = self.main_thread.get(mem).globals(mem);
+
+            match Image::capture(mem, globals.as_tagged(mem)).and_then(|image| image.save(path)) {
+                Ok(()) => println!("Saved image to {}", path.display()),
+                Err(e) => println!("error: {}", e),
+            }
+
+            return Ok(());
+        }
+
+        // ":load <file>" replaces the session's globals with those in an image
+        if let Some(path) = line.strip_prefix(":load ") {
+            let path = Path::new(path.trim());
+
+            match Image::load(path).and_then(|image| restore_thread(mem, &image)) {
+                Ok(thread) => {
+                    self.main_thread.set(thread);
+                    println!("Loaded image from {}", path.display());
+                }
+                Err(e) => println!("error: {}", e),
+            }
+
+            return Ok(());
+        }
+
+        // ":doc <name>" describes the function bound to a global name
+        if let Some(name) = line.strip_prefix(":doc ") {
+            let name = mem.lookup_sym(name.trim());
+            let globals = self.main_thread.get(mem).globals(mem);
+
+            match globals
+                .lookup(mem, name)
+                .and_then(|value| describe(mem, value))
+            {
+                Ok(text) => println!("{}", text),
+                Err(e) if *e.error_kind() == ErrorKind::KeyError => {
+                    println!("error: {} is not defined", name)
+                }
+                Err(e) => println!("error: {}", e),
+            }
+
+            return Ok(());
+        }
+
         let thread = self.main_thread.get(mem);
 
         // If the first 2 chars of the line are ":d", then the user has requested a debug
@@ -43,6 +160,13 @@ impl Mutator for ReadEvalPrint {
             (line.as_str(), false)
         };
 
+        // ":profile" evaluates the rest of the line with instruction counting
+        let (line, mut profiler) = if let Some(rest) = line.strip_prefix(":profile ") {
+            (rest, Some(Profiler::new()))
+        } else {
+            (line, None)
+        };
+
         match (|mem, line| -> Result<TaggedScopedPtr, RuntimeError> {
             let value = parse(mem, line)?;
 
@@ -59,23 +183,42 @@ impl Mutator for ReadEvalPrint {
                 println!("## Compiled:\n```\n{:?}\n```", function);
             }
 
-            let value = thread.quick_vm_eval(mem, function)?;
+            let value = match profiler {
+                Some(ref mut profiler) => thread.profiled_vm_eval(mem, function, profiler)?,
+                None => thread.quick_vm_eval(mem, function)?,
+            };
 
             if debug {
                 println!("## Evaluated:\n```\n{:?}\n```\n", value);
             }
 
             Ok(value)
-        })(mem, &line)
+        })(mem, line)
         {
-            Ok(value) => println!("{}", value),
+            Ok(value) => {
+                println!("{}", pretty_print(mem, value, RESULT_WIDTH));
+
+                if let Some(ref profiler) = profiler {
+                    print!("{}", profiler.report());
+                    profiler.write_folded(Path::new(PROFILE_FOLDED_FILE))?;
+                    println!("Folded call stacks written to {}", PROFILE_FOLDED_FILE);
+                }
+            }
 
             Err(e) => {
                 match e.error_kind() {
                     // non-fatal repl errors
-                    ErrorKind::LexerError(_) => e.print_with_source(&line),
-                    ErrorKind::ParseError(_) => e.print_with_source(&line),
-                    ErrorKind::EvalError(_) => e.print_with_source(&line),
+                    ErrorKind::LexerError(_) => e.print_with_traceback(line),
+                    ErrorKind::ParseError(_) => e.print_with_traceback(line),
+                    ErrorKind::EvalError(_) => e.print_with_traceback(line),
+                    ErrorKind::IOError(_) => e.print_with_traceback(line),
+                    ErrorKind::FileNotFound(_) => e.print_with_traceback(line),
+                    ErrorKind::PermissionDenied(_) => e.print_with_traceback(line),
+                    ErrorKind::InstructionLimit => e.print_with_traceback(line),
+                    ErrorKind::CallDepthLimit => e.print_with_traceback(line),
+                    ErrorKind::HeapLimit => e.print_with_traceback(line),
+                    ErrorKind::Timeout => e.print_with_traceback(line),
+                    ErrorKind::AssertionFailed(_) => e.print_with_traceback(line),
                     _ => return Err(e),
                 }
             }
@@ -84,3 +227,216 @@ impl Mutator for ReadEvalPrint {
         Ok(())
     }
 }
+
+/// Mutator that evaluates every expression of a source file in turn on the REPL's main thread,
+/// optionally reporting execution to a profiler
+pub struct ScriptRunner<'a> {
+    rep: &'a ReadEvalPrint,
+    source: &'a str,
+}
+
+impl<'a> ScriptRunner<'a> {
+    pub fn new(rep: &'a ReadEvalPrint, source: &'a str) -> ScriptRunner<'a> {
+        ScriptRunner { rep, source }
+    }
+}
+
+impl<'a> Mutator for ScriptRunner<'a> {
+    type Input = Option<&'a mut Profiler>;
+    type Output = ();
+
+    fn run(&self, mem: &MutatorView, mut profiler: Self::Input) -> Result<(), RuntimeError> {
+        let thread = self.rep.main_thread.get(mem);
+
+        for value in parse_all(mem, self.source)? {
+            let function = compile(mem, value)?;
+
+            match profiler.as_deref_mut() {
+                Some(profiler) => thread.profiled_vm_eval(mem, function, profiler)?,
+                None => thread.quick_vm_eval(mem, function)?,
+            };
+        }
+
+        Ok(())
+    }
+}
+
+/// The number of tests run by a `TestRunner` that passed and failed
+pub struct TestReport {
+    pub passed: usize,
+    pub failed: usize,
+}
+
+/// Mutator that evaluates every expression of a source file in turn on the REPL's main thread,
+/// then calls each test that was registered by a `deftest` while it ran. Every test gets a new
+/// Thread with its own copy of the globals as the file left them, so tests can't see each other's
+/// assignments. Each result is written to the console output, with the source line of a failed
+/// assertion.
+pub struct TestRunner<'a> {
+    rep: &'a ReadEvalPrint,
+    source: &'a str,
+}
+
+impl<'a> TestRunner<'a> {
+    pub fn new(rep: &'a ReadEvalPrint, source: &'a str) -> TestRunner<'a> {
+        TestRunner { rep, source }
+    }
+}
+
+impl<'a> Mutator for TestRunner<'a> {
+    type Input = ();
+    type Output = TestReport;
+
+    fn run(&self, mem: &MutatorView, _input: ()) -> Result<TestReport, RuntimeError> {
+        let thread = self.rep.main_thread.get(mem);
+
+        for value in parse_all(mem, self.source)? {
+            let function = compile(mem, value)?;
+            thread.quick_vm_eval(mem, function)?;
+        }
+
+        let tests: Vec<(TaggedScopedPtr, TaggedScopedPtr)> =
+            thread.tests(mem).access_slice(mem, |tests| {
+                tests
+                    .iter()
+                    .filter_map(|test| match *test.get(mem) {
+                        Value::Pair(test) => Some((test.first.get(mem), test.second.get(mem))),
+                        _ => None,
+                    })
+                    .collect()
+            });
+
+        let mut report = TestReport {
+            passed: 0,
+            failed: 0,
+        };
+
+        for (name, test) in tests {
+            let globals = thread.globals(mem).copy(mem)?;
+            let test_thread = Thread::alloc_with_globals(mem, globals)?;
+
+            let result = match *test {
+                Value::Function(function) => test_thread.quick_vm_eval(mem, function),
+                _ => Err(err_eval("A test must be a function")),
+            };
+            match result {
+                Ok(_) => {
+                    mem.write_output(format!("test {} ... ok\n", name).as_bytes())?;
+                    report.passed += 1;
+                }
+                Err(e) => {
+                    let failure = format!(
+                        "test {} ... FAILED\n{}",
+                        name,
+                        e.format_with_source(self.source)
+                    );
+                    mem.write_output(failure.as_bytes())?;
+                    report.failed += 1;
+                }
+            }
+        }
+
+        let status = if report.failed == 0 { "ok" } else { "FAILED" };
+        let summary = format!(
+            "test result: {}. {} passed; {} failed\n",
+            status, report.passed, report.failed
+        );
+        mem.write_output(summary.as_bytes())?;
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::interpreter::memory::Memory;
+
+    /// Run the tests in a source file in a new session with the prelude, returning the report
+    /// and everything the runner wrote
+    fn run_tests(source: &str) -> (TestReport, String) {
+        let mem = Memory::new();
+        let rep = mem.mutate(&RepMaker { prelude: true }, ()).unwrap();
+
+        struct Capture<'a> {
+            runner: TestRunner<'a>,
+        }
+
+        impl<'a> Mutator for Capture<'a> {
+            type Input = ();
+            type Output = (TestReport, String);
+
+            fn run(&self, mem: &MutatorView, _: ()) -> Result<Self::Output, RuntimeError> {
+                mem.capture_output();
+                let report = self.runner.run(mem, ());
+                let output = mem.take_output();
+                Ok((report?, output))
+            }
+        }
+
+        let capture = Capture {
+            runner: TestRunner::new(&rep, source),
+        };
+        mem.mutate(&capture, ()).unwrap()
+    }
+
+    #[test]
+    fn reports_results() {
+        let (report, output) = run_tests(
+            "(deftest adds (assert-equal 2 (+ 1 1)))
+(deftest fails
+  (assert-equal 3 (+ 1 1)))",
+        );
+
+        assert_eq!((report.passed, report.failed), (1, 1));
+        let mut lines = output.lines();
+        assert_eq!(lines.next(), Some("test adds ... ok"));
+        assert_eq!(lines.next(), Some("test fails ... FAILED"));
+        assert!(lines.next().unwrap().starts_with("error: "));
+        assert_eq!(lines.next(), Some("    3|  (assert-equal 3 (+ 1 1)))"));
+        assert_eq!(
+            output.lines().last(),
+            Some("test result: FAILED. 1 passed; 1 failed")
+        );
+    }
+
+    #[test]
+    fn tests_are_isolated() {
+        // each test starts from the globals as the file left them, whatever order they run in
+        let (report, output) = run_tests(
+            "(set (quote counter) 0)
+(deftest first (set (quote counter) (+ counter 1)) (assert-equal 1 counter))
+(deftest second (set (quote counter) (+ counter 1)) (assert-equal 1 counter))
+(deftest unchanged (assert-equal 0 counter))",
+        );
+        assert_eq!((report.passed, report.failed), (3, 0), "{}", output);
+    }
+
+    #[test]
+    fn tests_are_not_globals() {
+        // a test named after a global function doesn't replace it
+        let (report, output) = run_tests(
+            "(deftest length (assert-equal 2 (length (list 1 2))))
+(deftest later (assert-equal 3 (length (list 1 2 3))))",
+        );
+        assert_eq!((report.passed, report.failed), (2, 0), "{}", output);
+    }
+
+    #[test]
+    fn tests_are_registered_at_runtime() {
+        // tests defined anywhere while the file is loaded are run, in the order they were defined
+        let (report, output) = run_tests(
+            "(begin (deftest nested (assert-equal 1 2)))
+(def make-test () (deftest generated (assert true)))
+(make-test)
+(deftest nested (assert-equal 1 1))",
+        );
+        assert_eq!((report.passed, report.failed), (2, 0), "{}", output);
+        let names: Vec<&str> = output
+            .lines()
+            .filter_map(|line| line.strip_prefix("test "))
+            .filter(|line| !line.starts_with("result"))
+            .collect();
+        assert_eq!(names, vec!["nested ... ok", "generated ... ok"]);
+    }
+}
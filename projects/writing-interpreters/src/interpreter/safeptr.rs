@@ -3,9 +3,11 @@ use std::{cell::Cell, fmt, ops::Deref};
 use crate::memory::{AllocObject, RawPtr};
 
 use super::{
+    memory::write_barrier,
     pointerops::ScopedRef,
     printer::Print,
     taggedptr::{FatPtr, TaggedPtr, Value},
+    trace::{Trace, Tracer},
     TypeList,
 };
 
@@ -22,6 +24,12 @@ impl<'guard, T: Sized> ScopedPtr<'guard, T> {
         ScopedPtr { value }
     }
 
+    /// Return the reference for the whole mutator scope rather than only as long as this
+    /// pointer is borrowed, as `Deref` gives
+    pub fn into_ref(self) -> &'guard T {
+        self.value
+    }
+
     /// Convert the compile-time type pointer to a runtime type pointer
     pub fn as_tagged(&self, guard: &'guard dyn MutatorScope) -> TaggedScopedPtr<'guard>
     where
@@ -91,7 +99,14 @@ impl<T: Sized> CellPtr<T> {
     // the explicit 'guard lifetime bound to MutatorScope is omitted here since the ScopedPtr
     // carries this lifetime already so we can assume that this operation is safe
     pub fn set(&self, source: ScopedPtr<T>) {
-        self.inner.set(RawPtr::new(source.value))
+        self.inner.set(RawPtr::new(source.value));
+        write_barrier(self);
+    }
+}
+
+impl<T: Sized> Trace for CellPtr<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        tracer.mark_raw(self.inner.get());
     }
 }
 
@@ -110,6 +125,11 @@ impl<'guard> TaggedScopedPtr<'guard> {
             value: FatPtr::from(ptr).as_value(guard),
         }
     }
+
+    /// Return the raw TaggedPtr from within
+    pub fn get_ptr(&self) -> TaggedPtr {
+        self.ptr
+    }
 }
 
 impl<'guard> Deref for TaggedScopedPtr<'guard> {
@@ -174,12 +194,14 @@ impl TaggedCellPtr {
     /// The explicit 'guard lifetime bound to MutatorScope is omitted here since the TaggedScopedPtr
     /// carries this lifetime already so we can assume that this operation is safe
     pub fn set(&self, source: TaggedScopedPtr) {
-        self.inner.set(TaggedPtr::from(source.ptr))
+        self.inner.set(TaggedPtr::from(source.ptr));
+        write_barrier(self);
     }
 
     /// Take the pointer of another `TaggedCellPtr` and set this instance to point at that object too
     pub fn copy_from(&self, other: &TaggedCellPtr) {
         self.inner.set(other.inner.get());
+        write_barrier(self);
     }
 
     /// Return true if the pointer is nil
@@ -194,7 +216,8 @@ impl TaggedCellPtr {
 
     /// Set this pointer to another TaggedPtr
     pub fn set_to_ptr(&self, ptr: TaggedPtr) {
-        self.inner.set(ptr)
+        self.inner.set(ptr);
+        write_barrier(self);
     }
 
     /// Return the raw TaggedPtr from within
@@ -203,6 +226,12 @@ impl TaggedCellPtr {
     }
 }
 
+impl Trace for TaggedCellPtr {
+    fn trace(&self, tracer: &mut Tracer) {
+        tracer.mark_tagged(self.inner.get());
+    }
+}
+
 impl From<TaggedScopedPtr<'_>> for TaggedCellPtr {
     fn from(ptr: TaggedScopedPtr) -> TaggedCellPtr {
         TaggedCellPtr::new_with(ptr)
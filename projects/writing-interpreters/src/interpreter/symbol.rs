@@ -1,21 +1,27 @@
+use std::cell::Cell;
 use std::fmt;
 use std::hash::Hash;
 use std::hash::Hasher;
 use std::slice;
 use std::str;
 
+use super::error::err_eval;
 use super::hashable::Hashable;
+use super::native::NativeSpec;
 use super::printer::Print;
-use super::safeptr::MutatorScope;
+use super::safeptr::{MutatorScope, TaggedScopedPtr};
+use super::taggedptr::Value;
+use super::{MutatorView, RuntimeError};
 
 /// A Symbol is a unique object that has a unique name string. The backing storage for the
 /// underlying str data must have a lifetime of at least that of the Symbol instance to
 /// prevent use-after-free.
 /// See `SymbolMap`
-#[derive(Copy, Clone)]
 pub struct Symbol {
     name_ptr: *const u8,
     name_len: usize,
+    /// Set when a major collection finds the Symbol reachable
+    marked: Cell<bool>,
 }
 
 impl Symbol {
@@ -23,6 +29,7 @@ impl Symbol {
         Symbol {
             name_ptr: name.as_ptr(),
             name_len: name.len(),
+            marked: Cell::new(false),
         }
     }
 
@@ -34,6 +41,22 @@ impl Symbol {
     pub fn as_str<'guard>(&self, _guard: &'guard dyn MutatorScope) -> &'guard str {
         unsafe { &self.unguarded_as_str() }
     }
+
+    /// Return true if the Symbol is a keyword, such as `:name`. Keywords evaluate to themselves
+    /// and cannot be bound to a value.
+    pub fn is_keyword(&self) -> bool {
+        self.name_len > 1 && unsafe { *self.name_ptr } == b':'
+    }
+
+    /// Record that the Symbol is reachable
+    pub fn mark(&self) {
+        self.marked.set(true);
+    }
+
+    /// Clear the mark, returning whether it was set
+    pub fn take_mark(&self) -> bool {
+        self.marked.replace(false)
+    }
 }
 
 impl Hashable for Symbol {
@@ -52,3 +75,87 @@ impl Print for Symbol {
         write!(f, "{}", self.as_str(guard))
     }
 }
+
+/// Native functions for testing for Symbols and converting between Symbols and Text
+pub static SYMBOL_NATIVES: [NativeSpec; 4] = [
+    NativeSpec {
+        name: "symbol?",
+        min_args: 1,
+        max_args: Some(1),
+        function: is_symbol,
+    },
+    NativeSpec {
+        name: "keyword?",
+        min_args: 1,
+        max_args: Some(1),
+        function: is_keyword,
+    },
+    NativeSpec {
+        name: "string->symbol",
+        min_args: 1,
+        max_args: Some(1),
+        function: string_to_symbol,
+    },
+    NativeSpec {
+        name: "symbol->string",
+        min_args: 1,
+        max_args: Some(1),
+        function: symbol_to_string,
+    },
+];
+
+/// Return the `true` Symbol if the condition holds, nil otherwise
+fn boolean<'guard>(mem: &'guard MutatorView, condition: bool) -> TaggedScopedPtr<'guard> {
+    if condition {
+        mem.lookup_sym("true")
+    } else {
+        mem.nil()
+    }
+}
+
+/// (symbol? value) returns true if the value is a Symbol, including keywords
+fn is_symbol<'guard>(
+    mem: &'guard MutatorView,
+    args: &[TaggedScopedPtr<'guard>],
+) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+    Ok(boolean(mem, matches!(*args[0], Value::Symbol(_))))
+}
+
+/// (keyword? value) returns true if the value is a keyword such as `:name`
+fn is_keyword<'guard>(
+    mem: &'guard MutatorView,
+    args: &[TaggedScopedPtr<'guard>],
+) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+    Ok(boolean(
+        mem,
+        matches!(*args[0], Value::Symbol(s) if s.is_keyword()),
+    ))
+}
+
+/// (string->symbol text) returns the Symbol named by the text, interning it if it is new
+fn string_to_symbol<'guard>(
+    mem: &'guard MutatorView,
+    args: &[TaggedScopedPtr<'guard>],
+) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+    match *args[0] {
+        Value::Text(text) => {
+            let name = text.as_string(mem);
+            if name.is_empty() {
+                return Err(err_eval("string->symbol expected a non-empty text"));
+            }
+            Ok(mem.lookup_sym(&name))
+        }
+        _ => Err(err_eval("string->symbol expected a text")),
+    }
+}
+
+/// (symbol->string symbol) returns the name of the Symbol as a Text
+fn symbol_to_string<'guard>(
+    mem: &'guard MutatorView,
+    args: &[TaggedScopedPtr<'guard>],
+) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+    match *args[0] {
+        Value::Symbol(symbol) => mem.text(symbol.as_str(mem)),
+        _ => Err(err_eval("symbol->string expected a symbol")),
+    }
+}
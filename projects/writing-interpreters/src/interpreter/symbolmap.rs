@@ -1,38 +1,57 @@
 use std::{cell::RefCell, collections::HashMap};
 
-use super::{arena::Arena, symbol::Symbol};
-use crate::memory::{AllocRaw, RawPtr};
+use super::symbol::Symbol;
+use crate::memory::RawPtr;
 
 /// A mapping of symbol names (Strings) to Symbol pointers. Only one copy of the symbol
-/// name String is kept; a Symbol resides in managed memory with a raw pointer to the
-/// String. Thus the lifetime of the SymbolMap must be at least the lifetime of the
-/// managed memory. This is arranged here by maintaining Symbol memory alongside the
-/// mapping HashMap.
+/// name String is kept; a Symbol has a raw pointer to the String's contents, which don't move
+/// when the map is resized. Each Symbol is boxed so that its address, which identifies it,
+/// is also stable.
 ///
-/// No Symbol is ever deleted. Symbol name strings must be immutable.
+/// Symbols are not allocated in the garbage collected heap, so the collector can't free them.
+/// Instead, a major collection marks every Symbol it finds reachable and then calls `sweep()`
+/// to remove the rest. Symbol name strings must be immutable.
 pub struct SymbolMap {
-    map: RefCell<HashMap<String, RawPtr<Symbol>>>,
-    arena: Arena,
+    map: RefCell<HashMap<String, Box<Symbol>>>,
 }
 
 impl SymbolMap {
     pub fn new() -> SymbolMap {
         SymbolMap {
             map: RefCell::new(HashMap::new()),
-            arena: Arena::new(),
         }
     }
 
     pub fn lookup(&self, name: &str) -> RawPtr<Symbol> {
         {
-            if let Some(ptr) = self.map.borrow().get(name) {
-                return *ptr;
+            if let Some(symbol) = self.map.borrow().get(name) {
+                return RawPtr::new(&**symbol);
             }
         }
 
         let name = String::from(name);
-        let ptr = self.arena.alloc(Symbol::new(&name)).unwrap();
-        self.map.borrow_mut().insert(name, ptr);
+        let symbol = Box::new(Symbol::new(&name));
+        let ptr = RawPtr::new(&*symbol);
+        self.map.borrow_mut().insert(name, symbol);
         ptr
     }
+
+    /// Clear the mark on every Symbol, ready for a collection to mark those that are reachable
+    pub fn unmark_all(&self) {
+        for symbol in self.map.borrow().values() {
+            symbol.take_mark();
+        }
+    }
+
+    /// Free every Symbol that was not marked since the last `unmark_all()`. Must only be called
+    /// after a collection has traced everything that is reachable, as any pointer to a freed
+    /// Symbol would be left dangling.
+    pub fn sweep(&self) {
+        self.map.borrow_mut().retain(|_, symbol| symbol.take_mark());
+    }
+
+    /// Return the number of Symbols interned
+    pub fn count(&self) -> usize {
+        self.map.borrow().len()
+    }
 }
@@ -7,14 +7,16 @@ use super::{
     function::{Function, Partial},
     list::List,
     memory::HeapStorage,
-    number::NumberObject,
+    native::NativeFunction,
+    number::{Float, NumberObject},
     pair::Pair,
     pointerops::{get_tag, ScopedRef, Tagged, TAG_NUMBER, TAG_OBJECT, TAG_PAIR, TAG_SYMBOL},
     printer::Print,
+    record::Record,
     safeptr::MutatorScope,
     symbol::Symbol,
     text::Text,
-    vm::Upvalue,
+    vm::{Generator, Upvalue},
     ArrayU16, ArrayU32, ArrayU8, ScopedPtr,
 };
 
@@ -27,13 +29,17 @@ pub enum Value<'guard> {
     ArrayU16(ScopedPtr<'guard, ArrayU16>),
     ArrayU32(ScopedPtr<'guard, ArrayU32>),
     Dict(ScopedPtr<'guard, Dict>),
+    Float(ScopedPtr<'guard, Float>),
     Function(ScopedPtr<'guard, Function>),
+    Generator(ScopedPtr<'guard, Generator>),
     List(ScopedPtr<'guard, List>),
+    NativeFunction(ScopedPtr<'guard, NativeFunction>),
     Nil,
     Number(isize),
     NumberObject(ScopedPtr<'guard, NumberObject>),
     Pair(ScopedPtr<'guard, Pair>),
     Partial(ScopedPtr<'guard, Partial>),
+    Record(ScopedPtr<'guard, Record>),
     Symbol(ScopedPtr<'guard, Symbol>),
     Text(ScopedPtr<'guard, Text>),
     Upvalue(ScopedPtr<'guard, Upvalue>),
@@ -49,6 +55,7 @@ impl<'guard> fmt::Display for Value<'guard> {
             Value::Pair(p) => p.print(self, f),
             Value::Symbol(s) => s.print(self, f),
             Value::Number(n) => write!(f, "{}", *n),
+            Value::Float(n) => n.print(self, f),
             Value::Text(t) => t.print(self, f),
             Value::List(a) => a.print(self, f),
             Value::ArrayU8(a) => a.print(self, f),
@@ -56,7 +63,10 @@ impl<'guard> fmt::Display for Value<'guard> {
             Value::ArrayU32(a) => a.print(self, f),
             Value::Dict(d) => d.print(self, f),
             Value::Function(n) => n.print(self, f),
+            Value::Generator(g) => g.print(self, f),
+            Value::NativeFunction(n) => n.print(self, f),
             Value::Partial(p) => p.print(self, f),
+            Value::Record(r) => r.print(self, f),
             Value::Upvalue(_) => write!(f, "Upvalue"),
             _ => write!(f, "<unidentified-object-type>"),
         }
@@ -70,12 +80,16 @@ impl<'guard> fmt::Debug for Value<'guard> {
             Value::ArrayU16(a) => a.debug(self, f),
             Value::ArrayU32(a) => a.debug(self, f),
             Value::Dict(d) => d.debug(self, f),
+            Value::Float(n) => n.debug(self, f),
             Value::Function(n) => n.debug(self, f),
+            Value::Generator(g) => g.debug(self, f),
             Value::List(a) => a.debug(self, f),
+            Value::NativeFunction(n) => n.debug(self, f),
             Value::Nil => write!(f, "nil"),
             Value::Number(n) => write!(f, "{}", *n),
             Value::Pair(p) => p.debug(self, f),
             Value::Partial(p) => p.debug(self, f),
+            Value::Record(r) => r.debug(self, f),
             Value::Symbol(s) => s.debug(self, f),
             Value::Text(t) => t.debug(self, f),
             Value::Upvalue(_) => write!(f, "Upvalue"),
@@ -92,13 +106,17 @@ pub enum FatPtr {
     ArrayU16(RawPtr<ArrayU16>),
     ArrayU32(RawPtr<ArrayU32>),
     Dict(RawPtr<Dict>),
+    Float(RawPtr<Float>),
     Function(RawPtr<Function>),
+    Generator(RawPtr<Generator>),
     List(RawPtr<List>),
+    NativeFunction(RawPtr<NativeFunction>),
     Nil,
     Number(isize),
     NumberObject(RawPtr<NumberObject>),
     Pair(RawPtr<Pair>),
     Partial(RawPtr<Partial>),
+    Record(RawPtr<Record>),
     Symbol(RawPtr<Symbol>),
     Text(RawPtr<Text>),
     Upvalue(RawPtr<Upvalue>),
@@ -118,10 +136,19 @@ impl FatPtr {
                 Value::ArrayU32(ScopedPtr::new(guard, raw_ptr.scoped_ref(guard)))
             }
             FatPtr::Dict(raw_ptr) => Value::Dict(ScopedPtr::new(guard, raw_ptr.scoped_ref(guard))),
+            FatPtr::Float(raw_ptr) => {
+                Value::Float(ScopedPtr::new(guard, raw_ptr.scoped_ref(guard)))
+            }
             FatPtr::Function(raw_ptr) => {
                 Value::Function(ScopedPtr::new(guard, raw_ptr.scoped_ref(guard)))
             }
+            FatPtr::Generator(raw_ptr) => {
+                Value::Generator(ScopedPtr::new(guard, raw_ptr.scoped_ref(guard)))
+            }
             FatPtr::List(raw_ptr) => Value::List(ScopedPtr::new(guard, raw_ptr.scoped_ref(guard))),
+            FatPtr::NativeFunction(raw_ptr) => {
+                Value::NativeFunction(ScopedPtr::new(guard, raw_ptr.scoped_ref(guard)))
+            }
             FatPtr::Nil => Value::Nil,
             FatPtr::Number(num) => Value::Number(*num),
             FatPtr::NumberObject(raw_ptr) => {
@@ -131,6 +158,9 @@ impl FatPtr {
             FatPtr::Partial(raw_ptr) => {
                 Value::Partial(ScopedPtr::new(guard, raw_ptr.scoped_ref(guard)))
             }
+            FatPtr::Record(raw_ptr) => {
+                Value::Record(ScopedPtr::new(guard, raw_ptr.scoped_ref(guard)))
+            }
             FatPtr::Symbol(raw_ptr) => {
                 Value::Symbol(ScopedPtr::new(guard, raw_ptr.scoped_ref(guard)))
             }
@@ -156,11 +186,15 @@ fatptr_from_rawptr!(ArrayU8, ArrayU8);
 fatptr_from_rawptr!(ArrayU16, ArrayU16);
 fatptr_from_rawptr!(ArrayU32, ArrayU32);
 fatptr_from_rawptr!(Dict, Dict);
+fatptr_from_rawptr!(Float, Float);
 fatptr_from_rawptr!(Function, Function);
+fatptr_from_rawptr!(Generator, Generator);
 fatptr_from_rawptr!(List, List);
+fatptr_from_rawptr!(NativeFunction, NativeFunction);
 fatptr_from_rawptr!(NumberObject, NumberObject);
 fatptr_from_rawptr!(Pair, Pair);
 fatptr_from_rawptr!(Partial, Partial);
+fatptr_from_rawptr!(Record, Record);
 fatptr_from_rawptr!(Symbol, Symbol);
 fatptr_from_rawptr!(Text, Text);
 fatptr_from_rawptr!(Upvalue, Upvalue);
@@ -210,6 +244,28 @@ impl TaggedPtr {
         }
     }
 
+    /// Return the untagged address of the heap object this pointer refers to, or None for nil,
+    /// inline numbers and symbols, which are not allocated in the garbage collected heap
+    pub fn as_object_ptr(&self) -> Option<NonNull<()>> {
+        unsafe {
+            match get_tag(self.tag) {
+                TAG_PAIR => Some(RawPtr::untag(self.pair).as_untyped()),
+                TAG_OBJECT => Some(RawPtr::untag(self.object).as_untyped()),
+                _ => None,
+            }
+        }
+    }
+
+    /// Return the Symbol this pointer refers to, if it is a symbol
+    pub fn as_symbol_ptr(&self) -> Option<RawPtr<Symbol>> {
+        unsafe {
+            match get_tag(self.tag) {
+                TAG_SYMBOL if self.tag != 0 => Some(RawPtr::untag(self.symbol)),
+                _ => None,
+            }
+        }
+    }
+
     fn into_fat_ptr(&self) -> FatPtr {
         unsafe {
             if self.tag == 0 {
@@ -245,13 +301,17 @@ impl From<FatPtr> for TaggedPtr {
             FatPtr::ArrayU16(raw) => TaggedPtr::object(raw),
             FatPtr::ArrayU32(raw) => TaggedPtr::object(raw),
             FatPtr::Dict(raw) => TaggedPtr::object(raw),
+            FatPtr::Float(raw) => TaggedPtr::object(raw),
             FatPtr::Function(raw) => TaggedPtr::object(raw),
+            FatPtr::Generator(raw) => TaggedPtr::object(raw),
             FatPtr::List(raw) => TaggedPtr::object(raw),
+            FatPtr::NativeFunction(raw) => TaggedPtr::object(raw),
             FatPtr::Nil => TaggedPtr::nil(),
             FatPtr::Number(value) => TaggedPtr::number(value),
             FatPtr::NumberObject(raw) => TaggedPtr::object(raw),
             FatPtr::Pair(raw) => TaggedPtr::pair(raw),
             FatPtr::Partial(raw) => TaggedPtr::object(raw),
+            FatPtr::Record(raw) => TaggedPtr::object(raw),
             FatPtr::Text(raw) => TaggedPtr::object(raw),
             FatPtr::Symbol(raw) => TaggedPtr::symbol(raw),
             FatPtr::Upvalue(raw) => TaggedPtr::object(raw),
@@ -1,17 +1,63 @@
-use std::fmt;
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+};
 
-use super::{printer::Print, safeptr::MutatorScope};
+use super::{
+    containers::{ContainerFromSlice, SliceableContainer},
+    hashable::Hashable,
+    printer::Print,
+    safeptr::MutatorScope,
+    trace::{Trace, Tracer},
+    ArrayU8, CellPtr, MutatorView, RuntimeError, ScopedPtr,
+};
 
 /// While Text is somewhat similar to Symbol, it is instead garbage-collected heap allocated and not interned.
-#[derive(Copy, Clone)]
-pub struct Text {}
+/// The content is stored as UTF-8 bytes.
+#[derive(Clone)]
+pub struct Text {
+    content: CellPtr<ArrayU8>,
+}
+
+impl Text {
+    /// Allocate a Text object on the heap holding a copy of the given string
+    pub fn new_from_str<'guard>(
+        mem: &'guard MutatorView,
+        from_str: &str,
+    ) -> Result<ScopedPtr<'guard, Text>, RuntimeError> {
+        let content = ArrayU8::from_slice(mem, from_str.as_bytes())?;
+        mem.alloc(Text {
+            content: CellPtr::new_with(content),
+        })
+    }
+
+    /// Return a copy of the content as a String
+    pub fn as_string<'guard>(&self, guard: &'guard dyn MutatorScope) -> String {
+        self.content
+            .get(guard)
+            .access_slice(guard, |bytes| String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+impl Trace for Text {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.content.trace(tracer);
+    }
+}
+
+impl Hashable for Text {
+    fn hash<'guard, H: Hasher>(&self, guard: &'guard dyn MutatorScope, h: &mut H) {
+        self.as_string(guard).hash(h)
+    }
+}
 
 impl Print for Text {
+    /// Prints the text as a quoted string literal
     fn print<'guard>(
         &self,
         guard: &'guard dyn MutatorScope,
         f: &mut fmt::Formatter,
     ) -> fmt::Result {
-        write!(f, "\"(unimplemented)\"")
+        write!(f, "{:?}", self.as_string(guard))
     }
 }
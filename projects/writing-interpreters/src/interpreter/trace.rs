@@ -0,0 +1,132 @@
+use std::ptr::NonNull;
+
+use crate::memory::{AllocHeader, AllocRaw, RawPtr};
+
+use super::{
+    bytecode::{ArrayOpcode, ByteCode, InstructionStream},
+    dict::Dict,
+    function::{Function, Partial},
+    list::List,
+    memory::HeapStorage,
+    number::NumberObject,
+    pair::Pair,
+    record::Record,
+    safeptr::MutatorScope,
+    taggedptr::TaggedPtr,
+    text::Text,
+    vm::{CallFrameList, Generator, Thread, Upvalue},
+    ArrayU16, ArrayU32, ArrayU8, TypeList,
+};
+
+/// Any type that can hold pointers to heap objects must implement this trait so that the garbage
+/// collector can find the objects it refers to.
+///
+/// Implementations should call `trace()` on each member that may hold a pointer. Heap objects
+/// are only traced once per collection, the `Tracer` takes care of that.
+pub trait Trace {
+    fn trace(&self, tracer: &mut Tracer);
+}
+
+/// Maintains the worklist of a marking pass over the heap.
+pub struct Tracer<'heap> {
+    heap: &'heap HeapStorage,
+    worklist: Vec<NonNull<()>>,
+}
+
+impl<'heap> Tracer<'heap> {
+    pub fn new(heap: &'heap HeapStorage) -> Tracer<'heap> {
+        Tracer {
+            heap,
+            worklist: Vec::new(),
+        }
+    }
+
+    /// Mark the object referred to by a tagged pointer, if it refers to a heap object or a
+    /// Symbol
+    pub fn mark_tagged(&mut self, ptr: TaggedPtr) {
+        if let Some(object) = ptr.as_object_ptr() {
+            self.mark(object);
+        } else if let Some(symbol) = ptr.as_symbol_ptr() {
+            unsafe { &*symbol.as_ptr() }.mark();
+        }
+    }
+
+    /// Mark the object referred to by a typed pointer
+    pub fn mark_raw<T>(&mut self, ptr: RawPtr<T>) {
+        self.mark(ptr.as_untyped());
+    }
+
+    /// Mark the backing storage of an array. Array storage carries no type information so it is
+    /// not traced here, the owning container must trace its items.
+    pub fn mark_array_backing<T>(&mut self, ptr: *const T) {
+        if let Some(ptr) = NonNull::new(ptr as *mut ()) {
+            self.heap.mark_object(ptr);
+        }
+    }
+
+    /// Trace every object in the worklist, and every object they refer to, until none remain
+    pub fn trace_all(&mut self) {
+        while let Some(object) = self.worklist.pop() {
+            self.trace_object(object);
+        }
+    }
+
+    fn mark(&mut self, object: NonNull<()>) {
+        if self.heap.mark_object(object) {
+            self.worklist.push(object);
+        }
+    }
+
+    /// Dispatch on the header type id to trace the members of an object
+    fn trace_object(&mut self, object: NonNull<()>) {
+        let header = HeapStorage::get_header(object);
+        let type_id = unsafe { header.as_ref() }.type_id();
+
+        unsafe {
+            match type_id {
+                TypeList::ArrayOpcode => object.cast::<ArrayOpcode>().as_ref().trace(self),
+                TypeList::ArrayU8 => object.cast::<ArrayU8>().as_ref().trace(self),
+                TypeList::ArrayU16 => object.cast::<ArrayU16>().as_ref().trace(self),
+                TypeList::ArrayU32 => object.cast::<ArrayU32>().as_ref().trace(self),
+                TypeList::ByteCode => object.cast::<ByteCode>().as_ref().trace(self),
+                TypeList::CallFrameList => object.cast::<CallFrameList>().as_ref().trace(self),
+                TypeList::Dict => object.cast::<Dict>().as_ref().trace(self),
+                TypeList::Function => object.cast::<Function>().as_ref().trace(self),
+                TypeList::Generator => object.cast::<Generator>().as_ref().trace(self),
+                TypeList::InstructionStream => {
+                    object.cast::<InstructionStream>().as_ref().trace(self)
+                }
+                TypeList::List => object.cast::<List>().as_ref().trace(self),
+                TypeList::NumberObject => object.cast::<NumberObject>().as_ref().trace(self),
+                TypeList::Pair => object.cast::<Pair>().as_ref().trace(self),
+                TypeList::Partial => object.cast::<Partial>().as_ref().trace(self),
+                TypeList::Record => object.cast::<Record>().as_ref().trace(self),
+                TypeList::Text => object.cast::<Text>().as_ref().trace(self),
+                TypeList::Thread => object.cast::<Thread>().as_ref().trace(self),
+                TypeList::Upvalue => object.cast::<Upvalue>().as_ref().trace(self),
+
+                // These types hold no pointers
+                TypeList::ArrayBackingBytes
+                | TypeList::Float
+                | TypeList::NativeFunction
+                | TypeList::Symbol => (),
+            }
+        }
+    }
+}
+
+/// Array items may need to be read during tracing
+impl<'heap> MutatorScope for Tracer<'heap> {}
+
+/// Implement a no-op `Trace` for types that never hold pointers
+macro_rules! trace_nothing {
+    ($($T:ty),*) => {
+        $(
+            impl Trace for $T {
+                fn trace(&self, _tracer: &mut Tracer) {}
+            }
+        )*
+    };
+}
+
+trace_nothing!(u8, u16, u32, u64);
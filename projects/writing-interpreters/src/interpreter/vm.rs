@@ -1,21 +1,30 @@
-use std::cell::Cell;
+use std::{cell::Cell, fmt, time::Instant};
 
 use crate::memory::ArraySize;
 
 use super::{
     array::Array,
-    bytecode::{ByteCode, InstructionStream, Opcode},
+    bytecode::{ByteCode, InstructionStream, JumpOffset, LiteralId, NumArgs, Opcode, Register},
     containers::{
-        Container, FillAnyContainer, HashIndexedAnyContainer, IndexedAnyContainer,
-        IndexedContainer, SliceableContainer, StackAnyContainer, StackContainer,
+        AnyContainerFromSlice, Container, FillAnyContainer, HashIndexedAnyContainer,
+        IndexedAnyContainer, IndexedContainer, SliceableContainer, StackAnyContainer,
+        StackContainer,
     },
     dict::Dict,
-    error::err_eval,
-    function::{Function, Partial},
+    equality::equal,
+    error::{err_eval, spos, ErrorKind},
+    function::{Function, Partial, UNSUPPLIED},
+    limits::Limits,
     list::List,
+    native::{define_natives, NativeSpec},
+    number::{ArithmeticOp, Numeric},
     pair::Pair,
+    printer::{display, print, Print},
+    profiler::Profiler,
+    record::Record,
     safeptr::{MutatorScope, TaggedCellPtr, TaggedScopedPtr},
     taggedptr::{TaggedPtr, Value},
+    trace::{Trace, Tracer},
     CellPtr, MutatorView, RuntimeError, ScopedPtr,
 };
 
@@ -41,6 +50,103 @@ pub struct Thread {
     upvalues: CellPtr<Dict>,
     /// A dict that should only contain Symbol keys but any type as values
     globals: CellPtr<Dict>,
+    /// The Generators currently running on this Thread, the innermost last
+    generators: CellPtr<List>,
+    /// Tests defined by `deftest`, as (name . function) pairs in the order they were defined
+    tests: CellPtr<List>,
+    /// Error handlers set up by `Protect` instructions, the innermost last. Each is stored as
+    /// `HANDLER_SIZE` Numbers: the call depth and generator count when it was set up, the
+    /// instruction to continue at and the absolute stack register to put the error message in.
+    handlers: CellPtr<List>,
+    /// Instructions executed by the current evaluation
+    instructions: Cell<u64>,
+    /// When the current evaluation must stop by, if it has a timeout
+    deadline: Cell<Option<Instant>>,
+}
+
+/// The clock is checked for a timeout once per this many instructions
+const TIMEOUT_CHECK_INTERVAL: u64 = 256;
+
+/// The number of values in the `Thread::handlers` list for each error handler
+const HANDLER_SIZE: usize = 4;
+
+/// The interpreter loop's view of the current function while it runs a batch of instructions:
+/// the function's instructions and literals resolved to slices, the instruction pointer and the
+/// stack base. These are kept in locals rather than read through the Thread's heap objects for
+/// every instruction, and written back to the Thread's InstructionStream when the batch ends.
+struct Dispatch<'guard> {
+    bytecode: ScopedPtr<'guard, ByteCode>,
+    code: &'guard [Opcode],
+    literals: &'guard [TaggedCellPtr],
+    /// Index of the next instruction
+    ip: usize,
+    /// Index into the register stack where the current register window begins
+    base: usize,
+}
+
+impl<'guard> Dispatch<'guard> {
+    /// Resolve the given ByteCode to continue at `ip` with the register window at `base`
+    fn new(
+        guard: &'guard dyn MutatorScope,
+        bytecode: ScopedPtr<'guard, ByteCode>,
+        ip: ArraySize,
+        base: ArraySize,
+    ) -> Dispatch<'guard> {
+        // Compiled code is never modified and nothing is collected while a mutator runs, so the
+        // slices stay valid for the mutator scope
+        let (code, literals) = unsafe { bytecode.into_ref().as_slices(guard) };
+
+        Dispatch {
+            bytecode,
+            code,
+            literals,
+            ip: ip as usize,
+            base: base as usize,
+        }
+    }
+
+    /// Resolve the Thread's current position
+    fn load(guard: &'guard dyn MutatorScope, thread: &Thread) -> Dispatch<'guard> {
+        let instr = thread.instr.get(guard);
+        Dispatch::new(
+            guard,
+            instr.code(guard),
+            instr.get_next_ip(),
+            thread.stack_base.get(),
+        )
+    }
+
+    /// Write the position back to the Thread
+    fn save(&self, guard: &'guard dyn MutatorScope, thread: &Thread) {
+        thread
+            .instr
+            .get(guard)
+            .switch_frame(self.bytecode, self.ip as ArraySize);
+        thread.stack_base.set(self.base as ArraySize);
+    }
+
+    /// Fetch the next instruction, advancing the instruction pointer
+    fn next_opcode(&mut self) -> Result<Opcode, RuntimeError> {
+        let opcode = *self
+            .code
+            .get(self.ip)
+            .ok_or_else(|| RuntimeError::new(ErrorKind::BoundsError))?;
+        self.ip += 1;
+        Ok(opcode)
+    }
+
+    /// Return the literal at the given index into the literals list
+    fn literal(&self, id: LiteralId) -> Result<TaggedPtr, RuntimeError> {
+        self.literals
+            .get(id as usize)
+            .map(TaggedCellPtr::get_ptr)
+            .ok_or_else(|| RuntimeError::new(ErrorKind::BoundsError))
+    }
+
+    /// Adjust the instruction pointer by the given signed offset from the next instruction
+    fn jump(&mut self, offset: JumpOffset) {
+        self.ip = (self.ip as isize + offset as isize) as usize;
+    }
 }
 
 /// Call frames are stored in a separate stack to the register window stack. This simplifies types
@@ -59,12 +165,62 @@ pub struct CallFrame {
 }
 
 /// Evaluation control flow flags
-#[derive(PartialEq)]
 pub enum EvalStatus<'guard> {
     /// Eval result is pending, more instructions must be executed
     Pending,
     /// Eval is complete, here is the resulting value
     Return(TaggedScopedPtr<'guard>),
+    /// Resume a Generator, putting the value it yields or returns in the `dest` register
+    Resume {
+        generator: ScopedPtr<'guard, Generator>,
+        dest: Register,
+        value: TaggedScopedPtr<'guard>,
+    },
+    /// Suspend the innermost running Generator, yielding `value`. The `dest` register receives
+    /// the value the Generator is next resumed with.
+    Yield {
+        dest: Register,
+        value: TaggedScopedPtr<'guard>,
+    },
+    /// Grow the register stack to `size` registers before continuing
+    GrowStack { size: ArraySize },
+}
+
+/// The life cycle of a Generator
+#[derive(Copy, Clone, PartialEq)]
+pub enum GeneratorState {
+    /// The function has not been called yet
+    Ready,
+    /// The function is executing on a Thread
+    Running,
+    /// The function is paused at a `yield`
+    Suspended,
+    /// The function has returned, or failed with an error
+    Done,
+}
+
+/// A Generator is a one-shot delimited continuation: a call to a function that can be paused by
+/// `(yield value)` and later resumed where it left off by calling the Generator.
+///
+/// When a Generator yields, the call frames from its function up to the `yield` and the registers
+/// they use are moved off the Thread into the Generator. Resuming moves them back onto the Thread
+/// above the frame that resumed it, so the Generator may be resumed from anywhere.
+pub struct Generator {
+    /// The Function, Partial or NativeFunction to call
+    function: TaggedCellPtr,
+    /// The arguments to call the function with
+    args: CellPtr<List>,
+    state: Cell<GeneratorState>,
+    /// While suspended, the saved call frames. Stack bases are relative to the Generator's first
+    /// register.
+    frames: CellPtr<CallFrameList>,
+    /// While suspended, the saved registers from the Generator's first register up to the end of
+    /// the register window of the frame that yielded
+    registers: CellPtr<List>,
+    /// While suspended, the saved register that receives the value the Generator is resumed with
+    resume_reg: Cell<ArraySize>,
+    /// While running, the number of call frames beneath the Generator's own
+    depth: Cell<ArraySize>,
 }
 
 /// A closure upvalue as generally described by Lua 5.1 implementation.
@@ -81,6 +237,77 @@ pub struct Upvalue {
     location: ArraySize,
 }
 
+impl Trace for CallFrame {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.function.trace(tracer);
+    }
+}
+
+impl Trace for Upvalue {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.value.trace(tracer);
+    }
+}
+
+impl Trace for Generator {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.function.trace(tracer);
+        self.args.trace(tracer);
+        self.frames.trace(tracer);
+        self.registers.trace(tracer);
+    }
+}
+
+impl Generator {
+    /// Allocate a new Generator that will call `function` with `args` when it is first resumed
+    pub fn alloc<'guard>(
+        mem: &'guard MutatorView,
+        function: TaggedScopedPtr<'guard>,
+        args: &[TaggedScopedPtr<'guard>],
+    ) -> Result<ScopedPtr<'guard, Generator>, RuntimeError> {
+        match *function {
+            Value::Function(_) | Value::Partial(_) | Value::NativeFunction(_) => (),
+            _ => return Err(err_eval("A generator must be given a function to call")),
+        }
+
+        mem.alloc(Generator {
+            function: TaggedCellPtr::new_with(function),
+            args: CellPtr::new_with(List::from_slice(mem, args)?),
+            state: Cell::new(GeneratorState::Ready),
+            frames: CellPtr::new_with(CallFrameList::alloc(mem)?),
+            registers: CellPtr::new_with(List::alloc(mem)?),
+            resume_reg: Cell::new(0),
+            depth: Cell::new(0),
+        })
+    }
+
+    /// Return the current state
+    pub fn state(&self) -> GeneratorState {
+        self.state.get()
+    }
+
+    /// Return true if the Generator cannot be resumed again
+    pub fn is_done(&self) -> bool {
+        self.state.get() == GeneratorState::Done
+    }
+}
+
+impl Print for Generator {
+    fn print<'guard>(
+        &self,
+        _guard: &'guard dyn MutatorScope,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        let state = match self.state.get() {
+            GeneratorState::Ready => "ready",
+            GeneratorState::Running => "running",
+            GeneratorState::Suspended => "suspended",
+            GeneratorState::Done => "done",
+        };
+        write!(f, "(Generator {})", state)
+    }
+}
+
 impl Upvalue {
     /// Allocate a new Upvalue on the heap. The absolute stack index of the object must be
     /// provided.
@@ -95,6 +322,35 @@ impl Upvalue {
         })
     }
 
+    /// Allocate an Upvalue from the parts of one saved in a heap image
+    pub fn restore<'guard>(
+        mem: &'guard MutatorView,
+        value: TaggedScopedPtr<'guard>,
+        closed: bool,
+        location: ArraySize,
+    ) -> Result<ScopedPtr<'guard, Upvalue>, RuntimeError> {
+        mem.alloc(Upvalue {
+            value: TaggedCellPtr::new_with(value),
+            closed: Cell::new(closed),
+            location,
+        })
+    }
+
+    /// Return true if the value has been moved off the stack into the Upvalue
+    pub fn is_closed(&self) -> bool {
+        self.closed.get()
+    }
+
+    /// Return the absolute stack index of the value
+    pub fn location(&self) -> ArraySize {
+        self.location
+    }
+
+    /// Return the closed-over value. This is nil if the Upvalue is not closed.
+    pub fn closed_value<'guard>(&self, guard: &'guard dyn MutatorScope) -> TaggedScopedPtr<'guard> {
+        self.value.get(guard)
+    }
+
     /// Dereference the upvalue
     fn get<'guard>(
         &self,
@@ -137,11 +393,36 @@ impl Upvalue {
     }
 }
 
+impl Trace for Thread {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.frames.trace(tracer);
+        self.instr.trace(tracer);
+        self.stack.trace(tracer);
+        self.upvalues.trace(tracer);
+        self.globals.trace(tracer);
+        self.generators.trace(tracer);
+        self.tests.trace(tracer);
+        self.handlers.trace(tracer);
+    }
+}
+
 impl Thread {
     /// Allocate a new Thread with a minimal stack preallocated but not associated with any
     /// bytecode yet.
     pub fn alloc<'guard>(
         mem: &'guard MutatorView,
+    ) -> Result<ScopedPtr<'guard, Thread>, RuntimeError> {
+        // create a globals dict holding only the native functions
+        let globals = Dict::alloc(mem)?;
+        define_natives(mem, globals)?;
+
+        Thread::alloc_with_globals(mem, globals)
+    }
+
+    /// Allocate a new Thread like `alloc()` but with an existing set of global bindings
+    pub fn alloc_with_globals<'guard>(
+        mem: &'guard MutatorView,
+        globals: ScopedPtr<'guard, Dict>,
     ) -> Result<ScopedPtr<'guard, Thread>, RuntimeError> {
         // create an empty stack frame array
         let frames = CallFrameList::alloc_with_capacity(mem, 16)?;
@@ -153,9 +434,6 @@ impl Thread {
         // create an empty upvalue stack->heap mapping
         let upvalues = Dict::alloc(mem)?;
 
-        // create an empty globals dict
-        let globals = Dict::alloc(mem)?;
-
         // create an empty instruction stream
         let blank_code = ByteCode::alloc(mem)?;
         let instr = InstructionStream::alloc(mem, blank_code)?;
@@ -167,25 +445,71 @@ impl Thread {
             upvalues: CellPtr::new_with(upvalues),
             globals: CellPtr::new_with(globals),
             instr: CellPtr::new_with(instr),
+            generators: CellPtr::new_with(List::alloc(mem)?),
+            tests: CellPtr::new_with(List::alloc(mem)?),
+            handlers: CellPtr::new_with(List::alloc(mem)?),
+            instructions: Cell::new(0),
+            deadline: Cell::new(None),
         })
     }
 
+    /// Return the global bindings dict
+    pub fn globals<'guard>(&self, guard: &'guard dyn MutatorScope) -> ScopedPtr<'guard, Dict> {
+        self.globals.get(guard)
+    }
+
+    /// Return the tests registered on this thread, as (name . function) pairs in the order they
+    /// were defined
+    pub fn tests<'guard>(&self, guard: &'guard dyn MutatorScope) -> ScopedPtr<'guard, List> {
+        self.tests.get(guard)
+    }
+
     /// Evaluate a Function completely, returning the result. The Function passed in should expect
     /// no arguments.
     pub fn quick_vm_eval<'guard>(
         &self,
         mem: &'guard MutatorView,
         function: ScopedPtr<'guard, Function>,
+    ) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+        self.vm_eval(mem, function, None)
+    }
+
+    /// Evaluate a Function completely like `quick_vm_eval()`, reporting every instruction and
+    /// function call to the profiler.
+    pub fn profiled_vm_eval<'guard>(
+        &self,
+        mem: &'guard MutatorView,
+        function: ScopedPtr<'guard, Function>,
+        profiler: &mut Profiler,
+    ) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+        self.vm_eval(mem, function, Some(profiler))
+    }
+
+    fn vm_eval<'guard>(
+        &self,
+        mem: &'guard MutatorView,
+        function: ScopedPtr<'guard, Function>,
+        mut profiler: Option<&mut Profiler>,
     ) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
         let mut status = EvalStatus::Pending;
 
-        let frames = self.frames.get(mem);
-        frames.push(mem, CallFrame::new_main(function))?;
+        // the instruction limit and timeout apply to each evaluation separately
+        let limits = mem.limits();
+        self.instructions.set(0);
+        self.deadline
+            .set(limits.timeout.map(|timeout| Instant::now() + timeout));
+
+        self.push_frame(mem, CallFrame::new_main(function))?;
+
+        if let Some(profiler) = profiler.as_deref_mut() {
+            profiler.enter("<main>");
+        }
 
         let code = function.code(mem);
+        self.instr.get(mem).switch_frame(code, 0);
 
-        while status == EvalStatus::Pending {
-            status = self.vm_eval_stream(mem, code, 1024)?;
+        while let EvalStatus::Pending = status {
+            status = self.vm_eval_stream(mem, 1024, profiler.as_deref_mut())?;
             match status {
                 EvalStatus::Return(value) => return Ok(value),
                 _ => (),
@@ -195,107 +519,292 @@ impl Thread {
         Err(err_eval("Unexpected end of evaluation"))
     }
 
-    /// Given ByteCode, execute up to max_instr more instructions
+    /// Execute a batch of up to max_instr more instructions, continuing from the current
+    /// instruction stream position
     fn vm_eval_stream<'guard>(
         &self,
         mem: &'guard MutatorView,
-        code: ScopedPtr<'guard, ByteCode>,
         max_instr: ArraySize,
+        mut profiler: Option<&mut Profiler>,
     ) -> Result<EvalStatus<'guard>, RuntimeError> {
-        let instr = self.instr.get(mem);
-        // TODO this is broken logic, this function shouldn't switch back to this code object every
-        // time it is called
-        instr.switch_frame(code, 0);
+        // Generators are switched in and out, and the register stack grown, between batches
+        // while the register stack is not borrowed
+        let status = match self.eval_batch(mem, max_instr, profiler.as_deref_mut()) {
+            Ok(EvalStatus::Resume {
+                generator,
+                dest,
+                value,
+            }) => self.resume_generator(mem, generator, dest, value, profiler.as_deref_mut()),
+            Ok(EvalStatus::Yield { dest, value }) => {
+                self.suspend_generator(mem, dest, value, profiler.as_deref_mut())
+            }
+            Ok(EvalStatus::GrowStack { size }) => self
+                .stack
+                .get(mem)
+                .fill(mem, size, mem.nil())
+                .map(|_| EvalStatus::Pending),
+            status => status,
+        };
 
-        for _ in 0..max_instr {
-            match self.eval_next_instr(mem) {
-                // Evaluation paused or completed without error
-                Ok(exit_cond) => match exit_cond {
-                    EvalStatus::Return(value) => return Ok(EvalStatus::Return(value)),
-                    _ => (),
-                },
+        match status {
+            // Evaluation paused or completed without error
+            Ok(status) => Ok(status),
 
-                // Evaluation hit an error
-                Err(rt_error) => {
-                    // unwind the stack, printing a trace
-                    let frames = self.frames.get(mem);
+            // Evaluation hit an error that a Protect instruction set up a handler for
+            Err(rt_error) if self.catch_error(mem, &rt_error, profiler.as_deref_mut())? => {
+                Ok(EvalStatus::Pending)
+            }
 
-                    // Print a stack trace if the error is multiple call frames deep
-                    frames.access_slice(mem, |window| {
-                        if window.len() > 1 {
-                            println!("Error traceback:");
-                        }
+            // Evaluation hit an error
+            Err(rt_error) => {
+                // unwind the stack, recording a trace of every frame below the top level
+                let frames = self.frames.get(mem);
+                let traceback = frames.access_slice(mem, |window| {
+                    window
+                        .iter()
+                        .skip(1)
+                        .map(|frame| frame.as_string(mem))
+                        .collect()
+                });
+
+                // Unwind by clearing all frames from the stack
+                frames.clear(mem)?;
+                self.stack_base.set(0);
+                self.handlers.get(mem).clear(mem)?;
+
+                // Generators that were running lost their frames and can't be resumed
+                let generators = self.generators.get(mem);
+                while let Ok(generator) = StackAnyContainer::pop(&*generators, mem) {
+                    if let Value::Generator(generator) = *generator {
+                        generator.state.set(GeneratorState::Done);
+                    }
+                }
 
-                        for frame in &window[1..] {
-                            println!("  {}", frame.as_string(mem));
-                        }
-                    });
+                if let Some(profiler) = profiler {
+                    profiler.unwind();
+                }
 
-                    // Unwind by clearing all frames from the stack
-                    frames.clear(mem)?;
-                    self.stack_base.set(0);
+                Err(rt_error.with_traceback(traceback))
+            }
+        }
+    }
 
-                    return Err(rt_error);
-                }
+    /// Unwind to the innermost error handler, putting the error message in its register and
+    /// continuing at its instruction. Returns false if there is no handler or the error is an
+    /// exceeded resource limit, which can't be caught.
+    fn catch_error(
+        &self,
+        mem: &MutatorView,
+        error: &RuntimeError,
+        mut profiler: Option<&mut Profiler>,
+    ) -> Result<bool, RuntimeError> {
+        match error.error_kind() {
+            ErrorKind::InstructionLimit
+            | ErrorKind::CallDepthLimit
+            | ErrorKind::HeapLimit
+            | ErrorKind::Timeout => return Ok(false),
+            _ => (),
+        }
+
+        let handlers = self.handlers.get(mem);
+        if (handlers.length() as usize) < HANDLER_SIZE {
+            return Ok(false);
+        }
+
+        let mut handler = [0; HANDLER_SIZE];
+        for item in handler.iter_mut().rev() {
+            *item = match *StackAnyContainer::pop(&*handlers, mem)? {
+                Value::Number(n) => n as ArraySize,
+                _ => return Err(err_eval("Corrupt error handler")),
+            };
+        }
+        let [depth, generator_count, ip, dest] = handler;
+
+        // drop the frames of the calls that were made inside the protected code
+        let frames = self.frames.get(mem);
+        while frames.length() > depth {
+            frames.pop(mem)?;
+            if let Some(profiler) = profiler.as_deref_mut() {
+                profiler.leave();
             }
         }
 
-        Ok(EvalStatus::Pending)
+        // generators resumed inside the protected code lost their frames
+        let generators = self.generators.get(mem);
+        while generators.length() > generator_count {
+            if let Value::Generator(generator) = *StackAnyContainer::pop(&*generators, mem)? {
+                generator.state.set(GeneratorState::Done);
+            }
+        }
+
+        let frame = frames.top(mem)?;
+        self.stack_base.set(frame.base);
+        self.instr
+            .get(mem)
+            .switch_frame(frame.function.get(mem).code(mem), ip);
+
+        let message = mem.text(&format!("{}", error))?;
+        IndexedContainer::set(
+            &*self.stack.get(mem),
+            mem,
+            dest,
+            TaggedCellPtr::new_with(message),
+        )?;
+
+        Ok(true)
+    }
+
+    /// Count an instruction about to be executed against the instruction limit, checking the
+    /// clock every so often
+    fn count_instruction(&self, limits: &Limits) -> Result<(), RuntimeError> {
+        let count = self.instructions.get() + 1;
+        self.instructions.set(count);
+
+        if let Some(max) = limits.max_instructions {
+            if count > max {
+                return Err(RuntimeError::new(ErrorKind::InstructionLimit));
+            }
+        }
+
+        if let Some(deadline) = self.deadline.get() {
+            if count.is_multiple_of(TIMEOUT_CHECK_INTERVAL) && Instant::now() >= deadline {
+                return Err(RuntimeError::new(ErrorKind::Timeout));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Push a call frame onto the frame stack unless that would exceed the call depth limit
+    fn push_frame(&self, mem: &MutatorView, frame: CallFrame) -> Result<(), RuntimeError> {
+        let frames = self.frames.get(mem);
+
+        if let Some(max) = mem.limits().max_call_depth {
+            if frames.length() as usize >= max {
+                return Err(RuntimeError::new(ErrorKind::CallDepthLimit));
+            }
+        }
+
+        frames.push(mem, frame)
+    }
+
+    /// Execute up to `max_instr` instructions from the current instruction stream position,
+    /// stopping early at an instruction that completes evaluation, switches Generators, needs
+    /// the register stack to grow or fails.
+    ///
+    /// The register stack is borrowed as a slice for the whole batch and the current function's
+    /// instructions and literals are indexed directly through a `Dispatch`, so fetching an
+    /// instruction costs a slice index rather than a walk through the Thread's heap objects.
+    /// Calls and returns switch frames within the batch; the position is written back to the
+    /// Thread when the batch ends.
+    fn eval_batch<'guard>(
+        &self,
+        mem: &'guard MutatorView,
+        max_instr: ArraySize,
+        profiler: Option<&mut Profiler>,
+    ) -> Result<EvalStatus<'guard>, RuntimeError> {
+        self.stack.get(mem).access_slice(mem, |full_stack| {
+            let mut dispatch = Dispatch::load(mem, self);
+            let status = self.eval_instrs(mem, full_stack, &mut dispatch, max_instr, profiler);
+            dispatch.save(mem, self);
+            status
+        })
     }
 
-    /// Execute the next instruction in the current instruction stream
-    fn eval_next_instr<'guard>(
+    /// The interpreter loop: execute up to `max_instr` instructions. See `eval_batch()`.
+    fn eval_instrs<'guard>(
         &self,
         mem: &'guard MutatorView,
+        full_stack: &mut [TaggedCellPtr],
+        dispatch: &mut Dispatch<'guard>,
+        max_instr: ArraySize,
+        mut profiler: Option<&mut Profiler>,
     ) -> Result<EvalStatus<'guard>, RuntimeError> {
         let frames = self.frames.get(mem);
         let stack = self.stack.get(mem);
         let globals = self.globals.get(mem);
-        let instr = self.instr.get(mem);
+        let limits = mem.limits();
+        let true_sym = mem.lookup_sym("true");
 
-        // Establish a 256-register window into the stack from the stack base
-        stack.access_slice(mem, |full_stack| {
-            let stack_base = self.stack_base.get() as usize;
-            let window = &mut full_stack[stack_base..stack_base + 256];
+        // A 256-register window into the stack from the stack base, moved on frame switches
+        let stack_len = full_stack.len();
+        let mut window = &mut full_stack[dispatch.base..dispatch.base + 256];
 
+        for _ in 0..max_instr {
             // Fetch the next instruction and identify it
-            let opcode = instr.get_next_opcode(mem)?;
+            let opcode = dispatch.next_opcode()?;
+
+            // A called function gets a 256-register window from `dest`. Growing the stack would
+            // move it out from under the slice, so leave the batch to grow it and come back to
+            // this instruction, before it has been counted.
+            if let Opcode::Call { dest, .. } = opcode {
+                let size = dispatch.base + dest as usize + 256;
+                if size > stack_len {
+                    dispatch.ip -= 1;
+                    return Ok(EvalStatus::GrowStack {
+                        size: size as ArraySize,
+                    });
+                }
+            }
+
+            self.count_instruction(&limits)?;
+
+            if let Some(profiler) = profiler.as_deref_mut() {
+                profiler.instruction(&opcode);
+            }
 
             match opcode {
+                // Arithmetic - integer operands give an integer result, a float operand promotes
+                // the result to a float
                 Opcode::Add { dest, left, right } => {
-                    let left = match *window[left as usize].get(mem) {
-                        Value::Number(n) => n,
-                        _ => todo!(),
-                    };
-                    let right = match *window[right as usize].get(mem) {
-                        Value::Number(n) => n,
-                        _ => todo!(),
-                    };
-                    let result = left + right;
-                    let result = TaggedScopedPtr::new(mem, TaggedPtr::number(result));
+                    let result = eval_arithmetic(mem, window, ArithmeticOp::Add, left, right)?;
+                    window[dest as usize].set(result);
+                }
+                Opcode::Subtract { dest, left, right } => {
+                    let result = eval_arithmetic(mem, window, ArithmeticOp::Subtract, left, right)?;
                     window[dest as usize].set(result);
                 }
+                Opcode::Multiply { dest, left, right } => {
+                    let result = eval_arithmetic(mem, window, ArithmeticOp::Multiply, left, right)?;
+                    window[dest as usize].set(result);
+                }
+                Opcode::Divide { dest, left, right } => {
+                    let result = eval_arithmetic(mem, window, ArithmeticOp::Divide, left, right)?;
+                    window[dest as usize].set(result);
+                }
+                // Numeric conversions
+                Opcode::Floor { dest, reg } => {
+                    let value = Numeric::from_value(*window[reg as usize].get(mem))?;
+                    window[dest as usize].set(value.floor().as_tagged(mem)?);
+                }
+                Opcode::Round { dest, reg } => {
+                    let value = Numeric::from_value(*window[reg as usize].get(mem))?;
+                    window[dest as usize].set(value.round().as_tagged(mem)?);
+                }
+                Opcode::ToInteger { dest, reg } => {
+                    let value = Numeric::from_value(*window[reg as usize].get(mem))?;
+                    window[dest as usize].set(value.to_integer()?.as_tagged(mem)?);
+                }
+                Opcode::ToFloat { dest, reg } => {
+                    let value = Numeric::from_value(*window[reg as usize].get(mem))?;
+                    window[dest as usize].set(value.to_float().as_tagged(mem)?);
+                }
                 // Load a literal into a register from the function literals array
                 Opcode::LoadLiteral { dest, literal } => {
-                    let literal_ptr = instr.get_literal(mem, literal)?;
-                    window[dest as usize].set_to_ptr(literal_ptr);
+                    window[dest as usize].set_to_ptr(dispatch.literal(literal)?);
                 }
                 // Unconditional jump - advance the instruction pointer by `offset`
                 Opcode::Jump { offset } => {
-                    instr.jump(offset);
+                    dispatch.jump(offset);
                 }
                 Opcode::JumpIfTrue { test, offset } => {
-                    let test_val = window[test as usize].get(mem);
-                    let true_sum = mem.lookup_sym("true"); // TODO preload keyword syms
-                    if test_val == true_sum {
-                        instr.jump(offset);
+                    if window[test as usize].get(mem) == true_sym {
+                        dispatch.jump(offset);
                     }
                 }
                 Opcode::JumpIfNotTrue { test, offset } => {
-                    let test_val = window[test as usize].get(mem);
-                    let true_sum = mem.lookup_sym("true"); // TODO preload keyword syms
-                    if test_val != true_sum {
-                        instr.jump(offset);
+                    if window[test as usize].get(mem) != true_sym {
+                        dispatch.jump(offset);
                     }
                 }
                 // This operation should be generated by the compiler after a function definition
@@ -339,6 +848,10 @@ impl Thread {
 
                         Ok(())
                     })?;
+
+                    // Wrap the function and its environment in a Partial with no arguments yet
+                    let closure = Partial::alloc(mem, f, Some(env), &[])?;
+                    window[dest as usize].set(closure.as_tagged(mem));
                 }
                 Opcode::GetUpvalue { dest, src } => {
                     let closure_env = window[ENV_REG].get(mem);
@@ -355,7 +868,7 @@ impl Thread {
                         // Registers 0 and 1 cannot be closed over
                         if *reg >= FIRST_ARG_REG as u8 {
                             // calculate absolute stack offset of reg
-                            let location = stack_base as ArraySize + *reg as ArraySize;
+                            let location = (dispatch.base + *reg as usize) as ArraySize;
                             // find the Upvalue object by location
                             let (location_ptr, upvalue) = self.upvalue_lookup(mem, location)?;
                             // close it and unanchor from the Thread
@@ -371,17 +884,67 @@ impl Thread {
 
                     // remove this function's stack frame
                     frames.pop(mem)?;
+                    self.finish_generator(mem, frames.length())?;
+
+                    if let Some(profiler) = profiler.as_deref_mut() {
+                        profiler.leave();
+                    }
 
                     // if we just returned from the last stack frame, program evaluation is complete
                     if frames.length() == 0 {
                         return Ok(EvalStatus::Return(window[RETURN_REG].get(mem)));
-                    } else {
-                        // otherwise restore the previous stack frame settings
-                        let frame = frames.top(mem)?;
-                        self.stack_base.set(frame.base);
-                        instr.switch_frame(frame.function.get(mem).code(mem), frame.ip.get());
+                    }
+
+                    // otherwise restore the previous stack frame settings
+                    let frame = frames.top(mem)?;
+                    *dispatch = Dispatch::new(
+                        mem,
+                        frame.function.get(mem).code(mem),
+                        frame.ip.get(),
+                        frame.base,
+                    );
+                    window = &mut full_stack[dispatch.base..dispatch.base + 256];
+                }
+                // Suspend the innermost running Generator
+                Opcode::Yield { dest, value } => {
+                    return Ok(EvalStatus::Yield {
+                        dest,
+                        value: window[value as usize].get(mem),
+                    });
+                }
+                // Set up an error handler: if an error happens before the matching `Unprotect`,
+                // the error message is put in the `dest` register and execution continues at
+                // `offset` from the next instruction
+                Opcode::Protect { dest, offset } => {
+                    let handler = [
+                        frames.length() as isize,
+                        self.generators.get(mem).length() as isize,
+                        dispatch.ip as isize + offset as isize,
+                        dispatch.base as isize + dest as isize,
+                    ];
+
+                    let handlers = self.handlers.get(mem);
+                    for value in handler {
+                        StackAnyContainer::push(&*handlers, mem, mem.number(value))?;
+                    }
+                }
+                // Remove the innermost error handler
+                Opcode::Unprotect => {
+                    let handlers = self.handlers.get(mem);
+                    for _ in 0..HANDLER_SIZE {
+                        StackAnyContainer::pop(&*handlers, mem)?;
                     }
                 }
+                // Raise an assertion failure. The `reason` register holds a value, or a list of
+                // values to display one after another, and the `pos` register a pair of the line
+                // and column of the assertion, or nil.
+                Opcode::Fail { reason, pos } => {
+                    return Err(assertion_failure(
+                        mem,
+                        window[reason as usize].get(mem),
+                        window[pos as usize].get(mem),
+                    ));
+                }
                 // Set the register `dest` to `nil`
                 Opcode::LoadNil { dest } => {
                     window[dest as usize].set_to_nil();
@@ -415,7 +978,7 @@ impl Thread {
                         Value::Pair(_) => window[dest as usize].set_to_nil(),
                         Value::Nil => window[dest as usize].set_to_nil(),
                         // TODO what other types?
-                        _ => window[dest as usize].set(mem.lookup_sym("true")),
+                        _ => window[dest as usize].set(true_sym),
                     }
                 }
                 // Evaluate whether the `test` register contains `nil` - if so, set the `dest`
@@ -424,7 +987,7 @@ impl Thread {
                     let test_val = window[test as usize].get(mem);
 
                     match *test_val {
-                        Value::Nil => window[dest as usize].set(mem.lookup_sym("true")),
+                        Value::Nil => window[dest as usize].set(true_sym),
                         _ => window[dest as usize].set_to_nil(),
                     }
                 }
@@ -467,19 +1030,137 @@ impl Thread {
                     let test2_val = window[test2 as usize].get_ptr();
 
                     if test1_val == test2_val {
-                        window[dest as usize].set(mem.lookup_sym("true"));
+                        window[dest as usize].set(true_sym);
+                    } else {
+                        window[dest as usize].set(mem.nil());
+                    }
+                }
+                // Structural comparison - if `test1` and `test2` are `equal`, set `dest` to the
+                // symbol "true"
+                Opcode::IsEqual { dest, test1, test2 } => {
+                    let test1_val = window[test1 as usize].get(mem);
+                    let test2_val = window[test2 as usize].get(mem);
+
+                    if equal(mem, test1_val, test2_val) {
+                        window[dest as usize].set(true_sym);
+                    } else {
+                        window[dest as usize].set(mem.nil());
+                    }
+                }
+                // Allocate a record of the type described by the List in `descriptor`, taking the
+                // field values from the registers starting at `first`
+                Opcode::MakeRecord {
+                    dest,
+                    descriptor,
+                    first,
+                } => {
+                    let descriptor = match *window[descriptor as usize].get(mem) {
+                        Value::List(list) => list,
+                        _ => return Err(err_eval("Record type descriptor must be a List")),
+                    };
+
+                    let first = first as usize;
+                    let last = first + descriptor.length() as usize - 1;
+                    let values: Vec<TaggedScopedPtr> =
+                        window[first..last].iter().map(|reg| reg.get(mem)).collect();
+
+                    let record = Record::alloc(mem, descriptor, &values)?;
+                    window[dest as usize].set(record.as_tagged(mem));
+                }
+                // Set `dest` to the symbol "true" if `test` is a record of the type described by
+                // `descriptor`
+                Opcode::IsRecord {
+                    dest,
+                    test,
+                    descriptor,
+                } => {
+                    let descriptor = window[descriptor as usize].get(mem);
+                    let is_instance = match *window[test as usize].get(mem) {
+                        Value::Record(record) => record.is_instance(mem, descriptor),
+                        _ => false,
+                    };
+
+                    if is_instance {
+                        window[dest as usize].set(true_sym);
                     } else {
                         window[dest as usize].set(mem.nil());
                     }
                 }
+                // Raise an error unless `record` is a record of the type described by `descriptor`
+                Opcode::CheckRecord { record, descriptor } => {
+                    let descriptor = window[descriptor as usize].get(mem);
+                    let value = window[record as usize].get(mem);
+
+                    let is_instance = match *value {
+                        Value::Record(record) => record.is_instance(mem, descriptor),
+                        _ => false,
+                    };
+
+                    if !is_instance {
+                        let type_name = match *descriptor {
+                            Value::List(list) => IndexedAnyContainer::get(&*list, mem, 0)?,
+                            _ => descriptor,
+                        };
+                        return Err(err_eval(&format!(
+                            "Expected a {} record, got {}",
+                            type_name, value
+                        )));
+                    }
+                }
+                // Read the field at `index` of a record
+                Opcode::GetField {
+                    dest,
+                    record,
+                    index,
+                } => match *window[record as usize].get(mem) {
+                    Value::Record(record) => {
+                        window[dest as usize].set(record.get(mem, index as ArraySize)?)
+                    }
+                    _ => return Err(err_eval("Cannot read a field of a non-record type")),
+                },
+                // Write the field at `index` of a record
+                Opcode::SetField { record, index, src } => {
+                    match *window[record as usize].get(mem) {
+                        Value::Record(record) => {
+                            record.set(mem, index as ArraySize, window[src as usize].get(mem))?
+                        }
+                        _ => return Err(err_eval("Cannot write a field of a non-record type")),
+                    }
+                }
                 // Bind a symbol to the `src` register in the globals dict
                 Opcode::StoreGlobal { src, name } => {
                     let name_val = window[name as usize].get(mem);
-                    if let Value::Symbol(_) = *name_val {
-                        let src_val = window[src as usize].get(mem);
-                        globals.assoc(mem, name_val, src_val)?;
-                    } else {
-                        return Err(err_eval("Cannot bind global to non-symbol type"));
+                    match *name_val {
+                        Value::Symbol(s) if s.is_keyword() => {
+                            return Err(err_eval("A keyword cannot be bound to a value"));
+                        }
+                        Value::Symbol(_) => {
+                            let src_val = window[src as usize].get(mem);
+                            globals.assoc(mem, name_val, src_val)?;
+                        }
+                        _ => return Err(err_eval("Cannot bind global to non-symbol type")),
+                    }
+                }
+                // Register the Function in `src` as a test, under the symbol in the `name`
+                // register. A test defined again under the same name replaces the earlier one.
+                Opcode::RegisterTest { src, name } => {
+                    let name_val = window[name as usize].get(mem);
+                    let src_val = window[src as usize].get(mem);
+                    let tests = self.tests.get(mem);
+
+                    let existing = tests.access_slice(mem, |tests| {
+                        tests.iter().find_map(|test| match *test.get(mem) {
+                            Value::Pair(test) if test.first.get(mem) == name_val => Some(test),
+                            _ => None,
+                        })
+                    });
+                    match existing {
+                        Some(test) => test.second.set(src_val),
+                        None => StackAnyContainer::push(
+                            &*tests,
+                            mem,
+                            Pair::cons(mem, name_val, src_val)?,
+                        )?,
                     }
                 }
                 // Simple copy of one register to another
@@ -501,137 +1182,432 @@ impl Thread {
                     dest,
                     arg_count,
                 } => {
+                    // the register stack has already been checked to be big enough, see above
                     let binding = window[function as usize].get(mem);
+                    let status = self.call(
+                        mem,
+                        window,
+                        dispatch,
+                        binding,
+                        dest,
+                        arg_count,
+                        profiler.as_deref_mut(),
+                    )?;
+
+                    match status {
+                        EvalStatus::Pending => {
+                            window = &mut full_stack[dispatch.base..dispatch.base + 256]
+                        }
+                        status => return Ok(status),
+                    }
+                }
+            }
+        }
 
-                    // To avoid duplicating code in function and partial application cases,
-                    // this is declared as a closure so it can access local variables
-                    let new_call_frame = |function| -> Result<(), RuntimeError> {
-                        // Modify the current call frame, saving the return ip
-                        let current_frame_ip = instr.get_next_ip();
-                        frames.access_slice(mem, |f| {
-                            f.last()
-                                .expect("No CallFrames in slice!")
-                                .ip
-                                .set(current_frame_ip)
-                        });
-
-                        // Create a new call frame, pushing it to the frame stack
-                        let new_stack_base = self.stack_base.get() + dest as ArraySize;
-                        let frame = CallFrame::new(function, 0, new_stack_base);
-                        frames.push(mem, frame)?;
-
-                        // Update the instruction stream to point to the new function
-                        let code = function.code(mem);
-                        self.stack_base.set(new_stack_base);
-                        instr.switch_frame(code, 0);
-
-                        // Ensure the stack has 256 registers allocated
-                        // TODO reset to nil to avoid accidental leakage of previous call values
-                        // TODO Ruh-roh we shouldn't be able to modify the stack size from
-                        // within an access_slice() call :grimace:
-                        stack.fill(mem, new_stack_base + 256, mem.nil())?;
+        Ok(EvalStatus::Pending)
+    }
 
-                        Ok(())
-                    };
+    /// Call the value `binding` with the `arg_count` arguments in the registers following `dest`,
+    /// putting the result in the `dest` register. See the `Call` instruction.
+    ///
+    /// Entering a Function switches `dispatch` to it. The register stack must already hold the
+    /// 256 registers from `dest` that the Function's window needs.
+    #[allow(clippy::too_many_arguments)]
+    fn call<'guard>(
+        &self,
+        mem: &'guard MutatorView,
+        window: &mut [TaggedCellPtr],
+        dispatch: &mut Dispatch<'guard>,
+        binding: TaggedScopedPtr<'guard>,
+        dest: Register,
+        arg_count: NumArgs,
+        mut profiler: Option<&mut Profiler>,
+    ) -> Result<EvalStatus<'guard>, RuntimeError> {
+        let frames = self.frames.get(mem);
 
-                    // Handle the two similar-but-different cases: this might be a Function object
-                    // or a Partial application object
-                    match *binding {
-                        Value::Function(function) => {
-                            let arity = function.arity();
-
-                            if arg_count < arity {
-                                // Too few args, return a Partial object
-                                let args_start = dest as usize + FIRST_ARG_REG;
-                                let args_end = args_start + arg_count as usize;
-
-                                let partial = Partial::alloc(
-                                    mem,
-                                    function,
-                                    None,
-                                    &window[args_start..args_end],
-                                )?;
-
-                                window[dest as usize].set(partial.as_tagged(mem));
-
-                                return Ok(EvalStatus::Pending);
-                            } else if arg_count > arity {
-                                // Too many args, we haven't got a continuations stack (yet)
-                                return Err(err_eval(&format!(
-                                    "Function {} expected {} arguments, got {}",
-                                    binding,
-                                    function.arity(),
-                                    arg_count
-                                )));
-                            }
+        // To avoid duplicating code in function and partial application cases,
+        // this is declared as a closure so it can access local variables
+        let mut new_call_frame =
+            |function: ScopedPtr<'guard, Function>| -> Result<(), RuntimeError> {
+                // Modify the current call frame, saving the return ip
+                let current_frame_ip = dispatch.ip as ArraySize;
+                frames.access_slice(mem, |f| {
+                    f.last()
+                        .expect("No CallFrames in slice!")
+                        .ip
+                        .set(current_frame_ip)
+                });
+
+                // Create a new call frame, pushing it to the frame stack
+                // TODO reset the new window to nil to avoid accidental leakage of previous call values
+                let new_stack_base = (dispatch.base + dest as usize) as ArraySize;
+                let frame = CallFrame::new(function, 0, new_stack_base);
+                self.push_frame(mem, frame)?;
+
+                // Continue in the new function
+                *dispatch = Dispatch::new(mem, function.code(mem), 0, new_stack_base);
+
+                Ok(())
+            };
+
+        // Handle the two similar-but-different cases: this might be a Function object
+        // or a Partial application object
+        match *binding {
+            Value::Function(function) => {
+                let arity = function.arity();
+
+                if arg_count < arity {
+                    // Too few args, return a Partial object
+                    let args_start = dest as usize + FIRST_ARG_REG;
+                    let args_end = args_start + arg_count as usize;
+
+                    let partial =
+                        Partial::alloc(mem, function, None, &window[args_start..args_end])?;
+
+                    window[dest as usize].set(partial.as_tagged(mem));
+
+                    return Ok(EvalStatus::Pending);
+                } else if arg_count > arity && !function.takes_extra_args() {
+                    // Too many args, we haven't got a continuations stack (yet)
+                    return Err(err_eval(&format!(
+                        "Function {} expected {} arguments, got {}",
+                        binding,
+                        function.arity(),
+                        arg_count
+                    )));
+                }
 
-                            new_call_frame(function)?;
-                        }
+                if function.takes_extra_args() {
+                    let args_start = dest as usize + FIRST_ARG_REG;
+                    bind_arguments(mem, function, window, args_start, arg_count)?;
+                }
 
-                        Value::Partial(partial) => {
-                            let arity = partial.arity();
-
-                            if arg_count == 0 && arity > 0 {
-                                // Partial is unchanged, no args added, copy directly to dest
-                                window[dest as usize]
-                                    .set_to_ptr(window[function as usize].get_ptr());
-                                return Ok(EvalStatus::Pending);
-                            } else if arg_count < arity {
-                                // Too few args, bake a new Partial from the existing one, adding the new
-                                // arguments
-                                let args_start = dest as usize + FIRST_ARG_REG;
-                                let args_end = args_start + arg_count as usize;
-
-                                let new_partial = Partial::alloc_clone(
-                                    mem,
-                                    partial,
-                                    &window[args_start..args_end],
-                                )?;
-
-                                window[dest as usize].set(new_partial.as_tagged(mem));
-
-                                return Ok(EvalStatus::Pending);
-                            } else if arg_count > arity {
-                                // Too many args, we haven't got a continuations stack
-                                return Err(err_eval(&format!(
-                                    "Partial {} expected {} arguments, got {}",
-                                    binding,
-                                    partial.arity(),
-                                    arg_count
-                                )));
-                            }
+                new_call_frame(function)?;
 
-                            // Copy closure env pointer
-                            window[dest as usize + ENV_REG] = partial.closure_env();
+                if let Some(profiler) = profiler.as_deref_mut() {
+                    profiler.enter(function.name(mem));
+                }
+            }
 
-                            // Shunt _call_ args back into the window to make space for the
-                            // partially applied args
-                            let push_dist = partial.used();
-                            let from_reg = dest as usize + FIRST_ARG_REG;
-                            let to_reg = from_reg + push_dist as usize;
-                            for index in (0..arg_count as usize).rev() {
-                                window[to_reg + index] = window[from_reg + index].clone();
-                            }
+            Value::Partial(partial) => {
+                let arity = partial.arity();
+
+                if arg_count == 0 && arity > 0 {
+                    // Partial is unchanged, no args added, copy directly to dest
+                    window[dest as usize].set(binding);
+                    return Ok(EvalStatus::Pending);
+                } else if arg_count < arity {
+                    // Too few args, bake a new Partial from the existing one, adding the new
+                    // arguments
+                    let args_start = dest as usize + FIRST_ARG_REG;
+                    let args_end = args_start + arg_count as usize;
+
+                    let new_partial =
+                        Partial::alloc_clone(mem, partial, &window[args_start..args_end])?;
+
+                    window[dest as usize].set(new_partial.as_tagged(mem));
+
+                    return Ok(EvalStatus::Pending);
+                } else if arg_count > arity && !partial.function(mem).takes_extra_args() {
+                    // Too many args, we haven't got a continuations stack
+                    return Err(err_eval(&format!(
+                        "Partial {} expected {} arguments, got {}",
+                        binding,
+                        partial.arity(),
+                        arg_count
+                    )));
+                }
 
-                            // copy args from Partial to the register window
-                            let args = partial.args(mem);
-                            let start_reg = dest as usize + FIRST_ARG_REG;
-                            args.access_slice(mem, |items| {
-                                for (index, item) in items.iter().enumerate() {
-                                    window[start_reg + index] = item.clone();
-                                }
-                            });
+                // Copy closure env pointer
+                window[dest as usize + ENV_REG] = partial.closure_env();
 
-                            new_call_frame(partial.function(mem))?;
-                        }
+                // Shunt _call_ args back into the window to make space for the
+                // partially applied args
+                let push_dist = partial.used();
+                let from_reg = dest as usize + FIRST_ARG_REG;
+                let to_reg = from_reg + push_dist as usize;
+                for index in (0..arg_count as usize).rev() {
+                    window[to_reg + index] = window[from_reg + index].clone();
+                }
 
-                        _ => return Err(err_eval("Type is not callable")),
+                // copy args from Partial to the register window
+                let args = partial.args(mem);
+                let start_reg = dest as usize + FIRST_ARG_REG;
+                args.access_slice(mem, |items| {
+                    for (index, item) in items.iter().enumerate() {
+                        window[start_reg + index] = item.clone();
                     }
+                });
+
+                let function = partial.function(mem);
+                if function.takes_extra_args() {
+                    let total = partial.used() + arg_count;
+                    bind_arguments(mem, function, window, start_reg, total)?;
+                }
+
+                new_call_frame(function)?;
+
+                if let Some(profiler) = profiler {
+                    profiler.enter(function.name(mem));
                 }
             }
 
-            Ok(EvalStatus::Pending)
-        })
+            Value::Generator(generator) => {
+                // The Thread switches to the Generator once this instruction is complete
+                let value = match arg_count {
+                    0 => mem.nil(),
+                    1 => window[dest as usize + FIRST_ARG_REG].get(mem),
+                    _ => {
+                        return Err(err_eval(&format!(
+                            "Generator expected at most 1 argument, got {}",
+                            arg_count
+                        )))
+                    }
+                };
+
+                return Ok(EvalStatus::Resume {
+                    generator,
+                    dest,
+                    value,
+                });
+            }
+
+            Value::NativeFunction(native) => {
+                // Native functions run to completion without a call frame
+                let args_start = dest as usize + FIRST_ARG_REG;
+                let args_end = args_start + arg_count as usize;
+                let args: Vec<TaggedScopedPtr> = window[args_start..args_end]
+                    .iter()
+                    .map(|arg| arg.get(mem))
+                    .collect();
+
+                if let Some(profiler) = profiler {
+                    profiler.enter(native.name());
+                    profiler.leave();
+                }
+
+                let result = native.call(mem, &args)?;
+                window[dest as usize].set(result);
+            }
+
+            _ => return Err(err_eval("Type is not callable")),
+        }
+
+        Ok(EvalStatus::Pending)
+    }
+
+    /// Switch to a Generator: start it by calling its function or restore its saved frames and
+    /// registers above the current frame. What it yields or returns goes in the `dest` register
+    /// and `value` is the result of the `yield` it is resuming from.
+    fn resume_generator<'guard>(
+        &self,
+        mem: &'guard MutatorView,
+        generator: ScopedPtr<'guard, Generator>,
+        dest: Register,
+        value: TaggedScopedPtr<'guard>,
+        mut profiler: Option<&mut Profiler>,
+    ) -> Result<EvalStatus<'guard>, RuntimeError> {
+        let frames = self.frames.get(mem);
+        let stack = self.stack.get(mem);
+        let instr = self.instr.get(mem);
+
+        let state = generator.state.get();
+        match state {
+            GeneratorState::Running => return Err(err_eval("Generator is already running")),
+            GeneratorState::Done => return Err(err_eval("Generator has finished")),
+            _ => (),
+        }
+
+        // the Generator's frames sit on top of the current ones
+        let depth = frames.length();
+        generator.depth.set(depth);
+        generator.state.set(GeneratorState::Running);
+        StackAnyContainer::push(&*self.generators.get(mem), mem, generator.as_tagged(mem))?;
+
+        if state == GeneratorState::Ready {
+            // call the function as if by a Call instruction with the arguments after `dest`
+            let args = generator.args.get(mem);
+            let arg_count = args.length() as usize;
+            if dest as usize + FIRST_ARG_REG + arg_count > 256 {
+                return Err(err_eval("Too many generator arguments"));
+            }
+
+            // the function's register window starts at `dest`
+            let stack_base = self.stack_base.get();
+            stack.fill(mem, stack_base + dest as ArraySize + 256, mem.nil())?;
+
+            stack.access_slice(mem, |full_stack| {
+                let mut dispatch = Dispatch::load(mem, self);
+                let window = &mut full_stack[dispatch.base..dispatch.base + 256];
+
+                args.access_slice(mem, |items| {
+                    let start = dest as usize + FIRST_ARG_REG;
+                    window[start..start + arg_count].clone_from_slice(items);
+                });
+
+                let function = generator.function.get(mem);
+                let status = self.call(
+                    mem,
+                    window,
+                    &mut dispatch,
+                    function,
+                    dest,
+                    arg_count as NumArgs,
+                    profiler,
+                );
+                dispatch.save(mem, self);
+                status
+            })?;
+
+            // a native function or a partial application completes without entering a frame
+            if frames.length() == depth {
+                self.finish_generator(mem, depth)?;
+            }
+
+            return Ok(EvalStatus::Pending);
+        }
+
+        // save the return ip of the current frame
+        let return_ip = instr.get_next_ip();
+        frames.access_slice(mem, |f| {
+            f.last().expect("No CallFrames in slice!").ip.set(return_ip)
+        });
+
+        // move the saved registers back onto the stack
+        let base = self.stack_base.get() + dest as ArraySize;
+        let registers = generator.registers.get(mem);
+        stack.fill(mem, base + registers.length(), mem.nil())?;
+
+        registers.access_slice(mem, |saved| {
+            stack.access_slice(mem, |full_stack| {
+                let base = base as usize;
+                full_stack[base..base + saved.len()].clone_from_slice(saved);
+            })
+        });
+        IndexedContainer::set(
+            &*stack,
+            mem,
+            base + generator.resume_reg.get(),
+            TaggedCellPtr::new_with(value),
+        )?;
+
+        // and the saved frames back onto the frame stack
+        let saved_frames = generator.frames.get(mem);
+        for index in 0..saved_frames.length() {
+            let frame = IndexedContainer::get(&*saved_frames, mem, index)?;
+            let function = frame.function.get(mem);
+            self.push_frame(
+                mem,
+                CallFrame::new(function, frame.ip.get(), base + frame.base),
+            )?;
+
+            if let Some(profiler) = profiler.as_deref_mut() {
+                profiler.enter(function.name(mem));
+            }
+        }
+
+        // continue in the frame that yielded
+        let frame = frames.top(mem)?;
+        self.stack_base.set(frame.base);
+        instr.switch_frame(frame.function.get(mem).code(mem), frame.ip.get());
+
+        Ok(EvalStatus::Pending)
+    }
+
+    /// Move the frames and registers of the innermost running Generator off the Thread and into
+    /// the Generator, and return `value` to the frame that resumed it. The `dest` register of
+    /// the yielding frame will receive the value the Generator is next resumed with.
+    fn suspend_generator<'guard>(
+        &self,
+        mem: &'guard MutatorView,
+        dest: Register,
+        value: TaggedScopedPtr<'guard>,
+        mut profiler: Option<&mut Profiler>,
+    ) -> Result<EvalStatus<'guard>, RuntimeError> {
+        let frames = self.frames.get(mem);
+        let stack = self.stack.get(mem);
+        let instr = self.instr.get(mem);
+
+        let generator = match StackAnyContainer::pop(&*self.generators.get(mem), mem) {
+            Ok(generator) => match *generator {
+                Value::Generator(generator) => generator,
+                _ => return Err(err_eval("Thread generators list is corrupt")),
+            },
+            Err(_) => return Err(err_eval("Cannot yield outside of a generator")),
+        };
+
+        // save the resume ip of the yielding frame
+        let resume_ip = instr.get_next_ip();
+        frames.access_slice(mem, |f| {
+            f.last().expect("No CallFrames in slice!").ip.set(resume_ip)
+        });
+
+        // save the Generator's frames with their stack bases relative to its first register
+        let depth = generator.depth.get();
+        let base = IndexedContainer::get(&*frames, mem, depth)?.base;
+        let saved_frames = CallFrameList::alloc_with_capacity(mem, frames.length() - depth)?;
+        for index in depth..frames.length() {
+            let frame = IndexedContainer::get(&*frames, mem, index)?;
+            let function = frame.function.get(mem);
+            saved_frames.push(
+                mem,
+                CallFrame::new(function, frame.ip.get(), frame.base - base),
+            )?;
+
+            if let Some(profiler) = profiler.as_deref_mut() {
+                profiler.leave();
+            }
+        }
+
+        // save every register up to the end of the yielding frame's window
+        let top_base = self.stack_base.get();
+        let registers: Vec<TaggedScopedPtr> = stack.access_slice(mem, |full_stack| {
+            full_stack[base as usize..top_base as usize + 256]
+                .iter()
+                .map(|reg| reg.get(mem))
+                .collect()
+        });
+
+        generator.frames.set(saved_frames);
+        generator.registers.set(List::from_slice(mem, &registers)?);
+        generator
+            .resume_reg
+            .set(top_base - base + dest as ArraySize);
+        generator.state.set(GeneratorState::Suspended);
+
+        // return to the frame that resumed the Generator, the yielded value going in the
+        // register the Generator was called into
+        while frames.length() > depth {
+            frames.pop(mem)?;
+        }
+        IndexedContainer::set(&*stack, mem, base, TaggedCellPtr::new_with(value))?;
+
+        let frame = frames.top(mem)?;
+        self.stack_base.set(frame.base);
+        instr.switch_frame(frame.function.get(mem).code(mem), frame.ip.get());
+
+        Ok(EvalStatus::Pending)
+    }
+
+    /// If the frame that just returned was the first frame of the innermost running Generator,
+    /// the Generator is finished. `depth` is the number of frames remaining.
+    fn finish_generator<'guard>(
+        &self,
+        guard: &'guard dyn MutatorScope,
+        depth: ArraySize,
+    ) -> Result<(), RuntimeError> {
+        let generators = self.generators.get(guard);
+
+        if let Ok(top) = StackAnyContainer::top(&*generators, guard) {
+            if let Value::Generator(generator) = *top {
+                if generator.depth.get() == depth {
+                    generator.state.set(GeneratorState::Done);
+                    StackAnyContainer::pop(&*generators, guard)?;
+                }
+            }
+        }
+
+        Ok(())
     }
 
     /// Retrieve an Upvalue for the given absolute stack offset or allocate a new one if none was
@@ -712,6 +1688,109 @@ impl CallFrame {
     }
 }
 
+/// Apply an arithmetic operation to the numbers in the `left` and `right` registers
+/// Rearrange the arguments of a call to a Function that takes optional, rest or keyword
+/// parameters so that each parameter register holds its value. The `count` arguments start at
+/// `start` in the register window, the required arguments are already in place.
+///
+/// Extra arguments are collected into a list for the rest parameter, if there is one, and are
+/// otherwise matched up as `:keyword value` pairs with the keyword parameters. Optional and
+/// keyword parameters that are not given an argument are bound to the unsupplied Symbol so that
+/// the function code can evaluate their defaults.
+fn bind_arguments<'guard>(
+    mem: &'guard MutatorView,
+    function: ScopedPtr<'guard, Function>,
+    window: &mut [TaggedCellPtr],
+    start: usize,
+    count: u8,
+) -> Result<(), RuntimeError> {
+    let count = count as usize;
+    let positional = function.arity() as usize + function.optional() as usize;
+    let keywords = function.keywords(mem);
+
+    let extras: Vec<TaggedPtr> = window[start + positional.min(count)..start + count]
+        .iter()
+        .map(|arg| arg.get_ptr())
+        .collect();
+
+    if !extras.is_empty() && !function.has_rest() && keywords.is_none() {
+        return Err(err_eval(&format!(
+            "Function {} expected at most {} arguments, got {}",
+            function.name(mem),
+            positional,
+            count
+        )));
+    }
+
+    let unsupplied = mem.lookup_sym(UNSUPPLIED);
+    if count < positional {
+        for arg in &mut window[start + count..start + positional] {
+            arg.set(unsupplied);
+        }
+    }
+
+    let mut next_reg = start + positional;
+
+    if function.has_rest() {
+        let mut rest = mem.nil();
+        for arg in extras.iter().rev() {
+            let pair = Pair::new();
+            pair.first.set_to_ptr(*arg);
+            pair.second.set(rest);
+            rest = mem.alloc_tagged(pair)?;
+        }
+        window[next_reg].set(rest);
+        next_reg += 1;
+    }
+
+    if let Some(keywords) = keywords {
+        if extras.len() & 1 == 1 {
+            return Err(err_eval(&format!(
+                "Function {} expected :keyword value pairs after its positional arguments",
+                function.name(mem)
+            )));
+        }
+
+        let keywords = keywords.access_slice(mem, |keywords| {
+            keywords
+                .iter()
+                .map(|k| k.get_ptr())
+                .collect::<Vec<TaggedPtr>>()
+        });
+
+        for arg in &mut window[next_reg..next_reg + keywords.len()] {
+            arg.set(unsupplied);
+        }
+
+        for pair in extras.chunks(2) {
+            match keywords.iter().position(|keyword| *keyword == pair[0]) {
+                Some(index) => window[next_reg + index].set_to_ptr(pair[1]),
+                None => {
+                    return Err(err_eval(&format!(
+                        "Function {} has no keyword parameter {}",
+                        function.name(mem),
+                        TaggedScopedPtr::new(mem, pair[0])
+                    )))
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn eval_arithmetic<'guard>(
+    mem: &'guard MutatorView,
+    window: &[TaggedCellPtr],
+    op: ArithmeticOp,
+    left: Register,
+    right: Register,
+) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+    let left = Numeric::from_value(*window[left as usize].get(mem))?;
+    let right = Numeric::from_value(*window[right as usize].get(mem))?;
+    Numeric::arithmetic(op, left, right)?.as_tagged(mem)
+}
+
 /// Get the Upvalue for the index into the given closure environment.
 /// Function will panic if types are not as expected.
 fn env_upvalue_lookup<'guard>(
@@ -731,3 +1810,527 @@ fn env_upvalue_lookup<'guard>(
         _ => unreachable!(),
     }
 }
+
+/// Build the error raised by a `Fail` instruction. The reason is either a value to display, or
+/// a list of a format string and the values to print in place of each `{}` in it.
+fn assertion_failure<'guard>(
+    guard: &'guard dyn MutatorScope,
+    reason: TaggedScopedPtr<'guard>,
+    pos: TaggedScopedPtr<'guard>,
+) -> RuntimeError {
+    let message = match *reason {
+        Value::Pair(pair) => {
+            let format = display(*pair.first.get(guard));
+            let mut values = pair.second.get(guard);
+
+            let mut message = String::new();
+            let mut pieces = format.split("{}").peekable();
+            while let Some(piece) = pieces.next() {
+                message.push_str(piece);
+
+                if pieces.peek().is_some() {
+                    if let Value::Pair(value) = *values {
+                        message.push_str(&print(*value.first.get(guard)));
+                        values = value.second.get(guard);
+                    }
+                }
+            }
+            message
+        }
+        _ => display(*reason),
+    };
+
+    let kind = ErrorKind::AssertionFailed(message);
+    match *pos {
+        Value::Pair(pair) => match (*pair.first.get(guard), *pair.second.get(guard)) {
+            (Value::Number(line), Value::Number(column)) => {
+                RuntimeError::with_pos(kind, spos(line as u32, column as u32))
+            }
+            _ => RuntimeError::new(kind),
+        },
+        _ => RuntimeError::new(kind),
+    }
+}
+
+/// Native functions for creating and inspecting Generators
+pub static GENERATOR_NATIVES: [NativeSpec; 2] = [
+    NativeSpec {
+        name: "generator",
+        min_args: 1,
+        max_args: None,
+        function: make_generator,
+    },
+    NativeSpec {
+        name: "done?",
+        min_args: 1,
+        max_args: Some(1),
+        function: generator_done,
+    },
+];
+
+/// (generator function arg1 .. argn) returns a Generator that calls the function with the
+/// arguments when it is first resumed
+fn make_generator<'guard>(
+    mem: &'guard MutatorView,
+    args: &[TaggedScopedPtr<'guard>],
+) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+    Ok(Generator::alloc(mem, args[0], &args[1..])?.as_tagged(mem))
+}
+
+/// (done? generator) returns true if the Generator's function has returned
+fn generator_done<'guard>(
+    mem: &'guard MutatorView,
+    args: &[TaggedScopedPtr<'guard>],
+) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+    match *args[0] {
+        Value::Generator(generator) if generator.is_done() => Ok(mem.lookup_sym("true")),
+        Value::Generator(_) => Ok(mem.nil()),
+        _ => Err(err_eval("done? expected a generator")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    use crate::interpreter::compiler::compile;
+    use crate::interpreter::memory::Memory;
+    use crate::interpreter::parser::parse;
+    use crate::interpreter::printer::{display, print};
+    use crate::interpreter::Mutator;
+
+    fn eval_helper<'guard>(
+        mem: &'guard MutatorView,
+        thread: ScopedPtr<'guard, Thread>,
+        code: &str,
+    ) -> Result<TaggedScopedPtr<'guard>, RuntimeError> {
+        let function = compile(mem, parse(mem, code)?)?;
+        thread.quick_vm_eval(mem, function)
+    }
+
+    fn test_helper(test_fn: fn(&MutatorView) -> Result<(), RuntimeError>) {
+        limits_helper(Limits::none(), test_fn)
+    }
+
+    fn limits_helper(limits: Limits, test_fn: fn(&MutatorView) -> Result<(), RuntimeError>) {
+        let mem = Memory::new();
+        mem.set_limits(limits);
+
+        struct Test {}
+        impl Mutator for Test {
+            type Input = fn(&MutatorView) -> Result<(), RuntimeError>;
+            type Output = ();
+
+            fn run(
+                &self,
+                mem: &MutatorView,
+                test_fn: Self::Input,
+            ) -> Result<Self::Output, RuntimeError> {
+                test_fn(mem)
+            }
+        }
+
+        let test = Test {};
+        mem.mutate(&test, test_fn).unwrap();
+    }
+
+    #[test]
+    fn generator_yields_across_frames() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+
+            // each yield happens one recursive call deeper than the last
+            eval_helper(
+                mem,
+                t,
+                "(def count-to (n end)
+                   (if (is? n end)
+                     n
+                     (begin (yield n) (count-to (+ n 1) end))))",
+            )?;
+            eval_helper(mem, t, "(set (quote g) (generator count-to 0 3))")?;
+
+            assert_eq!(print(*eval_helper(mem, t, "g")?), "(Generator ready)");
+            for expected in 0..4 {
+                assert!(eval_helper(mem, t, "(done? g)")? == mem.nil());
+                assert!(eval_helper(mem, t, "(g)")? == mem.number(expected));
+            }
+            assert!(eval_helper(mem, t, "(done? g)")? == mem.lookup_sym("true"));
+            assert!(eval_helper(mem, t, "(g)").is_err());
+
+            // a generator may be resumed from inside another function, or another generator
+            eval_helper(
+                mem,
+                t,
+                "(def drain (g) (if (done? g) nil (cons (g) (drain g))))",
+            )?;
+            eval_helper(
+                mem,
+                t,
+                "(def doubled (g)
+                   (if (done? g)
+                     (quote end)
+                     (begin (yield (* 2 (g))) (doubled g))))",
+            )?;
+            let result = eval_helper(
+                mem,
+                t,
+                "(drain (generator doubled (generator count-to 0 3)))",
+            )?;
+            assert_eq!(print(*result), "(0 2 4 6 end)");
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn generator_resume_values() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+
+            // the value a generator is resumed with is the result of the yield
+            eval_helper(mem, t, "(def echo (x) (echo (yield x)))")?;
+            eval_helper(mem, t, "(set (quote e) (generator echo 1))")?;
+            assert!(eval_helper(mem, t, "(e)")? == mem.number(1));
+            assert!(eval_helper(mem, t, "(e 5)")? == mem.number(5));
+            assert_eq!(print(*eval_helper(mem, t, "(e (quote (7)))")?), "(7)");
+            assert_eq!(print(*eval_helper(mem, t, "e")?), "(Generator suspended)");
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn generator_errors() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+
+            assert!(eval_helper(mem, t, "(yield 1)").is_err());
+            assert!(eval_helper(mem, t, "(generator 1)").is_err());
+
+            // a generator that fails can't be resumed again
+            eval_helper(mem, t, "(def fails (x) (begin (yield x) (car x)))")?;
+            eval_helper(mem, t, "(set (quote f) (generator fails 1))")?;
+            assert!(eval_helper(mem, t, "(f)")? == mem.number(1));
+            assert!(eval_helper(mem, t, "(f)").is_err());
+            assert!(eval_helper(mem, t, "(done? f)")? == mem.lookup_sym("true"));
+
+            // the thread is still usable afterwards
+            assert!(eval_helper(mem, t, "(+ 1 2)")? == mem.number(3));
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn error_traceback() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+
+            // an error at the top level has no frames to report
+            let err = eval_helper(mem, t, "(car 1)").unwrap_err();
+            assert!(err.traceback().is_empty());
+
+            eval_helper(mem, t, "(def inner (x) (car x))")?;
+            eval_helper(mem, t, "(def outer (x) (cons (inner x) nil))")?;
+            let err = eval_helper(mem, t, "(outer 1)").unwrap_err();
+            // one entry per function frame, outermost first
+            let traceback = err.traceback();
+            assert_eq!(traceback.len(), 2);
+            assert!(traceback.iter().all(|frame| frame.starts_with("in ")));
+
+            // the next evaluation starts from a clean stack
+            assert!(eval_helper(mem, t, "(outer (quote (1)))")? != mem.nil());
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn instruction_limit() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+
+            assert_eq!(
+                *eval_helper(mem, t, "(while true nil)")
+                    .unwrap_err()
+                    .error_kind(),
+                ErrorKind::InstructionLimit
+            );
+
+            // the count starts again for each evaluation
+            for _ in 0..3 {
+                assert!(eval_helper(mem, t, "(+ 1 2)")? == mem.number(3));
+            }
+
+            Ok(())
+        }
+
+        let limits = Limits {
+            max_instructions: Some(10_000),
+            ..Limits::none()
+        };
+        limits_helper(limits, test_inner);
+    }
+
+    #[test]
+    fn call_depth_limit() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+
+            eval_helper(
+                mem,
+                t,
+                "(def down (n) (if (is? n 0) 0 (+ 1 (down (- n 1)))))",
+            )?;
+
+            assert!(eval_helper(mem, t, "(down 30)")? == mem.number(30));
+            assert_eq!(
+                *eval_helper(mem, t, "(down 100)").unwrap_err().error_kind(),
+                ErrorKind::CallDepthLimit
+            );
+            assert!(eval_helper(mem, t, "(down 30)")? == mem.number(30));
+
+            Ok(())
+        }
+
+        let limits = Limits {
+            max_call_depth: Some(32),
+            ..Limits::none()
+        };
+        limits_helper(limits, test_inner);
+    }
+
+    #[test]
+    fn heap_limit() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+
+            assert_eq!(
+                *eval_helper(
+                    mem,
+                    t,
+                    "(begin
+                       (set (quote xs) nil)
+                       (while true (set (quote xs) (cons 1 xs))))"
+                )
+                .unwrap_err()
+                .error_kind(),
+                ErrorKind::HeapLimit
+            );
+
+            Ok(())
+        }
+
+        let limits = Limits {
+            max_heap_bytes: Some(1 << 20),
+            ..Limits::none()
+        };
+        limits_helper(limits, test_inner);
+    }
+
+    #[test]
+    fn timeout() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+
+            assert_eq!(
+                *eval_helper(mem, t, "(while true nil)")
+                    .unwrap_err()
+                    .error_kind(),
+                ErrorKind::Timeout
+            );
+
+            Ok(())
+        }
+
+        let limits = Limits {
+            timeout: Some(Duration::from_millis(50)),
+            ..Limits::none()
+        };
+        limits_helper(limits, test_inner);
+    }
+
+    #[test]
+    fn sandboxed_thread_has_no_host_io() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+            let globals = t.globals(mem);
+
+            for name in ["print", "read-file", "write-file"] {
+                assert!(globals.lookup(mem, mem.lookup_sym(name)).is_err());
+            }
+            assert!(eval_helper(mem, t, "(print 1)").is_err());
+
+            // natives that don't touch the host are still available
+            assert!(globals.lookup(mem, mem.lookup_sym("generator")).is_ok());
+
+            Ok(())
+        }
+
+        limits_helper(Limits::sandboxed(), test_inner);
+    }
+
+    #[test]
+    fn limits_are_not_caught() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+
+            assert_eq!(
+                *eval_helper(mem, t, "(assert-error (while true nil))")
+                    .unwrap_err()
+                    .error_kind(),
+                ErrorKind::InstructionLimit
+            );
+
+            Ok(())
+        }
+
+        let limits = Limits {
+            max_instructions: Some(10_000),
+            ..Limits::none()
+        };
+        limits_helper(limits, test_inner);
+    }
+
+    #[test]
+    fn batches_and_stack_growth() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+
+            // deep enough that the register stack grows in the middle of a batch
+            eval_helper(
+                mem,
+                t,
+                "(def count-down (n) (if (is? n 0) (quote done) (count-down (- n 1))))",
+            )?;
+            let result = eval_helper(mem, t, "(count-down 100)")?;
+            assert!(result == mem.lookup_sym("done"));
+
+            // long enough to run over many batches, calling a partial application and a
+            // native function along the way
+            eval_helper(mem, t, "(def add (a b) (+ a b))")?;
+            eval_helper(mem, t, "(set (quote total) 0)")?;
+            let result = eval_helper(
+                mem,
+                t,
+                "(begin
+                   (set (quote add-two) (add 2))
+                   (while (nil? (is? total 2000))
+                     (set (quote total) (add-two total))
+                     (generator add))
+                   total)",
+            )?;
+            assert!(result == mem.number(2000));
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn profiled_loop() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+            eval_helper(mem, t, "(def step (n) (+ n 1))")?;
+
+            let profile = |code| {
+                let mut profiler = Profiler::new();
+                let function = compile(mem, parse(mem, code)?)?;
+                let result = t.profiled_vm_eval(mem, function, &mut profiler)?;
+                Ok::<_, RuntimeError>((result, profiler))
+            };
+
+            // the cost of one call of step, on its own
+            let (_, once) = profile("(step 0)")?;
+            let per_call = once.function("step").unwrap().exclusive;
+            assert!(per_call > 0);
+
+            let (result, profiler) = profile(
+                "(begin
+                   (set (quote i) 0)
+                   (while (nil? (is? i 10))
+                     (set (quote i) (step i)))
+                   i)",
+            )?;
+            assert!(result == mem.number(10));
+
+            let step = profiler.function("step").unwrap();
+            assert_eq!(step.calls, 10);
+            assert_eq!(step.exclusive, 10 * per_call);
+            // step calls nothing, so everything it ran was its own
+            assert_eq!(step.inclusive, step.exclusive);
+
+            // every instruction is attributed to either the loop or step
+            let main = profiler.function("<main>").unwrap();
+            assert_eq!(main.calls, 1);
+            assert_eq!(main.inclusive, profiler.instructions());
+            assert_eq!(main.exclusive + step.exclusive, profiler.instructions());
+
+            // one addition and one call per iteration, and the test is run one extra time. The
+            // first call grows the register stack and is retried, but only counted once.
+            assert_eq!(profiler.opcode_count("Add"), 10);
+            assert_eq!(profiler.opcode_count("Call"), 10);
+            assert_eq!(profiler.opcode_count("IsIdentical"), 11);
+            assert_eq!(profiler.opcode_count("Return"), 11);
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+
+    #[test]
+    fn function_introspection() {
+        fn test_inner(mem: &MutatorView) -> Result<(), RuntimeError> {
+            let t = Thread::alloc(mem)?;
+            let eval = |code| eval_helper(mem, t, code).map(|value| print(*value));
+
+            // a string is only a docstring if more expressions follow it
+            eval("(def add (a b) \"Adds two numbers\" (+ a b))")?;
+            eval("(def greeting () \"hello\")")?;
+            assert_eq!(eval("(add 1 2)")?, "3");
+            assert_eq!(eval("(greeting)")?, "\"hello\"");
+            assert_eq!(eval("(doc add)")?, "\"Adds two numbers\"");
+            assert_eq!(eval("(doc (add 1))")?, "\"Adds two numbers\"");
+            assert_eq!(eval("(doc greeting)")?, "nil");
+            assert_eq!(eval("(doc (lambda (x) \"Identity\" x))")?, "\"Identity\"");
+            assert_eq!(eval("(doc done?)")?, "nil");
+            assert!(eval("(doc 1)").is_err());
+
+            eval("(def f (a &optional (b 1) &key c . rest) a)")?;
+            assert_eq!(eval("(params add)")?, "(a b)");
+            assert_eq!(eval("(params (add 1))")?, "(b)");
+            assert_eq!(eval("(params f)")?, "(a &optional b &key c . rest)");
+            assert_eq!(eval("(params greeting)")?, "nil");
+
+            eval("(def g (a &optional b &key c) a)")?;
+            assert_eq!(eval("(arity add)")?, "2");
+            assert_eq!(eval("(arity (add 1))")?, "1");
+            assert_eq!(eval("(arity g)")?, "(1 . 4)");
+            assert_eq!(eval("(arity f)")?, "(1)");
+            assert_eq!(eval("(arity done?)")?, "1");
+
+            let listing = eval_helper(mem, t, "(disassemble add)")?;
+            assert_eq!(
+                display(*listing),
+                "(Function add a b)\n    0  Add { dest: 4, left: 2, right: 3 }\n    1  Return { reg: 4 }"
+            );
+            assert!(eval("(disassemble done?)").is_err());
+
+            Ok(())
+        }
+
+        test_helper(test_inner);
+    }
+}
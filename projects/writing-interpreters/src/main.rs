@@ -1,10 +1,93 @@
-use std::process;
+use std::{env, fs, path::Path, process, str::FromStr, time::Duration};
 
 use rustyline::{error::ReadlineError, Editor};
-use writing_interpreters::interpreter::{memory::Memory, repl::RepMaker, RuntimeError};
+use writing_interpreters::interpreter::{
+    limits::Limits,
+    memory::Memory,
+    pretty::format_source,
+    profiler::Profiler,
+    repl::{RepMaker, ScriptRunner, TestRunner},
+    RuntimeError,
+};
 
-/// Read a line at a time, printing the input back out
-fn read_print_loop() -> Result<(), RuntimeError> {
+/// Evaluate a source file, optionally printing a profile report and writing folded call stacks
+/// next to it when done
+fn run_script(
+    path: &str,
+    profile: bool,
+    prelude: bool,
+    limits: Limits,
+) -> Result<(), RuntimeError> {
+    let source = fs::read_to_string(path)?;
+
+    let mem = Memory::new();
+    mem.set_limits(limits);
+    let rep = mem.mutate(&RepMaker { prelude }, ())?;
+
+    let mut profiler = if profile { Some(Profiler::new()) } else { None };
+
+    if let Err(e) = mem.mutate(&ScriptRunner::new(&rep, &source), profiler.as_mut()) {
+        e.print_with_traceback(&source);
+        process::exit(1);
+    }
+
+    if let Some(profiler) = profiler {
+        print!("{}", profiler.report());
+
+        let folded = format!("{}.folded", path);
+        profiler.write_folded(Path::new(&folded))?;
+        println!("Folded call stacks written to {}", folded);
+    }
+
+    Ok(())
+}
+
+/// Load a source file and run the tests it defines, exiting with an error code if any failed
+fn run_tests(path: &str, prelude: bool, limits: Limits) -> Result<(), RuntimeError> {
+    let source = fs::read_to_string(path)?;
+
+    let mem = Memory::new();
+    mem.set_limits(limits);
+    let rep = mem.mutate(&RepMaker { prelude }, ())?;
+
+    match mem.mutate(&TestRunner::new(&rep, &source), ()) {
+        Ok(report) if report.failed == 0 => Ok(()),
+        Ok(_) => process::exit(1),
+        Err(e) => {
+            e.print_with_source(&source);
+            process::exit(1);
+        }
+    }
+}
+
+/// The width source files are formatted to
+const FORMAT_WIDTH: usize = 80;
+
+/// Reformat a source file in place
+fn format_file(path: &str) -> Result<(), RuntimeError> {
+    let source = fs::read_to_string(path)?;
+
+    match format_source(&source, FORMAT_WIDTH) {
+        Ok(formatted) => {
+            if formatted != source {
+                fs::write(path, formatted)?;
+            }
+            Ok(())
+        }
+        Err(e) => {
+            e.print_with_source(&source);
+            process::exit(1);
+        }
+    }
+}
+
+/// Read a line at a time, printing the input back out. The session starts from a heap image if
+/// one is given, which will already hold the prelude if the saved session did.
+fn read_print_loop(
+    image: Option<String>,
+    prelude: bool,
+    limits: Limits,
+) -> Result<(), RuntimeError> {
     // establish a repl input history file path
     let history_file = match dirs::home_dir() {
         Some(mut path) => {
@@ -25,9 +108,15 @@ fn read_print_loop() -> Result<(), RuntimeError> {
         }
     }
 
-    let mem = Memory::new();
-    let rep_maker = RepMaker {};
-    let rep = mem.mutate(&rep_maker, ())?;
+    let (mem, rep) = match image {
+        Some(path) => Memory::from_image(Path::new(&path), limits)?,
+        None => {
+            let mem = Memory::new();
+            mem.set_limits(limits);
+            let rep = mem.mutate(&RepMaker { prelude }, ())?;
+            (mem, rep)
+        }
+    };
 
     // repl
     loop {
@@ -38,6 +127,7 @@ fn read_print_loop() -> Result<(), RuntimeError> {
             Ok(line) => {
                 reader.add_history_entry(&line);
                 mem.mutate(&rep, line)?;
+                mem.collect_if_needed(&rep);
             }
 
             // some kind of program termination condition
@@ -59,9 +149,74 @@ fn read_print_loop() -> Result<(), RuntimeError> {
     }
 }
 
+/// Parse the number following a command line flag, exiting if there isn't one
+fn number_arg<T: FromStr>(flag: &str, value: Option<String>) -> T {
+    match value.and_then(|value| value.parse().ok()) {
+        Some(number) => number,
+        None => {
+            eprintln!("{} expects a number", flag);
+            process::exit(1);
+        }
+    }
+}
+
 fn main() {
+    let mut profile = false;
+    let mut prelude = true;
+    let mut image = None;
+    let mut script = None;
+    let mut format = None;
+    let mut test = None;
+    let mut limits = Limits::none();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--profile" => profile = true,
+            "--no-prelude" => prelude = false,
+            "--image" => image = args.next(),
+            "--format" => format = args.next(),
+            "--test" => test = args.next(),
+            "--sandbox" => limits.sandboxed = true,
+            "--max-instructions" => limits.max_instructions = Some(number_arg(&arg, args.next())),
+            "--max-depth" => limits.max_call_depth = Some(number_arg(&arg, args.next())),
+            "--max-heap" => limits.max_heap_bytes = Some(number_arg(&arg, args.next())),
+            "--timeout" => {
+                limits.timeout = Some(Duration::from_millis(number_arg(&arg, args.next())))
+            }
+            _ => script = Some(arg),
+        }
+    }
+
+    // if a file to format was given, reformat it and stop
+    if let Some(path) = format {
+        format_file(&path).unwrap_or_else(|err| {
+            eprintln!("Terminated: {}", err);
+            process::exit(1);
+        });
+        return;
+    }
+
+    // if a file of tests was given, run them and stop
+    if let Some(path) = test {
+        run_tests(&path, prelude, limits).unwrap_or_else(|err| {
+            eprintln!("Terminated: {}", err);
+            process::exit(1);
+        });
+        return;
+    }
+
+    // if a script file was given, evaluate it
+    if let Some(path) = script {
+        run_script(&path, profile, prelude, limits).unwrap_or_else(|err| {
+            eprintln!("Terminated: {}", err);
+            process::exit(1);
+        });
+        return;
+    }
+
     // otherwise begin a repl
-    read_print_loop().unwrap_or_else(|err| {
+    read_print_loop(image, prelude, limits).unwrap_or_else(|err| {
         eprintln!("Terminated: {}", err);
         process::exit(1);
     });
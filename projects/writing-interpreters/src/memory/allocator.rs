@@ -62,13 +62,27 @@ impl SizeClass {
     }
 }
 
-/// Every object is `Allocated` on creation.
+/// Every object is `Allocated` on creation. An object that survives a collection is stamped with
+/// the heap's current live mark, either `Even` or `Odd`, and is considered old from then on.
+///
+/// The live mark alternates on every full collection. Objects stamped with the previous live mark
+/// are thus unmarked again without having to visit them to clear their headers.
 #[repr(u8)]
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Mark {
     Allocated,
-    Unmarked,
-    Marked,
+    Even,
+    Odd,
+}
+
+impl Mark {
+    /// Return the alternate live mark value
+    pub fn flip(self) -> Mark {
+        match self {
+            Mark::Even => Mark::Odd,
+            _ => Mark::Even,
+        }
+    }
 }
 
 /// The type that describes the bounds of array sizing
@@ -90,11 +104,11 @@ pub trait AllocHeader: Sized {
     /// Create a new header for an array type
     fn new_array(size: ArraySize, size_class: SizeClass, mark: Mark) -> Self;
 
-    /// Set the Mark value to "marked"
-    fn mark(&mut self);
+    /// Set the Mark value
+    fn mark(&mut self, mark: Mark);
 
     /// Get the current Mark value
-    fn is_marked(&self) -> bool;
+    fn get_mark(&self) -> Mark;
 
     /// Get the size class of the object
     fn size_class(&self) -> SizeClass;
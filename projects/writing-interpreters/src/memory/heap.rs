@@ -1,9 +1,13 @@
+use std::collections::HashSet;
 use std::mem::size_of;
 use std::ptr::{write, NonNull};
 use std::slice::from_raw_parts_mut;
-use std::{cell::UnsafeCell, marker::PhantomData, mem::replace};
+use std::{
+    cell::{Cell, UnsafeCell},
+    marker::PhantomData,
+};
 
-use crate::memory::stickyimmix::BLOCK_CAPACITY;
+use crate::memory::stickyimmix::{BlockMeta, BLOCK_CAPACITY, BLOCK_SIZE};
 
 use super::allocator::{alloc_size_of, ArraySize};
 use super::{
@@ -12,8 +16,37 @@ use super::{
 };
 use super::{Mark, RawPtr};
 
+/// The kind of collection to run.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CollectionKind {
+    /// Only objects allocated since the last collection are traced. Objects that survived a
+    /// previous collection keep their mark ("sticky" mark bits) and are not visited again, so the
+    /// caller must supply the old-to-young pointers as additional roots.
+    Minor,
+    /// The live mark is flipped and all line marks are cleared, so every reachable object is
+    /// traced.
+    Major,
+}
+
+/// A summary of the block usage of the heap
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct HeapStats {
+    /// Total number of blocks owned by the heap
+    pub blocks: usize,
+    /// Blocks with no marked lines
+    pub free_blocks: usize,
+    /// Number of lines marked as containing live objects
+    pub marked_lines: usize,
+    /// Bytes allocated since the last collection finished
+    pub allocated_since_collection: usize,
+}
+
 pub struct StickyImmixHeap<H> {
     blocks: UnsafeCell<BlockList>,
+    /// The mark value stamped on objects that survived the most recent collection
+    live_mark: Cell<Mark>,
+    /// Bytes allocated since the last collection
+    allocated: Cell<usize>,
     _header_type: PhantomData<*const H>,
 }
 
@@ -21,10 +54,87 @@ impl<H> StickyImmixHeap<H> {
     pub fn new() -> Self {
         StickyImmixHeap {
             blocks: UnsafeCell::new(BlockList::new()),
+            live_mark: Cell::new(Mark::Even),
+            allocated: Cell::new(0),
             _header_type: PhantomData,
         }
     }
 
+    /// Return true if the address lies within a block owned by this heap
+    pub fn contains(&self, address: usize) -> bool {
+        let blocks = unsafe { &*self.blocks.get() };
+        blocks.bases.contains(&(address & !(BLOCK_SIZE - 1)))
+    }
+
+    /// Return true if the address lies on a line that survived a collection. Since allocation
+    /// only ever happens in unmarked lines, such an address belongs to an old object.
+    pub fn is_old(&self, address: usize) -> bool {
+        self.contains(address)
+            && unsafe { BlockMeta::containing(address) }.is_marked_at_address(address)
+    }
+
+    /// Return the number of bytes allocated since the last collection
+    pub fn allocated_since_collection(&self) -> usize {
+        self.allocated.get()
+    }
+
+    /// Prepare the heap for marking. A major collection invalidates every existing object and
+    /// line mark, a minor collection keeps them.
+    pub fn start_collection(&self, kind: CollectionKind) {
+        if kind == CollectionKind::Major {
+            self.live_mark.set(self.live_mark.get().flip());
+
+            let blocks = unsafe { &mut *self.blocks.get() };
+            blocks.for_each(|block| block.reset_lines());
+        }
+    }
+
+    /// Reclassify every block according to its line marks so that allocation continues in the
+    /// holes left by unreachable objects.
+    pub fn finish_collection(&self) {
+        let blocks = unsafe { &mut *self.blocks.get() };
+
+        let mut all = Vec::new();
+        all.extend(blocks.head.take());
+        all.extend(blocks.overflow.take());
+        all.append(&mut blocks.rest);
+        all.append(&mut blocks.recycled);
+
+        for mut block in all {
+            block.recycle();
+            if block.has_hole() {
+                blocks.recycled.push(block);
+            } else {
+                blocks.rest.push(block);
+            }
+        }
+
+        self.allocated.set(0);
+    }
+
+    /// Return block usage information
+    pub fn stats(&self) -> HeapStats {
+        let blocks = unsafe { &mut *self.blocks.get() };
+
+        let mut stats = HeapStats {
+            blocks: 0,
+            free_blocks: 0,
+            marked_lines: 0,
+            allocated_since_collection: self.allocated.get(),
+        };
+
+        blocks.for_each(|block| {
+            let marked_lines = block.marked_lines();
+            stats.blocks += 1;
+            stats.marked_lines += marked_lines;
+            if marked_lines == 0 {
+                stats.free_blocks += 1;
+            }
+        });
+
+        stats
+    }
+
     fn find_space(
         &self,
         alloc_size: usize,
@@ -34,35 +144,57 @@ impl<H> StickyImmixHeap<H> {
             return Err(AllocError::BadRequest);
         }
 
+        self.allocated.set(self.allocated.get() + alloc_size);
+
         let blocks = unsafe { &mut *self.blocks.get() };
-        let space = match blocks.head {
-            Some(ref mut head) => {
-                if size_class == SizeClass::Medium && alloc_size > head.current_hole_size() {
-                    return blocks.overflow_alloc(alloc_size);
-                }
-
-                match head.inner_alloc(alloc_size) {
-                    // the block has a suitable hole
-                    Some(space) => space,
-                    None => {
-                        let previous = replace(head, BumpBlock::new()?);
-                        blocks.rest.push(previous);
-                        head.inner_alloc(alloc_size).expect("Unexpected error!")
-                    }
-                }
+        if let Some(ref mut head) = blocks.head {
+            if size_class == SizeClass::Medium && alloc_size > head.current_hole_size() {
+                return blocks.overflow_alloc(alloc_size);
             }
-            None => {
-                let mut head = BumpBlock::new()?;
-                let space = head.inner_alloc(alloc_size).expect("Unexpected error!");
-                blocks.head = Some(head);
-                space
+
+            // the block has a suitable hole
+            if let Some(space) = head.inner_alloc(alloc_size) {
+                return Ok(space);
             }
-        };
+        }
+
+        let (head, space) = blocks.next_block(alloc_size)?;
+        if let Some(previous) = blocks.head.replace(head) {
+            blocks.rest.push(previous);
+        }
 
         Ok(space)
     }
 }
 
+impl<H: AllocHeader> StickyImmixHeap<H> {
+    /// Mark an object as live. The header is stamped with the current live mark and every line
+    /// the object spans is marked, so the lines are not reused by the allocator.
+    ///
+    /// Returns true if the object was newly marked and its children must be traced. Objects that
+    /// are already marked, and addresses outside of this heap, return false.
+    pub fn mark_object(&self, object: NonNull<()>) -> bool {
+        if !self.contains(object.as_ptr() as usize) {
+            return false;
+        }
+
+        let mut header = Self::get_header(object);
+        let header = unsafe { header.as_mut() };
+
+        let live_mark = self.live_mark.get();
+        if header.get_mark() == live_mark {
+            return false;
+        }
+        header.mark(live_mark);
+
+        let start = header as *const H as usize;
+        let end = object.as_ptr() as usize + header.size() as usize;
+        unsafe { BlockMeta::containing(start) }.mark_lines_spanning(start, end);
+
+        true
+    }
+}
+
 impl<H: AllocHeader> AllocRaw for StickyImmixHeap<H> {
     type Header = H;
 
@@ -144,6 +276,10 @@ struct BlockList {
     overflow: Option<BumpBlock>,
     /// allocated into but are not suitable for recycling
     rest: Vec<BumpBlock>,
+    /// blocks with holes left after a collection, to be allocated into before new blocks
+    recycled: Vec<BumpBlock>,
+    /// the base addresses of all blocks owned by this list
+    bases: HashSet<usize>,
 }
 
 impl BlockList {
@@ -152,33 +288,50 @@ impl BlockList {
             head: None,
             overflow: None,
             rest: Vec::new(),
+            recycled: Vec::new(),
+            bases: HashSet::new(),
+        }
+    }
+
+    /// Find a block to replace the head or overflow block, preferring recycled blocks over new
+    /// ones, and allocate into it
+    fn next_block(&mut self, alloc_size: usize) -> Result<(BumpBlock, *const u8), AllocError> {
+        while let Some(mut block) = self.recycled.pop() {
+            match block.inner_alloc(alloc_size) {
+                Some(space) => return Ok((block, space)),
+                // the holes in this block are too small for this object
+                None => self.rest.push(block),
+            }
         }
+
+        let mut block = BumpBlock::new()?;
+        self.bases.insert(block.base());
+        let space = block.inner_alloc(alloc_size).expect("Unexpected error!");
+        Ok((block, space))
+    }
+
+    /// Apply a function to every block
+    fn for_each<F: FnMut(&mut BumpBlock)>(&mut self, mut f: F) {
+        self.head.iter_mut().for_each(&mut f);
+        self.overflow.iter_mut().for_each(&mut f);
+        self.rest.iter_mut().for_each(&mut f);
+        self.recycled.iter_mut().for_each(&mut f);
     }
 
     /// Allocate a space for a medium object into an overflow block
     fn overflow_alloc(&mut self, alloc_size: usize) -> Result<*const u8, AllocError> {
         assert!(alloc_size <= BLOCK_CAPACITY);
-        let space = match self.overflow {
-            Some(ref mut overflow) => match overflow.inner_alloc(alloc_size) {
-                // the block has a suitable hole
-                Some(space) => space,
-                None => {
-                    let previous = replace(overflow, BumpBlock::new()?);
-                    self.rest.push(previous);
-                    overflow.inner_alloc(alloc_size).expect("Unexpected error!")
-                }
-            },
-            None => {
-                let mut overflow = BumpBlock::new()?;
-                let space = overflow
-                    .inner_alloc(alloc_size)
-                    .expect("We expected this object to fit!");
-
-                self.overflow = Some(overflow);
-
-                space
+        if let Some(ref mut overflow) = self.overflow {
+            // the block has a suitable hole
+            if let Some(space) = overflow.inner_alloc(alloc_size) {
+                return Ok(space);
             }
-        };
+        }
+
+        let (overflow, space) = self.next_block(alloc_size)?;
+        if let Some(previous) = self.overflow.replace(overflow) {
+            self.rest.push(previous);
+        }
 
         Ok(space)
     }
@@ -6,6 +6,6 @@ pub mod stickyimmix;
 
 pub use allocator::{AllocHeader, AllocObject, AllocRaw, AllocTypeId, ArraySize, Mark, SizeClass};
 pub use block::{Block, BlockError};
-pub use heap::StickyImmixHeap;
+pub use heap::{CollectionKind, HeapStats, StickyImmixHeap};
 pub use rawptr::RawPtr;
 pub use stickyimmix::{AllocError, BumpBlock};
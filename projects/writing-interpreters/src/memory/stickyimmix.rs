@@ -68,7 +68,7 @@ impl BumpBlock {
         let limit = self.limit as usize;
         let block_start_ptr = self.block.as_ptr() as usize;
         if next_ptr < limit {
-            let next_starting_at = unsafe { self.limit.sub(block_start_ptr) as usize } as usize;
+            let next_starting_at = limit - block_start_ptr;
 
             if next_starting_at > 0 {
                 if let Some((cursor, limit)) = self
@@ -87,6 +87,38 @@ impl BumpBlock {
             Some(next_ptr as *const u8)
         }
     }
+
+    /// Return the address of the start of the block
+    pub fn base(&self) -> usize {
+        self.block.as_ptr() as usize
+    }
+
+    /// Rewind the bump pointer to the top of the block so that allocation searches for holes
+    /// between marked lines again. Used when the block is reused after a collection.
+    pub fn recycle(&mut self) {
+        let top = unsafe { self.block.as_ptr().add(BLOCK_CAPACITY) };
+        self.cursor = top;
+        self.limit = top;
+    }
+
+    /// Clear all line marks, making the whole block available again
+    pub fn reset_lines(&mut self) {
+        self.meta.reset();
+    }
+
+    /// Return the number of marked lines in the block
+    pub fn marked_lines(&self) -> usize {
+        (0..LINE_COUNT)
+            .filter(|line| self.meta.is_occupied_at(*line))
+            .count()
+    }
+
+    /// Return true if the block has a hole of at least one line that can be allocated into
+    pub fn has_hole(&self) -> bool {
+        self.meta
+            .find_next_available_hole(BLOCK_CAPACITY, LINE_SIZE)
+            .is_some()
+    }
 }
 
 impl BlockMeta {
@@ -101,6 +133,32 @@ impl BlockMeta {
         meta
     }
 
+    /// Return the metadata of the block that contains the given address, without resetting it.
+    ///
+    /// # Safety
+    /// The address must lie inside a block allocated by `BumpBlock::new()`.
+    pub unsafe fn containing(address: usize) -> BlockMeta {
+        let block_ptr = (address & !(BLOCK_SIZE - 1)) as *const u8;
+        BlockMeta {
+            lines: block_ptr.add(LINE_MARK_START) as *mut u8,
+        }
+    }
+
+    /// Mark every line spanned by the address range `start..end`, which must lie inside this
+    /// block
+    pub fn mark_lines_spanning(&mut self, start: usize, end: usize) {
+        let first = (start & (BLOCK_SIZE - 1)) / LINE_SIZE;
+        let last = ((end - 1) & (BLOCK_SIZE - 1)) / LINE_SIZE;
+        for index in first..=last {
+            self.mark_line(index);
+        }
+    }
+
+    /// Return true if the line containing the given address is marked
+    pub fn is_marked_at_address(&self, address: usize) -> bool {
+        self.is_occupied_at((address & (BLOCK_SIZE - 1)) / LINE_SIZE)
+    }
+
     /// Mark the indexed line
     pub fn mark_line(&mut self, index: usize) {
         unsafe { *self.as_line_mark(index) = 1 };